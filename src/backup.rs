@@ -0,0 +1,192 @@
+use crate::config::AgentPaths;
+use anyhow::{Context, Result, bail};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use uuid::Uuid;
+
+/// Entries under the GoldAgent home that are runtime-only and never archived.
+const EXCLUDED_ENTRIES: [&str; 2] = ["scheduler.pid", "logs"];
+
+/// Archives the whole GoldAgent home into a gzip tarball at `dest`.
+/// Returns the number of files written into the archive.
+pub fn backup(paths: &AgentPaths, dest: &Path, no_secrets: bool) -> Result<usize> {
+    let staging = staging_dir("backup");
+    fs::create_dir_all(&staging)?;
+    let result = (|| {
+        let count = copy_tree(&paths.root, &staging, true)?;
+        if no_secrets {
+            strip_connect_secrets(&staging.join("connect.json"))?;
+        }
+        if let Some(parent) = dest.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+        run_tar(&[
+            "-czf".as_ref(),
+            dest.as_os_str(),
+            "-C".as_ref(),
+            staging.as_os_str(),
+            ".".as_ref(),
+        ])?;
+        Ok(count)
+    })();
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+/// Restores an archive created by [`backup`]. Without `merge` the current
+/// home contents (except runtime-only entries) are replaced; with `merge` the
+/// archive is layered on top and files missing from it are kept.
+pub fn restore(paths: &AgentPaths, archive: &Path, merge: bool) -> Result<usize> {
+    if !archive.is_file() {
+        bail!("备份文件不存在：{}", archive.display());
+    }
+    let staging = staging_dir("restore");
+    fs::create_dir_all(&staging)?;
+    let result = (|| {
+        run_tar(&[
+            "-xzf".as_ref(),
+            archive.as_os_str(),
+            "-C".as_ref(),
+            staging.as_os_str(),
+        ])?;
+        fs::create_dir_all(&paths.root)?;
+        if !merge {
+            clear_home(&paths.root)?;
+        }
+        copy_tree(&staging, &paths.root, false)
+    })();
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+fn staging_dir(kind: &str) -> std::path::PathBuf {
+    env::temp_dir().join(format!("goldagent-{kind}-{}", Uuid::new_v4()))
+}
+
+fn run_tar(args: &[&std::ffi::OsStr]) -> Result<()> {
+    let output = Command::new("tar")
+        .args(args)
+        .output()
+        .context("执行 `tar` 失败，请确认系统已安装 tar")?;
+    if !output.status.success() {
+        bail!(
+            "tar 执行失败：{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn is_excluded(name: &str) -> bool {
+    EXCLUDED_ENTRIES.contains(&name) || name.ends_with(".tmp") || name.ends_with(".lock")
+}
+
+fn copy_tree(from: &Path, to: &Path, top_level_filter: bool) -> Result<usize> {
+    let mut count = 0usize;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if top_level_filter && is_excluded(&name) {
+            continue;
+        }
+        let src = entry.path();
+        let dst = to.join(&name);
+        if src.is_dir() {
+            fs::create_dir_all(&dst)?;
+            count += copy_tree(&src, &dst, false)?;
+        } else {
+            fs::copy(&src, &dst).with_context(|| format!("复制文件失败：{}", src.display()))?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+fn clear_home(root: &Path) -> Result<()> {
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if is_excluded(&name) {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+fn strip_connect_secrets(connect_file: &Path) -> Result<()> {
+    if !connect_file.exists() {
+        return Ok(());
+    }
+    let raw = fs::read_to_string(connect_file)?;
+    let mut value = serde_json::from_str::<serde_json::Value>(&raw)
+        .with_context(|| format!("解析连接配置失败: {}", connect_file.display()))?;
+    if let Some(obj) = value.as_object_mut() {
+        for key in ["api_key", "openai_api_key"] {
+            if obj.contains_key(key) {
+                obj.insert(key.to_string(), serde_json::Value::Null);
+            }
+        }
+    }
+    fs::write(
+        connect_file,
+        format!("{}\n", serde_json::to_string_pretty(&value)?),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backup, restore};
+    use crate::config::AgentPaths;
+    use std::env;
+    use std::fs;
+    use uuid::Uuid;
+
+    fn temp_home(tag: &str) -> AgentPaths {
+        let root = env::temp_dir().join(format!("goldagent-{tag}-test-{}", Uuid::new_v4()));
+        let paths = AgentPaths::from_root(root);
+        paths.ensure().unwrap();
+        paths
+    }
+
+    #[test]
+    fn round_trips_home_through_backup() {
+        let source = temp_home("backup-src");
+        let skill_file = source.skills_dir.join("daily-summary").join("SKILL.md");
+        fs::write(&skill_file, "# custom skill\n").unwrap();
+        fs::write(source.root.join("scheduler.pid"), "12345\n").unwrap();
+        fs::write(
+            &source.connect_file,
+            "{\"provider\":\"openai\",\"mode\":\"open_a_i_api\",\"api_key\":\"sk-secret\"}\n",
+        )
+        .unwrap();
+        let archive = source.root.with_extension("tar.gz");
+
+        backup(&source, &archive, true).unwrap();
+
+        let target = temp_home("backup-dst");
+        fs::write(target.root.join("stale.txt"), "old").unwrap();
+        restore(&target, &archive, false).unwrap();
+
+        let restored = target.skills_dir.join("daily-summary").join("SKILL.md");
+        assert_eq!(fs::read_to_string(restored).unwrap(), "# custom skill\n");
+        assert!(!target.root.join("stale.txt").exists());
+        assert!(!target.root.join("scheduler.pid").exists());
+        let connect = fs::read_to_string(&target.connect_file).unwrap();
+        assert!(!connect.contains("sk-secret"));
+
+        let _ = fs::remove_dir_all(&source.root);
+        let _ = fs::remove_dir_all(&target.root);
+        let _ = fs::remove_file(&archive);
+    }
+}
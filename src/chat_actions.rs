@@ -3,11 +3,116 @@ use crate::daemon;
 use crate::hooks;
 use crate::jobs;
 use crate::memory;
+use crate::provider::ChatMessage;
+use crate::response_format::WrapperFilter;
 use anyhow::Result;
 use serde::Deserialize;
+use std::future::Future;
+
+fn scheduler_note(paths: &AgentPaths) -> String {
+    match daemon::auto_start_scheduler(paths) {
+        Ok(daemon::SchedulerStatus::Started(pid)) => format!("已自动启动调度服务（pid={pid}）。"),
+        Ok(daemon::SchedulerStatus::Reloaded(pid)) => {
+            format!("已重载调度服务以应用新任务（pid={pid}）。")
+        }
+        Ok(daemon::SchedulerStatus::Disabled) => daemon::MANUAL_RELOAD_HINT.to_string(),
+        Err(err) => format!(
+            "警告：任务已创建，但自动启动调度服务失败：{err}。请手动执行 `goldagent serve`。"
+        ),
+    }
+}
 
 const LOCAL_ACTION_PREFIX: &str = "[[LOCAL_ACTION:";
 
+/// Times one chat turn feeds action results back for a follow-up reply.
+pub(crate) const MAX_ACTION_FOLLOW_UPS: usize = 2;
+
+/// Message carrying the results of a reply's local actions back to the model,
+/// so it can comment on them (e.g. summarize `cron_list`) in the same turn.
+pub(crate) fn action_results_message(results: &[String]) -> ChatMessage {
+    ChatMessage::user(format!(
+        "[LOCAL_ACTION 执行结果]\n{}\n\n请根据以上结果回复用户，不要重复执行已完成的本地动作。",
+        results.join("\n\n")
+    ))
+}
+
+/// Runs one chat turn for `input`: asks `chat` for a reply (`None` when the
+/// request was cancelled), runs the reply's local actions through
+/// `run_actions`, and hands their results back for a follow-up reply, at
+/// most [`MAX_ACTION_FOLLOW_UPS`] times. `show` prints a reply part that the
+/// stream did not already show. Returns the replies of the turn, or `None`
+/// when the first request was cancelled and the turn was dropped.
+pub(crate) async fn run_turn<C, CF, A, AF>(
+    messages: &mut Vec<ChatMessage>,
+    input: &str,
+    streamed: bool,
+    wrapper_filter: &WrapperFilter,
+    mut chat: C,
+    mut run_actions: A,
+    mut show: impl FnMut(&str),
+) -> Result<Option<Vec<String>>>
+where
+    C: FnMut(Vec<ChatMessage>) -> CF,
+    CF: Future<Output = Option<Result<String>>>,
+    A: FnMut(Vec<ChatLocalAction>, Vec<String>) -> AF,
+    AF: Future<Output = Result<Vec<String>>>,
+{
+    let turn_start = messages.len();
+    messages.push(ChatMessage::user(input));
+    let mut replies = Vec::new();
+    let mut follow_ups = 0;
+    loop {
+        let Some(raw_response) = chat(messages.clone()).await else {
+            if replies.is_empty() {
+                messages.truncate(turn_start);
+                return Ok(None);
+            }
+            // Keep the completed part of the turn; drop the pending results.
+            messages.pop();
+            return Ok(Some(replies));
+        };
+        let raw_response = raw_response?;
+        let (actions, cleaned_response, parse_errors) =
+            extract_local_actions_from_response(&raw_response);
+        let cleaned_response = wrapper_filter.apply(&cleaned_response);
+        let attempted = !actions.is_empty();
+        let notes = run_actions(actions, parse_errors).await?;
+
+        let mut reply = notes
+            .iter()
+            .map(String::as_str)
+            .chain([cleaned_response.as_str()])
+            .filter(|part| !part.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        if reply.trim().is_empty() {
+            reply = "已执行。".to_string();
+        }
+
+        if !streamed {
+            show(&reply);
+        } else if !notes.is_empty() {
+            show(&notes.join("\n\n"));
+        } else if cleaned_response.trim().is_empty() {
+            show(&reply);
+        }
+        replies.push(reply);
+
+        // Hand the action results back so the model can comment on them
+        // before the turn ends.
+        if attempted && follow_ups < MAX_ACTION_FOLLOW_UPS {
+            follow_ups += 1;
+            messages.push(ChatMessage::assistant(raw_response.trim()));
+            messages.push(action_results_message(&notes));
+            continue;
+        }
+        if let Some(reply) = replies.last() {
+            messages.push(ChatMessage::assistant(reply.clone()));
+        }
+        return Ok(Some(replies));
+    }
+}
+
 #[derive(Debug, Deserialize, PartialEq)]
 #[serde(tag = "kind", rename_all = "snake_case")]
 pub(crate) enum ChatLocalAction {
@@ -53,6 +158,22 @@ pub(crate) enum ChatLocalAction {
         #[serde(default)]
         report_file: Option<String>,
     },
+    HookAddHg {
+        repo: String,
+        task: String,
+        #[serde(default)]
+        reference: Option<String>,
+        #[serde(default = "default_hook_interval_secs")]
+        interval_secs: u64,
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(default = "default_retry_max")]
+        retry_max: u8,
+        #[serde(default)]
+        rules_file: Option<String>,
+        #[serde(default)]
+        report_file: Option<String>,
+    },
     HookList,
     HookRemove {
         id: String,
@@ -63,6 +184,37 @@ pub(crate) enum ChatLocalAction {
     },
 }
 
+impl ChatLocalAction {
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            ChatLocalAction::CronAdd { .. } => "cron_add",
+            ChatLocalAction::CronList => "cron_list",
+            ChatLocalAction::CronRemove { .. } => "cron_remove",
+            ChatLocalAction::HookAddGit { .. } => "hook_add_git",
+            ChatLocalAction::HookAddP4 { .. } => "hook_add_p4",
+            ChatLocalAction::HookAddHg { .. } => "hook_add_hg",
+            ChatLocalAction::HookList => "hook_list",
+            ChatLocalAction::HookRemove { .. } => "hook_remove",
+            ChatLocalAction::HookRulesNew { .. } => "hook_rules_new",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ActionRoute {
+    Execute,
+    Confirm,
+}
+
+/// Allowlisted kinds run immediately; everything else asks the user first.
+pub(crate) fn route_action(action: &ChatLocalAction, allowlist: &[String]) -> ActionRoute {
+    if allowlist.iter().any(|kind| kind == action.kind()) {
+        ActionRoute::Execute
+    } else {
+        ActionRoute::Confirm
+    }
+}
+
 fn default_retry_max() -> u8 {
     1
 }
@@ -102,20 +254,27 @@ fn build_scheduled_task_command(task: &str) -> String {
     }
 }
 
-pub(crate) fn extract_local_action_from_response(
+pub(crate) fn is_local_action_line(line: &str) -> bool {
+    line.trim().starts_with(LOCAL_ACTION_PREFIX)
+}
+
+/// Pulls every `[[LOCAL_ACTION:...]]` line out of a model reply, in order.
+/// Returns the parsed actions, the remaining text and one error per line
+/// that failed to parse.
+pub(crate) fn extract_local_actions_from_response(
     raw: &str,
-) -> (Option<ChatLocalAction>, String, Option<String>) {
-    let mut action = None;
-    let mut parse_error = None;
+) -> (Vec<ChatLocalAction>, String, Vec<String>) {
+    let mut actions = Vec::new();
+    let mut parse_errors = Vec::new();
     let mut kept_lines = Vec::new();
 
-    for line in raw.lines() {
+    for (idx, line) in raw.lines().enumerate() {
         let trimmed = line.trim();
-        if action.is_none() && trimmed.starts_with(LOCAL_ACTION_PREFIX) && trimmed.ends_with("]]") {
+        if trimmed.starts_with(LOCAL_ACTION_PREFIX) && trimmed.ends_with("]]") {
             let payload = &trimmed[LOCAL_ACTION_PREFIX.len()..trimmed.len() - 2];
             match serde_json::from_str::<ChatLocalAction>(payload) {
-                Ok(parsed) => action = Some(parsed),
-                Err(err) => parse_error = Some(err.to_string()),
+                Ok(parsed) => actions.push(parsed),
+                Err(err) => parse_errors.push(format!("第 {} 行：{err}", idx + 1)),
             }
             continue;
         }
@@ -123,13 +282,16 @@ pub(crate) fn extract_local_action_from_response(
     }
 
     (
-        action,
+        actions,
         kept_lines.join("\n").trim().to_string(),
-        parse_error,
+        parse_errors,
     )
 }
 
-pub(crate) fn execute_local_action(paths: &AgentPaths, action: ChatLocalAction) -> Result<String> {
+pub(crate) async fn execute_local_action(
+    paths: &AgentPaths,
+    action: ChatLocalAction,
+) -> Result<String> {
     match action {
         ChatLocalAction::CronAdd {
             schedule,
@@ -138,27 +300,32 @@ pub(crate) fn execute_local_action(paths: &AgentPaths, action: ChatLocalAction)
             retry_max,
         } => {
             let command = build_scheduled_task_command(&task);
-            let job = jobs::add_job(paths, schedule, command, name, retry_max)?;
+            let job = jobs::add_job(
+                paths,
+                jobs::NewJob {
+                    schedule,
+                    command,
+                    name,
+                    retry_max,
+                    ..jobs::NewJob::default()
+                },
+            )?;
             let event = format!(
                 "用户通过聊天创建了定时任务：name={}，schedule={}，command={}",
                 job.name, job.schedule, job.command
             );
             memory::append_short_term(paths, "cron.add", &event)?;
-            let _ = memory::auto_capture_event(paths, "cron.add", &event)?;
-            let scheduler_note = match daemon::ensure_scheduler_running(paths) {
-                Ok(daemon::SchedulerStatus::Started(pid)) => {
-                    format!("已自动启动调度服务（pid={pid}）。")
-                }
-                Ok(daemon::SchedulerStatus::Reloaded(pid)) => {
-                    format!("已重载调度服务以应用新任务（pid={pid}）。")
-                }
-                Err(err) => format!(
-                    "警告：任务已创建，但自动启动调度服务失败：{err}。请手动执行 `goldagent serve`。"
-                ),
-            };
+            let _ = memory::auto_capture_event(paths, "cron.add", &event).await?;
+            let scheduler_note = scheduler_note(paths);
             Ok(format!(
-                "已自动创建定时任务：{} | {} | {} | retry={} | {}\n{}",
-                job.id, job.name, job.schedule, job.retry_max, job.command, scheduler_note
+                "已自动创建定时任务：{} | {} | {}（{}） | retry={} | {}\n{}",
+                job.id,
+                job.name,
+                job.schedule,
+                jobs::describe_schedule(&job.schedule),
+                job.retry_max,
+                job.command,
+                scheduler_note
             ))
         }
         ChatLocalAction::CronList => {
@@ -169,8 +336,13 @@ pub(crate) fn execute_local_action(paths: &AgentPaths, action: ChatLocalAction)
             let mut lines = vec!["当前定时任务：".to_string()];
             for job in jobs {
                 lines.push(format!(
-                    "- {} | {} | {} | retry={} | {}",
-                    job.id, job.name, job.schedule, job.retry_max, job.command
+                    "- {} | {} | {}（{}） | retry={} | {}",
+                    job.id,
+                    job.name,
+                    job.schedule,
+                    jobs::describe_schedule(&job.schedule),
+                    job.retry_max,
+                    job.command
                 ));
             }
             Ok(lines.join("\n"))
@@ -217,23 +389,15 @@ pub(crate) fn execute_local_action(paths: &AgentPaths, action: ChatLocalAction)
                 hook.command
             );
             memory::append_short_term(paths, "hook.add", &event)?;
-            let _ = memory::auto_capture_event(paths, "hook.add", &event)?;
-            let scheduler_note = match daemon::ensure_scheduler_running(paths) {
-                Ok(daemon::SchedulerStatus::Started(pid)) => {
-                    format!("已自动启动调度服务（pid={pid}）。")
-                }
-                Ok(daemon::SchedulerStatus::Reloaded(pid)) => {
-                    format!("已重载调度服务以应用新任务（pid={pid}）。")
-                }
-                Err(err) => format!(
-                    "警告：任务已创建，但自动启动调度服务失败：{err}。请手动执行 `goldagent serve`。"
-                ),
-            };
+            let _ = memory::auto_capture_event(paths, "hook.add", &event).await?;
+            let scheduler_note = scheduler_note(paths);
             let mode = if let Some(ref rf) = hook.rules_file {
                 format!(
                     "LLM审查 rules={} report={}",
                     rf,
-                    hook.report_file.as_deref().unwrap_or("<target>/goldagent-review.md")
+                    hook.report_file
+                        .as_deref()
+                        .unwrap_or("<target>/goldagent-review.md")
                 )
             } else {
                 format!("command={}", hook.command)
@@ -249,9 +413,10 @@ pub(crate) fn execute_local_action(paths: &AgentPaths, action: ChatLocalAction)
                 scheduler_note
             ))
         }
-        ChatLocalAction::HookAddP4 {
-            depot,
+        ChatLocalAction::HookAddHg {
+            repo,
             task,
+            reference,
             interval_secs,
             name,
             retry_max,
@@ -263,8 +428,17 @@ pub(crate) fn execute_local_action(paths: &AgentPaths, action: ChatLocalAction)
             } else {
                 build_scheduled_task_command(&task)
             };
-            let hook =
-                hooks::add_p4_hook(paths, depot, interval_secs, command, name, retry_max, rules_file, report_file)?;
+            let hook = hooks::add_hg_hook(
+                paths,
+                repo,
+                reference,
+                interval_secs,
+                command,
+                name,
+                retry_max,
+                rules_file,
+                report_file,
+            )?;
             let event = format!(
                 "用户通过聊天创建了 hook：name={}，source={}，target={}，command={}",
                 hook.name,
@@ -273,37 +447,80 @@ pub(crate) fn execute_local_action(paths: &AgentPaths, action: ChatLocalAction)
                 hook.command
             );
             memory::append_short_term(paths, "hook.add", &event)?;
-            let _ = memory::auto_capture_event(paths, "hook.add", &event)?;
-            let scheduler_note = match daemon::ensure_scheduler_running(paths) {
-                Ok(daemon::SchedulerStatus::Started(pid)) => {
-                    format!("已自动启动调度服务（pid={pid}）。")
-                }
-                Ok(daemon::SchedulerStatus::Reloaded(pid)) => {
-                    format!("已重载调度服务以应用新任务（pid={pid}）。")
-                }
-                Err(err) => format!(
-                    "警告：任务已创建，但自动启动调度服务失败：{err}。请手动执行 `goldagent serve`。"
-                ),
-            };
+            let _ = memory::auto_capture_event(paths, "hook.add", &event).await?;
+            let scheduler_note = scheduler_note(paths);
             let mode = if let Some(ref rf) = hook.rules_file {
                 format!(
                     "LLM审查 rules={} report={}",
                     rf,
-                    hook.report_file.as_deref().unwrap_or("<target>/goldagent-review.md")
+                    hook.report_file
+                        .as_deref()
+                        .unwrap_or("<target>/goldagent-review.md")
                 )
             } else {
                 format!("command={}", hook.command)
             };
             Ok(format!(
-                "已自动创建 P4 hook：{} | {} | interval={}s | retry={} | {}\n{}",
+                "已自动创建 Mercurial hook：{} | {} | ref={} | interval={}s | retry={} | {}\n{}",
                 hook.id,
                 hook.name,
+                hook.reference.as_deref().unwrap_or("tip"),
                 hook.interval_secs,
                 hook.retry_max,
                 mode,
                 scheduler_note
             ))
         }
+        ChatLocalAction::HookAddP4 {
+            depot,
+            task,
+            interval_secs,
+            name,
+            retry_max,
+            rules_file,
+            report_file,
+        } => {
+            let command = if rules_file.is_some() {
+                String::new()
+            } else {
+                build_scheduled_task_command(&task)
+            };
+            let hook = hooks::add_p4_hook(
+                paths,
+                depot,
+                interval_secs,
+                command,
+                name,
+                retry_max,
+                rules_file,
+                report_file,
+            )?;
+            let event = format!(
+                "用户通过聊天创建了 hook：name={}，source={}，target={}，command={}",
+                hook.name,
+                hook.source.as_str(),
+                hook.target,
+                hook.command
+            );
+            memory::append_short_term(paths, "hook.add", &event)?;
+            let _ = memory::auto_capture_event(paths, "hook.add", &event).await?;
+            let scheduler_note = scheduler_note(paths);
+            let mode = if let Some(ref rf) = hook.rules_file {
+                format!(
+                    "LLM审查 rules={} report={}",
+                    rf,
+                    hook.report_file
+                        .as_deref()
+                        .unwrap_or("<target>/goldagent-review.md")
+                )
+            } else {
+                format!("command={}", hook.command)
+            };
+            Ok(format!(
+                "已自动创建 P4 hook：{} | {} | interval={}s | retry={} | {}\n{}",
+                hook.id, hook.name, hook.interval_secs, hook.retry_max, mode, scheduler_note
+            ))
+        }
         ChatLocalAction::HookList => {
             let hooks = hooks::load_hooks(paths)?;
             if hooks.is_empty() {
@@ -345,34 +562,183 @@ pub(crate) fn execute_local_action(paths: &AgentPaths, action: ChatLocalAction)
 #[cfg(test)]
 mod tests {
     use super::{
-        ChatLocalAction, build_run_task_command, build_scheduled_task_command,
-        extract_local_action_from_response,
+        ActionRoute, ChatLocalAction, MAX_ACTION_FOLLOW_UPS, build_run_task_command,
+        build_scheduled_task_command, execute_local_action, extract_local_actions_from_response,
+        route_action, run_turn,
     };
+    use crate::config::{self, AgentConfig, AgentPaths};
+    use crate::daemon;
+    use crate::jobs;
+    use crate::provider::ChatMessage;
+    use crate::response_format::WrapperFilter;
+    use crate::scheduler;
+    use std::cell::RefCell;
+    use std::fs;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn skips_scheduler_start_when_auto_start_disabled() {
+        let root = std::env::temp_dir().join(format!("goldagent-actions-test-{}", Uuid::new_v4()));
+        let paths = AgentPaths::from_root(root);
+        paths.ensure().unwrap();
+        let mut cfg = AgentConfig::default();
+        cfg.scheduler.auto_start_scheduler = false;
+        config::save(&paths, &cfg).unwrap();
+
+        let out = execute_local_action(
+            &paths,
+            ChatLocalAction::CronAdd {
+                schedule: "daily@13:00".to_string(),
+                task: "午间复盘".to_string(),
+                name: None,
+                retry_max: 1,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(out.contains(daemon::MANUAL_RELOAD_HINT), "{out}");
+        assert_eq!(jobs::load_jobs(&paths).unwrap().len(), 1);
+        assert_eq!(scheduler::running_pid(&paths).unwrap(), None);
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[tokio::test]
+    async fn feeds_action_results_back_as_follow_up_message() {
+        let root = std::env::temp_dir().join(format!("goldagent-actions-test-{}", Uuid::new_v4()));
+        let paths = AgentPaths::from_root(root);
+        paths.ensure().unwrap();
+        let mut cfg = AgentConfig::default();
+        cfg.scheduler.auto_start_scheduler = false;
+        config::save(&paths, &cfg).unwrap();
+        jobs::add_job(
+            &paths,
+            jobs::NewJob {
+                schedule: "daily@09:00".to_string(),
+                command: "echo standup".to_string(),
+                name: Some("standup".to_string()),
+                retry_max: 1,
+                ..jobs::NewJob::default()
+            },
+        )
+        .unwrap();
+
+        // The model asks for the job list every time; the turn still ends.
+        let requests = RefCell::new(Vec::new());
+        let mut messages = vec![ChatMessage::system("sys")];
+        let replies = run_turn(
+            &mut messages,
+            "我有哪些定时任务？",
+            false,
+            &WrapperFilter::default(),
+            |request| {
+                requests.borrow_mut().push(request);
+                async { Some(Ok("[[LOCAL_ACTION:{\"kind\":\"cron_list\"}]]".to_string())) }
+            },
+            |actions, _| {
+                let paths = paths.clone();
+                async move {
+                    let mut results = Vec::new();
+                    for action in actions {
+                        results.push(execute_local_action(&paths, action).await?);
+                    }
+                    Ok(results)
+                }
+            },
+            |_| {},
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        let requests = requests.into_inner();
+        assert_eq!(requests.len(), MAX_ACTION_FOLLOW_UPS + 1);
+        assert_eq!(replies.len(), MAX_ACTION_FOLLOW_UPS + 1);
+        let follow_up = requests[1].last().unwrap();
+        assert_eq!(follow_up.role, "user");
+        assert!(
+            follow_up
+                .content
+                .starts_with("[LOCAL_ACTION 执行结果]\n当前定时任务："),
+            "{}",
+            follow_up.content
+        );
+        assert!(
+            follow_up.content.contains("standup"),
+            "{}",
+            follow_up.content
+        );
+        assert_eq!(messages.last().unwrap().role, "assistant");
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
 
     #[test]
     fn parses_cron_add_action_line() {
         let raw = "[[LOCAL_ACTION:{\"kind\":\"cron_add\",\"schedule\":\"daily@13:00\",\"task\":\"提醒我吃饭\"}]]\n好的，已为你设置。";
-        let (action, cleaned, err) = extract_local_action_from_response(raw);
-        assert!(err.is_none());
+        let (actions, cleaned, errors) = extract_local_actions_from_response(raw);
+        assert!(errors.is_empty());
         assert_eq!(
-            action,
-            Some(ChatLocalAction::CronAdd {
+            actions,
+            [ChatLocalAction::CronAdd {
                 schedule: "daily@13:00".to_string(),
                 task: "提醒我吃饭".to_string(),
                 name: None,
                 retry_max: 1,
-            })
+            }]
         );
         assert_eq!(cleaned, "好的，已为你设置。");
     }
 
+    #[test]
+    fn parses_every_action_line_in_order() {
+        let raw = "[[LOCAL_ACTION:{\"kind\":\"cron_add\",\"schedule\":\"daily@09:00\",\"task\":\"站会\"}]]\n\
+[[LOCAL_ACTION:{\"kind\":\"cron_remove\"}]]\n\
+[[LOCAL_ACTION:{\"kind\":\"cron_add\",\"schedule\":\"daily@18:00\",\"task\":\"写日报\"}]]\n\
+已添加两个任务。";
+        let (actions, cleaned, errors) = extract_local_actions_from_response(raw);
+        let schedules = actions
+            .iter()
+            .map(|action| match action {
+                ChatLocalAction::CronAdd { schedule, .. } => schedule.as_str(),
+                other => panic!("unexpected action {other:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(schedules, ["daily@09:00", "daily@18:00"]);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("第 2 行"), "{}", errors[0]);
+        assert_eq!(cleaned, "已添加两个任务。");
+    }
+
+    #[test]
+    fn parses_hook_add_hg_action_line() {
+        let raw = "[[LOCAL_ACTION:{\"kind\":\"hook_add_hg\",\"repo\":\"/srv/app\",\"task\":\"分析最新提交\"}]]";
+        let (actions, _, errors) = extract_local_actions_from_response(raw);
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(
+            actions,
+            [ChatLocalAction::HookAddHg {
+                repo: "/srv/app".to_string(),
+                task: "分析最新提交".to_string(),
+                reference: None,
+                interval_secs: 30,
+                name: None,
+                retry_max: 1,
+                rules_file: None,
+                report_file: None,
+            }]
+        );
+        assert_eq!(actions[0].kind(), "hook_add_hg");
+    }
+
     #[test]
     fn parses_invalid_action_as_error() {
         let raw = "[[LOCAL_ACTION:{\"kind\":\"cron_add\"}]]\n参数不完整";
-        let (action, cleaned, err) = extract_local_action_from_response(raw);
-        assert!(action.is_none());
+        let (actions, cleaned, errors) = extract_local_actions_from_response(raw);
+        assert!(actions.is_empty());
         assert_eq!(cleaned, "参数不完整");
-        assert!(err.is_some());
+        assert_eq!(errors.len(), 1);
     }
 
     #[test]
@@ -386,4 +752,17 @@ mod tests {
         let out = build_scheduled_task_command("提醒我喝水");
         assert_eq!(out, "goldagent remind \"提醒我喝水\"");
     }
+
+    #[test]
+    fn unlisted_action_kind_requires_confirmation() {
+        let allowlist = vec!["cron_list".to_string(), "hook_list".to_string()];
+        assert_eq!(
+            route_action(&ChatLocalAction::CronList, &allowlist),
+            ActionRoute::Execute
+        );
+        let remove = ChatLocalAction::CronRemove {
+            id: "job-1".to_string(),
+        };
+        assert_eq!(route_action(&remove, &allowlist), ActionRoute::Confirm);
+    }
 }
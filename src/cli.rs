@@ -1,8 +1,20 @@
-use clap::{Parser, Subcommand};
+use crate::hooks::FindingSeverity;
+use crate::response_format::ResponseFormat;
+use crate::skills::SkillContext;
+use crate::usage::ResetScope;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::ffi::OsString;
+use std::path::PathBuf;
 
 #[derive(Debug, Parser)]
 #[command(name = "goldagent", version, about = "GoldAgent 本地命令行助手")]
 pub struct Cli {
+    /// 输出额外的诊断信息
+    #[arg(long, short, global = true)]
+    pub verbose: bool,
+    /// 关闭回复中的 Markdown 着色（也可设置 GOLDAGENT_NO_COLOR=1）
+    #[arg(long, global = true)]
+    pub no_color: bool,
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -15,23 +27,80 @@ pub enum Commands {
     Chat {
         #[arg(long)]
         model: Option<String>,
+        /// 恢复上一次保存的对话
+        #[arg(long = "continue", default_value_t = false)]
+        resume: bool,
+        /// 回复字数上限
+        #[arg(long)]
+        max_words: Option<usize>,
+        /// 回复格式（json 会校验并在不合法时重试一次）
+        #[arg(long, value_enum)]
+        format: Option<ResponseFormat>,
+        #[command(flatten)]
+        profile: ProfileArgs,
     },
     /// 让模型执行一次单轮任务
     Run {
         task: String,
         #[arg(long)]
         model: Option<String>,
+        /// 回复字数上限
+        #[arg(long)]
+        max_words: Option<usize>,
+        /// 回复格式（json 会校验并在不合法时重试一次）
+        #[arg(long, value_enum)]
+        format: Option<ResponseFormat>,
+        /// 要求回复符合该 JSON Schema 文件：OpenAI 走原生结构化输出，其余厂商写入提示词；本地校验不通过时重试一次
+        #[arg(long, value_name = "FILE")]
+        json_schema: Option<String>,
+        #[command(flatten)]
+        profile: ProfileArgs,
+        /// 把回复连同带时间的标题追加到该文件（不覆盖），便于汇总成报告
+        #[arg(long, value_name = "PATH")]
+        append: Option<String>,
+        /// 生成遇到该序列时停止（可重复，最多 4 个；覆盖 profile 中的 stop）
+        #[arg(long = "stop", value_name = "SEQ")]
+        stop: Vec<String>,
+        /// 采样随机种子，便于复现输出（仅 OpenAI 兼容接口支持，覆盖 profile 中的 seed）
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Anthropic 扩展思考的 token 预算（1024 起，须小于 max_tokens；覆盖 profile 中的 thinking_budget）
+        #[arg(long, value_name = "N")]
+        thinking_budget: Option<u32>,
+        /// 把模型返回的思考内容输出到 stderr（需启用 thinking budget）
+        #[arg(long, default_value_t = false)]
+        show_thinking: bool,
+        /// 边生成边输出回复（不支持流式的后端、--format json 与 --json-schema 时仍整体输出）
+        #[arg(long, default_value_t = false)]
+        stream: bool,
     },
     /// 触发一次本地提醒（可用于定时任务）
-    Remind { message: String },
+    Remind {
+        #[command(subcommand)]
+        command: Option<RemindCommand>,
+        /// 提醒内容
+        message: Option<String>,
+    },
     /// 启动后台定时任务服务
-    Serve,
+    Serve {
+        /// 缺少任务/监听所需的 shell、git、p4 时拒绝启动（默认仅警告）
+        #[arg(long, default_value_t = false)]
+        strict: bool,
+        /// 只执行一轮：运行当前到点的任务、各监听轮询一次后退出（适合容器/外部 cron）
+        #[arg(long, default_value_t = false)]
+        once: bool,
+    },
     /// 执行一条 shell 命令
     Shell {
         cmd: String,
         #[arg(long, default_value_t = false)]
         force: bool,
+        /// 不执行，仅请模型解释命令含义并评估风险
+        #[arg(long, default_value_t = false, conflicts_with = "force")]
+        explain: bool,
     },
+    /// 生成 shell 补全脚本，如 `goldagent completions zsh > ~/.zfunc/_goldagent`
+    Completions { shell: clap_complete::Shell },
     /// 连接模型后端（登录态/API Key）
     Connect {
         #[command(subcommand)]
@@ -52,6 +121,66 @@ pub enum Commands {
         #[command(subcommand)]
         command: SkillCommand,
     },
+    /// 记忆文件管理
+    Memory {
+        #[command(subcommand)]
+        command: MemoryCommand,
+    },
+    /// 用量统计：按日期或模型汇总请求数、tokens 与估算费用
+    Usage {
+        #[command(subcommand)]
+        command: Option<UsageCommand>,
+        /// 只统计该日期（YYYY-MM-DD）及之后的用量
+        #[arg(long)]
+        since: Option<String>,
+        #[arg(long, value_enum, default_value_t = UsageGrouping::Day)]
+        by: UsageGrouping,
+        /// 以 JSON 输出
+        #[arg(long)]
+        json: bool,
+        /// 价格文件（默认 ~/.goldagent/prices.json），覆盖内置的每 1K tokens 单价
+        #[arg(long)]
+        prices: Option<String>,
+    },
+    /// 调度服务管理
+    Scheduler {
+        #[command(subcommand)]
+        command: SchedulerCommand,
+    },
+    /// 备份整个 GoldAgent 数据目录
+    Backup {
+        path: String,
+        /// 备份时清除连接配置中的 API Key
+        #[arg(long, default_value_t = false)]
+        no_secrets: bool,
+    },
+    /// 从备份恢复 GoldAgent 数据目录
+    Restore {
+        path: String,
+        /// 合并到现有数据（保留备份中不存在的文件）
+        #[arg(long, default_value_t = false)]
+        merge: bool,
+        /// 跳过确认
+        #[arg(long, default_value_t = false)]
+        yes: bool,
+    },
+    /// 外部扩展命令：`goldagent foo` 会执行 PATH 中的 `goldagent-foo`
+    #[command(external_subcommand)]
+    External(Vec<OsString>),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RemindCommand {
+    /// 列出提醒记录（待确认 / 已确认 / 已推迟）
+    List,
+    /// 确认一条提醒
+    Ack { id: String },
+    /// 推迟一条提醒，到时再次提醒
+    Snooze {
+        id: String,
+        #[arg(long, default_value_t = 10)]
+        minutes: u32,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -64,11 +193,41 @@ pub enum CronCommand {
         name: Option<String>,
         #[arg(long, default_value_t = 1)]
         retry_max: u8,
+        /// 按指定时区解析调度时间（IANA 名称，如 Asia/Shanghai；默认本机时区）
+        #[arg(long = "tz")]
+        timezone: Option<String>,
+        /// 调度服务（serve）启动时先立即执行一次
+        #[arg(long, default_value_t = false)]
+        run_on_start: bool,
+        /// 命令的工作目录（保存为绝对路径；默认为 serve 启动时的目录）
+        #[arg(long, value_name = "DIR")]
+        cwd: Option<String>,
+        /// 首次失败即发送通知（默认连续失败 scheduler.notify_after_failures 次才通知）
+        #[arg(long, default_value_t = false)]
+        onefail: bool,
     },
     /// 列出所有 cron 任务
     List,
     /// 删除一条 cron 任务
     Remove { id: String },
+    /// 立即执行一次 cron 任务（不影响原有调度）
+    Run {
+        id: String,
+        /// 跳过 scheduler.command_policy 的程序白名单/黑名单检查
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+    /// 用文字解释时间表达式（不创建任务），如 `0 13 * * 1-5` → 每个工作日 13:00
+    Explain { schedule: String },
+    /// 启用一条 cron 任务
+    Enable { id: String },
+    /// 停用一条 cron 任务（保留配置）
+    Disable { id: String },
+    /// 查看所有任务接下来的触发时间
+    Next {
+        #[arg(long, default_value_t = 5)]
+        limit: usize,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -94,6 +253,28 @@ pub enum HookCommand {
         #[arg(long, default_value_t = 1)]
         retry_max: u8,
     },
+    /// 新增 Mercurial（hg）提交轮询触发任务
+    AddHg {
+        repo: String,
+        /// Shell 命令（与 --rules-file 二选一）
+        #[arg(long)]
+        command: Option<String>,
+        /// LLM 审查规则文件路径（与 --command 二选一）
+        #[arg(long)]
+        rules_file: Option<String>,
+        /// LLM 审查报告输出路径（默认为 <repo>/goldagent-review.md）
+        #[arg(long)]
+        report_file: Option<String>,
+        /// 轮询的修订（默认 tip）
+        #[arg(long = "ref")]
+        reference: Option<String>,
+        #[arg(long, default_value_t = 30)]
+        interval: u64,
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long, default_value_t = 1)]
+        retry_max: u8,
+    },
     /// 新增 P4 提交轮询触发任务
     AddP4 {
         depot: String,
@@ -115,6 +296,15 @@ pub enum HookCommand {
     },
     /// 列出所有 hook 任务
     List,
+    /// 用仓库当前版本试运行一次 hook（previous 与 current 均取当前签名），便于检查 ${HOOK_*} 占位符
+    Test {
+        id: String,
+        /// 只打印渲染后的命令，不执行
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+    /// 只读取一次 hook 来源的当前签名（提交哈希 / changelist），检查仓库路径、分支、p4 连接是否可用
+    Check { id: String },
     /// 删除一条 hook 任务
     Remove { id: String },
     /// 生成 LLM 审查规则文件模板
@@ -123,6 +313,117 @@ pub enum HookCommand {
         #[arg(default_value = "./review-rules.md")]
         path: String,
     },
+    /// 设置 LLM 审查发现问题时执行的命令（不传 command 则清除）
+    OnFindings {
+        id: String,
+        /// 支持 ${FINDINGS}、${FINDINGS_COUNT}、${REPORT_FILE} 及 ${HOOK_*} 占位符
+        command: Option<String>,
+        /// 触发所需的最低严重程度
+        #[arg(long, value_enum, default_value_t = FindingSeverity::Critical)]
+        min_severity: FindingSeverity,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MemoryCommand {
+    /// 检查 MEMORY.md 结构（标题、段落、条目标记）
+    Check {
+        /// 备份后修复检测到的问题（保留条目内容）
+        #[arg(long, default_value_t = false)]
+        repair: bool,
+    },
+    /// 在长期记忆与每日短期记忆中全文搜索
+    Search {
+        query: String,
+        /// 最多显示的条目数
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+        /// 只搜索 tags 中包含该值的长期记忆
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    /// 删除长期记忆条目（按 id 或内容匹配；不会删除能力声明与连接规则）
+    Forget {
+        /// 记忆 id（如 mem_20260101000000）
+        #[arg(required_unless_present = "matching")]
+        id: Option<String>,
+        /// 删除内容包含该文本的条目（忽略大小写）
+        #[arg(long)]
+        matching: Option<String>,
+        /// 匹配到多条时确认全部删除
+        #[arg(long, default_value_t = false)]
+        yes: bool,
+    },
+    /// 导出长期记忆与每日短期记忆为结构化数据
+    Export {
+        #[arg(long, value_enum, default_value_t = MemoryExportFormat::Json)]
+        format: MemoryExportFormat,
+        /// 输出文件路径（默认输出到 stdout）
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum MemoryExportFormat {
+    Json,
+}
+
+/// 采样参数：`--profile` 选择 config.json 中（或内置）的命名配置，
+/// `--temperature` / `--effort` 单独覆盖其中的字段。
+#[derive(Debug, Clone, Default, Args)]
+pub struct ProfileArgs {
+    /// 采样配置名（内置：creative / precise / review）
+    #[arg(long)]
+    pub profile: Option<String>,
+    /// 采样温度，覆盖 profile 中的设置
+    #[arg(long)]
+    pub temperature: Option<f32>,
+    /// 推理强度（low / medium / high / xhigh），覆盖 profile 中的设置
+    #[arg(long)]
+    pub effort: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum UsageCommand {
+    /// 按模型或日期列出用量最高的条目
+    Top {
+        #[arg(long, value_enum, default_value_t = UsageGrouping::Model)]
+        by: UsageGrouping,
+        #[arg(long, default_value_t = 5)]
+        limit: usize,
+    },
+    /// 清空用量统计（all / total / by-day / by-model）
+    Reset {
+        #[arg(value_enum, default_value_t = ResetScope::All)]
+        scope: ResetScope,
+        /// 跳过确认
+        #[arg(long, default_value_t = false)]
+        yes: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum UsageGrouping {
+    Model,
+    Day,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SchedulerCommand {
+    /// 查看或清理调度服务的 pid 文件
+    Pid {
+        #[command(subcommand)]
+        command: SchedulerPidCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SchedulerPidCommand {
+    /// 显示 pid 文件内容与进程存活状态
+    Show,
+    /// 强制删除 pid 文件（用于清理残留的 pid 文件）
+    Clear,
 }
 
 #[derive(Debug, Subcommand)]
@@ -131,19 +432,81 @@ pub enum SkillCommand {
     List,
     /// 创建一个新的技能模板
     New { name: String },
+    /// 查看技能详情与运行次数
+    Info { name: String },
+    /// 删除一个技能目录（目录内有 SKILL.md 以外的文件时需 --force）
+    Remove {
+        name: String,
+        /// 确认删除
+        #[arg(long, default_value_t = false)]
+        yes: bool,
+        /// 允许删除内置的 daily-summary 或含其他文件的技能目录
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+    /// 把技能目录打包成单个可分享的文本文件
+    Export {
+        name: String,
+        /// 输出文件路径（默认输出到 stdout），习惯以 .skill.md 结尾
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// 从 skill export 生成的文件安装技能
+    Import {
+        path: PathBuf,
+        /// 覆盖同名的已有技能
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+    /// 重命名技能（新名称中的空格与斜杠会替换为 -）
+    Rename {
+        from: String,
+        to: String,
+        /// 允许重命名内置的 daily-summary
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
     /// 运行一个技能并传入输入内容
     Run {
         name: String,
+        /// 输入内容；`-` 从标准输入读取，`@路径` 读取文件内容，`@@` 开头表示字面的 `@`
         input: String,
         #[arg(long)]
         model: Option<String>,
+        /// 输出未通过技能声明的校验时，带上错误让模型自我修正后重试
+        #[arg(long, default_value_t = false)]
+        repeat_on_fail: bool,
+        /// 单次运行的超时时间（秒）
+        #[arg(long)]
+        timeout: Option<u64>,
+        /// 最多调用模型的次数（含自我修正）
+        #[arg(long)]
+        max_steps: Option<u32>,
+        #[command(flatten)]
+        profile: ProfileArgs,
+        /// 把输出连同带时间的标题追加到该文件（不覆盖），便于汇总成报告
+        #[arg(long, value_name = "PATH")]
+        append: Option<String>,
+        /// 注入提示的近期数据：memory（记忆末尾）、runs（最近的任务/hook 运行日志）或 none
+        #[arg(long, value_enum, default_value_t = SkillContext::Memory)]
+        context: SkillContext,
     },
 }
 
 #[derive(Debug, Subcommand)]
 pub enum ConnectCommand {
     /// 查看当前连接状态
-    Status,
+    Status {
+        /// 持续刷新（清屏重绘），显示用量增量；按 q 或 Ctrl-C 退出
+        #[arg(long, default_value_t = false)]
+        watch: bool,
+        /// 刷新间隔（秒）
+        #[arg(long, default_value_t = 5, requires = "watch", value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+        /// 每次刷新时探测后端地址的 TCP 连接延迟并显示趋势
+        #[arg(long, default_value_t = false, requires = "watch")]
+        probe: bool,
+    },
     /// 使用登录态（可选指定 model）
     Login {
         #[arg(long)]
@@ -151,12 +514,35 @@ pub enum ConnectCommand {
     },
     /// 使用 API Key（可通过 --provider 选择厂商）
     Api {
-        api_key: String,
+        /// API Key（本地 Ollama 可省略；建议改用 --api-key-file 避免写入 shell 历史）
+        api_key: Option<String>,
+        /// 从文件读取 API Key（自动去除首尾空白）
+        #[arg(long, conflicts_with = "api_key")]
+        api_key_file: Option<String>,
         #[arg(long, default_value = "openai")]
         provider: String,
         #[arg(long)]
         zhipu_api_type: Option<String>,
         #[arg(long)]
         model: Option<String>,
+        /// OpenAI 兼容网关地址（如 http://localhost:4000/v1），传空字符串恢复默认
+        #[arg(long)]
+        base_url: Option<String>,
+    },
+    /// 导出连接配置（默认不含 API Key），便于团队共享
+    Export {
+        path: String,
+        /// 同时导出 API Key
+        #[arg(long, default_value_t = false)]
+        with_secrets: bool,
+    },
+    /// 导入连接配置（默认保留本地已有的 API Key）
+    Import {
+        path: String,
+        /// 用导入文件中的 API Key 覆盖本地 Key
+        #[arg(long, default_value_t = false)]
+        overwrite_key: bool,
     },
+    /// 获取当前厂商提供的模型列表并缓存，供 /model 补全使用
+    Models,
 }
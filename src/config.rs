@@ -1,19 +1,79 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// Loads the nearest `.env` in `start` or its ancestors. Variables already set
+/// in the process environment win; set `GOLDAGENT_NO_DOTENV=1` to skip.
+///
+/// # Safety
+///
+/// Sets process environment variables, so it must run before any other
+/// thread (including the tokio runtime) is started.
+pub unsafe fn load_dotenv(start: &Path) -> Result<Option<PathBuf>> {
+    if env::var_os("GOLDAGENT_NO_DOTENV").is_some_and(|value| value != "0") {
+        return Ok(None);
+    }
+    let Some(path) = find_dotenv(start) else {
+        return Ok(None);
+    };
+    for (key, value) in read_dotenv(&path)? {
+        if env::var_os(&key).is_none() {
+            // SAFETY: the caller guarantees the process is still single-threaded.
+            unsafe { env::set_var(key, value) };
+        }
+    }
+    Ok(Some(path))
+}
+
+/// The nearest `.env` file in `start` or its ancestors.
+pub fn find_dotenv(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(".env"))
+        .find(|path| path.is_file())
+}
+
+/// Parses a `.env` file without touching the process environment.
+pub fn read_dotenv(path: &Path) -> Result<Vec<(String, String)>> {
+    let parse_error = || format!("解析 .env 失败: {}", path.display());
+    dotenvy::from_path_iter(path)
+        .with_context(parse_error)?
+        .map(|item| item.with_context(parse_error))
+        .collect()
+}
 
 #[derive(Debug, Clone)]
 pub struct AgentPaths {
     pub root: PathBuf,
     pub memory_file: PathBuf,
     pub memory_dir: PathBuf,
+    /// Sidecar cache of long-term entry embeddings for semantic dedup.
+    pub memory_embeddings_file: PathBuf,
     pub jobs_file: PathBuf,
+    pub reminders_file: PathBuf,
     pub hooks_file: PathBuf,
     pub connect_file: PathBuf,
+    /// Model ids last fetched from each provider by `connect models`.
+    pub models_cache_file: PathBuf,
+    /// Last signature seen per hook id, kept between `serve --once` passes.
+    pub hook_signatures_file: PathBuf,
+    /// Time up to which fires have been handled per job id, kept between
+    /// `serve --once` passes.
+    pub job_checks_file: PathBuf,
+    /// Consecutive failures per job id, for failure notifications.
+    pub job_failures_file: PathBuf,
     pub usage_file: PathBuf,
+    pub config_file: PathBuf,
+    pub session_file: PathBuf,
+    /// Named chat sessions written by `/save`.
+    pub sessions_dir: PathBuf,
     pub logs_dir: PathBuf,
     pub skills_dir: PathBuf,
+    /// Locks for files outside this directory, e.g. `--append` transcripts.
+    pub locks_dir: PathBuf,
 }
 
 impl AgentPaths {
@@ -25,17 +85,31 @@ impl AgentPaths {
             home.join(".goldagent")
         };
 
-        Ok(Self {
+        Ok(Self::from_root(root))
+    }
+
+    pub fn from_root(root: PathBuf) -> Self {
+        Self {
             memory_file: root.join("MEMORY.md"),
             memory_dir: root.join("memory"),
+            memory_embeddings_file: root.join("memory-embeddings.json"),
             jobs_file: root.join("jobs.json"),
+            reminders_file: root.join("reminders.json"),
             hooks_file: root.join("hooks.json"),
             connect_file: root.join("connect.json"),
+            models_cache_file: root.join("models-cache.json"),
+            hook_signatures_file: root.join("hook-signatures.json"),
+            job_checks_file: root.join("job-checks.json"),
+            job_failures_file: root.join("job-failures.json"),
             usage_file: root.join("usage.json"),
+            config_file: root.join("config.json"),
+            session_file: root.join("last_session.json"),
+            sessions_dir: root.join("sessions"),
             logs_dir: root.join("logs"),
             skills_dir: root.join("skills"),
+            locks_dir: root.join("locks"),
             root,
-        })
+        }
     }
 
     pub fn ensure(&self) -> Result<()> {
@@ -44,10 +118,21 @@ impl AgentPaths {
         fs::create_dir_all(&self.logs_dir)?;
         fs::create_dir_all(&self.skills_dir)?;
 
-        ensure_file_with(
-            &self.memory_file,
-            "# GoldAgent 长期记忆\n\n此文件用于保存长期、可复用的记忆。\n\n",
-        )?;
+        if !self.config_file.exists() {
+            save(self, &AgentConfig::default())?;
+        }
+        // Seed with the configured header even when the capability sections
+        // are unmanaged, since nothing rewrites the title block afterwards.
+        let memory_seed = load(self)
+            .unwrap_or_default()
+            .memory
+            .memory_header
+            .filter(|header| !header.trim().is_empty())
+            .map_or_else(
+                || "# GoldAgent 长期记忆\n\n此文件用于保存长期、可复用的记忆。\n\n".to_string(),
+                |header| format!("{}\n\n", header.trim_end()),
+            );
+        ensure_file_with(&self.memory_file, &memory_seed)?;
         ensure_file_with(&self.jobs_file, "[]\n")?;
         ensure_file_with(&self.hooks_file, "[]\n")?;
         ensure_file_with(
@@ -79,3 +164,396 @@ fn ensure_file_with(path: &PathBuf, default_content: &str) -> Result<()> {
     }
     Ok(())
 }
+
+/// User-tunable runtime options persisted in `config.json`. Every section and
+/// field has a default so older or hand-trimmed files keep loading.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AgentConfig {
+    #[serde(default)]
+    pub scheduler: SchedulerConfig,
+    #[serde(default)]
+    pub chat: ChatConfig,
+    /// Named sampling profiles selected with `--profile`; entries here
+    /// override the built-in `creative` / `precise` / `review`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ModelProfile>,
+    #[serde(default)]
+    pub budget: BudgetConfig,
+    #[serde(default)]
+    pub memory: MemoryConfig,
+}
+
+/// Optional embeddings-backed dedup for automatically captured long-term
+/// memory. Off by default so offline setups never call an embeddings API.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryConfig {
+    #[serde(default)]
+    pub semantic_dedup: bool,
+    /// Embeddings model; defaults to the backend's standard model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>,
+    /// Cosine similarity at or above which a candidate counts as a duplicate.
+    #[serde(default = "default_similarity_threshold")]
+    pub similarity_threshold: f32,
+    /// Most entries one input may auto-promote to long-term memory; `0`
+    /// turns auto-capture off. Explicit "记住" requests are not limited.
+    #[serde(default = "default_max_auto_captures")]
+    pub max_auto_captures: usize,
+    /// Keep the capability and connect-rules sections of `MEMORY.md` in sync
+    /// on every start. When off, the file is never rewritten at startup, so
+    /// sections the user removed stay removed.
+    #[serde(default = "default_manage_capability_sections")]
+    pub manage_capability_sections: bool,
+    /// Replaces the `# GoldAgent 长期记忆` title block of `MEMORY.md`; its first
+    /// non-empty line is the title `memory check` looks for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_header: Option<String>,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            semantic_dedup: false,
+            embedding_model: None,
+            similarity_threshold: default_similarity_threshold(),
+            max_auto_captures: default_max_auto_captures(),
+            manage_capability_sections: default_manage_capability_sections(),
+            memory_header: None,
+        }
+    }
+}
+
+fn default_similarity_threshold() -> f32 {
+    0.9
+}
+
+fn default_max_auto_captures() -> usize {
+    3
+}
+
+fn default_manage_capability_sections() -> bool {
+    true
+}
+
+/// Hard monthly token limits checked before every model request; unset
+/// means unlimited. `GOLDAGENT_BUDGET_OVERRIDE=1` bypasses them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BudgetConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_input_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_output_tokens: Option<u64>,
+}
+
+impl BudgetConfig {
+    pub fn is_set(&self) -> bool {
+        self.monthly_input_tokens.is_some() || self.monthly_output_tokens.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelProfile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Reasoning effort (`low`/`medium`/`high`/`xhigh`); only OpenAI models
+    /// and the Codex login backend use it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effort: Option<String>,
+    /// Stop sequences sent with every request; `run --stop` replaces them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    /// Sampling seed for reproducible output where the provider supports it
+    /// (OpenAI-compatible APIs); `run --seed` replaces it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Extended-thinking token budget for Anthropic models; `run
+    /// --thinking-budget` replaces it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking_budget: Option<u32>,
+}
+
+fn builtin_profile(name: &str) -> Option<ModelProfile> {
+    let (temperature, effort) = match name {
+        "creative" => (Some(1.0), None),
+        "precise" => (Some(0.0), None),
+        "review" => (None, Some("high")),
+        _ => return None,
+    };
+    Some(ModelProfile {
+        temperature,
+        effort: effort.map(str::to_string),
+        stop: Vec::new(),
+        seed: None,
+        thinking_budget: None,
+    })
+}
+
+/// Resolves `--profile` against `config.json` and the built-in profiles, then
+/// applies `--temperature` / `--effort`, which win over the profile's fields.
+pub fn resolve_profile(
+    config: &AgentConfig,
+    name: Option<&str>,
+    temperature: Option<f32>,
+    effort: Option<String>,
+) -> Result<ModelProfile> {
+    let mut profile = match name.map(str::trim) {
+        None => ModelProfile::default(),
+        Some(name) => match config
+            .profiles
+            .get(name)
+            .cloned()
+            .or_else(|| builtin_profile(name))
+        {
+            Some(profile) => profile,
+            None => {
+                let mut known = config.profiles.keys().cloned().collect::<Vec<_>>();
+                for builtin in ["creative", "precise", "review"] {
+                    if !known.iter().any(|name| name == builtin) {
+                        known.push(builtin.to_string());
+                    }
+                }
+                bail!("未知的 profile：{name}（可用：{}）", known.join(", "));
+            }
+        },
+    };
+    if temperature.is_some() {
+        profile.temperature = temperature;
+    }
+    if effort.is_some() {
+        profile.effort = effort;
+    }
+    Ok(profile)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatConfig {
+    /// Save the chat history to `last_session.json` every N turns (0 = only
+    /// on clean exit).
+    #[serde(default = "default_autosave_every_turns")]
+    pub autosave_every_turns: u32,
+    /// Never write the chat history to disk.
+    #[serde(default)]
+    pub privacy_mode: bool,
+    /// `LOCAL_ACTION` kinds (e.g. `cron_list`) that run without asking;
+    /// any other kind prompts for confirmation first.
+    #[serde(default = "default_auto_action_allowlist")]
+    pub auto_action_allowlist: Vec<String>,
+    /// Custom identity guard appended to every system prompt; `{backend}` is
+    /// replaced with the live backend label. Uses the built-in text when unset.
+    #[serde(default)]
+    pub identity_guard: Option<String>,
+    /// Label in front of the first line of every answer.
+    #[serde(default = "default_assistant_prefix")]
+    pub assistant_prefix: String,
+    /// Optional line printed after every answer (e.g. `────────`).
+    #[serde(default)]
+    pub assistant_footer: Option<String>,
+    /// Wrap long answer lines to the terminal width.
+    #[serde(default = "default_wrap_output")]
+    pub wrap_output: bool,
+    /// Turns re-sent to the model when resuming with `--continue`
+    /// (0 = the whole history).
+    #[serde(default = "default_resume_window")]
+    pub resume_window: usize,
+    /// Replace turns outside the resume window with a short system note.
+    #[serde(default = "default_resume_summarize_older")]
+    pub resume_summarize_older: bool,
+    /// Run every `LOCAL_ACTION` line of a reply in order; when off, only the
+    /// first one runs and the rest are reported as skipped.
+    #[serde(default = "default_multiple_actions")]
+    pub multiple_actions: bool,
+    /// Strip wrapper lines (`chat.wrapper_patterns`) and a single outer
+    /// ```text/markdown fence from answers before display.
+    #[serde(default = "default_strip_wrappers")]
+    pub strip_wrappers: bool,
+    /// Regexes matched against the first and last non-empty line of an
+    /// answer; a matching line is removed.
+    #[serde(default = "default_wrapper_patterns")]
+    pub wrapper_patterns: Vec<String>,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            autosave_every_turns: default_autosave_every_turns(),
+            privacy_mode: false,
+            auto_action_allowlist: default_auto_action_allowlist(),
+            identity_guard: None,
+            assistant_prefix: default_assistant_prefix(),
+            assistant_footer: None,
+            wrap_output: default_wrap_output(),
+            resume_window: default_resume_window(),
+            resume_summarize_older: default_resume_summarize_older(),
+            multiple_actions: default_multiple_actions(),
+            strip_wrappers: default_strip_wrappers(),
+            wrapper_patterns: default_wrapper_patterns(),
+        }
+    }
+}
+
+fn default_assistant_prefix() -> String {
+    "goldagent: ".to_string()
+}
+
+fn default_wrap_output() -> bool {
+    true
+}
+
+fn default_resume_window() -> usize {
+    20
+}
+
+fn default_resume_summarize_older() -> bool {
+    true
+}
+
+fn default_multiple_actions() -> bool {
+    true
+}
+
+fn default_strip_wrappers() -> bool {
+    true
+}
+
+fn default_wrapper_patterns() -> Vec<String> {
+    [
+        r"^(?i)(here is|here's) (the |your |my )?(final )?(answer|response|result|output)[:：.]?$",
+        r"^以下是.{0,30}[:：]$",
+        r"^(?i)(assistant|codex)[:：]$",
+        r"^(?i)(hope this helps|let me know if you (have|need) .*)[.!]?$",
+    ]
+    .into_iter()
+    .map(str::to_string)
+    .collect()
+}
+
+fn default_autosave_every_turns() -> u32 {
+    5
+}
+
+fn default_auto_action_allowlist() -> Vec<String> {
+    [
+        "cron_add",
+        "cron_list",
+        "cron_remove",
+        "hook_add_git",
+        "hook_add_p4",
+        "hook_add_hg",
+        "hook_list",
+        "hook_remove",
+        "hook_rules_new",
+    ]
+    .into_iter()
+    .map(str::to_string)
+    .collect()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchedulerConfig {
+    /// When enabled, network-dependent jobs and LLM hooks wait for basic
+    /// connectivity before running (useful when `serve` starts at login).
+    #[serde(default)]
+    pub wait_for_network: bool,
+    /// `host:port` used as the TCP connectivity probe.
+    #[serde(default = "default_network_probe_addr")]
+    pub network_probe_addr: String,
+    /// Upper bound for the whole readiness wait before running anyway.
+    #[serde(default = "default_network_wait_max_secs")]
+    pub network_wait_max_secs: u64,
+    /// `host:port` for the status HTTP server (`GET /metrics`); disabled when
+    /// unset.
+    #[serde(default)]
+    pub status_addr: Option<String>,
+    /// Random spread (±percent of `interval_secs`) applied to each hook poll
+    /// so watchers with the same interval do not poll in lockstep.
+    #[serde(default = "default_hook_jitter_percent")]
+    pub hook_jitter_percent: u8,
+    /// Start or reload `serve` after jobs/hooks change. Turn off when the
+    /// scheduler runs under an external supervisor such as systemd.
+    #[serde(default = "default_auto_start_scheduler")]
+    pub auto_start_scheduler: bool,
+    #[serde(default)]
+    pub command_policy: CommandPolicy,
+    /// Consecutive failed runs of a job before a desktop notification is
+    /// sent (plus one when it recovers); 0 disables. Jobs added with
+    /// `--onefail` notify on the first failure.
+    #[serde(default = "default_notify_after_failures")]
+    pub notify_after_failures: u32,
+    /// Scrub credentials echoed from the diff out of LLM review output
+    /// before it reaches the report file, `on_findings` and memory.
+    #[serde(default = "default_redact_review_reports")]
+    pub redact_review_reports: bool,
+}
+
+/// Programs that scheduled commands (cron jobs, hook commands and
+/// `on_findings`) may start, matched by basename against every command in a
+/// pipeline or `&&` chain. An empty `allow` permits everything not in `deny`.
+/// Wrappers (`sudo`, `doas`, `env`, `nohup`, `exec`, `command`) are checked
+/// like any program and so is the program they start, so an allowlist needs
+/// both. Builtins that start nothing (`cd`, `export`, `set`, ...) are exempt.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandPolicy {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            wait_for_network: false,
+            network_probe_addr: default_network_probe_addr(),
+            network_wait_max_secs: default_network_wait_max_secs(),
+            status_addr: None,
+            hook_jitter_percent: default_hook_jitter_percent(),
+            auto_start_scheduler: default_auto_start_scheduler(),
+            command_policy: CommandPolicy::default(),
+            notify_after_failures: default_notify_after_failures(),
+            redact_review_reports: default_redact_review_reports(),
+        }
+    }
+}
+
+fn default_network_probe_addr() -> String {
+    "1.1.1.1:443".to_string()
+}
+
+fn default_network_wait_max_secs() -> u64 {
+    120
+}
+
+fn default_hook_jitter_percent() -> u8 {
+    10
+}
+
+fn default_auto_start_scheduler() -> bool {
+    true
+}
+
+fn default_notify_after_failures() -> u32 {
+    3
+}
+
+fn default_redact_review_reports() -> bool {
+    true
+}
+
+pub fn load(paths: &AgentPaths) -> Result<AgentConfig> {
+    if !paths.config_file.exists() {
+        return Ok(AgentConfig::default());
+    }
+
+    let raw = fs::read_to_string(&paths.config_file)
+        .with_context(|| format!("读取配置文件失败: {}", paths.config_file.display()))?;
+    let cfg = serde_json::from_str::<AgentConfig>(&raw)
+        .with_context(|| format!("解析配置文件失败: {}", paths.config_file.display()))?;
+    Ok(cfg)
+}
+
+pub fn save(paths: &AgentPaths, config: &AgentConfig) -> Result<()> {
+    let raw = serde_json::to_string_pretty(config)?;
+    fs::write(&paths.config_file, format!("{raw}\n"))
+        .with_context(|| format!("写入配置文件失败: {}", paths.config_file.display()))?;
+    Ok(())
+}
@@ -1,8 +1,10 @@
 use crate::config::AgentPaths;
+use crate::fsutil;
 use anyhow::{Context, Result, bail};
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::fs;
+use std::path::Path;
 use std::process::Command;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
@@ -15,6 +17,10 @@ pub enum ConnectProvider {
     Anthropic,
     #[serde(rename = "zhipu")]
     Zhipu,
+    #[serde(rename = "ollama")]
+    Ollama,
+    #[serde(rename = "deepseek")]
+    DeepSeek,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,6 +48,10 @@ pub struct ConnectConfig {
     pub api_key: Option<String>,
     #[serde(default)]
     pub zhipu_api_type: ZhipuApiType,
+    /// Base URL of an OpenAI-compatible gateway (e.g. `http://localhost:4000/v1`);
+    /// only used by the OpenAI and Ollama providers.
+    #[serde(default)]
+    pub base_url: Option<String>,
 }
 
 impl Default for ConnectConfig {
@@ -52,10 +62,65 @@ impl Default for ConnectConfig {
             model: None,
             api_key: None,
             zhipu_api_type: ZhipuApiType::Coding,
+            base_url: None,
+        }
+    }
+}
+
+impl ConnectConfig {
+    /// Fields that differ from `self` (before) to `other` (after), as
+    /// `(field, old, new)`. API keys are masked.
+    pub fn diff(&self, other: &Self) -> Vec<(&'static str, String, String)> {
+        let optional = |value: Option<&str>| value.unwrap_or("（无）").to_string();
+        let key = |value: Option<&str>| value.map_or_else(|| "（无）".to_string(), mask_api_key);
+        let mut fields = vec![
+            (
+                "provider",
+                provider_label(&self.provider).to_string(),
+                provider_label(&other.provider).to_string(),
+            ),
+            (
+                "mode",
+                mode_label(&self.mode).to_string(),
+                mode_label(&other.mode).to_string(),
+            ),
+            (
+                "model",
+                optional(self.model.as_deref()),
+                optional(other.model.as_deref()),
+            ),
+            (
+                "api_key",
+                key(self.api_key.as_deref()),
+                key(other.api_key.as_deref()),
+            ),
+            (
+                "base_url",
+                optional(self.base_url.as_deref()),
+                optional(other.base_url.as_deref()),
+            ),
+        ];
+        if self.provider == ConnectProvider::Zhipu || other.provider == ConnectProvider::Zhipu {
+            fields.push((
+                "zhipu_api_type",
+                zhipu_api_type_label(self.zhipu_api_type).to_string(),
+                zhipu_api_type_label(other.zhipu_api_type).to_string(),
+            ));
         }
+        fields.retain(|(_, old, new)| old != new);
+        fields
     }
 }
 
+/// Renders a [`ConnectConfig::diff`] as `field: old → new, ...`.
+pub fn format_diff(changes: &[(&'static str, String, String)]) -> String {
+    changes
+        .iter()
+        .map(|(field, old, new)| format!("{field}: {old} → {new}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 pub fn load(paths: &AgentPaths) -> Result<ConnectConfig> {
     if !paths.connect_file.exists() {
         return Ok(ConnectConfig::default());
@@ -70,11 +135,95 @@ pub fn load(paths: &AgentPaths) -> Result<ConnectConfig> {
 
 pub fn save(paths: &AgentPaths, config: &ConnectConfig) -> Result<()> {
     let raw = serde_json::to_string_pretty(config)?;
-    fs::write(&paths.connect_file, format!("{raw}\n"))
+    fsutil::write_atomic(&paths.connect_file, format!("{raw}\n"))
         .with_context(|| format!("写入连接配置失败: {}", paths.connect_file.display()))?;
     Ok(())
 }
 
+/// Live model ids of one provider, as last fetched by `connect models`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedModels {
+    provider: ConnectProvider,
+    fetched_at: String,
+    models: Vec<String>,
+}
+
+/// Models cached for `provider`; empty when never fetched or unreadable.
+pub fn load_cached_models(paths: &AgentPaths, provider: &ConnectProvider) -> Vec<String> {
+    load_models_cache(paths)
+        .into_iter()
+        .find(|entry| &entry.provider == provider)
+        .map(|entry| entry.models)
+        .unwrap_or_default()
+}
+
+/// Replaces the cached model list of `provider`.
+pub fn save_cached_models(
+    paths: &AgentPaths,
+    provider: &ConnectProvider,
+    models: Vec<String>,
+) -> Result<()> {
+    let mut cache = load_models_cache(paths);
+    cache.retain(|entry| &entry.provider != provider);
+    cache.push(CachedModels {
+        provider: provider.clone(),
+        fetched_at: chrono::Utc::now().to_rfc3339(),
+        models,
+    });
+    let raw = serde_json::to_string_pretty(&cache)?;
+    fsutil::write_atomic(&paths.models_cache_file, format!("{raw}\n")).with_context(|| {
+        format!(
+            "写入模型列表缓存失败: {}",
+            paths.models_cache_file.display()
+        )
+    })
+}
+
+fn load_models_cache(paths: &AgentPaths) -> Vec<CachedModels> {
+    fs::read_to_string(&paths.models_cache_file)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+/// Writes the current connection setup to `dest` for sharing. The API key is
+/// nulled out unless `with_secrets` is set.
+pub fn export(paths: &AgentPaths, dest: &Path, with_secrets: bool) -> Result<ConnectConfig> {
+    let mut cfg = load(paths)?;
+    if !with_secrets {
+        cfg.api_key = None;
+    }
+    let raw = serde_json::to_string_pretty(&cfg)?;
+    fs::write(dest, format!("{raw}\n"))
+        .with_context(|| format!("写入导出文件失败: {}", dest.display()))?;
+    Ok(cfg)
+}
+
+/// Applies a shared connection setup from `src`. Provider, mode, model and
+/// Zhipu API type are taken from the file; an existing local API key is kept
+/// unless `overwrite_key` is set and the file carries a key.
+pub fn import(paths: &AgentPaths, src: &Path, overwrite_key: bool) -> Result<ConnectConfig> {
+    let raw =
+        fs::read_to_string(src).with_context(|| format!("读取导入文件失败: {}", src.display()))?;
+    let incoming: ConnectConfig = serde_json::from_str(&raw)
+        .with_context(|| format!("解析导入文件失败: {}", src.display()))?;
+    let local = load(paths).unwrap_or_default();
+    let has_local_key = local
+        .api_key
+        .as_deref()
+        .is_some_and(|key| !key.trim().is_empty());
+    let api_key = match incoming.api_key {
+        Some(key) if overwrite_key || !has_local_key => Some(key),
+        _ => local.api_key,
+    };
+    let merged = ConnectConfig {
+        api_key,
+        ..incoming
+    };
+    save(paths, &merged)?;
+    Ok(merged)
+}
+
 pub fn set_login(paths: &AgentPaths, model: Option<String>) -> Result<ConnectConfig> {
     let mut cfg = load(paths).unwrap_or_default();
     cfg.provider = ConnectProvider::OpenAi;
@@ -91,20 +240,25 @@ pub fn set_provider_api(
     model: Option<String>,
     zhipu_api_type: Option<ZhipuApiType>,
 ) -> Result<ConnectConfig> {
-    validate_api_key(&provider, &api_key)?;
+    let keyless_ollama = matches!(provider, ConnectProvider::Ollama) && api_key.trim().is_empty();
+    if !keyless_ollama {
+        validate_api_key(&provider, &api_key)?;
+    }
     let mut cfg = load(paths).unwrap_or_default();
     let provider_changed = cfg.provider != provider;
     cfg.provider = provider.clone();
     cfg.mode = ConnectMode::OpenAIApi;
-    cfg.api_key = Some(api_key);
+    cfg.api_key = (!keyless_ollama).then_some(api_key);
+    if provider_changed {
+        cfg.base_url = None;
+    }
     if matches!(provider, ConnectProvider::Zhipu) {
-        cfg.zhipu_api_type = zhipu_api_type.unwrap_or_else(|| {
-            if provider_changed {
-                ZhipuApiType::Coding
-            } else {
-                cfg.zhipu_api_type
-            }
-        });
+        let fallback = if provider_changed {
+            ZhipuApiType::Coding
+        } else {
+            cfg.zhipu_api_type
+        };
+        cfg.zhipu_api_type = zhipu_api_type.unwrap_or(fallback);
     }
     if let Some(model) = model {
         cfg.model = Some(normalize_model_for_provider(&provider, &model));
@@ -115,6 +269,39 @@ pub fn set_provider_api(
     Ok(cfg)
 }
 
+/// Reads an API key from `path`, trimming surrounding whitespace, so keys do
+/// not have to appear in shell history or `ps`.
+pub fn read_api_key_file(path: &Path) -> Result<String> {
+    let raw = fs::read_to_string(path)
+        .with_context(|| format!("读取 API Key 文件失败: {}", path.display()))?;
+    let key = raw.trim();
+    if key.is_empty() {
+        bail!("API Key 文件为空：{}", path.display());
+    }
+    Ok(key.to_string())
+}
+
+/// Interactive key input: `@/path/to/file` loads the key from a file, any
+/// other value is used as-is.
+pub fn resolve_api_key_input(raw: &str) -> Result<String> {
+    match raw.trim().strip_prefix('@') {
+        Some(path) => read_api_key_file(Path::new(path.trim())),
+        None => Ok(raw.trim().to_string()),
+    }
+}
+
+/// Sets the OpenAI-compatible base URL; an empty value restores the default.
+pub fn set_base_url(paths: &AgentPaths, base_url: &str) -> Result<ConnectConfig> {
+    let trimmed = base_url.trim().trim_end_matches('/');
+    if !trimmed.is_empty() && !trimmed.starts_with("http://") && !trimmed.starts_with("https://") {
+        bail!("base URL 需以 http:// 或 https:// 开头：{trimmed}");
+    }
+    let mut cfg = load(paths).unwrap_or_default();
+    cfg.base_url = (!trimmed.is_empty()).then(|| trimmed.to_string());
+    save(paths, &cfg)?;
+    Ok(cfg)
+}
+
 pub fn set_model(paths: &AgentPaths, model: Option<String>) -> Result<ConnectConfig> {
     let mut cfg = load(paths).unwrap_or_default();
     cfg.model = model.map(|m| normalize_model_for_provider(&cfg.provider, &m));
@@ -127,6 +314,8 @@ pub fn default_model_for_provider(provider: &ConnectProvider) -> &'static str {
         ConnectProvider::OpenAi => "gpt-5.2",
         ConnectProvider::Anthropic => "claude-sonnet-4-5",
         ConnectProvider::Zhipu => "glm-5",
+        ConnectProvider::Ollama => "llama3.1",
+        ConnectProvider::DeepSeek => "deepseek-chat",
     }
 }
 
@@ -149,6 +338,8 @@ pub fn normalize_model_for_provider(provider: &ConnectProvider, model: &str) ->
                 _ => trimmed.to_string(),
             }
         }
+        ConnectProvider::Ollama => trimmed.to_string(),
+        ConnectProvider::DeepSeek => trimmed.to_ascii_lowercase(),
     }
 }
 
@@ -236,6 +427,8 @@ pub fn provider_label(provider: &ConnectProvider) -> &'static str {
         ConnectProvider::OpenAi => "OpenAI",
         ConnectProvider::Anthropic => "Anthropic",
         ConnectProvider::Zhipu => "智谱",
+        ConnectProvider::Ollama => "Ollama",
+        ConnectProvider::DeepSeek => "DeepSeek",
     }
 }
 
@@ -254,19 +447,28 @@ pub fn account_label(cfg: &ConnectConfig) -> String {
         },
         ConnectMode::OpenAIApi => {
             let env_var = provider_env_var(&cfg.provider);
-            effective_api_key(cfg)
-                .as_ref()
-                .map(|key| format!("API Key({env_var}): {}", mask_api_key(key)))
-                .unwrap_or_else(|| format!("API Key 未配置（{env_var}）"))
+            match effective_api_key(cfg) {
+                Some(key) => format!("API Key({env_var}): {}", mask_api_key(&key)),
+                None if api_key_optional(cfg) => "本地 Ollama（无需 API Key）".to_string(),
+                None => format!("API Key 未配置（{env_var}）"),
+            }
         }
     }
 }
 
 pub fn effective_api_key(cfg: &ConnectConfig) -> Option<String> {
+    effective_api_key_with(cfg, |name| env::var(name).ok())
+}
+
+/// [`effective_api_key`] with the environment lookup injected.
+fn effective_api_key_with(
+    cfg: &ConnectConfig,
+    env_var: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
     cfg.api_key
         .as_ref()
         .cloned()
-        .or_else(|| env::var(provider_env_var(&cfg.provider)).ok())
+        .or_else(|| env_var(provider_env_var(&cfg.provider)))
 }
 
 pub fn validate_api_key(provider: &ConnectProvider, api_key: &str) -> Result<()> {
@@ -300,15 +502,45 @@ pub fn validate_api_key(provider: &ConnectProvider, api_key: &str) -> Result<()>
                 bail!("智谱 API Key 长度过短");
             }
         }
+        ConnectProvider::DeepSeek => {
+            if !key.starts_with("sk-") {
+                bail!("DeepSeek API Key 通常以 `sk-` 开头");
+            }
+            if key.len() < 20 {
+                bail!("DeepSeek API Key 长度过短");
+            }
+        }
+        ConnectProvider::Ollama => {}
     }
     Ok(())
 }
 
+/// Ollama on this machine needs no API key; a remote Ollama (custom
+/// `base_url`) is expected to sit behind a key-checking proxy.
+pub fn api_key_optional(cfg: &ConnectConfig) -> bool {
+    matches!(cfg.provider, ConnectProvider::Ollama)
+        && cfg.base_url.as_deref().is_none_or(is_local_url)
+}
+
+fn is_local_url(url: &str) -> bool {
+    let rest = url
+        .trim()
+        .trim_start_matches("http://")
+        .trim_start_matches("https://");
+    let authority = rest.split('/').next().unwrap_or_default();
+    let host = match authority.strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or_default(),
+        None => authority.split(':').next().unwrap_or_default(),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
+
 fn looks_like_model_name(s: &str) -> bool {
     let lower = s.to_ascii_lowercase();
     lower.starts_with("gpt-")
         || lower.starts_with("glm-")
         || lower.starts_with("claude-")
+        || lower.starts_with("deepseek-")
         || lower.contains("-codex")
         || lower.contains("-mini")
         || lower.contains("-nano")
@@ -323,6 +555,8 @@ pub fn provider_env_var(provider: &ConnectProvider) -> &'static str {
         ConnectProvider::OpenAi => "OPENAI_API_KEY",
         ConnectProvider::Anthropic => "ANTHROPIC_API_KEY",
         ConnectProvider::Zhipu => "ZHIPU_API_KEY",
+        ConnectProvider::Ollama => "OLLAMA_API_KEY",
+        ConnectProvider::DeepSeek => "DEEPSEEK_API_KEY",
     }
 }
 
@@ -372,3 +606,181 @@ fn mask_api_key(key: &str) -> String {
     let tail = &key[key.len() - visible..];
     format!("{head}****{tail}")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ConnectConfig, ConnectMode, ConnectProvider, api_key_optional, effective_api_key_with,
+        export, format_diff, import, load, read_api_key_file, resolve_api_key_input, save,
+        set_provider_api,
+    };
+    use crate::config::{self, AgentPaths};
+    use std::env;
+    use std::fs;
+    use uuid::Uuid;
+
+    fn temp_paths() -> AgentPaths {
+        let root = env::temp_dir().join(format!("goldagent-connect-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        AgentPaths::from_root(root)
+    }
+
+    #[test]
+    fn dotenv_key_becomes_effective_when_unset() {
+        let paths = temp_paths();
+        let nested = paths.root.join("project").join("src");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(
+            paths.root.join("project").join(".env"),
+            "# provider keys\nDEEPSEEK_API_KEY=sk-dotenv-0123456789abcd\n",
+        )
+        .unwrap();
+
+        let cfg = ConnectConfig {
+            provider: ConnectProvider::DeepSeek,
+            mode: ConnectMode::OpenAIApi,
+            ..ConnectConfig::default()
+        };
+        let found = config::find_dotenv(&nested);
+        assert_eq!(found, Some(paths.root.join("project").join(".env")));
+        let vars = config::read_dotenv(&found.unwrap()).unwrap();
+        let lookup = |name: &str| {
+            vars.iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        };
+        assert_eq!(
+            effective_api_key_with(&cfg, lookup).as_deref(),
+            Some("sk-dotenv-0123456789abcd")
+        );
+        let configured = ConnectConfig {
+            api_key: Some("sk-configured-0123456789ab".to_string()),
+            ..cfg
+        };
+        assert_eq!(
+            effective_api_key_with(&configured, lookup).as_deref(),
+            Some("sk-configured-0123456789ab")
+        );
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn loads_api_key_from_file_reference() {
+        let paths = temp_paths();
+        let key_file = paths.root.join("openai.key");
+        fs::write(&key_file, "  sk-from-file-0123456789abcd\n").unwrap();
+
+        let key = resolve_api_key_input(&format!("@{}", key_file.display())).unwrap();
+        assert_eq!(key, "sk-from-file-0123456789abcd");
+        assert_eq!(read_api_key_file(&key_file).unwrap(), key);
+        assert_eq!(resolve_api_key_input(" sk-inline ").unwrap(), "sk-inline");
+
+        fs::write(&key_file, "\n").unwrap();
+        assert!(read_api_key_file(&key_file).is_err());
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn diff_lists_changed_provider_and_model() {
+        let before = ConnectConfig {
+            mode: ConnectMode::OpenAIApi,
+            model: Some("gpt-5.2".to_string()),
+            ..ConnectConfig::default()
+        };
+        let after = ConnectConfig {
+            provider: ConnectProvider::Anthropic,
+            model: Some("claude-sonnet-4-5".to_string()),
+            ..before.clone()
+        };
+        let changes = before.diff(&after);
+        assert_eq!(
+            changes,
+            [
+                ("provider", "OpenAI".to_string(), "Anthropic".to_string()),
+                (
+                    "model",
+                    "gpt-5.2".to_string(),
+                    "claude-sonnet-4-5".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            format_diff(&changes),
+            "provider: OpenAI → Anthropic, model: gpt-5.2 → claude-sonnet-4-5"
+        );
+        assert!(after.diff(&after).is_empty());
+    }
+
+    #[test]
+    fn ollama_key_is_optional_only_for_local_hosts() {
+        let mut cfg = ConnectConfig {
+            provider: ConnectProvider::Ollama,
+            mode: ConnectMode::OpenAIApi,
+            ..ConnectConfig::default()
+        };
+        assert!(api_key_optional(&cfg));
+        cfg.base_url = Some("http://127.0.0.1:11434/v1".to_string());
+        assert!(api_key_optional(&cfg));
+        cfg.base_url = Some("https://ollama.example.com/v1".to_string());
+        assert!(!api_key_optional(&cfg));
+
+        let openai = ConnectConfig::default();
+        assert!(!api_key_optional(&openai));
+    }
+
+    #[test]
+    fn connects_ollama_without_api_key() {
+        let paths = temp_paths();
+        let cfg =
+            set_provider_api(&paths, ConnectProvider::Ollama, String::new(), None, None).unwrap();
+        assert_eq!(cfg.model.as_deref(), Some("llama3.1"));
+        assert!(cfg.api_key.is_none());
+        assert!(
+            set_provider_api(&paths, ConnectProvider::OpenAi, String::new(), None, None).is_err()
+        );
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn export_strips_key_and_import_keeps_local_key() {
+        let source = temp_paths();
+        save(
+            &source,
+            &ConnectConfig {
+                provider: ConnectProvider::Anthropic,
+                mode: ConnectMode::OpenAIApi,
+                model: Some("claude-sonnet-4-5".to_string()),
+                api_key: Some("sk-ant-shared-secret".to_string()),
+                ..ConnectConfig::default()
+            },
+        )
+        .unwrap();
+        let exported_file = source.root.join("team-connect.json");
+        let exported = export(&source, &exported_file, false).unwrap();
+        assert!(exported.api_key.is_none());
+        let raw = fs::read_to_string(&exported_file).unwrap();
+        assert!(raw.contains("\"api_key\": null"));
+
+        let target = temp_paths();
+        save(
+            &target,
+            &ConnectConfig {
+                api_key: Some("sk-local-key".to_string()),
+                ..ConnectConfig::default()
+            },
+        )
+        .unwrap();
+        import(&target, &exported_file, false).unwrap();
+
+        let merged = load(&target).unwrap();
+        assert_eq!(merged.provider, ConnectProvider::Anthropic);
+        assert_eq!(merged.model.as_deref(), Some("claude-sonnet-4-5"));
+        assert_eq!(merged.api_key.as_deref(), Some("sk-local-key"));
+
+        let _ = fs::remove_dir_all(&source.root);
+        let _ = fs::remove_dir_all(&target.root);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::config::AgentPaths;
+use crate::config::{self, AgentPaths};
 use crate::scheduler;
 use anyhow::{Context, Result, anyhow};
 use std::fs::OpenOptions;
@@ -10,6 +10,19 @@ use std::time::Duration;
 pub enum SchedulerStatus {
     Started(u32),
     Reloaded(u32),
+    /// `scheduler.auto_start_scheduler` is off; nothing was spawned.
+    Disabled,
+}
+
+pub const MANUAL_RELOAD_HINT: &str = "已关闭自动启动调度服务（scheduler.auto_start_scheduler=false），请手动重启 `goldagent serve` 或通过进程管理器（如 systemd）重载以应用变更。";
+
+/// Like [`ensure_scheduler_running`], but honors
+/// `scheduler.auto_start_scheduler` from `config.json`.
+pub fn auto_start_scheduler(paths: &AgentPaths) -> Result<SchedulerStatus> {
+    if !config::load(paths)?.scheduler.auto_start_scheduler {
+        return Ok(SchedulerStatus::Disabled);
+    }
+    ensure_scheduler_running(paths)
 }
 
 pub fn ensure_scheduler_running(paths: &AgentPaths) -> Result<SchedulerStatus> {
@@ -72,8 +85,11 @@ fn wait_until_started(paths: &AgentPaths) -> Result<u32> {
     ))
 }
 
+/// Waits out the scheduler's shutdown grace period, during which it lets
+/// running jobs finish after SIGTERM.
 fn wait_until_stopped(paths: &AgentPaths) -> Result<()> {
-    for _ in 0..40 {
+    let polls = (scheduler::SHUTDOWN_GRACE + Duration::from_secs(4)).as_millis() / 100;
+    for _ in 0..polls {
         if scheduler::running_pid(paths)?.is_none() {
             return Ok(());
         }
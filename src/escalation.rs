@@ -0,0 +1,103 @@
+use crate::config::AgentPaths;
+use crate::fsutil::{self, FileLock};
+use crate::jobs::Job;
+use crate::notify;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fs;
+
+/// Notification owed after a job run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Escalation {
+    /// The job just reached `threshold` consecutive failures.
+    Failing { failures: u32 },
+    /// The job succeeded after having escalated.
+    Recovered { failures: u32 },
+}
+
+/// Updates the consecutive-failure count of job `id` and decides whether to
+/// notify. Only the run that reaches `threshold` escalates, so a job that
+/// keeps failing alerts once; 0 turns escalation off.
+pub fn record_outcome(
+    failures: &mut BTreeMap<String, u32>,
+    id: &str,
+    success: bool,
+    threshold: u32,
+) -> Option<Escalation> {
+    if success {
+        let previous = failures.remove(id).unwrap_or(0);
+        return (threshold > 0 && previous >= threshold)
+            .then_some(Escalation::Recovered { failures: previous });
+    }
+    let count = failures.entry(id.to_string()).or_insert(0);
+    *count = count.saturating_add(1);
+    (threshold > 0 && *count == threshold).then_some(Escalation::Failing { failures: *count })
+}
+
+/// Records the outcome of a scheduled run of `job` and sends the
+/// notification it calls for, if any.
+pub fn track_job_run(paths: &AgentPaths, job: &Job, success: bool, threshold: u32) -> Result<()> {
+    let path = &paths.job_failures_file;
+    let escalation = {
+        let _lock = FileLock::acquire(path)?;
+        let mut failures: BTreeMap<String, u32> = fs::read_to_string(path)
+            .ok()
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default();
+        let escalation = record_outcome(&mut failures, &job.id, success, threshold);
+        fsutil::write_atomic(path, serde_json::to_string_pretty(&failures)?)?;
+        escalation
+    };
+    match escalation {
+        Some(Escalation::Failing { failures }) => {
+            notify::send_notification(
+                "GoldAgent 任务失败",
+                &format!("任务 {} 已连续失败 {failures} 次", job.name),
+            );
+        }
+        Some(Escalation::Recovered { failures }) => {
+            notify::send_notification(
+                "GoldAgent 任务恢复",
+                &format!("任务 {} 在连续失败 {failures} 次后恢复成功", job.name),
+            );
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Escalation, record_outcome};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn notifies_once_at_threshold_and_again_on_recovery() {
+        let mut failures = BTreeMap::new();
+        assert_eq!(record_outcome(&mut failures, "j1", false, 3), None);
+        assert_eq!(record_outcome(&mut failures, "j1", false, 3), None);
+        assert_eq!(failures["j1"], 2);
+        assert_eq!(
+            record_outcome(&mut failures, "j1", false, 3),
+            Some(Escalation::Failing { failures: 3 })
+        );
+        assert_eq!(record_outcome(&mut failures, "j1", false, 3), None);
+        assert_eq!(
+            record_outcome(&mut failures, "j1", true, 3),
+            Some(Escalation::Recovered { failures: 4 })
+        );
+        assert!(!failures.contains_key("j1"));
+
+        // A transient failure below the threshold recovers silently.
+        assert_eq!(record_outcome(&mut failures, "j2", false, 3), None);
+        assert_eq!(record_outcome(&mut failures, "j2", true, 3), None);
+
+        assert_eq!(
+            record_outcome(&mut failures, "j3", false, 1),
+            Some(Escalation::Failing { failures: 1 })
+        );
+        assert_eq!(record_outcome(&mut failures, "j4", false, 0), None);
+        assert_eq!(failures["j4"], 1);
+        assert_eq!(record_outcome(&mut failures, "j4", true, 0), None);
+    }
+}
@@ -0,0 +1,123 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Exclusive advisory lock on `<path>.lock`, released on drop. Used so the
+/// foreground chat/run process and the background scheduler never interleave
+/// read-modify-write cycles on the same shared file.
+pub struct FileLock {
+    #[cfg_attr(not(unix), allow(dead_code))]
+    file: File,
+}
+
+impl FileLock {
+    pub fn acquire(path: &Path) -> Result<Self> {
+        let lock_path = lock_path_for(path);
+        if let Some(parent) = lock_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .with_context(|| format!("无法打开锁文件: {}", lock_path.display()))?;
+        lock_exclusive(&file)
+            .with_context(|| format!("无法获取文件锁: {}", lock_path.display()))?;
+        Ok(Self { file })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        use std::os::unix::io::AsRawFd;
+        let _ = unsafe { libc::flock(self.file.as_raw_fd(), libc::LOCK_UN) };
+    }
+}
+
+#[cfg(unix)]
+fn lock_exclusive(file: &File) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    loop {
+        let rc = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) };
+        if rc == 0 {
+            return Ok(());
+        }
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn lock_exclusive(_file: &File) -> std::io::Result<()> {
+    Ok(())
+}
+
+fn lock_path_for(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    path.with_file_name(name)
+}
+
+/// Writes `contents` to a sibling temp file and renames it over `path`, so
+/// readers never observe a half-written file.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(format!(".{}.tmp", Uuid::new_v4()));
+    let tmp_path = path.with_file_name(tmp_name);
+    fs::write(&tmp_path, contents)
+        .with_context(|| format!("写入临时文件失败: {}", tmp_path.display()))?;
+    if let Err(err) = fs::rename(&tmp_path, path) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(err).with_context(|| format!("替换文件失败: {}", path.display()));
+    }
+    Ok(())
+}
+
+/// Appends `contents` with a single write while holding the file lock, so
+/// concurrent appenders never interleave their entries.
+pub fn append_locked(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    append_with_lock(path, path, contents)
+}
+
+/// Like [`append_locked`], but holds the [`FileLock`] of `lock` instead, for
+/// files whose directory should not get a `.lock` file.
+pub fn append_with_lock(path: &Path, lock: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    use std::io::Write;
+    let _lock = FileLock::acquire(lock)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("打开文件失败: {}", path.display()))?;
+    file.write_all(contents.as_ref())
+        .with_context(|| format!("追加写入失败: {}", path.display()))?;
+    Ok(())
+}
+
+/// Finds `name` (plus the platform executable suffix) in a PATH-style list.
+pub fn find_executable(name: &str, path_var: &OsStr) -> Option<PathBuf> {
+    let file_name = format!("{name}{}", env::consts::EXE_SUFFIX);
+    env::split_paths(path_var)
+        .map(|dir| dir.join(&file_name))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
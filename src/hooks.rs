@@ -1,12 +1,19 @@
 use crate::config::AgentPaths;
+use crate::fsutil::{self, FileLock};
 use crate::shell;
 use anyhow::{Context, Result, bail};
 use chrono::Utc;
+use clap::ValueEnum;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::future::Future;
 use std::path::Path;
+use std::sync::LazyLock;
 use uuid::Uuid;
 
+const HG_DEFAULT_REF: &str = "tip";
+
 const RULES_TEMPLATE: &str = r#"# LLM 代码审查规则
 # 本文件由 `goldagent hook rules-new` 生成，可随时编辑，无需重建 hook。
 
@@ -38,7 +45,7 @@ const RULES_TEMPLATE: &str = r#"# LLM 代码审查规则
 - 循环内的重复计算
 
 ## 输出格式要求
-- 每个问题单独列出，格式：`文件名:行号 — 问题描述 — 修改建议`
+- 每个问题单独列出，格式：`文件名:行号 — 严重程度（严重/警告/建议）：问题描述 — 修改建议`
 - 按严重程度排序：严重 > 警告 > 建议
 - 若无问题，直接回复：**未发现明显问题。**
 - 结尾给出一句总体评价
@@ -49,10 +56,10 @@ pub fn write_rules_template(path: &str) -> Result<()> {
     if p.exists() {
         bail!("文件已存在：{path}，请删除后重试或指定其他路径。");
     }
-    if let Some(parent) = p.parent() {
-        if !parent.as_os_str().is_empty() {
-            fs::create_dir_all(parent)?;
-        }
+    if let Some(parent) = p.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::create_dir_all(parent)?;
     }
     fs::write(p, RULES_TEMPLATE)?;
     Ok(())
@@ -63,6 +70,7 @@ pub fn write_rules_template(path: &str) -> Result<()> {
 pub enum HookSource {
     Git,
     P4,
+    Hg,
 }
 
 impl HookSource {
@@ -70,6 +78,7 @@ impl HookSource {
         match self {
             Self::Git => "git",
             Self::P4 => "p4",
+            Self::Hg => "hg",
         }
     }
 }
@@ -95,6 +104,59 @@ pub struct Hook {
     /// `<target>/goldagent-review.md` when absent.
     #[serde(default)]
     pub report_file: Option<String>,
+    /// Command run after an LLM review that reports findings at or above
+    /// `on_findings_min_severity`. Supports the `${HOOK_*}` placeholders plus
+    /// `${FINDINGS}` (shell-quoted finding lines), `${FINDINGS_COUNT}` and
+    /// `${REPORT_FILE}`.
+    #[serde(default)]
+    pub on_findings: Option<String>,
+    #[serde(default)]
+    pub on_findings_min_severity: FindingSeverity,
+    /// Directory `command` and `on_findings` run in. Git and Mercurial hooks
+    /// default to the repository (see [`Hook::command_cwd`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+}
+
+impl Hook {
+    /// Working directory for the hook's shell commands: the explicit `cwd`,
+    /// else the repository for Git and Mercurial hooks. P4 targets are depot
+    /// paths, so P4 hooks keep the process's working directory.
+    pub fn command_cwd(&self) -> Option<&Path> {
+        match (&self.cwd, &self.source) {
+            (Some(cwd), _) => Some(Path::new(cwd)),
+            (None, HookSource::Git | HookSource::Hg) => Some(Path::new(&self.target)),
+            (None, HookSource::P4) => None,
+        }
+    }
+}
+
+/// Severity levels used by the review rules template (严重 > 警告 > 建议).
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingSeverity {
+    Suggestion,
+    Warning,
+    #[default]
+    Critical,
+}
+
+impl FindingSeverity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Suggestion => "suggestion",
+            Self::Warning => "warning",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReviewFinding {
+    pub severity: FindingSeverity,
+    pub line: String,
 }
 
 pub fn load_hooks(paths: &AgentPaths) -> Result<Vec<Hook>> {
@@ -118,6 +180,7 @@ pub fn add_git_hook(
 ) -> Result<Hook> {
     validate_interval(interval_secs)?;
 
+    let _lock = FileLock::acquire(&paths.hooks_file)?;
     let mut hooks = load_hooks(paths)?;
     let id = Uuid::new_v4().to_string();
     let hook = Hook {
@@ -133,6 +196,9 @@ pub fn add_git_hook(
         created_at: Utc::now().to_rfc3339(),
         rules_file,
         report_file,
+        on_findings: None,
+        on_findings_min_severity: FindingSeverity::default(),
+        cwd: None,
     };
     hooks.push(hook.clone());
     save_hooks(paths, &hooks)?;
@@ -152,6 +218,7 @@ pub fn add_p4_hook(
 ) -> Result<Hook> {
     validate_interval(interval_secs)?;
 
+    let _lock = FileLock::acquire(&paths.hooks_file)?;
     let mut hooks = load_hooks(paths)?;
     let id = Uuid::new_v4().to_string();
     let hook = Hook {
@@ -167,6 +234,50 @@ pub fn add_p4_hook(
         created_at: Utc::now().to_rfc3339(),
         rules_file,
         report_file,
+        on_findings: None,
+        on_findings_min_severity: FindingSeverity::default(),
+        cwd: None,
+    };
+    hooks.push(hook.clone());
+    save_hooks(paths, &hooks)?;
+    Ok(hook)
+}
+
+/// Adds a Mercurial hook; `reference` defaults to `tip` and is stored so
+/// `${HOOK_REF}` renders the revision actually polled.
+#[allow(clippy::too_many_arguments)]
+pub fn add_hg_hook(
+    paths: &AgentPaths,
+    repo: String,
+    reference: Option<String>,
+    interval_secs: u64,
+    command: String,
+    name: Option<String>,
+    retry_max: u8,
+    rules_file: Option<String>,
+    report_file: Option<String>,
+) -> Result<Hook> {
+    validate_interval(interval_secs)?;
+
+    let _lock = FileLock::acquire(&paths.hooks_file)?;
+    let mut hooks = load_hooks(paths)?;
+    let id = Uuid::new_v4().to_string();
+    let hook = Hook {
+        id: id.clone(),
+        name: name.unwrap_or_else(|| format!("hook-{id}")),
+        source: HookSource::Hg,
+        target: repo,
+        reference: Some(reference.unwrap_or_else(|| HG_DEFAULT_REF.to_string())),
+        interval_secs,
+        command,
+        enabled: true,
+        retry_max,
+        created_at: Utc::now().to_rfc3339(),
+        rules_file,
+        report_file,
+        on_findings: None,
+        on_findings_min_severity: FindingSeverity::default(),
+        cwd: None,
     };
     hooks.push(hook.clone());
     save_hooks(paths, &hooks)?;
@@ -174,6 +285,7 @@ pub fn add_p4_hook(
 }
 
 pub fn remove_hook(paths: &AgentPaths, id: &str) -> Result<bool> {
+    let _lock = FileLock::acquire(&paths.hooks_file)?;
     let mut hooks = load_hooks(paths)?;
     let before = hooks.len();
     hooks.retain(|hook| hook.id != id);
@@ -185,15 +297,34 @@ pub fn remove_hook(paths: &AgentPaths, id: &str) -> Result<bool> {
 }
 
 pub async fn read_signature(hook: &Hook) -> Result<String> {
+    read_signature_with(hook, |cmd| async move {
+        Ok(shell::run_shell_command(&cmd, false).await?.stdout)
+    })
+    .await
+}
+
+/// [`read_signature`] with the shell command runner injected; `run` returns
+/// the command's stdout.
+async fn read_signature_with<F, Fut>(hook: &Hook, run: F) -> Result<String>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    let reference = hook.reference.as_deref();
     match hook.source {
-        HookSource::Git => read_git_signature(&hook.target, hook.reference.as_deref()).await,
-        HookSource::P4 => read_p4_signature(&hook.target).await,
+        HookSource::Git => read_git_signature(&hook.target, reference, run).await,
+        HookSource::P4 => read_p4_signature(&hook.target, run).await,
+        HookSource::Hg => read_hg_signature(&hook.target, reference, run).await,
     }
 }
 
 pub fn render_command_template(hook: &Hook, previous: &str, current: &str) -> String {
+    render_template(hook, &hook.command, previous, current)
+}
+
+fn render_template(hook: &Hook, template: &str, previous: &str, current: &str) -> String {
     let reference = hook.reference.as_deref().unwrap_or("HEAD");
-    hook.command
+    template
         .replace("${HOOK_ID}", &hook.id)
         .replace("${HOOK_NAME}", &hook.name)
         .replace("${HOOK_SOURCE}", hook.source.as_str())
@@ -203,10 +334,92 @@ pub fn render_command_template(hook: &Hook, previous: &str, current: &str) -> St
         .replace("${HOOK_CURRENT}", current)
 }
 
+/// Sets (or clears, with `None`) the follow-up command of a review hook.
+pub fn set_on_findings(
+    paths: &AgentPaths,
+    id: &str,
+    command: Option<String>,
+    min_severity: FindingSeverity,
+) -> Result<Option<Hook>> {
+    let _lock = FileLock::acquire(&paths.hooks_file)?;
+    let mut hooks = load_hooks(paths)?;
+    let Some(hook) = hooks.iter_mut().find(|hook| hook.id == id) else {
+        return Ok(None);
+    };
+    hook.on_findings = command;
+    hook.on_findings_min_severity = min_severity;
+    let updated = hook.clone();
+    save_hooks(paths, &hooks)?;
+    Ok(Some(updated))
+}
+
+/// A finding line of the rules template, `文件名:行号 — 严重程度：描述 — 建议`,
+/// optionally as a list item or with the location and severity in markdown.
+/// The severity is optional, for rules files written against the older
+/// `文件名:行号 — 问题描述 — 修改建议` template.
+static FINDING_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^(?:[-*+]\s+|\d+[.)]\s+)?[`*]*[^\s`*]+:\d+(?:-\d+)?[`*]*\s*(?:—|–|--|-)(?:\s*[*\[【]*(严重|警告|建议|critical|warning|suggestion)[*\]】]*\s*(?:[：:(（—–-]|$))?",
+    )
+    .expect("valid finding pattern")
+});
+
+/// Extracts findings from an LLM review: lines in the template's
+/// `文件名:行号 — 严重程度：…` format, by the severity in the second field.
+/// A finding without one counts as a suggestion. Severity words elsewhere
+/// (e.g. "未发现严重问题") are not findings.
+pub fn parse_review_findings(review: &str) -> Vec<ReviewFinding> {
+    review
+        .lines()
+        .map(str::trim)
+        .filter_map(|line| {
+            let captures = FINDING_LINE.captures(line)?;
+            let word = captures.get(1).map(|m| m.as_str().to_lowercase());
+            let severity = match word.as_deref() {
+                Some("严重" | "critical") => FindingSeverity::Critical,
+                Some("警告" | "warning") => FindingSeverity::Warning,
+                _ => FindingSeverity::Suggestion,
+            };
+            Some(ReviewFinding {
+                severity,
+                line: line.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Returns the rendered `on_findings` command when the review reports
+/// findings at or above the hook's threshold.
+pub fn render_on_findings_command(
+    hook: &Hook,
+    review: &str,
+    previous: &str,
+    current: &str,
+    report_file: &Path,
+) -> Option<String> {
+    let template = hook.on_findings.as_deref()?;
+    let findings = parse_review_findings(review)
+        .into_iter()
+        .filter(|finding| finding.severity >= hook.on_findings_min_severity)
+        .map(|finding| finding.line)
+        .collect::<Vec<_>>();
+    if findings.is_empty() {
+        return None;
+    }
+    let rendered = render_template(hook, template, previous, current)
+        .replace("${FINDINGS_COUNT}", &findings.len().to_string())
+        .replace(
+            "${REPORT_FILE}",
+            &shell::quote(&report_file.display().to_string()),
+        )
+        .replace("${FINDINGS}", &shell::quote(&findings.join("\n")));
+    Some(rendered)
+}
+
+/// Callers hold the `hooks.json` [`FileLock`] across load and save.
 fn save_hooks(paths: &AgentPaths, hooks: &[Hook]) -> Result<()> {
     let serialized = serde_json::to_string_pretty(hooks)?;
-    fs::write(&paths.hooks_file, serialized)?;
-    Ok(())
+    fsutil::write_atomic(&paths.hooks_file, serialized)
 }
 
 fn validate_interval(interval_secs: u64) -> Result<()> {
@@ -216,43 +429,104 @@ fn validate_interval(interval_secs: u64) -> Result<()> {
     Ok(())
 }
 
-async fn read_git_signature(repo: &str, reference: Option<&str>) -> Result<String> {
+async fn read_git_signature<F, Fut>(repo: &str, reference: Option<&str>, run: F) -> Result<String>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
     let reference = reference.unwrap_or("HEAD");
     let cmd = format!(
         "git -C {} rev-parse {}",
-        shell_quote(repo),
-        shell_quote(reference)
+        shell::quote(repo),
+        shell::quote(reference)
     );
-    let output = shell::run_shell_command(&cmd, false).await?;
-    let signature = output.stdout.trim();
+    let stdout = run(cmd).await?;
+    let signature = stdout.trim();
     if signature.is_empty() {
         bail!("git rev-parse returned empty output for repo `{repo}`");
     }
     Ok(signature.to_string())
 }
 
-async fn read_p4_signature(depot: &str) -> Result<String> {
-    let cmd = format!("p4 changes -m 1 {}", shell_quote(depot));
-    let output = shell::run_shell_command(&cmd, false).await?;
-    let Some(line) = output
-        .stdout
-        .lines()
-        .map(str::trim)
-        .find(|line| !line.is_empty())
-    else {
+async fn read_hg_signature<F, Fut>(repo: &str, reference: Option<&str>, run: F) -> Result<String>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    let stdout = run(hg_id_command(repo, reference)).await?;
+    let signature = stdout.trim();
+    if signature.is_empty() {
+        bail!("hg id returned empty output for repo `{repo}`");
+    }
+    Ok(signature.to_string())
+}
+
+/// `hg id -i` takes the revision through `-r`; a bare argument would be read
+/// as a repository source.
+fn hg_id_command(repo: &str, reference: Option<&str>) -> String {
+    format!(
+        "hg -R {} id -i -r {}",
+        shell::quote(repo),
+        shell::quote(reference.unwrap_or(HG_DEFAULT_REF))
+    )
+}
+
+async fn read_p4_signature<F, Fut>(depot: &str, run: F) -> Result<String>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    let stdout = run(format!("p4 changes -m 1 {}", shell::quote(depot))).await?;
+    let Some(line) = stdout.lines().map(str::trim).find(|line| !line.is_empty()) else {
         bail!("p4 changes returned empty output for depot `{depot}`");
     };
     Ok(line.to_string())
 }
 
-fn shell_quote(raw: &str) -> String {
-    let escaped = raw.replace('\'', "'\"'\"'");
-    format!("'{escaped}'")
-}
-
 #[cfg(test)]
 mod tests {
-    use super::{Hook, HookSource, render_command_template};
+    use super::{
+        FindingSeverity, Hook, HookSource, add_hg_hook, hg_id_command, load_hooks,
+        parse_review_findings, read_signature_with, render_command_template,
+        render_on_findings_command,
+    };
+    use crate::config::AgentPaths;
+    use anyhow::bail;
+    use std::cell::RefCell;
+    use std::fs;
+    use std::path::Path;
+    use uuid::Uuid;
+
+    #[test]
+    fn hg_hooks_poll_tip_by_default_and_render_their_ref() {
+        let root = std::env::temp_dir().join(format!("goldagent-hooks-test-{}", Uuid::new_v4()));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(&paths.root).unwrap();
+
+        let hook = add_hg_hook(
+            &paths,
+            "/srv/hg repo".to_string(),
+            None,
+            30,
+            "echo ${HOOK_SOURCE} ${HOOK_REF} ${HOOK_CURRENT}".to_string(),
+            None,
+            1,
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(hook.source.as_str(), "hg");
+        assert_eq!(hook.reference.as_deref(), Some("tip"));
+        assert_eq!(load_hooks(&paths).unwrap()[0].source.as_str(), "hg");
+        assert_eq!(render_command_template(&hook, "a1", "b2"), "echo hg tip b2");
+        assert_eq!(
+            hg_id_command(&hook.target, hook.reference.as_deref()),
+            "hg -R '/srv/hg repo' id -i -r 'tip'"
+        );
+        assert_eq!(hook.command_cwd(), Some(Path::new("/srv/hg repo")));
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
 
     #[test]
     fn renders_hook_placeholders() {
@@ -269,8 +543,121 @@ mod tests {
             created_at: "2025-01-01T00:00:00Z".to_string(),
             rules_file: None,
             report_file: None,
+            on_findings: None,
+            on_findings_min_severity: FindingSeverity::Critical,
+            cwd: None,
         };
         let out = render_command_template(&hook, "a", "b");
         assert_eq!(out, "echo git a -> b");
     }
+
+    #[test]
+    fn dispatches_on_findings_only_for_serious_reviews() {
+        let hook = Hook {
+            id: "h2".to_string(),
+            name: "review".to_string(),
+            source: HookSource::Git,
+            target: "/tmp/repo".to_string(),
+            reference: None,
+            interval_secs: 30,
+            command: String::new(),
+            enabled: true,
+            retry_max: 1,
+            created_at: "2025-01-01T00:00:00Z".to_string(),
+            rules_file: Some("review-rules.md".to_string()),
+            report_file: None,
+            on_findings: Some("notify ${HOOK_CURRENT} ${FINDINGS_COUNT} ${FINDINGS}".to_string()),
+            on_findings_min_severity: FindingSeverity::Critical,
+            cwd: None,
+        };
+        let report = Path::new("/tmp/repo/goldagent-review.md");
+
+        let serious = "src/db.rs:42 — 严重：SQL 拼接存在注入风险 — 使用参数化查询\n\
+src/lib.rs:7 — 建议：提取常量\n总体评价：需要修复后再合并。";
+        let command = render_on_findings_command(&hook, serious, "a1", "b2", report).unwrap();
+        assert!(command.starts_with("notify b2 1 '"));
+        assert!(command.contains("SQL 拼接存在注入风险"));
+        assert!(!command.contains("提取常量"));
+
+        let clean = "**未发现明显问题。**\n总体评价：改动清晰。";
+        assert!(render_on_findings_command(&hook, clean, "a1", "b2", report).is_none());
+    }
+
+    #[test]
+    fn review_findings_follow_the_template_format() {
+        let review = "- `src/db.rs:42` — **警告**：连接未关闭 — 使用 RAII\n\
+2. src/api.rs:10-12 - critical: token logged - redact it\n\
+src/lib.rs:7 — 建议：提取常量\n\
+未发现严重问题，仅有建议。\n\
+严重 > 警告 > 建议\n\
+总体评价：没有严重问题。";
+        let findings = parse_review_findings(review);
+        let severities = findings.iter().map(|f| f.severity).collect::<Vec<_>>();
+        assert_eq!(
+            severities,
+            vec![
+                FindingSeverity::Warning,
+                FindingSeverity::Critical,
+                FindingSeverity::Suggestion
+            ]
+        );
+        assert!(parse_review_findings("未发现严重问题。").is_empty());
+    }
+
+    #[test]
+    fn findings_in_the_old_template_count_as_suggestions() {
+        let review = "- src/db.rs:42 — 连接未关闭 — 使用 RAII
+src/api.rs:10 — 警告信息被吞掉 — 记录日志
+总体评价：整体可用。";
+        let findings = parse_review_findings(review);
+        assert_eq!(findings.len(), 2);
+        assert!(
+            findings
+                .iter()
+                .all(|f| f.severity == FindingSeverity::Suggestion)
+        );
+        assert_eq!(findings[0].line, "- src/db.rs:42 — 连接未关闭 — 使用 RAII");
+    }
+
+    #[tokio::test]
+    async fn git_signature_reads_rev_parse_through_the_runner() {
+        let hook = Hook {
+            id: "h3".to_string(),
+            name: "repo".to_string(),
+            source: HookSource::Git,
+            target: "/tmp/my repo".to_string(),
+            reference: Some("main".to_string()),
+            interval_secs: 30,
+            command: "true".to_string(),
+            enabled: true,
+            retry_max: 1,
+            created_at: "2025-01-01T00:00:00Z".to_string(),
+            rules_file: None,
+            report_file: None,
+            on_findings: None,
+            on_findings_min_severity: FindingSeverity::Critical,
+            cwd: None,
+        };
+        let commands = RefCell::new(Vec::new());
+        let signature = read_signature_with(&hook, |cmd| {
+            commands.borrow_mut().push(cmd);
+            async { Ok("3f9c2a1d8e\n".to_string()) }
+        })
+        .await
+        .unwrap();
+        assert_eq!(signature, "3f9c2a1d8e");
+        assert_eq!(
+            commands.into_inner(),
+            ["git -C '/tmp/my repo' rev-parse 'main'"]
+        );
+
+        let err = read_signature_with(&hook, |_| async { bail!("fatal: not a git repository") })
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not a git repository"));
+        let err = read_signature_with(&hook, |_| async { Ok(String::new()) })
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("empty output"));
+    }
 }
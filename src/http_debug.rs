@@ -0,0 +1,136 @@
+use crate::redact::REDACTED;
+use chrono::Local;
+use reqwest::StatusCode;
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const SECRET_HEADERS: [&str; 3] = ["authorization", "x-api-key", "api-key"];
+
+/// Opt-in (`GOLDAGENT_DEBUG_HTTP=1`) log of raw provider traffic, written to
+/// `http-debug.log` in the logs directory with credentials scrubbed.
+#[derive(Debug, Clone)]
+pub struct HttpDebugLog {
+    path: PathBuf,
+    headers: HeaderMap,
+}
+
+impl HttpDebugLog {
+    /// Returns a logger writing under `logs_dir` when debugging is enabled.
+    /// `headers` are the client's default headers, which reqwest does not
+    /// expose per request.
+    pub fn from_env(logs_dir: &Path, headers: &HeaderMap) -> Option<Self> {
+        let enabled = env::var("GOLDAGENT_DEBUG_HTTP")
+            .map(|value| matches!(value.trim(), "1" | "true" | "yes" | "on"))
+            .unwrap_or(false);
+        if !enabled {
+            return None;
+        }
+        Some(Self {
+            path: logs_dir.join("http-debug.log"),
+            headers: headers.clone(),
+        })
+    }
+
+    pub fn log_request(&self, endpoint: &str, body: &impl Serialize) {
+        let body = serde_json::to_value(body).unwrap_or(serde_json::Value::Null);
+        self.append(&render_request(endpoint, &self.headers, body));
+    }
+
+    pub fn log_response(&self, endpoint: &str, status: StatusCode, body: &str) {
+        self.append(&render_response(endpoint, status, body));
+    }
+
+    fn append(&self, entry: &str) {
+        if let Ok(mut file) = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+        {
+            let _ = file.write_all(entry.as_bytes());
+        }
+    }
+}
+
+fn render_request(endpoint: &str, headers: &HeaderMap, mut body: serde_json::Value) -> String {
+    scrub_json(&mut body);
+    let mut out = format!(
+        "=== {} request POST {endpoint}\n",
+        Local::now().to_rfc3339()
+    );
+    for (name, value) in headers {
+        let value = if SECRET_HEADERS.contains(&name.as_str()) {
+            REDACTED.to_string()
+        } else {
+            value.to_str().unwrap_or("<binary>").to_string()
+        };
+        out.push_str(&format!("{name}: {value}\n"));
+    }
+    out.push('\n');
+    out.push_str(&serde_json::to_string_pretty(&body).unwrap_or_default());
+    out.push_str("\n\n");
+    out
+}
+
+fn render_response(endpoint: &str, status: StatusCode, body: &str) -> String {
+    let body = match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(mut value) => {
+            scrub_json(&mut value);
+            serde_json::to_string_pretty(&value).unwrap_or_default()
+        }
+        Err(_) => body.to_string(),
+    };
+    format!(
+        "=== {} response {status} {endpoint}\n{body}\n\n",
+        Local::now().to_rfc3339()
+    )
+}
+
+fn scrub_json(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                let key = key.to_ascii_lowercase();
+                if key.contains("api_key") || key.contains("apikey") || key == "authorization" {
+                    *item = serde_json::Value::String(REDACTED.to_string());
+                } else {
+                    scrub_json(item);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(scrub_json),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_request;
+    use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderValue};
+
+    #[test]
+    fn redacts_auth_header_and_api_keys_in_request_log() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_static("Bearer sk-live-secret"),
+        );
+        let body = serde_json::json!({
+            "model": "gpt-4.1-mini",
+            "api_key": "sk-body-secret",
+            "messages": [{"role": "user", "content": "hi"}]
+        });
+
+        let logged = render_request("https://api.example.com/v1", &headers, body);
+
+        assert!(logged.contains("authorization: [REDACTED]"));
+        assert!(logged.contains("content-type: application/json"));
+        assert!(logged.contains("\"model\": \"gpt-4.1-mini\""));
+        assert!(!logged.contains("sk-live-secret"));
+        assert!(!logged.contains("sk-body-secret"));
+    }
+}
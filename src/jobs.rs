@@ -1,6 +1,8 @@
 use crate::config::AgentPaths;
-use anyhow::{Context, Result, bail};
-use chrono::Utc;
+use crate::fsutil::{self, FileLock};
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Local, LocalResult, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
 use cron::Schedule;
 use serde::{Deserialize, Serialize};
 use std::fs;
@@ -16,6 +18,25 @@ pub struct Job {
     pub enabled: bool,
     pub retry_max: u8,
     pub created_at: String,
+    /// IANA timezone (e.g. `Asia/Shanghai`) the schedule is evaluated in;
+    /// the machine's local timezone when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// Fires a single time and is then removed; set by [`add_job`] for `at@`
+    /// and `at:` schedules and the only one-shot marker the scheduler reads.
+    #[serde(default)]
+    pub once: bool,
+    /// Also runs once when `serve` starts, before waiting for the schedule.
+    #[serde(default)]
+    pub run_on_start: bool,
+    /// Absolute directory the command runs in; the `serve` process's working
+    /// directory when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// Notify on the first failed run instead of after
+    /// `scheduler.notify_after_failures` consecutive ones.
+    #[serde(default)]
+    pub onefail: bool,
 }
 
 pub fn load_jobs(paths: &AgentPaths) -> Result<Vec<Job>> {
@@ -25,32 +46,75 @@ pub fn load_jobs(paths: &AgentPaths) -> Result<Vec<Job>> {
     Ok(jobs)
 }
 
-pub fn add_job(
-    paths: &AgentPaths,
-    schedule: String,
-    command: String,
-    name: Option<String>,
-    retry_max: u8,
-) -> Result<Job> {
+/// What [`add_job`] needs to create a job; the id, `created_at` and `once`
+/// are filled in when it is saved.
+#[derive(Debug, Clone, Default)]
+pub struct NewJob {
+    pub schedule: String,
+    pub command: String,
+    pub name: Option<String>,
+    pub retry_max: u8,
+    pub timezone: Option<String>,
+    pub run_on_start: bool,
+    pub cwd: Option<String>,
+    pub onefail: bool,
+}
+
+pub fn add_job(paths: &AgentPaths, new_job: NewJob) -> Result<Job> {
+    let NewJob {
+        schedule,
+        command,
+        name,
+        retry_max,
+        timezone,
+        run_on_start,
+        cwd,
+        onefail,
+    } = new_job;
     validate_schedule(&schedule)?;
+    if let Some(tz) = &timezone {
+        parse_timezone(tz)?;
+        if schedule.trim().starts_with(RFC3339_PREFIX) {
+            bail!("`at:` schedules carry their own UTC offset; drop --tz.");
+        }
+    }
+    let cwd = cwd.map(|dir| resolve_cwd(&dir)).transpose()?;
 
+    let _lock = FileLock::acquire(&paths.jobs_file)?;
     let mut jobs = load_jobs(paths)?;
     let id = Uuid::new_v4().to_string();
     let job = Job {
         id: id.clone(),
         name: name.unwrap_or_else(|| format!("job-{id}")),
+        once: is_one_shot(&schedule),
         schedule,
         command,
         enabled: true,
         retry_max,
         created_at: Utc::now().to_rfc3339(),
+        timezone,
+        run_on_start,
+        cwd,
+        onefail,
     };
     jobs.push(job.clone());
     save_jobs(paths, &jobs)?;
     Ok(job)
 }
 
+/// Makes `dir` absolute so the job runs in the same place no matter where
+/// `serve` is started from.
+fn resolve_cwd(dir: &str) -> Result<String> {
+    let path =
+        fs::canonicalize(dir).with_context(|| format!("Working directory {dir} does not exist"))?;
+    if !path.is_dir() {
+        bail!("Working directory {dir} is not a directory");
+    }
+    Ok(path.to_string_lossy().into_owned())
+}
+
 pub fn remove_job(paths: &AgentPaths, id: &str) -> Result<bool> {
+    let _lock = FileLock::acquire(&paths.jobs_file)?;
     let mut jobs = load_jobs(paths)?;
     let before = jobs.len();
     jobs.retain(|job| job.id != id);
@@ -61,14 +125,64 @@ pub fn remove_job(paths: &AgentPaths, id: &str) -> Result<bool> {
     Ok(removed)
 }
 
+/// Flips `enabled` on the job with `id`. Returns `None` if it doesn't exist.
+pub fn set_job_enabled(paths: &AgentPaths, id: &str, enabled: bool) -> Result<Option<Job>> {
+    let _lock = FileLock::acquire(&paths.jobs_file)?;
+    let mut jobs = load_jobs(paths)?;
+    let Some(job) = jobs.iter_mut().find(|job| job.id == id) else {
+        return Ok(None);
+    };
+    job.enabled = enabled;
+    let updated = job.clone();
+    save_jobs(paths, &jobs)?;
+    Ok(Some(updated))
+}
+
+/// Callers hold the `jobs.json` [`FileLock`] across load and save.
 fn save_jobs(paths: &AgentPaths, jobs: &[Job]) -> Result<()> {
     let serialized = serde_json::to_string_pretty(jobs)?;
-    fs::write(&paths.jobs_file, serialized)?;
-    Ok(())
+    fsutil::write_atomic(&paths.jobs_file, serialized)
+}
+
+const ONE_SHOT_PREFIX: &str = "at@";
+const ONE_SHOT_FORMAT: &str = "%Y-%m-%d %H:%M";
+const RFC3339_PREFIX: &str = "at:";
+
+/// `at@YYYY-MM-DD HH:MM` schedule that fires exactly once at `at`.
+pub fn one_shot_schedule(at: DateTime<Local>) -> String {
+    format!("{ONE_SHOT_PREFIX}{}", at.format(ONE_SHOT_FORMAT))
+}
+
+fn is_one_shot(schedule: &str) -> bool {
+    let schedule = schedule.trim();
+    schedule.starts_with(ONE_SHOT_PREFIX) || schedule.starts_with(RFC3339_PREFIX)
+}
+
+/// Parses the time of an `at:` schedule: full RFC 3339 (converted to local
+/// time), or `YYYY-MM-DDTHH:MM[:SS]` taken as wall-clock time.
+fn parse_at_time(raw: &str) -> Result<NaiveDateTime> {
+    let raw = raw.trim();
+    if let Ok(at) = DateTime::parse_from_rfc3339(raw) {
+        return Ok(at.with_timezone(&Local).naive_local());
+    }
+    NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M"))
+        .with_context(|| {
+            format!("Invalid time `{raw}`. Expected RFC 3339, e.g. 2025-06-01T09:00:00+08:00.")
+        })
 }
 
 pub fn normalize_schedule(expr: &str) -> Result<String> {
     let expr = expr.trim();
+    if let Some(raw) = expr.strip_prefix(ONE_SHOT_PREFIX) {
+        let at = NaiveDateTime::parse_from_str(raw.trim(), ONE_SHOT_FORMAT)
+            .with_context(|| format!("Invalid time `{raw}`. Expected YYYY-MM-DD HH:MM."))?;
+        return Ok(format!("{}", at.format("0 %M %H %d %m * %Y")));
+    }
+    if let Some(raw) = expr.strip_prefix(RFC3339_PREFIX) {
+        let at = parse_at_time(raw)?;
+        return Ok(format!("{}", at.format("%S %M %H %d %m * %Y")));
+    }
     if let Some(time) = expr.strip_prefix("daily@") {
         let (hour, minute) = parse_hh_mm(time)?;
         return Ok(format!("0 {minute} {hour} * * *"));
@@ -83,17 +197,235 @@ pub fn normalize_schedule(expr: &str) -> Result<String> {
         5 => Ok(format!("0 {expr}")),
         6 => Ok(expr.to_string()),
         _ => bail!(
-            "Invalid schedule `{expr}`. Expected: 5-field cron (min hour day month weekday), 6-field cron (sec min hour day month weekday), `daily@HH:MM`, or `weekdays@HH:MM`."
+            "Invalid schedule `{expr}`. Expected: 5-field cron (min hour day month weekday), 6-field cron (sec min hour day month weekday), `daily@HH:MM`, `weekdays@HH:MM`, `at@YYYY-MM-DD HH:MM`, or `at:<rfc3339>`."
         ),
     }
 }
 
+/// Describes a schedule in words, e.g. `0 0 13 * * 1-5` → `每个工作日 13:00`.
+/// Falls back to the normalized expression for shapes it does not know.
+pub fn describe_schedule(expr: &str) -> String {
+    let Ok(normalized) = normalize_schedule(expr) else {
+        return format!("无法解析：{}", expr.trim());
+    };
+    let fields = normalized.split_whitespace().collect::<Vec<_>>();
+    describe_fields(&fields).unwrap_or_else(|| format!("cron `{normalized}`"))
+}
+
+fn describe_fields(fields: &[&str]) -> Option<String> {
+    let [sec, min, hour, dom, month, dow] = fields.get(..6)? else {
+        return None;
+    };
+    let (time, is_clock) = describe_time(sec, min, hour)?;
+    if let Some(year) = fields.get(6) {
+        let date = format!("{year}-{:02}-{:02}", number(month, 12)?, number(dom, 31)?);
+        return is_clock.then(|| format!("{date} {time}（仅一次）"));
+    }
+    let days = describe_days(dom, month, dow)?;
+    Some(match (days.is_empty(), is_clock) {
+        (true, true) => format!("每天 {time}"),
+        (true, false) => time,
+        (false, _) => format!("{days} {time}"),
+    })
+}
+
+/// Returns the time-of-day part and whether it is a plain clock time.
+fn describe_time(sec: &str, min: &str, hour: &str) -> Option<(String, bool)> {
+    if let Some(hours) = numbers(hour, 23) {
+        let minute = number(min, 59)?;
+        let second = number(sec, 59)?;
+        let clocks = hours
+            .iter()
+            .map(|hour| match second {
+                0 => format!("{hour:02}:{minute:02}"),
+                _ => format!("{hour:02}:{minute:02}:{second:02}"),
+            })
+            .collect::<Vec<_>>();
+        return Some((clocks.join("、"), true));
+    }
+    if sec != "0" {
+        return match (sec.strip_prefix("*/"), min, hour) {
+            (Some(every), "*", "*") => Some((format!("每 {every} 秒"), false)),
+            _ if (sec, min, hour) == ("*", "*", "*") => Some(("每秒".to_string(), false)),
+            _ => None,
+        };
+    }
+    let within = match hour.split_once('-') {
+        Some((start, end)) => format!("{} 点至 {} 点", number(start, 23)?, number(end, 23)?),
+        None if hour == "*" => String::new(),
+        None => {
+            let every = hour.strip_prefix("*/")?;
+            let minute = number(min, 59)?;
+            return Some((format!("每 {every} 小时的第 {minute} 分"), false));
+        }
+    };
+    let text = match (min, min.strip_prefix("*/")) {
+        ("*", _) => "每分钟".to_string(),
+        (_, Some(every)) => format!("每 {every} 分钟"),
+        _ => format!("每小时第 {} 分", number(min, 59)?),
+    };
+    Some((format!("{within}{text}"), false))
+}
+
+/// Returns the day part, empty when the schedule runs every day.
+fn describe_days(dom: &str, month: &str, dow: &str) -> Option<String> {
+    match (dom, month, dow) {
+        ("*", "*", "*") => Some(String::new()),
+        ("*", "*", _) => describe_weekdays(dow),
+        (_, "*", "*") => {
+            let days = numbers(dom, 31)?;
+            Some(format!("每月 {} 日", join_numbers(&days)))
+        }
+        (_, _, "*") => Some(format!(
+            "每年 {} 月 {} 日",
+            number(month, 12)?,
+            number(dom, 31)?
+        )),
+        _ => None,
+    }
+}
+
+/// Day-of-week names; numbers follow standard cron, 0 and 7 being Sunday.
+const WEEKDAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+fn describe_weekdays(dow: &str) -> Option<String> {
+    let dow = dow.to_ascii_uppercase();
+    let index = |day: &str| match day.parse::<usize>() {
+        Ok(number) => (number <= 7).then_some(number % 7),
+        Err(_) => WEEKDAY_NAMES.iter().position(|name| *name == day),
+    };
+    let chinese = ["日", "一", "二", "三", "四", "五", "六"];
+    if let Some((start, end)) = dow.split_once('-') {
+        return match (index(start)?, index(end)?) {
+            (1, 5) => Some("每个工作日".to_string()),
+            (start, end) => Some(format!("每周{}至周{}", chinese[start], chinese[end])),
+        };
+    }
+    let days = dow.split(',').map(index).collect::<Option<Vec<_>>>()?;
+    if days.len() == 2 && days.contains(&0) && days.contains(&6) {
+        return Some("每个周末".to_string());
+    }
+    let days = days
+        .iter()
+        .map(|day| format!("周{}", chinese[*day]))
+        .collect::<Vec<_>>();
+    Some(format!("每{}", days.join("、")))
+}
+
+fn number(raw: &str, max: u32) -> Option<u32> {
+    raw.parse::<u32>().ok().filter(|value| *value <= max)
+}
+
+fn numbers(raw: &str, max: u32) -> Option<Vec<u32>> {
+    raw.split(',').map(|item| number(item, max)).collect()
+}
+
+fn join_numbers(values: &[u32]) -> String {
+    values
+        .iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join("、")
+}
+
 pub fn validate_schedule(expr: &str) -> Result<()> {
     let normalized = normalize_schedule(expr)?;
     Schedule::from_str(&normalized).with_context(|| format!("Invalid cron expression: {expr}"))?;
     Ok(())
 }
 
+pub fn parse_timezone(name: &str) -> Result<Tz> {
+    name.trim().parse::<Tz>().map_err(|_| {
+        anyhow!("Unknown timezone `{name}`. Expected an IANA name like Asia/Shanghai.")
+    })
+}
+
+/// Fire times of `schedule` after `now`, evaluated in the job's timezone and
+/// converted back to local time for sleeping.
+pub fn fires_after<'a>(
+    job: &Job,
+    schedule: &'a Schedule,
+    now: DateTime<Local>,
+) -> Result<Box<dyn Iterator<Item = DateTime<Local>> + Send + 'a>> {
+    Ok(match &job.timezone {
+        Some(name) => {
+            let tz = parse_timezone(name)?;
+            Box::new(wall_clock_fires(schedule, tz, now))
+        }
+        None => Box::new(wall_clock_fires(schedule, Local, now)),
+    })
+}
+
+/// Evaluates `schedule` on the wall clock of `tz` (through UTC, which has no
+/// DST) and maps each time back: a time repeated by a DST fall-back fires once
+/// at its first occurrence, and a time skipped by a spring-forward gap fires
+/// when the gap ends instead of not at all.
+fn wall_clock_fires<'a, Z>(
+    schedule: &'a Schedule,
+    tz: Z,
+    now: DateTime<Local>,
+) -> impl Iterator<Item = DateTime<Local>> + Send + 'a
+where
+    Z: TimeZone + Send + 'a,
+    Z::Offset: Send,
+{
+    let wall_now = Utc.from_utc_datetime(&now.with_timezone(&tz).naive_local());
+    let mut last = now;
+    schedule
+        .after(&wall_now)
+        .filter_map(move |at| resolve_wall_clock(&tz, at.naive_utc()))
+        .filter(move |at| {
+            let later = *at > last;
+            if later {
+                last = *at;
+            }
+            later
+        })
+}
+
+fn resolve_wall_clock<Z: TimeZone>(tz: &Z, wall: NaiveDateTime) -> Option<DateTime<Local>> {
+    let resolved = match tz.from_local_datetime(&wall) {
+        LocalResult::Single(at) | LocalResult::Ambiguous(at, _) => at,
+        LocalResult::None => (1..=180).find_map(|minutes| {
+            tz.from_local_datetime(&(wall + chrono::Duration::minutes(minutes)))
+                .earliest()
+        })?,
+    };
+    Some(resolved.with_timezone(&Local))
+}
+
+/// One upcoming fire of a job, used to build a cross-job timeline.
+#[derive(Debug, Clone)]
+pub struct UpcomingFire {
+    pub at: DateTime<Local>,
+    pub job_id: String,
+    pub job_name: String,
+}
+
+/// Next `limit` fire times after `now` for every enabled job, merged into a
+/// single chronological timeline.
+pub fn upcoming_fires(
+    jobs: &[Job],
+    limit: usize,
+    now: DateTime<Local>,
+) -> Result<Vec<UpcomingFire>> {
+    let mut fires = Vec::new();
+    for job in jobs.iter().filter(|job| job.enabled) {
+        let normalized = normalize_schedule(&job.schedule)?;
+        let schedule = Schedule::from_str(&normalized)
+            .with_context(|| format!("Invalid cron expression: {}", job.schedule))?;
+        for at in fires_after(job, &schedule, now)?.take(limit) {
+            fires.push(UpcomingFire {
+                at,
+                job_id: job.id.clone(),
+                job_name: job.name.clone(),
+            });
+        }
+    }
+    fires.sort_by_key(|fire| fire.at);
+    Ok(fires)
+}
+
 fn parse_hh_mm(raw: &str) -> Result<(u8, u8)> {
     let Some((hour_raw, minute_raw)) = raw.split_once(':') else {
         bail!("Invalid time `{raw}`. Expected HH:MM.");
@@ -116,7 +448,174 @@ fn parse_hh_mm(raw: &str) -> Result<(u8, u8)> {
 
 #[cfg(test)]
 mod tests {
-    use super::normalize_schedule;
+    use super::{
+        Job, NewJob, add_job, describe_schedule, fires_after, load_jobs, normalize_schedule,
+        parse_timezone, set_job_enabled, upcoming_fires,
+    };
+    use crate::config::AgentPaths;
+    use chrono::{Local, TimeZone};
+    use cron::Schedule;
+    use std::fs;
+    use std::str::FromStr;
+    use uuid::Uuid;
+
+    fn job(id: &str, schedule: &str) -> Job {
+        Job {
+            id: id.to_string(),
+            name: id.to_string(),
+            schedule: schedule.to_string(),
+            command: "echo hi".to_string(),
+            enabled: true,
+            retry_max: 1,
+            created_at: "2025-01-01T00:00:00Z".to_string(),
+            timezone: None,
+            once: false,
+            run_on_start: false,
+            cwd: None,
+            onefail: false,
+        }
+    }
+
+    #[test]
+    fn dst_changes_neither_skip_nor_repeat_fires() {
+        let tz = parse_timezone("America/New_York").unwrap();
+        let mut fall_back = job("fall", "30 1 * * *");
+        fall_back.timezone = Some("America/New_York".to_string());
+        let schedule =
+            Schedule::from_str(&normalize_schedule(&fall_back.schedule).unwrap()).unwrap();
+        let start = tz.with_ymd_and_hms(2025, 11, 2, 0, 0, 0).unwrap();
+        let fires = fires_after(&fall_back, &schedule, start.with_timezone(&Local))
+            .unwrap()
+            .take(2)
+            .map(|at| at.with_timezone(&tz).to_rfc3339())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fires,
+            ["2025-11-02T01:30:00-04:00", "2025-11-03T01:30:00-05:00"]
+        );
+
+        let mut spring = job("spring", "*/20 2 * * *");
+        spring.timezone = Some("America/New_York".to_string());
+        let schedule = Schedule::from_str(&normalize_schedule(&spring.schedule).unwrap()).unwrap();
+        let start = tz.with_ymd_and_hms(2025, 3, 9, 0, 0, 0).unwrap();
+        let first = fires_after(&spring, &schedule, start.with_timezone(&Local))
+            .unwrap()
+            .next()
+            .unwrap();
+        assert_eq!(
+            first.with_timezone(&tz).to_rfc3339(),
+            "2025-03-09T03:00:00-04:00"
+        );
+        let after_gap = fires_after(&spring, &schedule, first)
+            .unwrap()
+            .next()
+            .unwrap();
+        assert_eq!(
+            after_gap.with_timezone(&tz).to_rfc3339(),
+            "2025-03-10T02:00:00-04:00"
+        );
+    }
+
+    #[test]
+    fn at_schedule_fires_exactly_once() {
+        let out = normalize_schedule("at:2025-06-01T09:00:00Z").expect("normalize should succeed");
+        let expected = chrono::Utc
+            .with_ymd_and_hms(2025, 6, 1, 9, 0, 0)
+            .unwrap()
+            .with_timezone(&Local);
+        assert_eq!(out, expected.format("%S %M %H %d %m * %Y").to_string());
+        assert_eq!(
+            normalize_schedule("at:2025-06-01T09:00").unwrap(),
+            "00 00 09 01 06 * 2025"
+        );
+
+        let mut once = job("once", "at:2025-06-01T09:00:00Z");
+        once.once = true;
+        let before = expected - chrono::Duration::hours(1);
+        let fires = upcoming_fires(&[once], 5, before).unwrap();
+        assert_eq!(fires.len(), 1);
+        assert_eq!(fires[0].at, expected);
+        assert!(normalize_schedule("at:tomorrow").is_err());
+    }
+
+    #[test]
+    fn evaluates_schedule_in_job_timezone() {
+        let mut shanghai = job("sh", "daily@09:00");
+        shanghai.timezone = Some("Asia/Shanghai".to_string());
+        let now = chrono::Utc
+            .with_ymd_and_hms(2026, 1, 1, 0, 30, 0)
+            .unwrap()
+            .with_timezone(&Local);
+
+        let fires = upcoming_fires(&[shanghai], 1, now).expect("fires should compute");
+        // 09:00 in Shanghai is 01:00 UTC, whatever the machine's timezone.
+        assert_eq!(
+            fires[0].at.with_timezone(&chrono::Utc),
+            chrono::Utc.with_ymd_and_hms(2026, 1, 1, 1, 0, 0).unwrap()
+        );
+        assert!(super::parse_timezone("Mars/Olympus").is_err());
+    }
+
+    #[test]
+    fn concurrent_add_job_keeps_every_job() {
+        let root = std::env::temp_dir().join(format!("goldagent-jobs-test-{}", Uuid::new_v4()));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(&paths.root).unwrap();
+
+        let workers = (0..16)
+            .map(|i| {
+                let paths = paths.clone();
+                std::thread::spawn(move || {
+                    add_job(
+                        &paths,
+                        NewJob {
+                            schedule: "0 9 * * *".to_string(),
+                            command: format!("echo {i}"),
+                            retry_max: 1,
+                            ..NewJob::default()
+                        },
+                    )
+                    .unwrap()
+                })
+            })
+            .collect::<Vec<_>>();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let raw = fs::read_to_string(&paths.jobs_file).unwrap();
+        let saved = serde_json::from_str::<Vec<Job>>(&raw).expect("jobs.json stays valid JSON");
+        assert_eq!(saved.len(), 16);
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn toggles_job_enabled_and_persists() {
+        let root = std::env::temp_dir().join(format!("goldagent-jobs-test-{}", Uuid::new_v4()));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(&paths.root).unwrap();
+        let added = add_job(
+            &paths,
+            NewJob {
+                schedule: "0 9 * * *".to_string(),
+                command: "echo hi".to_string(),
+                retry_max: 1,
+                ..NewJob::default()
+            },
+        )
+        .unwrap();
+
+        let disabled = set_job_enabled(&paths, &added.id, false).unwrap().unwrap();
+        assert!(!disabled.enabled);
+        assert!(!load_jobs(&paths).unwrap()[0].enabled);
+
+        set_job_enabled(&paths, &added.id, true).unwrap();
+        assert!(load_jobs(&paths).unwrap()[0].enabled);
+        assert!(set_job_enabled(&paths, "missing", true).unwrap().is_none());
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
 
     #[test]
     fn normalizes_five_field_cron() {
@@ -142,9 +641,45 @@ mod tests {
         assert_eq!(out, "0 0 13 * * 1-5");
     }
 
+    #[test]
+    fn describes_schedules_in_words() {
+        for (expr, expected) in [
+            ("0 0 13 * * 1-5", "每个工作日 13:00"),
+            ("weekdays@13:00", "每个工作日 13:00"),
+            ("daily@09:30", "每天 09:30"),
+            ("0 9,18 * * *", "每天 09:00、18:00"),
+            ("30 8 * * 6,0", "每个周末 08:30"),
+            ("0 10 * * 1,3,5", "每周一、周三、周五 10:00"),
+            ("0 10 * * 2-4", "每周二至周四 10:00"),
+            ("0 */15 * * * *", "每 15 分钟"),
+            ("*/30 9-18 * * 1-5", "每个工作日 9 点至 18 点每 30 分钟"),
+            ("5 * * * *", "每小时第 5 分"),
+            ("0 8 1,15 * *", "每月 1、15 日 08:00"),
+            ("0 8 24 12 *", "每年 12 月 24 日 08:00"),
+            ("at@2026-10-18 14:30", "2026-10-18 14:30（仅一次）"),
+            ("0 0 9 L * *", "cron `0 0 9 L * *`"),
+            ("not a schedule", "无法解析：not a schedule"),
+        ] {
+            assert_eq!(describe_schedule(expr), expected, "{expr}");
+        }
+    }
+
     #[test]
     fn rejects_invalid_shortcut_time() {
         let err = normalize_schedule("daily@25:00").expect_err("normalize should fail");
         assert!(err.to_string().contains("Invalid hour"));
     }
+
+    #[test]
+    fn merges_upcoming_fires_chronologically() {
+        let now = Local.with_ymd_and_hms(2025, 1, 1, 8, 0, 0).unwrap();
+        let jobs = vec![job("noon", "daily@12:00"), job("nine", "daily@09:00")];
+        let fires = upcoming_fires(&jobs, 2, now).expect("timeline should build");
+        let order = fires
+            .iter()
+            .map(|fire| fire.job_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(order, vec!["nine", "noon", "nine", "noon"]);
+        assert!(fires.windows(2).all(|w| w[0].at <= w[1].at));
+    }
 }
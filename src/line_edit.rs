@@ -0,0 +1,135 @@
+use crate::render::display_width;
+
+/// Text and cursor of the chat prompt line, kept apart from the terminal so
+/// mid-line editing can be tested directly. `cursor` is a byte offset that
+/// always sits on a char boundary.
+#[derive(Debug, Clone, Default)]
+pub struct LineEditor {
+    text: String,
+    cursor: usize,
+}
+
+impl LineEditor {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn into_text(self) -> String {
+        self.text
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn at_end(&self) -> bool {
+        self.cursor == self.text.len()
+    }
+
+    /// Replaces the whole line (e.g. after a completion) and moves the cursor
+    /// to the end.
+    pub fn set(&mut self, text: String) {
+        self.cursor = text.len();
+        self.text = text;
+    }
+
+    pub fn insert(&mut self, piece: &str) {
+        self.text.insert_str(self.cursor, piece);
+        self.cursor += piece.len();
+    }
+
+    /// Deletes the character before the cursor.
+    pub fn backspace(&mut self) {
+        if let Some(prev) = self.prev_boundary() {
+            self.text.replace_range(prev..self.cursor, "");
+            self.cursor = prev;
+        }
+    }
+
+    /// Deletes the character under the cursor.
+    pub fn delete(&mut self) {
+        if let Some(next) = self.next_boundary() {
+            self.text.replace_range(self.cursor..next, "");
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        if let Some(prev) = self.prev_boundary() {
+            self.cursor = prev;
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        if let Some(next) = self.next_boundary() {
+            self.cursor = next;
+        }
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    /// 1-based terminal column of the cursor after `prompt`, counting CJK
+    /// characters as double width, for `\x1b[<n>G`.
+    pub fn cursor_column(&self, prompt: &str) -> usize {
+        display_width(prompt) + display_width(&self.text[..self.cursor]) + 1
+    }
+
+    fn prev_boundary(&self) -> Option<usize> {
+        self.text[..self.cursor]
+            .char_indices()
+            .next_back()
+            .map(|(idx, _)| idx)
+    }
+
+    fn next_boundary(&self) -> Option<usize> {
+        self.text[self.cursor..]
+            .chars()
+            .next()
+            .map(|ch| self.cursor + ch.len_utf8())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LineEditor;
+
+    #[test]
+    fn edits_in_the_middle_of_chinese_text() {
+        let mut editor = LineEditor::default();
+        editor.insert("帮我总结今天的日志");
+        for _ in 0..5 {
+            editor.move_left();
+        }
+        assert_eq!(editor.cursor_column("> "), 2 + 8 + 1);
+
+        editor.backspace();
+        editor.backspace();
+        editor.insert("整理");
+        assert_eq!(editor.text(), "帮我整理今天的日志");
+        assert!(!editor.at_end());
+
+        editor.delete();
+        editor.insert("昨");
+        assert_eq!(editor.text(), "帮我整理昨天的日志");
+
+        editor.home();
+        editor.backspace();
+        editor.insert("请");
+        editor.end();
+        editor.move_right();
+        editor.insert("。");
+        assert_eq!(editor.text(), "请帮我整理昨天的日志。");
+        assert!(editor.at_end());
+
+        editor.set("/model ".to_string());
+        editor.move_left();
+        editor.delete();
+        assert_eq!(editor.text(), "/model");
+        assert_eq!(editor.cursor_column(""), 7);
+    }
+}
@@ -1,54 +1,186 @@
+mod backup;
 mod chat_actions;
 mod cli;
 mod config;
 mod connect;
 mod daemon;
+mod escalation;
+mod fsutil;
 mod hooks;
+mod http_debug;
 mod jobs;
+mod line_edit;
 mod memory;
+mod metrics;
+mod multiline;
 mod notify;
+mod picker;
+mod plugin;
 mod provider;
+mod redact;
+mod reminders;
+mod render;
+mod response_format;
+mod run_log;
 mod scheduler;
+mod session;
 mod shell;
 mod skills;
+mod status_watch;
+mod transcript;
 mod usage;
 
-use anyhow::{Result, bail};
-use chat_actions::{execute_local_action, extract_local_action_from_response};
-use clap::Parser;
-use cli::{Cli, Commands, CronCommand, HookCommand, SkillCommand};
-use config::AgentPaths;
+use anyhow::{Context, Result, anyhow, bail};
+use chat_actions::{
+    ActionRoute, ChatLocalAction, execute_local_action, is_local_action_line, route_action,
+    run_turn,
+};
+use clap::{CommandFactory, Parser};
+use cli::{
+    Cli, Commands, ConnectCommand, CronCommand, HookCommand, MemoryCommand, MemoryExportFormat,
+    ProfileArgs, RemindCommand, SchedulerCommand, SchedulerPidCommand, SkillCommand, UsageCommand,
+    UsageGrouping,
+};
+use config::{AgentPaths, ChatConfig};
+use line_edit::LineEditor;
+use multiline::{LineInput, MultilineBuffer};
+use picker::{PickerOutcome, PickerState};
 use provider::{ChatMessage, ProviderClient};
+use render::{BlockPrinter, BlockStyle};
+use response_format::{
+    JsonSchema, ResponseConstraints, ValidationFailed, WrapperFilter, run_with_self_correction,
+};
+use status_watch::StatusWatch;
 use std::cmp;
-use std::io::{self, Read, Write};
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn main() -> Result<()> {
+    // SAFETY: `.env` is applied while the process is still single-threaded,
+    // before the tokio runtime starts its workers.
+    if let Ok(cwd) = env::current_dir()
+        && let Err(err) = unsafe { config::load_dotenv(&cwd) }
+    {
+        eprintln!("警告：{err:#}");
+    }
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run())
+}
 
-#[tokio::main]
-async fn main() -> Result<()> {
+async fn run() -> Result<()> {
     let cli = Cli::parse();
+    if let Some(Commands::Completions { shell }) = &cli.command {
+        clap_complete::generate(*shell, &mut Cli::command(), "goldagent", &mut io::stdout());
+        return Ok(());
+    }
     let paths = AgentPaths::new()?;
     paths.ensure()?;
     memory::ensure_capability_declarations(&paths)?;
 
-    let command = cli.command.unwrap_or(Commands::Chat { model: None });
+    if cli.no_color {
+        render::disable_color();
+    }
+    let command = cli.command.unwrap_or(Commands::Chat {
+        model: None,
+        resume: false,
+        max_words: None,
+        format: None,
+        profile: ProfileArgs::default(),
+    });
+    if cli.verbose && matches!(command, Commands::Chat { .. } | Commands::Run { .. }) {
+        note_active_scheduler(&paths);
+    }
 
     match command {
         Commands::Init => {
             println!("GoldAgent 已初始化：{}", paths.root.display());
         }
-        Commands::Chat { model } => {
-            chat_loop(&paths, model).await?;
+        Commands::Chat {
+            model,
+            resume,
+            max_words,
+            format,
+            profile,
+        } => {
+            let constraints = ResponseConstraints {
+                max_words,
+                format,
+                ..ResponseConstraints::default()
+            };
+            chat_loop(&paths, model, &profile, resume, constraints).await?;
+        }
+        Commands::Run {
+            task,
+            model,
+            max_words,
+            format,
+            json_schema,
+            profile,
+            append,
+            stop,
+            seed,
+            thinking_budget,
+            show_thinking,
+            stream,
+        } => {
+            let schema = json_schema
+                .map(|path| JsonSchema::load(Path::new(&path)))
+                .transpose()?;
+            let constraints = ResponseConstraints {
+                max_words,
+                format,
+                schema: schema.map(Arc::new),
+                native_schema: false,
+            };
+            let options = RunOptions {
+                stop,
+                seed,
+                thinking_budget,
+                show_thinking,
+                stream,
+            };
+            run_task(
+                &paths,
+                &task,
+                model,
+                &profile,
+                constraints,
+                append.as_deref(),
+                options,
+            )
+            .await?;
         }
-        Commands::Run { task, model } => {
-            run_task(&paths, &task, model).await?;
+        Commands::Remind {
+            command: Some(command),
+            ..
+        } => handle_remind_command(&paths, command)?,
+        Commands::Remind { message, .. } => {
+            run_remind_command(&paths, message.as_deref().unwrap_or_default())?;
         }
-        Commands::Remind { message } => {
-            run_remind_command(&paths, &message)?;
+        Commands::Serve { strict, once } => {
+            scheduler::serve(paths, strict, once).await?;
         }
-        Commands::Serve => {
-            scheduler::serve(paths).await?;
+        Commands::Shell {
+            cmd, explain: true, ..
+        } => {
+            let client = ProviderClient::from_paths(&paths, None)?;
+            let explanation =
+                shell::explain_command(
+                    &cmd,
+                    |messages| async move { client.chat(&messages).await },
+                )
+                .await?;
+            println!("{}", explanation.trim_end());
+            memory::append_short_term(&paths, "shell.explain", &format!("$ {cmd}"))?;
         }
-        Commands::Shell { cmd, force } => {
-            let output = shell::run_shell_command(&cmd, force).await?;
+        Commands::Shell { cmd, force, .. } => {
+            let output = shell::run_manual_command(&paths, &cmd, force).await?;
             if !output.stdout.trim().is_empty() {
                 println!("{}", output.stdout.trim_end());
             }
@@ -57,34 +189,186 @@ async fn main() -> Result<()> {
             }
             memory::append_short_term(&paths, "shell.manual", &format!("$ {cmd}"))?;
         }
-        Commands::Connect { command } => provider::handle_connect_command(&paths, command)?,
-        Commands::Cron { command } => handle_cron_command(&paths, command)?,
-        Commands::Hook { command } => handle_hook_command(&paths, command)?,
+        // Printed before the data directory is set up.
+        Commands::Completions { .. } => {}
+        Commands::Connect {
+            command:
+                ConnectCommand::Status {
+                    watch: true,
+                    interval,
+                    probe,
+                },
+        } => watch_connect_status(&paths, interval, probe).await?,
+        Commands::Connect { command } => provider::handle_connect_command(&paths, command).await?,
+        Commands::Cron { command } => handle_cron_command(&paths, command).await?,
+        Commands::Hook { command } => handle_hook_command(&paths, command).await?,
         Commands::Skill { command } => handle_skill_command(&paths, command).await?,
+        Commands::Memory { command } => handle_memory_command(&paths, command)?,
+        Commands::Usage {
+            command: Some(command),
+            ..
+        } => handle_usage_command(&paths, command)?,
+        Commands::Usage {
+            command: None,
+            since,
+            by,
+            json,
+            prices,
+        } => print_usage_report(&paths, since.as_deref(), by, json, prices.as_deref())?,
+        Commands::Scheduler { command } => handle_scheduler_command(&paths, command)?,
+        Commands::Backup { path, no_secrets } => {
+            let count = backup::backup(&paths, Path::new(&path), no_secrets)?;
+            println!("已备份 {count} 个文件到：{path}");
+            if no_secrets {
+                println!("已清除备份中的 API Key。");
+            }
+        }
+        Commands::Restore { path, merge, yes } => run_restore_command(&paths, &path, merge, yes)?,
+        Commands::External(args) => run_external_command(&paths, &args)?,
     }
 
     Ok(())
 }
 
-async fn run_task(paths: &AgentPaths, task: &str, model: Option<String>) -> Result<()> {
-    let client = ProviderClient::from_paths(paths, model)?;
-    let _ = memory::capture_explicit_remember(paths, "run.task", task)?;
-    let system = build_system_prompt(paths, &client, true)?;
+/// Builds the model client for `run` / `chat` / `skill run`, applying the
+/// `--profile` / `--temperature` / `--effort` flags.
+fn client_with_profile(
+    paths: &AgentPaths,
+    model: Option<String>,
+    args: &ProfileArgs,
+) -> Result<ProviderClient> {
+    let cfg = config::load(paths).unwrap_or_default();
+    let profile = config::resolve_profile(
+        &cfg,
+        args.profile.as_deref(),
+        args.temperature,
+        args.effort.clone(),
+    )?;
+    ProviderClient::from_paths(paths, model)?.with_profile(&profile)
+}
 
-    let response = client
-        .chat(&[ChatMessage::system(system), ChatMessage::user(task)])
-        .await?;
+/// Per-run sampling and output options from `run` flags.
+struct RunOptions {
+    stop: Vec<String>,
+    seed: Option<u64>,
+    thinking_budget: Option<u32>,
+    show_thinking: bool,
+    stream: bool,
+}
+
+async fn run_task(
+    paths: &AgentPaths,
+    task: &str,
+    model: Option<String>,
+    profile: &ProfileArgs,
+    mut constraints: ResponseConstraints,
+    append: Option<&str>,
+    options: RunOptions,
+) -> Result<()> {
+    if !provider::has_usable_backend(paths) {
+        provider::print_no_backend_guide();
+        bail!("未配置可用的模型后端");
+    }
+    let client = client_with_profile(paths, model, profile)?
+        .with_stop(options.stop)?
+        .with_seed(options.seed)
+        .with_thinking(options.thinking_budget, options.show_thinking)?;
+    let client = match &constraints.schema {
+        Some(schema) if client.supports_response_schema() => {
+            constraints.native_schema = true;
+            client.with_response_schema(Some(schema.schema().clone()))
+        }
+        _ => client,
+    };
+    let _ = memory::capture_explicit_remember(paths, "run.task", task).await?;
+    let system = build_system_prompt(paths, &client, true)?;
 
-    println!("{response}");
+    let messages = [ChatMessage::system(system), ChatMessage::user(task)];
+    let filter = WrapperFilter::from_config(&config::load(paths).unwrap_or_default().chat);
+    // JSON output is validated as a whole, so it cannot be streamed.
+    let response = if options.stream && constraints.validation().is_none() {
+        let request = apply_response_constraints(&messages, &constraints);
+        let mut stdout = io::stdout();
+        let mut printer = filter.stream();
+        let response = client
+            .chat_stream(&request, |delta| {
+                let _ = stdout.write_all(printer.push(delta).as_bytes());
+                let _ = stdout.flush();
+            })
+            .await?;
+        print!("{}", printer.finish());
+        filter.apply(&response)
+    } else {
+        if options.stream {
+            eprintln!("提示：JSON 输出需整体校验，已改为完整输出。");
+        }
+        let response = chat_with_constraints(&client, &messages, &constraints).await?;
+        let response = filter.apply(&response);
+        println!("{response}");
+        response
+    };
+    if let Some(path) = append {
+        append_transcript(paths, path, "run", task, &response)?;
+    }
     memory::append_short_term(
         paths,
         "run.task",
         &format!("task:\n{task}\n\nresponse:\n{response}"),
     )?;
-    memory::auto_capture_long_term(paths, "run.task", task)?;
+    memory::auto_capture_long_term(paths, "run.task", task).await?;
+    Ok(())
+}
+
+/// Applies `chat.strip_wrappers` to one-shot `run`/skill output.
+fn strip_wrappers(paths: &AgentPaths, response: &str) -> String {
+    let chat_config = config::load(paths).unwrap_or_default().chat;
+    WrapperFilter::from_config(&chat_config).apply(response)
+}
+
+fn append_transcript(
+    paths: &AgentPaths,
+    path: &str,
+    title: &str,
+    prompt: &str,
+    response: &str,
+) -> Result<()> {
+    transcript::append_entry(
+        paths,
+        Path::new(path),
+        title,
+        prompt,
+        response,
+        chrono::Local::now(),
+    )?;
+    eprintln!("已追加到：{path}");
     Ok(())
 }
 
+fn run_external_command(paths: &AgentPaths, args: &[OsString]) -> Result<()> {
+    let name = args
+        .first()
+        .map(|arg| arg.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    match plugin::resolve_plugin(&name, &path_var) {
+        Some(program) => plugin::run_plugin(paths, &program, &args[1..]),
+        None => Cli::command()
+            .error(
+                clap::error::ErrorKind::InvalidSubcommand,
+                format!("unrecognized subcommand '{name}'"),
+            )
+            .exit(),
+    }
+}
+
+fn note_active_scheduler(paths: &AgentPaths) {
+    if let Ok(Some(pid)) = scheduler::running_pid(paths) {
+        eprintln!(
+            "提示：调度服务正在运行（pid={pid}），共享文件（用量、记忆）的写入将加锁以避免冲突。"
+        );
+    }
+}
+
 fn run_remind_command(paths: &AgentPaths, message: &str) -> Result<()> {
     let msg = message.trim();
     if msg.is_empty() {
@@ -94,31 +378,184 @@ fn run_remind_command(paths: &AgentPaths, message: &str) -> Result<()> {
 
     println!("{msg}");
     let _ = notify::send_notification("GoldAgent 提醒", msg);
+    let now = chrono::Local::now();
     memory::append_short_term(
         paths,
         "remind.fire",
-        &format!(
-            "time={}\nmessage={}",
-            chrono::Local::now().to_rfc3339(),
-            msg
-        ),
+        &format!("time={}\nmessage={}", now.to_rfc3339(), msg),
     )?;
+
+    let reminder = reminders::record_fired(paths, msg, now)?;
+    // Scheduled runs have no terminal; the reminder stays pending for later.
+    if !(io::stdin().is_terminal() && io::stdout().is_terminal()) {
+        println!(
+            "提醒 id={}，可用 `goldagent remind ack|snooze {}` 处理。",
+            reminder.id, reminder.id
+        );
+        return Ok(());
+    }
+    let answer = prompt_line("回车确认；输入分钟数稍后再提醒（如 10）；输入 s 暂不处理: ")?;
+    let answer = answer.trim();
+    if answer.is_empty() {
+        handle_remind_command(paths, RemindCommand::Ack { id: reminder.id })
+    } else if let Ok(minutes) = answer.parse::<u32>() {
+        handle_remind_command(
+            paths,
+            RemindCommand::Snooze {
+                id: reminder.id,
+                minutes,
+            },
+        )
+    } else {
+        println!("提醒保持待确认：{}", reminder.id);
+        Ok(())
+    }
+}
+
+fn handle_remind_command(paths: &AgentPaths, command: RemindCommand) -> Result<()> {
+    match command {
+        RemindCommand::List => {
+            let reminders = reminders::load(paths)?;
+            if reminders.is_empty() {
+                println!("当前没有提醒记录。");
+            }
+            for reminder in reminders {
+                let snoozed = reminder
+                    .snoozed_until
+                    .map(|until| format!(" | 推迟至 {until}"))
+                    .unwrap_or_default();
+                println!(
+                    "{} | {} | {} | {}{}",
+                    reminder.id,
+                    reminder.status.label(),
+                    reminder.fired_at,
+                    reminder.message,
+                    snoozed
+                );
+            }
+        }
+        RemindCommand::Ack { id } => match reminders::acknowledge(paths, &id)? {
+            Some(reminder) => {
+                println!("已确认提醒：{}", reminder.message);
+                memory::append_short_term(paths, "remind.ack", &format!("id={id}"))?;
+            }
+            None => println!("Reminder not found: {id}"),
+        },
+        RemindCommand::Snooze { id, minutes } => {
+            match reminders::snooze(paths, &id, minutes, chrono::Local::now())? {
+                Some((reminder, job)) => {
+                    println!(
+                        "已推迟提醒：{}（{} 分钟后，任务 {}）",
+                        reminder.message, minutes, job.id
+                    );
+                    print_scheduler_auto_start_result(paths, "提醒已推迟");
+                    memory::append_short_term(
+                        paths,
+                        "remind.snooze",
+                        &format!("id={id}\nminutes={minutes}\njob={}", job.id),
+                    )?;
+                }
+                None => println!("Reminder not found: {id}"),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_restore_command(paths: &AgentPaths, path: &str, merge: bool, yes: bool) -> Result<()> {
+    if !yes {
+        let mode = if merge { "合并到" } else { "覆盖" };
+        let answer = prompt_line(&format!(
+            "将从 {path} {mode} {}，是否继续？[y/N] ",
+            paths.root.display()
+        ))?;
+        if !matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+            println!("已取消恢复。");
+            return Ok(());
+        }
+    }
+    let count = backup::restore(paths, Path::new(path), merge)?;
+    println!("已从 {path} 恢复 {count} 个文件。");
     Ok(())
 }
 
-async fn chat_loop(paths: &AgentPaths, model: Option<String>) -> Result<()> {
-    let mut client = ProviderClient::from_paths(paths, model)?;
+fn apply_response_constraints(
+    messages: &[ChatMessage],
+    constraints: &ResponseConstraints,
+) -> Vec<ChatMessage> {
+    let mut request = messages.to_vec();
+    if let Some(system) = request.first_mut()
+        && system.role == "system"
+    {
+        system.content = constraints.apply_to_system(std::mem::take(&mut system.content));
+    }
+    request
+}
+
+/// Sends `messages` with the per-request constraints appended to the system
+/// prompt; JSON output gets one repair round when it does not parse.
+async fn chat_with_constraints(
+    client: &ProviderClient,
+    messages: &[ChatMessage],
+    constraints: &ResponseConstraints,
+) -> Result<String> {
+    let request = apply_response_constraints(messages, constraints);
+    run_with_self_correction(
+        request,
+        constraints.validation(),
+        1,
+        |messages| async move { client.chat(&messages).await },
+    )
+    .await
+}
+
+async fn chat_loop(
+    paths: &AgentPaths,
+    model: Option<String>,
+    profile: &ProfileArgs,
+    resume: bool,
+    constraints: ResponseConstraints,
+) -> Result<()> {
+    let mut client = client_with_profile(paths, model, profile)?;
     let mut messages = vec![ChatMessage::system(build_system_prompt(
         paths, &client, false,
     )?)];
+    let chat_config = config::load(paths).unwrap_or_default().chat;
+    let block_style = BlockStyle::from_config(&chat_config);
+    let wrapper_filter = WrapperFilter::from_config(&chat_config);
+    let mut autosaver =
+        session::Autosaver::new(chat_config.autosave_every_turns, chat_config.privacy_mode);
+    if resume {
+        match session::load(paths)? {
+            Some(saved) => {
+                let restored = session::restore_window(
+                    saved,
+                    chat_config.resume_window,
+                    chat_config.resume_summarize_older,
+                );
+                if restored.dropped_turns > 0 {
+                    println!(
+                        "已恢复上次对话（{} 条消息，省略更早的 {} 轮）。",
+                        restored.messages.len(),
+                        restored.dropped_turns
+                    );
+                } else {
+                    println!("已恢复上次对话（{} 条消息）。", restored.messages.len());
+                }
+                messages.extend(restored.messages);
+            }
+            None => println!("没有可恢复的对话，开始新会话。"),
+        }
+    }
 
     print_chat_header(&client);
     print_chat_commands_hint();
+    if !provider::has_usable_backend(paths) {
+        provider::print_no_backend_guide();
+        println!();
+    }
 
-    loop {
-        let Some(line) = readline_with_inline_hint(paths, "you ❯ ")? else {
-            break;
-        };
+    while let Some(line) = read_chat_message(paths)? {
         let input = line.trim();
 
         if input.is_empty() {
@@ -133,64 +570,136 @@ async fn chat_loop(paths: &AgentPaths, model: Option<String>) -> Result<()> {
             continue;
         }
 
-        let _ = memory::capture_explicit_remember(paths, "chat.turn", input)?;
-        messages.push(ChatMessage::user(input));
-        let raw_response = client.chat(&messages).await?;
-        let (action, cleaned_response, parse_error) =
-            extract_local_action_from_response(&raw_response);
-        let mut response = cleaned_response;
-
-        if let Some(err) = parse_error {
-            let msg = format!("本地动作解析失败：{err}");
-            response = if response.trim().is_empty() {
-                msg
-            } else {
-                format!("{msg}\n\n{response}")
-            };
+        if !provider::has_usable_backend(paths) {
+            provider::print_no_backend_guide();
+            continue;
         }
 
-        if let Some(action) = action {
-            match execute_local_action(paths, action) {
-                Ok(action_msg) => {
-                    response = if response.trim().is_empty() {
-                        action_msg
+        let _ = memory::capture_explicit_remember(paths, "chat.turn", input).await?;
+        // JSON output is validated as a whole, so it cannot be streamed.
+        let streamed = constraints.validation().is_none();
+        let (turn_client, turn_constraints, turn_style) = (&client, &constraints, &block_style);
+        let replies = run_turn(
+            &mut messages,
+            input,
+            streamed,
+            &wrapper_filter,
+            |messages: Vec<ChatMessage>| async move {
+                let mut printer = StreamPrinter::new(turn_style);
+                let request = async {
+                    if streamed {
+                        let request = apply_response_constraints(&messages, turn_constraints);
+                        turn_client
+                            .chat_stream(&request, |delta| printer.push(delta))
+                            .await
                     } else {
-                        format!("{action_msg}\n\n{response}")
-                    };
+                        chat_with_constraints(turn_client, &messages, turn_constraints).await
+                    }
+                };
+                // Ctrl-C drops the request, which also kills a running `codex exec`.
+                let outcome = tokio::select! {
+                    result = request => Some(result),
+                    _ = tokio::signal::ctrl_c() => None,
+                };
+                if streamed {
+                    printer.finish();
                 }
-                Err(err) => {
-                    let msg = format!("本地动作执行失败：{err}");
-                    response = if response.trim().is_empty() {
-                        msg
-                    } else {
-                        format!("{msg}\n\n{response}")
-                    };
+                match outcome {
+                    None => {
+                        println!("已取消本轮请求。");
+                        None
+                    }
+                    // An invalid `--format json` reply drops the turn, not the session.
+                    Some(Err(err)) if err.is::<ValidationFailed>() => {
+                        eprintln!("{err}；已丢弃本轮对话。");
+                        None
+                    }
+                    outcome => outcome,
                 }
-            }
-        }
-
-        if response.trim().is_empty() {
-            response = "已执行。".to_string();
-        }
-
-        print_assistant_block(&response);
-        messages.push(ChatMessage::assistant(response.clone()));
+            },
+            |actions, parse_errors| run_local_actions(paths, &chat_config, actions, parse_errors),
+            |text| print_assistant_block(&block_style, text),
+        )
+        .await?;
+        let Some(replies) = replies else {
+            continue;
+        };
+        let response = replies.join("\n\n");
 
-        silently_capture_before_compaction(paths, &messages)?;
-        trim_history(&mut messages, 14);
+        silently_capture_before_compaction(paths, &messages).await?;
+        compact_history(paths, &client, &mut messages, 14).await;
 
         memory::append_short_term(
             paths,
             "chat.turn",
             &format!("user:\n{input}\n\nassistant:\n{response}"),
         )?;
-        memory::auto_capture_long_term(paths, "chat.turn", input)?;
+        memory::auto_capture_long_term(paths, "chat.turn", input).await?;
+        if let Err(err) = autosaver.after_turn(paths, &messages) {
+            eprintln!("警告：自动保存会话失败：{err}");
+        }
     }
 
+    if autosaver.enabled() {
+        session::save(paths, &messages)?;
+    }
     println!("已退出 GoldAgent 对话。");
     Ok(())
 }
 
+/// Runs (or, when not allowlisted, asks before running) the actions of one
+/// reply and returns a note per action, skipped batch and parse error.
+async fn run_local_actions(
+    paths: &AgentPaths,
+    chat_config: &ChatConfig,
+    mut actions: Vec<ChatLocalAction>,
+    parse_errors: Vec<String>,
+) -> Result<Vec<String>> {
+    let mut notes = Vec::new();
+    let skipped = if !chat_config.multiple_actions && actions.len() > 1 {
+        actions.split_off(1)
+    } else {
+        Vec::new()
+    };
+
+    for action in actions {
+        let kind = action.kind();
+        let approved = match route_action(&action, &chat_config.auto_action_allowlist) {
+            ActionRoute::Execute => true,
+            ActionRoute::Confirm => {
+                let answer =
+                    prompt_line(&format!("模型请求执行本地动作 `{kind}`，是否执行？[y/N] "))?;
+                matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+            }
+        };
+        let result = if approved {
+            execute_local_action(paths, action).await
+        } else {
+            Ok(format!("已取消本地动作：{kind}"))
+        };
+        match result {
+            Ok(action_msg) => notes.push(action_msg),
+            Err(err) => notes.push(format!("本地动作执行失败：{err}")),
+        }
+    }
+
+    if !skipped.is_empty() {
+        let kinds = skipped
+            .iter()
+            .map(|action| action.kind())
+            .collect::<Vec<_>>();
+        notes.push(format!(
+            "已跳过其余 {} 个本地动作（chat.multiple_actions 已关闭）：{}",
+            kinds.len(),
+            kinds.join(", ")
+        ));
+    }
+    for err in parse_errors {
+        notes.push(format!("本地动作解析失败：{err}"));
+    }
+    Ok(notes)
+}
+
 fn print_chat_header(client: &ProviderClient) {
     println!();
     println!("  ____  ___  _     ____    _    ____ _____ _   _ _____ ");
@@ -204,22 +713,74 @@ fn print_chat_header(client: &ProviderClient) {
 }
 
 fn print_chat_commands_hint() {
-    println!("输入 `/` 可查看命令。");
+    println!(
+        "输入 `/` 可查看命令；行尾输入 `\\` 或按 Alt-Enter 进入多行输入，空行或 `/end` 发送。"
+    );
     println!();
 }
 
-fn print_assistant_block(response: &str) {
-    let mut lines = response.lines();
-    match lines.next() {
-        Some(first) => {
-            println!("goldagent: {first}");
-            for line in lines {
-                println!("           {line}");
-            }
+fn print_assistant_block(style: &BlockStyle, response: &str) {
+    let mut printer = BlockPrinter::new(style.clone());
+    for line in response.lines() {
+        for row in printer.render_line(line) {
+            println!("{row}");
+        }
+    }
+    if printer.is_empty() {
+        println!("{}", printer.empty_row());
+    }
+    if let Some(footer) = printer.footer() {
+        println!("{footer}");
+    }
+}
+
+/// Prints streamed deltas in the `goldagent:` block layout, one completed
+/// line at a time, hiding `LOCAL_ACTION` control lines.
+struct StreamPrinter {
+    pending: String,
+    block: BlockPrinter,
+}
+
+impl StreamPrinter {
+    fn new(style: &BlockStyle) -> Self {
+        Self {
+            pending: String::new(),
+            block: BlockPrinter::new(style.clone()),
+        }
+    }
+
+    fn push(&mut self, delta: &str) {
+        self.pending.push_str(delta);
+        while let Some(pos) = self.pending.find('\n') {
+            let line = self.pending[..pos].to_string();
+            self.pending.drain(..=pos);
+            self.print_line(&line);
         }
-        None => {
-            println!("goldagent:");
+    }
+
+    fn finish(&mut self) {
+        let rest = std::mem::take(&mut self.pending);
+        if !rest.is_empty() {
+            self.print_line(&rest);
+        }
+        if !self.block.is_empty()
+            && let Some(footer) = self.block.footer()
+        {
+            println!("{footer}");
+        }
+    }
+
+    fn print_line(&mut self, line: &str) {
+        if is_local_action_line(line) {
+            return;
+        }
+        if self.block.is_empty() && line.trim().is_empty() {
+            return;
+        }
+        for row in self.block.render_line(line) {
+            println!("{row}");
         }
+        let _ = io::stdout().flush();
     }
 }
 
@@ -235,7 +796,7 @@ fn build_system_prompt(
     } else {
         prompt.push_str(
             "Auto-execution protocol:\n\
-When user asks to perform local operations (cron/hook), emit exactly one control line at the start of your reply:\n\
+When user asks to perform local operations (cron/hook), emit one control line per operation at the start of your reply (they run in order):\n\
 [[LOCAL_ACTION:{\"kind\":\"cron_add\",\"schedule\":\"daily@13:00\",\"task\":\"提醒我吃饭\"}]]\n\
 Supported kinds:\n\
 - cron_add {schedule, task, optional name, optional retry_max}\n\
@@ -243,21 +804,23 @@ Supported kinds:\n\
 - cron_remove {id}\n\
 - hook_add_git {repo, task, optional reference, optional interval_secs, optional name, optional retry_max, optional rules_file(LLM审查模式：填规则文件路径，设置后忽略task), optional report_file}\n\
 - hook_add_p4 {depot, task, optional interval_secs, optional name, optional retry_max, optional rules_file(LLM审查模式：填规则文件路径，设置后忽略task), optional report_file}\n\
+- hook_add_hg {repo, task, optional reference(默认 tip), optional interval_secs, optional name, optional retry_max, optional rules_file(LLM审查模式：填规则文件路径，设置后忽略task), optional report_file}\n\
 - hook_list {}\n\
 - hook_remove {id}\n\
 - hook_rules_new {optional path}\n\
 Rules:\n\
 - If required fields are missing or ambiguous, ask follow-up questions and DO NOT emit LOCAL_ACTION.\n\
-- If the user clearly requests execution, prefer emitting LOCAL_ACTION rather than giving command suggestions.\n\n",
+- If the user clearly requests execution, prefer emitting LOCAL_ACTION rather than giving command suggestions.\n\
+- Results come back in a `[LOCAL_ACTION 执行结果]` message; answer the user from them without repeating actions that already ran.\n\n",
         );
     }
+    let guard_template = config::load(paths).unwrap_or_default().chat.identity_guard;
     prompt.push_str(&format!(
-        "Current backend: {}.\n\
-If asked about model/backend identity, answer strictly based on Current backend, not historical memory.\n\
-Never claim a fixed model family unless it matches Current backend.\n\n\
-Memory context:\n{}",
+        "Current backend: {}.\n\n\
+Memory context:\n{}\n\n{}",
         client.backend_label(),
-        memory_context
+        memory_context,
+        client.identity_guard(guard_template.as_deref())
     ));
     Ok(prompt)
 }
@@ -303,6 +866,17 @@ async fn handle_chat_slash(
     }
 
     if input == "/connect" || input == "/connect " {
+        if !stdin_is_tty() {
+            provider::print_connect_help(paths)?;
+            return Ok(SlashAction::Continue);
+        }
+        if run_connect_picker(paths, client)? {
+            refresh_chat_system_prompt(paths, client, messages)?;
+        }
+        return Ok(SlashAction::Continue);
+    }
+
+    if input == "/connect help" {
         provider::print_connect_help(paths)?;
         return Ok(SlashAction::Continue);
     }
@@ -325,6 +899,91 @@ async fn handle_chat_slash(
         return Ok(SlashAction::Continue);
     }
 
+    if let Some(command) = input
+        .strip_prefix("/shell")
+        .filter(|rest| rest.is_empty() || rest.starts_with(' '))
+    {
+        let command = command.trim();
+        if command.is_empty() {
+            println!("用法：/shell <命令>（输出会加入对话上下文）");
+            return Ok(SlashAction::Continue);
+        }
+        match shell::run_into_context(paths, command, messages).await {
+            Ok(output) => {
+                if !output.stdout.trim().is_empty() {
+                    println!("{}", output.stdout.trim_end());
+                }
+                if !output.stderr.trim().is_empty() {
+                    eprintln!("{}", output.stderr.trim_end());
+                }
+                println!("（命令输出已加入对话上下文）");
+                compact_history(paths, client, messages, 14).await;
+            }
+            Err(err) => println!("{err}"),
+        }
+        return Ok(SlashAction::Continue);
+    }
+
+    if input == "/sessions" {
+        match session::list_named(paths) {
+            Ok(sessions) if sessions.is_empty() => {
+                println!("还没有保存的会话，可用 /save <名称> 保存当前对话。")
+            }
+            Ok(sessions) => {
+                for (name, turns) in sessions {
+                    println!("- {name}（{turns} 轮）");
+                }
+            }
+            Err(err) => println!("读取会话列表失败：{err}"),
+        }
+        return Ok(SlashAction::Continue);
+    }
+
+    if let Some(name) = input
+        .strip_prefix("/save")
+        .filter(|rest| rest.is_empty() || rest.starts_with(' '))
+    {
+        let name = name.trim();
+        if name.is_empty() {
+            println!("用法：/save <名称>");
+            return Ok(SlashAction::Continue);
+        }
+        match session::save_named(paths, name, messages) {
+            Ok(turns) => println!("已保存会话 {name}（{turns} 轮）。"),
+            Err(err) => println!("{err}"),
+        }
+        return Ok(SlashAction::Continue);
+    }
+
+    if let Some(name) = input
+        .strip_prefix("/load")
+        .filter(|rest| rest.is_empty() || rest.starts_with(' '))
+    {
+        let name = name.trim();
+        if name.is_empty() {
+            println!("用法：/load <名称>（/sessions 查看已保存的会话）");
+            return Ok(SlashAction::Continue);
+        }
+        let loaded = match session::load_named(paths, name) {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                println!("{err}");
+                return Ok(SlashAction::Continue);
+            }
+        };
+        messages.truncate(1);
+        messages.extend(loaded);
+        println!(
+            "已载入会话 {name}（{} 条消息）。",
+            messages.len().saturating_sub(1)
+        );
+        if let Some(last) = messages.iter().rev().find(|m| m.role == "assistant") {
+            let chat_config = config::load(paths).unwrap_or_default().chat;
+            print_assistant_block(&BlockStyle::from_config(&chat_config), &last.content);
+        }
+        return Ok(SlashAction::Continue);
+    }
+
     if input == "/skill" || input == "/skill " {
         println!("用法：/skill <skill名> <输入内容>");
         print_skills_for_chat(paths)?;
@@ -350,9 +1009,25 @@ async fn handle_chat_slash(
             return Ok(SlashAction::Continue);
         };
 
-        let response =
-            run_skill_and_record(paths, client, skill_name.trim(), skill_input.trim()).await?;
-        print_assistant_block(&response);
+        let resolved_input = match skills::resolve_skill_input(skill_input.trim(), None) {
+            Ok(input) => input,
+            Err(err) => {
+                println!("{err:#}");
+                return Ok(SlashAction::Continue);
+            }
+        };
+        let response = run_skill_and_record(
+            paths,
+            client,
+            skill_name.trim(),
+            resolved_input.trim(),
+            false,
+            skills::SkillBudget::default(),
+            skills::SkillContext::default(),
+        )
+        .await?;
+        let chat_config = config::load(paths).unwrap_or_default().chat;
+        print_assistant_block(&BlockStyle::from_config(&chat_config), &response);
 
         messages.push(ChatMessage::user(format!(
             "/skill {} {}",
@@ -360,8 +1035,8 @@ async fn handle_chat_slash(
             skill_input.trim()
         )));
         messages.push(ChatMessage::assistant(response));
-        silently_capture_before_compaction(paths, messages)?;
-        trim_history(messages, 14);
+        silently_capture_before_compaction(paths, messages).await?;
+        compact_history(paths, client, messages, 14).await;
         return Ok(SlashAction::Continue);
     }
 
@@ -389,6 +1064,10 @@ fn print_command_palette(paths: &AgentPaths) -> Result<()> {
     println!("- /connect anthropic ...");
     println!("- /connect zhipu ...");
     println!("- /skill <skill名> <输入内容>");
+    println!("- /shell <命令>");
+    println!("- /save <名称>");
+    println!("- /load <名称>");
+    println!("- /sessions");
     provider::print_connect_status(paths)?;
     print_skills_for_chat(paths)?;
     println!();
@@ -435,6 +1114,10 @@ fn base_command_items() -> Vec<(&'static str, &'static str, &'static str)> {
         ("/model", "查看/切换模型", "/model "),
         ("/connect", "连接模型后端", "/connect "),
         ("/skill", "使用技能", "/skill "),
+        ("/shell", "执行命令并把输出加入上下文", "/shell "),
+        ("/save", "保存当前对话", "/save "),
+        ("/load", "载入已保存的对话", "/load "),
+        ("/sessions", "列出已保存的对话", "/sessions"),
         ("/clear", "清空当前屏幕", "/clear"),
         ("/exit", "退出对话", "/exit"),
     ]
@@ -566,7 +1249,30 @@ fn command_inline_hint_items(paths: &AgentPaths, input: &str) -> Vec<HintItem> {
     items
 }
 
-fn readline_with_inline_hint(paths: &AgentPaths, prompt: &str) -> io::Result<Option<String>> {
+/// Reads one chat message; continuation lines are joined in multiline mode,
+/// during which the inline hint panel stays hidden.
+fn read_chat_message(paths: &AgentPaths) -> io::Result<Option<String>> {
+    let mut buffer = MultilineBuffer::default();
+    loop {
+        let (prompt, show_hints) = if buffer.is_active() {
+            ("    … ", false)
+        } else {
+            ("you ❯ ", true)
+        };
+        let Some(line) = readline_with_inline_hint(paths, prompt, show_hints)? else {
+            return Ok(None);
+        };
+        if let Some(message) = buffer.push(line) {
+            return Ok(Some(message));
+        }
+    }
+}
+
+fn readline_with_inline_hint(
+    paths: &AgentPaths,
+    prompt: &str,
+    show_hints: bool,
+) -> io::Result<Option<LineInput>> {
     if !stdin_is_tty() {
         let mut stdout = io::stdout();
         write!(stdout, "{prompt}")?;
@@ -577,20 +1283,27 @@ fn readline_with_inline_hint(paths: &AgentPaths, prompt: &str) -> io::Result<Opt
             return Ok(None);
         }
         let trimmed = line.trim_end_matches(['\r', '\n']).to_string();
-        return Ok(Some(trimmed));
+        return Ok(Some(LineInput::Submit(trimmed)));
     }
 
-    let _raw = RawMode::new()?;
+    let _raw = RawMode::reading_ctrl_c()?;
     let mut stdout = io::stdout();
     let mut stdin = io::stdin();
 
-    let mut input = String::new();
+    let hints_for = |input: &str| {
+        if show_hints {
+            command_inline_hint_items(paths, input)
+        } else {
+            Vec::new()
+        }
+    };
+    let mut editor = LineEditor::default();
     let mut pending_utf8 = Vec::<u8>::new();
     let mut shown_hint_lines = 0usize;
     let mut selected = None;
-    let mut hints = command_inline_hint_items(paths, &input);
+    let mut hints = hints_for(editor.text());
     normalize_selected_index(&mut selected, hints.len());
-    redraw_prompt_line(&mut stdout, prompt, &input)?;
+    redraw_prompt_line(&mut stdout, prompt, &editor)?;
     render_hint_panel(&mut stdout, &hints, selected, &mut shown_hint_lines)?;
     stdout.flush()?;
 
@@ -598,75 +1311,90 @@ fn readline_with_inline_hint(paths: &AgentPaths, prompt: &str) -> io::Result<Opt
         let mut byte = [0u8; 1];
         if stdin.read_exact(&mut byte).is_err() {
             render_hint_panel(&mut stdout, &[], None, &mut shown_hint_lines)?;
-            write!(stdout, "\n")?;
+            writeln!(stdout)?;
             stdout.flush()?;
             return Ok(None);
         }
 
         match byte[0] {
             b'\r' | b'\n' => {
-                if apply_selected_completion(&mut input, &hints, selected) {
-                    hints = command_inline_hint_items(paths, &input);
+                if apply_selected_completion(&mut editor, &hints, selected) {
+                    hints = hints_for(editor.text());
                     normalize_selected_index(&mut selected, hints.len());
-                    redraw_prompt_line(&mut stdout, prompt, &input)?;
+                    redraw_prompt_line(&mut stdout, prompt, &editor)?;
                     render_hint_panel(&mut stdout, &hints, selected, &mut shown_hint_lines)?;
                     stdout.flush()?;
                     continue;
                 }
                 render_hint_panel(&mut stdout, &[], None, &mut shown_hint_lines)?;
-                write!(stdout, "\n")?;
+                writeln!(stdout)?;
                 stdout.flush()?;
-                return Ok(Some(input));
+                return Ok(Some(LineInput::Submit(editor.into_text())));
             }
             b'\t' => {
-                if apply_selected_completion(&mut input, &hints, selected) {
-                    hints = command_inline_hint_items(paths, &input);
+                if apply_selected_completion(&mut editor, &hints, selected) {
+                    hints = hints_for(editor.text());
                     normalize_selected_index(&mut selected, hints.len());
-                    redraw_prompt_line(&mut stdout, prompt, &input)?;
+                    redraw_prompt_line(&mut stdout, prompt, &editor)?;
                     render_hint_panel(&mut stdout, &hints, selected, &mut shown_hint_lines)?;
                     stdout.flush()?;
                 }
                 continue;
             }
             27 => {
-                let mut seq = [0u8; 2];
-                if stdin.read_exact(&mut seq).is_ok() && seq[0] == b'[' {
-                    match seq[1] {
-                        b'A' => move_selection_up(&mut selected, hints.len()),
-                        b'B' => move_selection_down(&mut selected, hints.len()),
-                        b'C' => {
-                            if apply_selected_completion(&mut input, &hints, selected) {
-                                hints = command_inline_hint_items(paths, &input);
-                                normalize_selected_index(&mut selected, hints.len());
-                            }
-                        }
-                        _ => {}
+                let mut seq = [0u8; 1];
+                if stdin.read_exact(&mut seq).is_err() {
+                    continue;
+                }
+                match seq[0] {
+                    b'\r' | b'\n' => {
+                        render_hint_panel(&mut stdout, &[], None, &mut shown_hint_lines)?;
+                        writeln!(stdout)?;
+                        stdout.flush()?;
+                        return Ok(Some(LineInput::Continue(editor.into_text())));
                     }
+                    b'[' | b'O' => match read_escape_key(&mut stdin) {
+                        Some(EscapeKey::Up) => move_selection_up(&mut selected, hints.len()),
+                        Some(EscapeKey::Down) => move_selection_down(&mut selected, hints.len()),
+                        Some(EscapeKey::Right)
+                            if editor.at_end()
+                                && apply_selected_completion(&mut editor, &hints, selected) => {}
+                        Some(EscapeKey::Right) => editor.move_right(),
+                        Some(EscapeKey::Left) => editor.move_left(),
+                        Some(EscapeKey::Home) => editor.home(),
+                        Some(EscapeKey::End) => editor.end(),
+                        Some(EscapeKey::Delete) => editor.delete(),
+                        None => {}
+                    },
+                    _ => {}
                 }
             }
+            1 => editor.home(),
             3 => {
                 render_hint_panel(&mut stdout, &[], None, &mut shown_hint_lines)?;
-                write!(stdout, "\n")?;
+                writeln!(stdout)?;
                 stdout.flush()?;
                 return Ok(None);
             }
             4 => {
-                if input.is_empty() {
+                if editor.is_empty() {
                     render_hint_panel(&mut stdout, &[], None, &mut shown_hint_lines)?;
-                    write!(stdout, "\n")?;
+                    writeln!(stdout)?;
                     stdout.flush()?;
                     return Ok(None);
                 }
+                editor.delete();
             }
+            5 => editor.end(),
             8 | 127 => {
                 pending_utf8.clear();
-                let _ = input.pop();
+                editor.backspace();
             }
             b if b < 32 => {}
             b => {
                 pending_utf8.push(b);
                 if let Ok(piece) = std::str::from_utf8(&pending_utf8) {
-                    input.push_str(piece);
+                    editor.insert(piece);
                     pending_utf8.clear();
                 } else if pending_utf8.len() > 4 {
                     pending_utf8.clear();
@@ -674,59 +1402,343 @@ fn readline_with_inline_hint(paths: &AgentPaths, prompt: &str) -> io::Result<Opt
             }
         }
 
-        hints = command_inline_hint_items(paths, &input);
+        hints = hints_for(editor.text());
         normalize_selected_index(&mut selected, hints.len());
-        redraw_prompt_line(&mut stdout, prompt, &input)?;
+        redraw_prompt_line(&mut stdout, prompt, &editor)?;
         render_hint_panel(&mut stdout, &hints, selected, &mut shown_hint_lines)?;
         stdout.flush()?;
     }
 }
 
-fn render_hint_panel(
-    stdout: &mut io::Stdout,
-    hints: &[HintItem],
-    selected: Option<usize>,
-    shown_hint_lines: &mut usize,
-) -> io::Result<()> {
-    let lines_to_touch = cmp::max(*shown_hint_lines, hints.len());
-    write!(stdout, "\x1b[s")?;
-    for idx in 0..lines_to_touch {
-        write!(stdout, "\n\r\x1b[2K")?;
-        if idx < hints.len() {
-            let marker = if Some(idx) == selected { ">" } else { " " };
-            write!(
-                stdout,
-                "{} {:<24} {}",
-                marker, hints[idx].label, hints[idx].desc
-            )?;
-        }
-    }
-    write!(stdout, "\x1b[u")?;
-    *shown_hint_lines = hints.len();
-    Ok(())
+/// Cursor and editing keys sent as `ESC [ …` / `ESC O …` sequences.
+enum EscapeKey {
+    Up,
+    Down,
+    Right,
+    Left,
+    Home,
+    End,
+    Delete,
 }
 
-fn redraw_prompt_line(stdout: &mut io::Stdout, prompt: &str, input: &str) -> io::Result<()> {
-    write!(stdout, "\r\x1b[2K{prompt}{input}")?;
-    Ok(())
+/// Reads the rest of an escape sequence after `ESC [` or `ESC O`, covering
+/// both the letter forms (`ESC [ D`) and the numbered ones (`ESC [ 3 ~`).
+fn read_escape_key(stdin: &mut io::Stdin) -> Option<EscapeKey> {
+    let mut byte = [0u8; 1];
+    stdin.read_exact(&mut byte).ok()?;
+    let key = match byte[0] {
+        b'A' => EscapeKey::Up,
+        b'B' => EscapeKey::Down,
+        b'C' => EscapeKey::Right,
+        b'D' => EscapeKey::Left,
+        b'H' => EscapeKey::Home,
+        b'F' => EscapeKey::End,
+        digit @ b'0'..=b'9' => {
+            let mut number = vec![digit];
+            loop {
+                stdin.read_exact(&mut byte).ok()?;
+                if !byte[0].is_ascii_digit() {
+                    break;
+                }
+                number.push(byte[0]);
+            }
+            if byte[0] != b'~' {
+                return None;
+            }
+            match number.as_slice() {
+                b"1" | b"7" => EscapeKey::Home,
+                b"4" | b"8" => EscapeKey::End,
+                b"3" => EscapeKey::Delete,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    Some(key)
 }
 
-fn normalize_selected_index(selected: &mut Option<usize>, len: usize) {
-    if len == 0 {
-        *selected = None;
-        return;
-    }
-    *selected = Some((*selected).unwrap_or(0).min(len - 1));
-}
+/// Provider → method → model menus for `/connect`; returns whether the
+/// backend changed.
+fn run_connect_picker(paths: &AgentPaths, client: &mut ProviderClient) -> Result<bool> {
+    let providers = provider::PICKER_PROVIDERS;
+    let labels = providers
+        .iter()
+        .map(|p| connect::provider_label(p).to_string())
+        .collect::<Vec<_>>();
+    let PickerOutcome::Selected(idx) = run_picker("选择模型厂商", labels, false)? else {
+        println!("已取消连接。");
+        return Ok(false);
+    };
+    let provider = providers[idx].clone();
 
-fn move_selection_up(selected: &mut Option<usize>, len: usize) {
-    if len == 0 {
-        *selected = None;
-        return;
-    }
-    *selected = Some(match *selected {
-        Some(0) | None => len - 1,
-        Some(i) => i - 1,
+    let methods = provider::connect_methods_for_provider(&provider);
+    let method = if methods.len() == 1 {
+        methods[0]
+    } else {
+        let labels = methods
+            .iter()
+            .map(|m| provider::connect_method_label(m).to_string())
+            .collect::<Vec<_>>();
+        let PickerOutcome::Selected(idx) = run_picker("选择连接方式", labels, false)? else {
+            println!("已取消连接。");
+            return Ok(false);
+        };
+        methods[idx]
+    };
+
+    let models = provider::picker_models(&provider, method);
+    let labels = models.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+    let model = match run_picker("选择模型（无匹配时回车使用输入的名称）", labels, true)?
+    {
+        PickerOutcome::Selected(idx) => models[idx].to_string(),
+        PickerOutcome::Custom(model) => model,
+        _ => {
+            println!("已取消连接。");
+            return Ok(false);
+        }
+    };
+
+    let choice = provider::ConnectChoice {
+        provider,
+        method,
+        model,
+    };
+    provider::apply_connect_choice(paths, client, choice, prompt_line)
+}
+
+/// Full-screen arrow-key menu on the alternate screen. Typing filters the
+/// list; Esc or Ctrl-C cancels.
+fn run_picker(title: &str, items: Vec<String>, allow_custom: bool) -> io::Result<PickerOutcome> {
+    let mut state = PickerState::new(items, allow_custom);
+    let _raw = RawMode::new()?;
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b[?1049h")?;
+
+    #[cfg(unix)]
+    let mut stdin = UnbufferedStdin;
+    #[cfg(not(unix))]
+    let mut stdin = io::stdin();
+    let outcome = picker::drive(
+        &mut state,
+        &mut stdin,
+        |_| stdin_has_pending_input(),
+        |state| render_picker(&mut io::stdout(), title, state),
+    )?;
+
+    write!(stdout, "\x1b[?1049l")?;
+    stdout.flush()?;
+    Ok(outcome)
+}
+
+fn render_picker(stdout: &mut io::Stdout, title: &str, state: &PickerState) -> io::Result<()> {
+    write!(stdout, "\x1b[2J\x1b[H")?;
+    write!(stdout, "{title}\r\n筛选：{}\r\n\r\n", state.filter())?;
+    let matches = state.matches();
+    if matches.is_empty() {
+        write!(stdout, "  （无匹配项）\r\n")?;
+    }
+    for (row, idx) in matches.iter().enumerate() {
+        let marker = if Some(row) == state.selected() {
+            ">"
+        } else {
+            " "
+        };
+        write!(stdout, "{marker} {}\r\n", state.item(*idx))?;
+    }
+    write!(stdout, "\r\n↑/↓ 选择，输入文字筛选，Enter 确认，Esc 取消")?;
+    stdout.flush()
+}
+
+/// `connect status --watch`: redraws the status every `interval` seconds
+/// until q, Esc or Ctrl-C.
+async fn watch_connect_status(paths: &AgentPaths, interval: u64, probe: bool) -> Result<()> {
+    if !io::stdout().is_terminal() {
+        bail!("--watch 需要在终端中运行");
+    }
+    let probe_addr = probe.then(|| {
+        ProviderClient::from_paths(paths, None)
+            .ok()
+            .and_then(|client| client.endpoint_addr())
+            .unwrap_or_else(|| {
+                config::load(paths)
+                    .unwrap_or_default()
+                    .scheduler
+                    .network_probe_addr
+            })
+    });
+    let interval = Duration::from_secs(interval);
+    let _raw = RawMode::new()?;
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    let mut watch = StatusWatch::default();
+
+    loop {
+        let status = provider::connect_status_lines(paths)?;
+        let total = usage::load(&paths.usage_file).unwrap_or_default().total;
+        let probe = match &probe_addr {
+            Some(addr) => Some(status_watch::Probe {
+                addr: addr.clone(),
+                latency: scheduler::probe_tcp_latency(addr.clone()).await,
+            }),
+            None => None,
+        };
+        let lines = watch.frame(status, &total, probe);
+
+        let mut stdout = io::stdout();
+        write!(stdout, "\x1b[2J\x1b[H")?;
+        writeln!(
+            stdout,
+            "每 {}s 刷新 · {} · 按 q 或 Ctrl-C 退出",
+            interval.as_secs(),
+            chrono::Local::now().format("%H:%M:%S")
+        )?;
+        writeln!(stdout)?;
+        for line in lines {
+            writeln!(stdout, "{line}")?;
+        }
+        stdout.flush()?;
+
+        let deadline = tokio::time::Instant::now() + interval;
+        while tokio::time::Instant::now() < deadline {
+            tokio::select! {
+                _ = &mut ctrl_c => return Ok(()),
+                _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+            }
+            if quit_key_pressed()? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// True for q, or for an Esc with nothing after it; arrow keys and other
+/// escape sequences are read and ignored.
+#[cfg(unix)]
+fn quit_key_pressed() -> io::Result<bool> {
+    let mut stdin = UnbufferedStdin;
+    let mut byte = [0_u8; 1];
+    while stdin_has_pending_input() {
+        if stdin.read(&mut byte)? == 0 {
+            return Ok(false);
+        }
+        match byte[0] {
+            b'q' | b'Q' => return Ok(true),
+            0x1b if !stdin_has_pending_input() => return Ok(true),
+            0x1b => skip_escape_sequence(&mut stdin)?,
+            _ => {}
+        }
+    }
+    Ok(false)
+}
+
+/// Consumes the rest of an `ESC [ …` / `ESC O …` sequence up to its final
+/// byte.
+#[cfg(unix)]
+fn skip_escape_sequence(stdin: &mut UnbufferedStdin) -> io::Result<()> {
+    let mut byte = [0_u8; 1];
+    stdin.read_exact(&mut byte)?;
+    if !matches!(byte[0], b'[' | b'O') {
+        return Ok(());
+    }
+    loop {
+        stdin.read_exact(&mut byte)?;
+        if (0x40..=0x7e).contains(&byte[0]) {
+            return Ok(());
+        }
+    }
+}
+
+/// Reads fd 0 directly. `io::Stdin` buffers a whole escape sequence on its
+/// first read, which hides the rest from the `poll` in
+/// [`stdin_has_pending_input`].
+#[cfg(unix)]
+struct UnbufferedStdin;
+
+#[cfg(unix)]
+impl Read for UnbufferedStdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+        if read < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(read as usize)
+    }
+}
+
+#[cfg(not(unix))]
+fn quit_key_pressed() -> io::Result<bool> {
+    Ok(false)
+}
+
+#[cfg(unix)]
+fn stdin_has_pending_input() -> bool {
+    let mut fds = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    unsafe { libc::poll(&mut fds, 1, 30) > 0 }
+}
+
+#[cfg(not(unix))]
+fn stdin_has_pending_input() -> bool {
+    true
+}
+
+fn render_hint_panel(
+    stdout: &mut io::Stdout,
+    hints: &[HintItem],
+    selected: Option<usize>,
+    shown_hint_lines: &mut usize,
+) -> io::Result<()> {
+    let lines_to_touch = cmp::max(*shown_hint_lines, hints.len());
+    write!(stdout, "\x1b[s")?;
+    for idx in 0..lines_to_touch {
+        write!(stdout, "\n\r\x1b[2K")?;
+        if idx < hints.len() {
+            let marker = if Some(idx) == selected { ">" } else { " " };
+            write!(
+                stdout,
+                "{} {:<24} {}",
+                marker, hints[idx].label, hints[idx].desc
+            )?;
+        }
+    }
+    write!(stdout, "\x1b[u")?;
+    *shown_hint_lines = hints.len();
+    Ok(())
+}
+
+fn redraw_prompt_line(
+    stdout: &mut io::Stdout,
+    prompt: &str,
+    editor: &LineEditor,
+) -> io::Result<()> {
+    write!(
+        stdout,
+        "\r\x1b[2K{prompt}{}\x1b[{}G",
+        editor.text(),
+        editor.cursor_column(prompt)
+    )?;
+    Ok(())
+}
+
+fn normalize_selected_index(selected: &mut Option<usize>, len: usize) {
+    if len == 0 {
+        *selected = None;
+        return;
+    }
+    *selected = Some((*selected).unwrap_or(0).min(len - 1));
+}
+
+fn move_selection_up(selected: &mut Option<usize>, len: usize) {
+    if len == 0 {
+        *selected = None;
+        return;
+    }
+    *selected = Some(match *selected {
+        Some(0) | None => len - 1,
+        Some(i) => i - 1,
     });
 }
 
@@ -742,7 +1754,7 @@ fn move_selection_down(selected: &mut Option<usize>, len: usize) {
 }
 
 fn apply_selected_completion(
-    input: &mut String,
+    input: &mut LineEditor,
     hints: &[HintItem],
     selected: Option<usize>,
 ) -> bool {
@@ -756,10 +1768,10 @@ fn apply_selected_completion(
     if target.is_empty() {
         return false;
     }
-    if input == target || input == target.trim_end() {
+    if input.text() == target || input.text() == target.trim_end() {
         return false;
     }
-    *input = target.to_string();
+    input.set(target.to_string());
     true
 }
 
@@ -790,6 +1802,17 @@ struct RawMode {
 #[cfg(unix)]
 impl RawMode {
     fn new() -> io::Result<Self> {
+        Self::configure(true)
+    }
+
+    /// Raw mode in which Ctrl-C arrives as byte 3 instead of SIGINT. The chat
+    /// prompt uses it: once a request has listened for Ctrl-C, the runtime
+    /// keeps SIGINT for the rest of the process and it no longer exits.
+    fn reading_ctrl_c() -> io::Result<Self> {
+        Self::configure(false)
+    }
+
+    fn configure(keep_sigint: bool) -> io::Result<Self> {
         let fd = libc::STDIN_FILENO;
         let mut original = unsafe { std::mem::zeroed::<libc::termios>() };
         if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
@@ -798,6 +1821,10 @@ impl RawMode {
 
         let mut raw = original;
         raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+        if !keep_sigint {
+            // Disable only the interrupt character; Ctrl-Z still suspends.
+            raw.c_cc[libc::VINTR] = libc::_POSIX_VDISABLE;
+        }
         raw.c_cc[libc::VMIN] = 1;
         raw.c_cc[libc::VTIME] = 0;
 
@@ -824,9 +1851,16 @@ impl RawMode {
     fn new() -> io::Result<Self> {
         Ok(Self)
     }
+
+    fn reading_ctrl_c() -> io::Result<Self> {
+        Ok(Self)
+    }
 }
 
-fn silently_capture_before_compaction(paths: &AgentPaths, messages: &[ChatMessage]) -> Result<()> {
+async fn silently_capture_before_compaction(
+    paths: &AgentPaths,
+    messages: &[ChatMessage],
+) -> Result<()> {
     if messages.len() < 14 {
         return Ok(());
     }
@@ -840,11 +1874,51 @@ fn silently_capture_before_compaction(paths: &AgentPaths, messages: &[ChatMessag
         .collect::<Vec<_>>();
 
     for user_text in recent_user_texts {
-        let _ = memory::auto_capture_long_term(paths, "chat.compaction", &user_text)?;
+        let _ = memory::auto_capture_long_term(paths, "chat.compaction", &user_text).await?;
     }
     Ok(())
 }
 
+/// Shrinks the history to `max_non_system` messages. With
+/// `GOLDAGENT_COMPACT=summarize` the dropped messages are summarized into a
+/// note kept after the system prompt (and mined for long-term memory);
+/// otherwise, or when summarizing fails, they are simply dropped.
+async fn compact_history(
+    paths: &AgentPaths,
+    client: &ProviderClient,
+    messages: &mut Vec<ChatMessage>,
+    max_non_system: usize,
+) {
+    if session::CompactMode::from_env() == session::CompactMode::Drop {
+        trim_history(messages, max_non_system);
+        return;
+    }
+    let dropped = session::take_overflow(messages, max_non_system);
+    if dropped.is_empty() {
+        return;
+    }
+    let summary = match client.chat(&session::summary_request(&dropped)).await {
+        Ok(summary) if summary.trim().is_empty() => Err(anyhow!("模型返回了空摘要")),
+        result => result,
+    };
+    match summary {
+        Ok(summary) => {
+            session::insert_summary_note(messages, &summary);
+            if let Err(err) =
+                memory::auto_capture_long_term(paths, "chat.compaction", &summary).await
+            {
+                eprintln!("警告：从对话摘要提取长期记忆失败：{err}");
+            }
+        }
+        Err(err) => {
+            eprintln!("警告：对话摘要失败，已直接丢弃较早的消息：{err}");
+            if let Some(note) = dropped.into_iter().find(session::is_summary_note) {
+                messages.insert(1, note);
+            }
+        }
+    }
+}
+
 fn trim_history(messages: &mut Vec<ChatMessage>, max_non_system: usize) {
     if messages.is_empty() {
         return;
@@ -862,42 +1936,91 @@ fn trim_history(messages: &mut Vec<ChatMessage>, max_non_system: usize) {
     messages.extend(trimmed);
 }
 
-fn print_scheduler_auto_start_result(paths: &AgentPaths) {
-    match daemon::ensure_scheduler_running(paths) {
+/// Reports how the scheduler picked up a change; `done` describes the change
+/// (e.g. "任务已创建") for the failure warning.
+fn print_scheduler_auto_start_result(paths: &AgentPaths, done: &str) {
+    match daemon::auto_start_scheduler(paths) {
         Ok(daemon::SchedulerStatus::Started(pid)) => {
             println!("已自动启动调度服务（pid={pid}）。");
         }
         Ok(daemon::SchedulerStatus::Reloaded(pid)) => {
-            println!("已重载调度服务以应用新任务（pid={pid}）。");
+            println!("已重载调度服务以应用变更（pid={pid}）。");
         }
+        Ok(daemon::SchedulerStatus::Disabled) => println!("{}", daemon::MANUAL_RELOAD_HINT),
         Err(err) => {
-            eprintln!("警告：任务已创建，但自动启动调度服务失败：{err}");
+            eprintln!("警告：{done}，但自动启动调度服务失败：{err}");
             eprintln!("请手动执行：goldagent serve");
         }
     }
 }
 
-fn handle_cron_command(paths: &AgentPaths, command: CronCommand) -> Result<()> {
+async fn toggle_cron_job(paths: &AgentPaths, id: &str, enabled: bool) -> Result<()> {
+    let Some(job) = jobs::set_job_enabled(paths, id, enabled)? else {
+        println!("Job not found: {id}");
+        return Ok(());
+    };
+    let state = if enabled { "启用" } else { "停用" };
+    println!("已{state}定时任务：{} ({})", job.name, job.id);
+    print_scheduler_auto_start_result(paths, &format!("任务已{state}"));
+    let event = format!("用户{state}了定时任务：name={}，id={}", job.name, job.id);
+    memory::append_short_term(paths, "cron.toggle", &event)?;
+    let _ = memory::auto_capture_event(paths, "cron.toggle", &event).await?;
+    Ok(())
+}
+
+async fn handle_cron_command(paths: &AgentPaths, command: CronCommand) -> Result<()> {
     match command {
         CronCommand::Add {
             schedule,
             command,
             name,
             retry_max,
+            timezone,
+            run_on_start,
+            cwd,
+            onefail,
         } => {
-            let job = jobs::add_job(paths, schedule, command, name, retry_max)?;
+            let job = jobs::add_job(
+                paths,
+                jobs::NewJob {
+                    schedule,
+                    command,
+                    name,
+                    retry_max,
+                    timezone,
+                    run_on_start,
+                    cwd,
+                    onefail,
+                },
+            )?;
             println!("Added job:");
             println!("id: {}", job.id);
             println!("name: {}", job.name);
-            println!("schedule: {}", job.schedule);
+            println!(
+                "schedule: {} ({})",
+                job.schedule,
+                jobs::describe_schedule(&job.schedule)
+            );
+            if let Some(tz) = &job.timezone {
+                println!("timezone: {tz}");
+            }
             println!("command: {}", job.command);
-            print_scheduler_auto_start_result(paths);
+            if let Some(cwd) = &job.cwd {
+                println!("cwd: {cwd}");
+            }
+            if job.run_on_start {
+                println!("run_on_start: true");
+            }
+            if job.onefail {
+                println!("onefail: true");
+            }
+            print_scheduler_auto_start_result(paths, "任务已创建");
             let event = format!(
                 "用户创建了定时任务：name={}，schedule={}，command={}",
                 job.name, job.schedule, job.command
             );
             memory::append_short_term(paths, "cron.add", &event)?;
-            let _ = memory::auto_capture_event(paths, "cron.add", &event)?;
+            let _ = memory::auto_capture_event(paths, "cron.add", &event).await?;
         }
         CronCommand::List => {
             let jobs = jobs::load_jobs(paths)?;
@@ -905,9 +2028,21 @@ fn handle_cron_command(paths: &AgentPaths, command: CronCommand) -> Result<()> {
                 println!("当前没有定时任务。");
             } else {
                 for job in jobs {
+                    let cwd = job
+                        .cwd
+                        .as_deref()
+                        .map(|cwd| format!(" | cwd={cwd}"))
+                        .unwrap_or_default();
                     println!(
-                        "{} | {} | {} | retry={} | {}",
-                        job.id, job.name, job.schedule, job.retry_max, job.command
+                        "{} | {} | {} ({}) | tz={} | retry={}{} | {}",
+                        job.id,
+                        job.name,
+                        job.schedule,
+                        jobs::describe_schedule(&job.schedule),
+                        job.timezone.as_deref().unwrap_or("local"),
+                        job.retry_max,
+                        cwd,
+                        job.command
                     );
                 }
             }
@@ -920,11 +2055,49 @@ fn handle_cron_command(paths: &AgentPaths, command: CronCommand) -> Result<()> {
                 println!("Job not found: {id}");
             }
         }
+        CronCommand::Explain { schedule } => {
+            let normalized = jobs::normalize_schedule(&schedule)?;
+            jobs::validate_schedule(&schedule)?;
+            println!("cron: {normalized}");
+            println!("{}", jobs::describe_schedule(&schedule));
+        }
+        CronCommand::Enable { id } => toggle_cron_job(paths, &id, true).await?,
+        CronCommand::Disable { id } => toggle_cron_job(paths, &id, false).await?,
+        CronCommand::Run { id, force } => {
+            let jobs = jobs::load_jobs(paths)?;
+            let Some(job) = jobs.iter().find(|job| job.id == id) else {
+                println!("Job not found: {id}");
+                return Ok(());
+            };
+            let output = scheduler::run_job_now(paths, job, force).await?;
+            if !output.stdout.trim().is_empty() {
+                println!("{}", output.stdout.trim_end());
+            }
+            if !output.stderr.trim().is_empty() {
+                eprintln!("{}", output.stderr.trim_end());
+            }
+        }
+        CronCommand::Next { limit } => {
+            let jobs = jobs::load_jobs(paths)?;
+            let fires = jobs::upcoming_fires(&jobs, limit, chrono::Local::now())?;
+            if fires.is_empty() {
+                println!("当前没有启用的定时任务。");
+            } else {
+                for fire in fires {
+                    println!(
+                        "{} | {} | {}",
+                        fire.at.format("%Y-%m-%d %H:%M:%S"),
+                        fire.job_name,
+                        fire.job_id
+                    );
+                }
+            }
+        }
     }
     Ok(())
 }
 
-fn handle_hook_command(paths: &AgentPaths, command: HookCommand) -> Result<()> {
+async fn handle_hook_command(paths: &AgentPaths, command: HookCommand) -> Result<()> {
     match command {
         HookCommand::AddGit {
             repo,
@@ -969,7 +2142,62 @@ fn handle_hook_command(paths: &AgentPaths, command: HookCommand) -> Result<()> {
             } else {
                 println!("command: {}", hook.command);
             }
-            print_scheduler_auto_start_result(paths);
+            print_scheduler_auto_start_result(paths, "hook 已创建");
+            let event = format!(
+                "用户创建了 hook：name={}，source={}，target={}，rules_file={:?}，command={}",
+                hook.name,
+                hook.source.as_str(),
+                hook.target,
+                hook.rules_file,
+                hook.command
+            );
+            memory::append_short_term(paths, "hook.add", &event)?;
+            let _ = memory::auto_capture_event(paths, "hook.add", &event).await?;
+        }
+        HookCommand::AddHg {
+            repo,
+            command,
+            rules_file,
+            report_file,
+            reference,
+            interval,
+            name,
+            retry_max,
+        } => {
+            if command.is_none() && rules_file.is_none() {
+                bail!("必须提供 --command 或 --rules-file 之一");
+            }
+            let command = command.unwrap_or_default();
+            let hook = hooks::add_hg_hook(
+                paths,
+                repo,
+                reference,
+                interval,
+                command,
+                name,
+                retry_max,
+                rules_file,
+                report_file,
+            )?;
+            println!("Added hook:");
+            println!("id: {}", hook.id);
+            println!("name: {}", hook.name);
+            println!("source: {}", hook.source.as_str());
+            println!("target: {}", hook.target);
+            println!("reference: {}", hook.reference.as_deref().unwrap_or("tip"));
+            println!("interval_secs: {}", hook.interval_secs);
+            if let Some(ref rf) = hook.rules_file {
+                println!("rules_file: {rf}");
+                println!(
+                    "report_file: {}",
+                    hook.report_file
+                        .as_deref()
+                        .unwrap_or("<target>/goldagent-review.md")
+                );
+            } else {
+                println!("command: {}", hook.command);
+            }
+            print_scheduler_auto_start_result(paths, "hook 已创建");
             let event = format!(
                 "用户创建了 hook：name={}，source={}，target={}，rules_file={:?}，command={}",
                 hook.name,
@@ -979,7 +2207,7 @@ fn handle_hook_command(paths: &AgentPaths, command: HookCommand) -> Result<()> {
                 hook.command
             );
             memory::append_short_term(paths, "hook.add", &event)?;
-            let _ = memory::auto_capture_event(paths, "hook.add", &event)?;
+            let _ = memory::auto_capture_event(paths, "hook.add", &event).await?;
         }
         HookCommand::AddP4 {
             depot,
@@ -1021,7 +2249,7 @@ fn handle_hook_command(paths: &AgentPaths, command: HookCommand) -> Result<()> {
             } else {
                 println!("command: {}", hook.command);
             }
-            print_scheduler_auto_start_result(paths);
+            print_scheduler_auto_start_result(paths, "hook 已创建");
             let event = format!(
                 "用户创建了 hook：name={}，source={}，target={}，rules_file={:?}，command={}",
                 hook.name,
@@ -1031,7 +2259,7 @@ fn handle_hook_command(paths: &AgentPaths, command: HookCommand) -> Result<()> {
                 hook.command
             );
             memory::append_short_term(paths, "hook.add", &event)?;
-            let _ = memory::auto_capture_event(paths, "hook.add", &event)?;
+            let _ = memory::auto_capture_event(paths, "hook.add", &event).await?;
         }
         HookCommand::List => {
             let hooks = hooks::load_hooks(paths)?;
@@ -1058,6 +2286,59 @@ fn handle_hook_command(paths: &AgentPaths, command: HookCommand) -> Result<()> {
                 }
             }
         }
+        HookCommand::Test { id, dry_run } => {
+            let hooks = hooks::load_hooks(paths)?;
+            let Some(hook) = hooks.iter().find(|hook| hook.id == id) else {
+                println!("Hook not found: {id}");
+                return Ok(());
+            };
+            let current = hooks::read_signature(hook).await?;
+            println!("source: {}", hook.source.as_str());
+            println!("target: {}", hook.target);
+            println!("current: {current}（previous 同样取此值用于演示）");
+            if hook.rules_file.is_some() {
+                println!("mode: llm-review");
+                println!(
+                    "rules_file: {}",
+                    scheduler::resolve_rules_path(hook).display()
+                );
+                println!(
+                    "report_file: {}",
+                    scheduler::resolve_report_path(hook).display()
+                );
+                return Ok(());
+            }
+            let command = hooks::render_command_template(hook, &current, &current);
+            println!("command: {command}");
+            if dry_run {
+                return Ok(());
+            }
+            let output =
+                shell::run_scheduled_command(paths, &command, false, hook.command_cwd()).await?;
+            if !output.stdout.trim().is_empty() {
+                println!("{}", output.stdout.trim_end());
+            }
+            if !output.stderr.trim().is_empty() {
+                eprintln!("{}", output.stderr.trim_end());
+            }
+        }
+        HookCommand::Check { id } => {
+            let hooks = hooks::load_hooks(paths)?;
+            let Some(hook) = hooks.iter().find(|hook| hook.id == id) else {
+                println!("Hook not found: {id}");
+                return Ok(());
+            };
+            let current = hooks::read_signature(hook).await.with_context(|| {
+                format!(
+                    "读取 hook {id} 的签名失败（{} {}）",
+                    hook.source.as_str(),
+                    hook.target
+                )
+            })?;
+            println!("source: {}", hook.source.as_str());
+            println!("target: {}", hook.target);
+            println!("signature: {current}");
+        }
         HookCommand::Remove { id } => {
             let removed = hooks::remove_hook(paths, &id)?;
             if removed {
@@ -1072,6 +2353,269 @@ fn handle_hook_command(paths: &AgentPaths, command: HookCommand) -> Result<()> {
             println!("编辑完成后，用以下命令创建 hook：");
             println!("  goldagent hook add-git <repo> --ref main --rules-file {path}");
         }
+        HookCommand::OnFindings {
+            id,
+            command,
+            min_severity,
+        } => match hooks::set_on_findings(paths, &id, command, min_severity)? {
+            None => println!("Hook not found: {id}"),
+            Some(hook) => match hook.on_findings.as_deref() {
+                Some(cmd) => println!(
+                    "已设置 hook {} 的 on_findings（>= {}）：{cmd}",
+                    hook.id,
+                    hook.on_findings_min_severity.as_str()
+                ),
+                None => println!("已清除 hook {} 的 on_findings。", hook.id),
+            },
+        },
+    }
+    Ok(())
+}
+
+fn handle_memory_command(paths: &AgentPaths, command: MemoryCommand) -> Result<()> {
+    match command {
+        MemoryCommand::Check { repair } => {
+            let report = memory::check(paths, repair)?;
+            if report.anomalies.is_empty() {
+                println!("MEMORY.md 结构正常。");
+                return Ok(());
+            }
+            println!("发现 {} 个结构问题：", report.anomalies.len());
+            for anomaly in &report.anomalies {
+                println!("- {anomaly}");
+            }
+            match report.backup_file {
+                Some(backup) => println!("已修复，原文件备份到：{}", backup.display()),
+                None => println!("使用 `goldagent memory check --repair` 修复。"),
+            }
+        }
+        MemoryCommand::Search { query, limit, tag } => {
+            let hits = memory::search(paths, &query, limit, tag.as_deref())?;
+            if hits.is_empty() {
+                println!("没有找到匹配的记忆。");
+                return Ok(());
+            }
+            for hit in hits {
+                let kind = if hit.long_term {
+                    format!("长期 {}", hit.id)
+                } else {
+                    format!("短期 {}", hit.source.as_deref().unwrap_or("-"))
+                };
+                let labels = if hit.tags.is_empty() {
+                    String::new()
+                } else {
+                    format!(" | tags: {}", hit.tags.join(", "))
+                };
+                println!(
+                    "[{}] {kind} | {}{labels}",
+                    hit.score,
+                    if hit.timestamp.is_empty() {
+                        "-"
+                    } else {
+                        &hit.timestamp
+                    }
+                );
+                for line in hit.content.lines().take(4) {
+                    println!("    {line}");
+                }
+            }
+        }
+        MemoryCommand::Export { format, out } => {
+            let export = memory::export(paths)?;
+            let rendered = match format {
+                MemoryExportFormat::Json => serde_json::to_string_pretty(&export)?,
+            };
+            match out {
+                Some(path) => {
+                    fsutil::write_atomic(&path, format!("{rendered}\n"))?;
+                    eprintln!(
+                        "已导出 {} 条长期记忆、{} 天短期记忆到：{}",
+                        export.long_term.len(),
+                        export.short_term.len(),
+                        path.display()
+                    );
+                }
+                None => println!("{rendered}"),
+            }
+        }
+        MemoryCommand::Forget { id, matching, yes } => {
+            let outcome = memory::forget(paths, id.as_deref(), matching.as_deref(), yes)?;
+            if outcome.matched.is_empty() {
+                println!("没有匹配的长期记忆。");
+            } else if outcome.removed == 0 {
+                println!(
+                    "匹配到 {} 条记忆：{}",
+                    outcome.matched.len(),
+                    outcome.matched.join(", ")
+                );
+                println!("确认全部删除请加 --yes，或按 id 逐条删除。");
+            } else {
+                println!(
+                    "已删除 {} 条记忆：{}",
+                    outcome.removed,
+                    outcome.matched.join(", ")
+                );
+                memory::append_short_term(
+                    paths,
+                    "memory.forget",
+                    &format!("removed={}", outcome.matched.join(",")),
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_usage_command(paths: &AgentPaths, command: UsageCommand) -> Result<()> {
+    match command {
+        UsageCommand::Top { by, limit } => {
+            let stats = usage::load(&paths.usage_file)?;
+            let (label, entries) = match by {
+                UsageGrouping::Model => ("模型", &stats.by_model),
+                UsageGrouping::Day => ("日期", &stats.by_day),
+            };
+            let top = usage::top_entries(entries, limit);
+            if top.is_empty() {
+                println!("暂无用量记录。");
+                return Ok(());
+            }
+            println!("用量最高的{label}（按输出 tokens 排序）：");
+            for (key, counter) in top {
+                println!(
+                    "{key} | requests={} | input={} | output={}",
+                    counter.requests, counter.input_tokens, counter.output_tokens
+                );
+            }
+        }
+        UsageCommand::Reset { scope, yes } => {
+            if !yes {
+                let answer = prompt_line(&format!("将清空{}，是否继续？[y/N] ", scope.label()))?;
+                if !matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+                    println!("已取消重置。");
+                    return Ok(());
+                }
+            }
+            let stats = usage::reset(&paths.usage_file, scope)?;
+            println!(
+                "已清空{}（{}）。",
+                scope.label(),
+                stats.updated_at.as_deref().unwrap_or_default()
+            );
+            memory::append_short_term(paths, "usage.reset", &format!("scope={scope:?}"))?;
+        }
+    }
+    Ok(())
+}
+
+fn print_usage_report(
+    paths: &AgentPaths,
+    since: Option<&str>,
+    by: UsageGrouping,
+    json: bool,
+    prices_path: Option<&str>,
+) -> Result<()> {
+    let since = since
+        .map(|raw| {
+            chrono::NaiveDate::parse_from_str(raw.trim(), "%Y-%m-%d")
+                .with_context(|| format!("--since 需要 YYYY-MM-DD 格式的日期: {raw}"))
+        })
+        .transpose()?;
+    let prices_file = match prices_path {
+        Some(raw) => {
+            let path = PathBuf::from(raw);
+            if !path.exists() {
+                bail!("价格文件不存在: {}", path.display());
+            }
+            path
+        }
+        None => paths.root.join("prices.json"),
+    };
+    let prices = usage::load_prices(&prices_file)?;
+    let stats = usage::load(&paths.usage_file)?;
+    let (label, rows) = match by {
+        UsageGrouping::Day => ("日期", usage::report_by_day(&stats, since, &prices)),
+        UsageGrouping::Model => ("模型", usage::report_by_model(&stats, since, &prices)),
+    };
+    let total = usage::report_total(&rows);
+
+    if json {
+        let report = serde_json::json!({
+            "by": match by {
+                UsageGrouping::Day => "day",
+                UsageGrouping::Model => "model",
+            },
+            "since": since.map(|day| day.to_string()),
+            "rows": rows,
+            "total": total,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    if rows.is_empty() {
+        println!("暂无用量记录。");
+        return Ok(());
+    }
+    let key_width = rows
+        .iter()
+        .map(|row| row.key.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(label.len());
+    let format_cost = |cost: Option<f64>| match cost {
+        Some(cost) => format!("${cost:.4}"),
+        None => "-".to_string(),
+    };
+    println!(
+        "{label:<key_width$}  {:>8}  {:>12}  {:>12}  {:>10}",
+        "requests", "input", "output", "cost"
+    );
+    for row in rows.iter().chain(std::iter::once(&total)) {
+        println!(
+            "{:<key_width$}  {:>8}  {:>12}  {:>12}  {:>10}",
+            row.key,
+            row.counter.requests,
+            row.counter.input_tokens,
+            row.counter.output_tokens,
+            format_cost(row.cost)
+        );
+    }
+    if since.is_some() && matches!(by, UsageGrouping::Model) {
+        println!("注：按模型 + --since 只统计带模型明细的日期（升级后记录的用量）。");
+    }
+    println!("费用为按内置/价格文件单价的估算值，\"-\" 表示缺少单价或模型明细。");
+    Ok(())
+}
+
+fn handle_scheduler_command(paths: &AgentPaths, command: SchedulerCommand) -> Result<()> {
+    match command {
+        SchedulerCommand::Pid {
+            command: SchedulerPidCommand::Show,
+        } => match scheduler::pid_file_status(paths)? {
+            None => println!("没有调度服务 pid 文件。"),
+            Some(status) => {
+                println!("pid 文件：{}", status.path.display());
+                println!("内容：{}", status.contents);
+                let state = match status.pid {
+                    None => "无效（不是合法的 pid）",
+                    Some(_) if !status.alive => "进程不存在（残留文件）",
+                    Some(_) if !status.is_scheduler => {
+                        "进程存活，但不是 goldagent serve（pid 被复用）"
+                    }
+                    Some(_) => "调度服务运行中",
+                };
+                println!("状态：{state}");
+            }
+        },
+        SchedulerCommand::Pid {
+            command: SchedulerPidCommand::Clear,
+        } => {
+            if scheduler::clear_pid_file(paths)? {
+                println!("已删除调度服务 pid 文件。");
+            } else {
+                println!("没有调度服务 pid 文件。");
+            }
+        }
     }
     Ok(())
 }
@@ -1093,17 +2637,111 @@ async fn handle_skill_command(paths: &AgentPaths, command: SkillCommand) -> Resu
                 }
             }
         }
+        SkillCommand::Info { name } => {
+            let details = skills::skill_info(paths, &name)?;
+            println!("名称：{}", details.info.name);
+            println!("描述：{}", details.info.description);
+            println!(
+                "模型：{}",
+                details.metadata.model.as_deref().unwrap_or("未声明")
+            );
+            if details.parameters.is_empty() {
+                println!("参数：未声明");
+            } else {
+                println!("参数：");
+                for parameter in &details.parameters {
+                    println!("- {parameter}");
+                }
+            }
+            if let Some(secs) = details.metadata.min_interval_secs {
+                println!("最小运行间隔：{secs} 秒");
+            }
+            println!("运行次数：{}", details.stats.runs);
+            println!(
+                "最近运行：{}",
+                details.stats.last_run_at.as_deref().unwrap_or("从未运行")
+            );
+            println!("路径：{}", details.info.path.display());
+        }
         SkillCommand::New { name } => {
             let path = skills::create_skill(paths, &name)?;
             println!("已创建技能模板：{}", path.display());
             let event = format!("用户创建了技能：name={}，path={}", name, path.display());
             memory::append_short_term(paths, "skill.new", &event)?;
-            let _ = memory::auto_capture_event(paths, "skill.new", &event)?;
+            let _ = memory::auto_capture_event(paths, "skill.new", &event).await?;
+        }
+        SkillCommand::Remove { name, yes, force } => {
+            if !yes {
+                println!(
+                    "将删除技能目录 {}，确认请加 --yes。",
+                    paths.skills_dir.join(&name).display()
+                );
+                return Ok(());
+            }
+            let dir = skills::remove_skill(paths, &name, force)?;
+            println!("已删除技能：{name}");
+            let event = format!("用户删除了技能：name={}，path={}", name, dir.display());
+            memory::append_short_term(paths, "skill.remove", &event)?;
+        }
+        SkillCommand::Export { name, out } => {
+            let bundle = skills::export_skill(paths, &name)?;
+            match out {
+                Some(path) => {
+                    fsutil::write_atomic(&path, bundle)?;
+                    eprintln!("已导出技能 {name} 到：{}", path.display());
+                }
+                None => print!("{bundle}"),
+            }
+        }
+        SkillCommand::Import { path, force } => {
+            let bundle = std::fs::read_to_string(&path)
+                .with_context(|| format!("读取技能包失败: {}", path.display()))?;
+            let dir = skills::import_skill(paths, &bundle, force)?;
+            println!("已导入技能：{}", dir.display());
+            let event = format!(
+                "用户导入了技能：from={}，path={}",
+                path.display(),
+                dir.display()
+            );
+            memory::append_short_term(paths, "skill.import", &event)?;
+        }
+        SkillCommand::Rename { from, to, force } => {
+            let dir = skills::rename_skill(paths, &from, &to, force)?;
+            println!("已重命名技能：{from} -> {}", dir.display());
+            let event = format!("用户重命名了技能：from={}，to={}", from, dir.display());
+            memory::append_short_term(paths, "skill.rename", &event)?;
         }
-        SkillCommand::Run { name, input, model } => {
-            let client = ProviderClient::from_paths(paths, model)?;
-            let response = run_skill_and_record(paths, &client, &name, &input).await?;
+        SkillCommand::Run {
+            name,
+            input,
+            model,
+            repeat_on_fail,
+            timeout,
+            max_steps,
+            profile,
+            append,
+            context,
+        } => {
+            let input = skills::resolve_skill_input(&input, Some(&mut io::stdin()))?;
+            let client = client_with_profile(paths, model, &profile)?;
+            let budget = skills::SkillBudget {
+                timeout: timeout.map(Duration::from_secs),
+                max_steps,
+            };
+            let response = run_skill_and_record(
+                paths,
+                &client,
+                &name,
+                &input,
+                repeat_on_fail,
+                budget,
+                context,
+            )
+            .await?;
             println!("{response}");
+            if let Some(path) = append {
+                append_transcript(paths, &path, &format!("skill {name}"), &input, &response)?;
+            }
         }
     }
     Ok(())
@@ -1114,13 +2752,21 @@ async fn run_skill_and_record(
     client: &ProviderClient,
     name: &str,
     input: &str,
+    repeat_on_fail: bool,
+    budget: skills::SkillBudget,
+    context: skills::SkillContext,
 ) -> Result<String> {
-    let response = skills::run_skill(paths, client, name, input).await?;
+    let response =
+        skills::run_skill(paths, client, name, input, repeat_on_fail, budget, context).await?;
+    let response = strip_wrappers(paths, &response);
+    if let Err(err) = skills::record_run(paths, name) {
+        eprintln!("记录技能 {name} 的运行次数失败：{err:#}");
+    }
     memory::append_short_term(
         paths,
         &format!("skill.{name}"),
         &format!("input:\n{input}\n\nresponse:\n{response}"),
     )?;
-    memory::auto_capture_long_term(paths, &format!("skill.{name}"), input)?;
+    memory::auto_capture_long_term(paths, &format!("skill.{name}"), input).await?;
     Ok(response)
 }
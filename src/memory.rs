@@ -1,16 +1,30 @@
-use crate::config::AgentPaths;
-use anyhow::Result;
+use crate::config::{self, AgentPaths};
+use crate::fsutil::{self, FileLock};
+use crate::provider::ProviderClient;
+use anyhow::{Result, bail};
 use chrono::{Local, Utc};
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::{self, OpenOptions};
+use std::future::Future;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 
 const LONG_TERM_MEMORY_TITLE: &str = "# GoldAgent 长期记忆";
 const LONG_TERM_MEMORY_HEADER: &str =
     "# GoldAgent 长期记忆\n\n此文件用于保存长期、可复用的记忆。\n";
 const CAPABILITY_DECLARATION_TITLE: &str = "## GoldAgent 能力声明";
 const CONNECT_MEMORY_RULES_TITLE: &str = "## GoldAgent 连接与记忆规则";
+const ENTRY_SEPARATOR: &str = "---";
+
+/// Result of `goldagent memory check`.
+#[derive(Debug, Default)]
+pub struct MemoryCheckReport {
+    pub anomalies: Vec<String>,
+    /// Copy of the original file written before a repair.
+    pub backup_file: Option<PathBuf>,
+}
 
 pub fn append_global(paths: &AgentPaths, content: &str, tags: &[String]) -> Result<String> {
     let ts = Utc::now();
@@ -32,6 +46,7 @@ content:\n\
         ts.to_rfc3339()
     );
 
+    let _lock = FileLock::acquire(&paths.memory_file)?;
     let mut file = OpenOptions::new()
         .create(true)
         .append(true)
@@ -40,7 +55,326 @@ content:\n\
     Ok(id)
 }
 
+/// A `## ...` section of a memory file. `text` runs from the heading up to
+/// the next heading, so concatenating all blocks after the preamble
+/// reproduces the file.
+#[derive(Debug, Clone, Copy)]
+struct MemoryBlock<'a> {
+    heading: &'a str,
+    text: &'a str,
+}
+
+impl<'a> MemoryBlock<'a> {
+    /// Value of a `key: value` line, e.g. `tags` or `source`.
+    fn field(&self, key: &str) -> Option<&'a str> {
+        self.text.lines().find_map(|line| {
+            line.strip_prefix(key)
+                .and_then(|rest| rest.strip_prefix(':'))
+                .map(str::trim)
+        })
+    }
+
+    /// Body after the `content:` line, without the entry separator.
+    fn content(&self) -> &'a str {
+        let body = self
+            .text
+            .split_once("\ncontent:\n")
+            .map(|(_, body)| body)
+            .unwrap_or(self.text);
+        body.trim_end()
+            .strip_suffix(ENTRY_SEPARATOR)
+            .unwrap_or(body)
+            .trim()
+    }
+
+    fn is_long_term_entry(&self) -> bool {
+        self.heading.starts_with("mem_")
+    }
+}
+
+/// Splits `content` into the text before the first `## ` heading and the
+/// blocks that follow.
+fn split_blocks(content: &str) -> (&str, Vec<MemoryBlock<'_>>) {
+    let mut starts = Vec::new();
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        if line.starts_with("## ") {
+            starts.push(offset);
+        }
+        offset += line.len();
+    }
+    let preamble = &content[..starts.first().copied().unwrap_or(content.len())];
+    let blocks = starts
+        .iter()
+        .enumerate()
+        .map(|(idx, &start)| {
+            let end = starts.get(idx + 1).copied().unwrap_or(content.len());
+            let text = &content[start..end];
+            let heading = text.lines().next().unwrap_or_default()[3..].trim();
+            MemoryBlock { heading, text }
+        })
+        .collect();
+    (preamble, blocks)
+}
+
+/// A long-term entry as written by [`append_global`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LongTermEntry {
+    pub id: String,
+    pub timestamp: String,
+    pub tags: Vec<String>,
+    pub content: String,
+}
+
+/// A block of a daily short-term file as written by [`append_short_term`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ShortTermEntry {
+    pub timestamp: String,
+    pub source: Option<String>,
+    pub content: String,
+}
+
+/// Parses the `mem_...` entries of `MEMORY.md`, skipping the managed
+/// capability/connect sections.
+pub fn parse_long_term(content: &str) -> Vec<LongTermEntry> {
+    split_blocks(content)
+        .1
+        .into_iter()
+        .filter(MemoryBlock::is_long_term_entry)
+        .map(|block| LongTermEntry {
+            id: block.heading.to_string(),
+            timestamp: block.field("timestamp").unwrap_or_default().to_string(),
+            tags: block
+                .field("tags")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty() && *tag != "none")
+                .map(str::to_string)
+                .collect(),
+            content: block.content().to_string(),
+        })
+        .collect()
+}
+
+/// Parses the timestamped blocks of one daily short-term file.
+pub fn parse_short_term(content: &str) -> Vec<ShortTermEntry> {
+    split_blocks(content)
+        .1
+        .into_iter()
+        .map(|block| ShortTermEntry {
+            timestamp: block.heading.to_string(),
+            source: block.field("source").map(str::to_string),
+            content: block.content().to_string(),
+        })
+        .collect()
+}
+
+/// Entries of one `memory/YYYY-MM-DD.md` file.
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyMemory {
+    pub date: String,
+    pub entries: Vec<ShortTermEntry>,
+}
+
+/// Output of `goldagent memory export`.
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryExport {
+    pub long_term: Vec<LongTermEntry>,
+    pub short_term: Vec<DailyMemory>,
+}
+
+/// Collects all long-term entries and daily files, oldest day first.
+pub fn export(paths: &AgentPaths) -> Result<MemoryExport> {
+    let long_term = parse_long_term(&fs::read_to_string(&paths.memory_file).unwrap_or_default());
+    let mut files = list_short_term_files(&paths.memory_dir)?;
+    files.sort();
+    let short_term = files
+        .iter()
+        .map(|file| DailyMemory {
+            date: file
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            entries: parse_short_term(&fs::read_to_string(file).unwrap_or_default()),
+        })
+        .collect();
+    Ok(MemoryExport {
+        long_term,
+        short_term,
+    })
+}
+
+/// One result of `goldagent memory search`.
+#[derive(Debug, Clone)]
+pub struct MemoryHit {
+    /// `mem_...` id for long-term entries, the timestamp heading otherwise.
+    pub id: String,
+    pub long_term: bool,
+    pub timestamp: String,
+    pub tags: Vec<String>,
+    pub source: Option<String>,
+    pub content: String,
+    pub score: usize,
+}
+
+/// Score of a whole-query match relative to a single matching token.
+const PHRASE_MATCH_WEIGHT: usize = 5;
+
+/// Searches long-term entries and daily short-term files. Blocks score for
+/// the whole query and for every whitespace-separated query token they
+/// contain, compared via [`normalize_for_compare`]. With `tag`, only
+/// long-term entries whose tags contain it are returned.
+pub fn search(
+    paths: &AgentPaths,
+    query: &str,
+    limit: usize,
+    tag: Option<&str>,
+) -> Result<Vec<MemoryHit>> {
+    let phrase = normalize_for_compare(query);
+    let tokens = query
+        .split_whitespace()
+        .map(normalize_for_compare)
+        .filter(|token| !token.is_empty())
+        .collect::<HashSet<_>>();
+    let tag = tag.map(str::to_lowercase);
+    let score = |text: &str| {
+        let haystack = normalize_for_compare(text);
+        let phrase_score = if !phrase.is_empty() && haystack.contains(&phrase) {
+            PHRASE_MATCH_WEIGHT
+        } else {
+            0
+        };
+        phrase_score
+            + tokens
+                .iter()
+                .filter(|token| haystack.contains(token.as_str()))
+                .count()
+    };
+
+    let mut hits = Vec::new();
+    let long_term = fs::read_to_string(&paths.memory_file).unwrap_or_default();
+    for entry in parse_long_term(&long_term) {
+        if let Some(wanted) = &tag
+            && !entry
+                .tags
+                .iter()
+                .any(|tag| tag.to_lowercase().contains(wanted))
+        {
+            continue;
+        }
+        let score = score(&format!("{}\n{}", entry.content, entry.tags.join(" ")));
+        if score > 0 {
+            hits.push(MemoryHit {
+                id: entry.id,
+                long_term: true,
+                timestamp: entry.timestamp,
+                tags: entry.tags,
+                source: None,
+                content: entry.content,
+                score,
+            });
+        }
+    }
+
+    if tag.is_none() {
+        for file in list_short_term_files(&paths.memory_dir)? {
+            let raw = fs::read_to_string(&file).unwrap_or_default();
+            for entry in parse_short_term(&raw) {
+                let score = score(&entry.content);
+                if score > 0 {
+                    hits.push(MemoryHit {
+                        id: entry.timestamp.clone(),
+                        long_term: false,
+                        timestamp: entry.timestamp,
+                        tags: Vec::new(),
+                        source: entry.source,
+                        content: entry.content,
+                        score,
+                    });
+                }
+            }
+        }
+    }
+
+    hits.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then_with(|| b.timestamp.cmp(&a.timestamp))
+    });
+    hits.truncate(limit);
+    Ok(hits)
+}
+
+/// Result of `goldagent memory forget`.
+#[derive(Debug, Default)]
+pub struct ForgetOutcome {
+    /// Ids of the long-term entries that matched.
+    pub matched: Vec<String>,
+    /// Number of entries removed; 0 when several matched without `confirmed`.
+    pub removed: usize,
+}
+
+/// Removes long-term `mem_` entries whose id equals `id` or whose content
+/// contains `matching` (case-insensitive). The title, capability declaration
+/// and connect-rules sections are never touched. A `matching` hitting more
+/// than one entry only removes them when `confirmed`.
+pub fn forget(
+    paths: &AgentPaths,
+    id: Option<&str>,
+    matching: Option<&str>,
+    confirmed: bool,
+) -> Result<ForgetOutcome> {
+    let id = id.map(str::trim).filter(|id| !id.is_empty());
+    let needle = matching
+        .map(|text| text.trim().to_lowercase())
+        .filter(|text| !text.is_empty());
+    if id.is_none() && needle.is_none() {
+        bail!("请提供要删除的记忆 id 或 --matching 文本");
+    }
+
+    let _lock = FileLock::acquire(&paths.memory_file)?;
+    let existing = fs::read_to_string(&paths.memory_file).unwrap_or_default();
+    let (preamble, blocks) = split_blocks(&existing);
+    let is_target = |block: &MemoryBlock| {
+        block.is_long_term_entry()
+            && (id.is_some_and(|id| block.heading == id)
+                || needle
+                    .as_ref()
+                    .is_some_and(|needle| block.content().to_lowercase().contains(needle)))
+    };
+    let matched = blocks
+        .iter()
+        .filter(|block| is_target(block))
+        .map(|block| block.heading.to_string())
+        .collect::<Vec<_>>();
+    if matched.is_empty() || (needle.is_some() && matched.len() > 1 && !confirmed) {
+        return Ok(ForgetOutcome {
+            matched,
+            removed: 0,
+        });
+    }
+
+    let mut rewritten = preamble.to_string();
+    for block in blocks.iter().filter(|block| !is_target(block)) {
+        rewritten.push_str(block.text);
+    }
+    fsutil::write_atomic(&paths.memory_file, rewritten)?;
+    Ok(ForgetOutcome {
+        removed: matched.len(),
+        matched,
+    })
+}
+
+/// Rewrites `MEMORY.md` with the header and the managed capability and
+/// connect-rules sections up to date. Does nothing when
+/// `memory.manage_capability_sections` is off.
 pub fn ensure_capability_declarations(paths: &AgentPaths) -> Result<()> {
+    let memory_config = config::load(paths).unwrap_or_default().memory;
+    if !memory_config.manage_capability_sections {
+        return Ok(());
+    }
+    let _lock = FileLock::acquire(&paths.memory_file)?;
     let existing = fs::read_to_string(&paths.memory_file).unwrap_or_default();
     let declaration = render_capability_declaration(paths);
     let connect_memory_rules = render_connect_memory_rules(paths);
@@ -48,8 +382,10 @@ pub fn ensure_capability_declarations(paths: &AgentPaths) -> Result<()> {
         strip_named_section_block(&existing, CAPABILITY_DECLARATION_TITLE);
     let body_without_managed_sections =
         strip_named_section_block(&body_without_declaration, CONNECT_MEMORY_RULES_TITLE);
-    let (header_block, body_without_header) =
-        extract_memory_header_block(&body_without_managed_sections);
+    let (header_block, body_without_header) = extract_memory_header_block(
+        &body_without_managed_sections,
+        memory_config.memory_header.as_deref(),
+    );
     let body = body_without_header.trim_start_matches('\n');
 
     let mut next_content = String::new();
@@ -62,22 +398,43 @@ pub fn ensure_capability_declarations(paths: &AgentPaths) -> Result<()> {
     }
 
     if next_content != existing {
-        fs::write(&paths.memory_file, next_content)?;
+        fsutil::write_atomic(&paths.memory_file, next_content)?;
     }
     Ok(())
 }
 
-fn extract_memory_header_block(input: &str) -> (String, String) {
+/// The configured `memory.memory_header`, or the built-in header.
+fn memory_header(custom: Option<&str>) -> &str {
+    custom
+        .filter(|header| !header.trim().is_empty())
+        .unwrap_or(LONG_TERM_MEMORY_HEADER)
+}
+
+/// First non-empty line of `header`, used to find it in `MEMORY.md`.
+fn header_title(header: &str) -> &str {
+    header
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or(LONG_TERM_MEMORY_TITLE)
+}
+
+/// Splits the title block off `input`. A configured `custom` header replaces
+/// the block found in the file (including the built-in one, so switching to
+/// a custom header does not keep both); otherwise the file's block is kept.
+fn extract_memory_header_block(input: &str, custom: Option<&str>) -> (String, String) {
+    let header = memory_header(custom);
     let lines = input.lines().collect::<Vec<_>>();
     if lines.is_empty() {
-        return (LONG_TERM_MEMORY_HEADER.to_string(), String::new());
+        return (header.to_string(), String::new());
     }
 
-    let Some(start) = lines
-        .iter()
-        .position(|line| line.trim() == LONG_TERM_MEMORY_TITLE)
-    else {
-        return (LONG_TERM_MEMORY_HEADER.to_string(), input.to_string());
+    let title = header_title(header);
+    let Some(start) = lines.iter().position(|line| {
+        let line = line.trim();
+        line == title || line == LONG_TERM_MEMORY_TITLE
+    }) else {
+        return (header.to_string(), input.to_string());
     };
 
     let mut end = start + 1;
@@ -88,9 +445,9 @@ fn extract_memory_header_block(input: &str) -> (String, String) {
         end += 1;
     }
 
-    let mut header = lines[start..end].join("\n");
-    if header.trim().is_empty() {
-        header = LONG_TERM_MEMORY_HEADER.to_string();
+    let mut header_block = lines[start..end].join("\n");
+    if custom.is_some() || header_block.trim().is_empty() {
+        header_block = header.to_string();
     }
 
     let mut remainder_lines = Vec::with_capacity(lines.len().saturating_sub(end - start));
@@ -102,7 +459,7 @@ fn extract_memory_header_block(input: &str) -> (String, String) {
         body.push('\n');
     }
 
-    (header, body)
+    (header_block, body)
 }
 
 fn strip_named_section_block(input: &str, section_title: &str) -> String {
@@ -145,6 +502,131 @@ fn strip_named_section_block(input: &str, section_title: &str) -> String {
     merged
 }
 
+/// Validates the structure of `MEMORY.md` (single leading title, at most one
+/// copy of each managed section, `---` only closing a `## mem_*` entry). With
+/// `repair`, backs the file up and rewrites it with the anomalies removed;
+/// entry content is kept as-is.
+pub fn check(paths: &AgentPaths, repair: bool) -> Result<MemoryCheckReport> {
+    let mut report = MemoryCheckReport::default();
+    let custom_header = config::load(paths).unwrap_or_default().memory.memory_header;
+    let header = memory_header(custom_header.as_deref());
+    {
+        let _lock = FileLock::acquire(&paths.memory_file)?;
+        let existing = fs::read_to_string(&paths.memory_file).unwrap_or_default();
+        report.anomalies = find_structure_anomalies(&existing, header_title(header));
+        if !repair || report.anomalies.is_empty() {
+            return Ok(report);
+        }
+
+        let mut backup_name = paths
+            .memory_file
+            .file_name()
+            .unwrap_or_default()
+            .to_os_string();
+        backup_name.push(format!(".bak-{}", Local::now().format("%Y%m%d%H%M%S")));
+        let backup_file = paths.memory_file.with_file_name(backup_name);
+        fs::copy(&paths.memory_file, &backup_file)?;
+        report.backup_file = Some(backup_file);
+
+        fsutil::write_atomic(&paths.memory_file, repair_structure(&existing, header))?;
+    }
+    ensure_capability_declarations(paths)?;
+    Ok(report)
+}
+
+fn find_structure_anomalies(content: &str, title: &str) -> Vec<String> {
+    let mut anomalies = Vec::new();
+    let lines = content.lines().collect::<Vec<_>>();
+
+    let title_count = lines.iter().filter(|line| line.trim() == title).count();
+    match title_count {
+        0 => anomalies.push(format!("缺少标题 `{title}`")),
+        1 => {}
+        n => anomalies.push(format!("标题 `{title}` 重复 {n} 次")),
+    }
+    if title_count > 0
+        && lines
+            .iter()
+            .find(|line| !line.trim().is_empty())
+            .map(|line| line.trim())
+            != Some(title)
+    {
+        anomalies.push("标题不在文件开头".to_string());
+    }
+
+    for section in [CAPABILITY_DECLARATION_TITLE, CONNECT_MEMORY_RULES_TITLE] {
+        let count = lines.iter().filter(|line| line.trim() == section).count();
+        if count > 1 {
+            anomalies.push(format!("段落 `{section}` 重复 {count} 次"));
+        }
+    }
+
+    let mut in_entry = false;
+    let mut seen_ids = HashSet::new();
+    for (idx, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if let Some(id) = trimmed.strip_prefix("## mem_") {
+            if in_entry {
+                anomalies.push(format!("第 {} 行：上一条记忆缺少结束标记 `---`", idx + 1));
+            }
+            if !seen_ids.insert(id.to_string()) {
+                anomalies.push(format!("第 {} 行：记忆 ID mem_{id} 重复", idx + 1));
+            }
+            in_entry = true;
+        } else if trimmed == ENTRY_SEPARATOR {
+            if in_entry {
+                in_entry = false;
+            } else {
+                anomalies.push(format!("第 {} 行：多余的结束标记 `---`", idx + 1));
+            }
+        }
+    }
+    anomalies
+}
+
+fn repair_structure(content: &str, header: &str) -> String {
+    let header_lines = header
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<HashSet<_>>();
+    let without_managed = strip_named_section_block(
+        &strip_named_section_block(content, CAPABILITY_DECLARATION_TITLE),
+        CONNECT_MEMORY_RULES_TITLE,
+    );
+
+    let mut body = Vec::new();
+    let mut in_entry = false;
+    for line in without_managed.lines() {
+        let trimmed = line.trim();
+        if header_lines.contains(trimmed) {
+            continue;
+        }
+        if trimmed.starts_with("## mem_") {
+            in_entry = true;
+        } else if trimmed == ENTRY_SEPARATOR {
+            if !in_entry {
+                continue;
+            }
+            in_entry = false;
+        }
+        let previous_blank = body.last().is_none_or(|last: &&str| last.trim().is_empty());
+        if trimmed.is_empty() && previous_blank {
+            continue;
+        }
+        body.push(line);
+    }
+
+    let mut repaired = format!("{}\n", header.trim_end());
+    repaired.push('\n');
+    let body = body.join("\n");
+    if !body.trim().is_empty() {
+        repaired.push_str(body.trim_end());
+        repaired.push_str("\n\n");
+    }
+    repaired
+}
+
 fn render_capability_declaration(paths: &AgentPaths) -> String {
     format!(
         "{CAPABILITY_DECLARATION_TITLE}\n\
@@ -152,7 +634,8 @@ fn render_capability_declaration(paths: &AgentPaths) -> String {
 - 持久化文件：`{jobs_file}`\n\
 - 创建方式：`goldagent cron add \"<schedule>\" \"goldagent run \\\"<task>\\\"\"`\n\
 - 常用 schedule：`daily@HH:MM`、`weekdays@HH:MM`、5/6 段 cron 表达式\n\
-- 管理命令：`goldagent cron list`、`goldagent cron remove <job_id>`\n\
+- 一次性任务：`at:2025-06-01T09:00:00+08:00`（RFC 3339）或 `at@YYYY-MM-DD HH:MM`，触发一次后自动删除\n\
+- 管理命令：`goldagent cron list`、`goldagent cron next [--limit N]`、`goldagent cron remove <job_id>`\n\
 \n\
 ### Hook 事件触发\n\
 - 持久化文件：`{hooks_file}`\n\
@@ -160,6 +643,7 @@ fn render_capability_declaration(paths: &AgentPaths) -> String {
 - LLM 审查 hook：`goldagent hook add-git <repo_path> --rules-file <rules.md> --ref <branch> --interval <seconds>`\n\
 - 生成审查规则模板：`goldagent hook rules-new [path]`\n\
 - P4 hook：`goldagent hook add-p4 <depot_path> --command \"...\" --interval <seconds>`\n\
+- Mercurial hook：`goldagent hook add-hg <repo_path> --command \"...\" --ref <rev，默认 tip> --interval <seconds>`\n\
 - Hook 模板变量：`${{HOOK_ID}}`、`${{HOOK_NAME}}`、`${{HOOK_SOURCE}}`、`${{HOOK_TARGET}}`、`${{HOOK_REF}}`、`${{HOOK_PREVIOUS}}`、`${{HOOK_CURRENT}}`\n\
 - 管理命令：`goldagent hook list`、`goldagent hook remove <hook_id>`\n\
 \n\
@@ -173,6 +657,8 @@ fn render_capability_declaration(paths: &AgentPaths) -> String {
 - 单轮任务：`goldagent run \"<task>\"`\n\
 - 交互会话：`goldagent` 或 `goldagent chat`\n\
 - 调度服务：`goldagent serve`（同时运行 cron 与 hook watcher）\n\
+- 备份恢复：`goldagent backup <path> [--no-secrets]`、`goldagent restore <path> [--merge]`\n\
+- Shell 补全：`goldagent completions <bash|zsh|fish> > <file>`（输出命令补全脚本）\n\
 \n",
         jobs_file = paths.jobs_file.display(),
         hooks_file = paths.hooks_file.display(),
@@ -187,11 +673,12 @@ fn render_connect_memory_rules(paths: &AgentPaths) -> String {
 - 配置文件：`{connect_file}`\n\
 - 状态查询：`goldagent connect status`\n\
 - 登录态：`goldagent connect login --model <model>`\n\
-- API 模式：`goldagent connect api <api_key> --provider <openai|anthropic|zhipu> --model <model>`\n\
+- API 模式：`goldagent connect api <api_key> --provider <openai|anthropic|zhipu|deepseek|ollama> --model <model>`\n\
 \n\
 ### 记忆机制\n\
 - 长期记忆文件：`{memory_file}`\n\
 - 短期记忆目录：`{memory_dir}`（按天写入 `YYYY-MM-DD.md`）\n\
+- 结构检查：`goldagent memory check [--repair]`（修复前自动备份）\n\
 - 自动晋升规则：关键词命中、显式“记住”、或同句在短期记忆重复出现 >= 3 次\n\
 - 去重规则：规范化后文本重复则不重复写入长期记忆\n\
 \n",
@@ -227,6 +714,38 @@ pub fn tail_context(paths: &AgentPaths, max_chars: usize) -> Result<String> {
     Ok(take_tail_chars(&merged, max_chars))
 }
 
+/// Short-term sources written when jobs and hooks are created or toggled,
+/// which share the `cron.` / `hook.` prefix with run logs.
+const ADMIN_SOURCES: [&str; 3] = ["cron.add", "cron.toggle", "hook.add"];
+
+/// Most recent cron job and hook run logs (`cron.<job_id>`, `hook.<hook_id>`)
+/// from short-term memory, oldest first, kept within `max_chars`.
+pub fn recent_runs(paths: &AgentPaths, max_chars: usize) -> Result<String> {
+    let mut files = list_short_term_files(&paths.memory_dir)?;
+    files.sort();
+    let mut blocks = Vec::new();
+    let mut used = 0;
+    'files: for file in files.iter().rev() {
+        let content = fs::read_to_string(file).unwrap_or_default();
+        for entry in parse_short_term(&content).into_iter().rev() {
+            let Some(source) = entry.source.filter(|source| {
+                (source.starts_with("cron.") || source.starts_with("hook."))
+                    && !ADMIN_SOURCES.contains(&source.as_str())
+            }) else {
+                continue;
+            };
+            let block = format!("### {} {source}\n{}", entry.timestamp, entry.content);
+            used += block.chars().count();
+            if used > max_chars && !blocks.is_empty() {
+                break 'files;
+            }
+            blocks.push(take_tail_chars(&block, max_chars));
+        }
+    }
+    blocks.reverse();
+    Ok(blocks.join("\n\n"))
+}
+
 pub fn append_short_term(paths: &AgentPaths, source: &str, content: &str) -> Result<()> {
     let now = Local::now();
     let filename = format!("{}.md", now.format("%Y-%m-%d"));
@@ -237,86 +756,123 @@ pub fn append_short_term(paths: &AgentPaths, source: &str, content: &str) -> Res
         now.to_rfc3339()
     );
 
+    let _lock = FileLock::acquire(&file_path)?;
     let mut file = OpenOptions::new()
         .create(true)
         .append(true)
-        .open(file_path)?;
+        .open(&file_path)?;
     file.write_all(block.as_bytes())?;
     Ok(())
 }
 
-pub fn auto_capture_long_term(
+pub async fn auto_capture_long_term(
     paths: &AgentPaths,
     source: &str,
     user_input: &str,
 ) -> Result<Vec<String>> {
+    let max_captures = config::load(paths)
+        .unwrap_or_default()
+        .memory
+        .max_auto_captures;
     let mut memory_index =
         normalize_for_compare(&fs::read_to_string(&paths.memory_file).unwrap_or_default());
     let mut seen = HashSet::new();
     let mut added = Vec::new();
+    let mut semantic = SemanticDedup::from_config(paths);
 
-    for candidate in extract_memory_candidates(user_input) {
-        let tags = vec![
-            "auto".to_string(),
-            source.to_string(),
-            infer_memory_tag(&candidate).to_string(),
-        ];
+    let mut candidates = extract_memory_candidates(user_input)
+        .into_iter()
+        .map(|candidate| {
+            let tag = infer_memory_tag(&candidate);
+            (candidate, tag)
+        })
+        .collect::<Vec<_>>();
+    // 除关键词外，再做“重复出现 >=3 次”的自动晋升。
+    for sentence in split_sentences(user_input) {
+        if is_repeat_candidate(&sentence) && count_short_term_occurrences(paths, &sentence)? >= 3 {
+            candidates.push((sentence, "repeated"));
+        }
+    }
+    // 每次最多写入 max_auto_captures 条：显式“记住”与偏好优先，泛泛事实最后。
+    candidates.sort_by_key(|(candidate, tag)| capture_priority(candidate, tag));
+    if let Some(semantic) = semantic.as_mut() {
+        let texts = candidates
+            .iter()
+            .map(|(candidate, _)| candidate.clone())
+            .collect::<Vec<_>>();
+        semantic.prepare(paths, &texts).await;
+    }
+
+    for (candidate, tag) in candidates {
+        if added.len() >= max_captures {
+            break;
+        }
+        let tags = vec!["auto".to_string(), source.to_string(), tag.to_string()];
         try_capture_candidate(
             paths,
             &mut memory_index,
             &mut seen,
             &mut added,
+            semantic.as_mut(),
             candidate,
             tags,
         )?;
     }
-
-    // 除关键词外，再做“重复出现 >=3 次”的自动晋升。
-    for sentence in split_sentences(user_input) {
-        if !is_repeat_candidate(&sentence) {
-            continue;
-        }
-        let count = count_short_term_occurrences(paths, &sentence)?;
-        if count >= 3 {
-            let tags = vec![
-                "auto".to_string(),
-                source.to_string(),
-                "repeated".to_string(),
-            ];
-            try_capture_candidate(
-                paths,
-                &mut memory_index,
-                &mut seen,
-                &mut added,
-                sentence,
-                tags,
-            )?;
-        }
+    if let Some(semantic) = &semantic {
+        semantic.save(paths);
     }
 
     Ok(added)
 }
 
-pub fn auto_capture_event(paths: &AgentPaths, source: &str, event_text: &str) -> Result<bool> {
+fn capture_priority(candidate: &str, tag: &str) -> u8 {
+    if is_explicit_remember_sentence(candidate) {
+        return 0;
+    }
+    match tag {
+        "preference" => 1,
+        "constraint" => 2,
+        "goal" => 3,
+        "repeated" => 4,
+        _ => 5,
+    }
+}
+
+pub async fn auto_capture_event(
+    paths: &AgentPaths,
+    source: &str,
+    event_text: &str,
+) -> Result<bool> {
     let mut memory_index =
         normalize_for_compare(&fs::read_to_string(&paths.memory_file).unwrap_or_default());
     let mut seen = HashSet::new();
     let mut added = Vec::new();
+    let mut semantic = SemanticDedup::from_config(paths);
 
+    let candidate = event_text.trim().to_string();
+    if let Some(semantic) = semantic.as_mut() {
+        semantic
+            .prepare(paths, std::slice::from_ref(&candidate))
+            .await;
+    }
     let tags = vec!["auto".to_string(), source.to_string(), "event".to_string()];
     try_capture_candidate(
         paths,
         &mut memory_index,
         &mut seen,
         &mut added,
-        event_text.trim().to_string(),
+        semantic.as_mut(),
+        candidate,
         tags,
     )?;
+    if let Some(semantic) = &semantic {
+        semantic.save(paths);
+    }
 
     Ok(!added.is_empty())
 }
 
-pub fn capture_explicit_remember(
+pub async fn capture_explicit_remember(
     paths: &AgentPaths,
     source: &str,
     text: &str,
@@ -325,11 +881,16 @@ pub fn capture_explicit_remember(
         normalize_for_compare(&fs::read_to_string(&paths.memory_file).unwrap_or_default());
     let mut seen = HashSet::new();
     let mut added = Vec::new();
+    let mut semantic = SemanticDedup::from_config(paths);
 
-    for sentence in split_sentences(text) {
-        if !is_explicit_remember_sentence(&sentence) {
-            continue;
-        }
+    let sentences = split_sentences(text)
+        .into_iter()
+        .filter(|sentence| is_explicit_remember_sentence(sentence))
+        .collect::<Vec<_>>();
+    if let Some(semantic) = semantic.as_mut() {
+        semantic.prepare(paths, &sentences).await;
+    }
+    for sentence in sentences {
         let tags = vec![
             "auto".to_string(),
             source.to_string(),
@@ -340,10 +901,14 @@ pub fn capture_explicit_remember(
             &mut memory_index,
             &mut seen,
             &mut added,
+            semantic.as_mut(),
             sentence,
             tags,
         )?;
     }
+    if let Some(semantic) = &semantic {
+        semantic.save(paths);
+    }
     Ok(added)
 }
 
@@ -352,6 +917,7 @@ fn try_capture_candidate(
     memory_index: &mut String,
     seen: &mut HashSet<String>,
     added: &mut Vec<String>,
+    semantic: Option<&mut SemanticDedup>,
     candidate: String,
     tags: Vec<String>,
 ) -> Result<()> {
@@ -363,6 +929,11 @@ fn try_capture_candidate(
     if seen.contains(&normalized) || memory_index.contains(&normalized) {
         return Ok(());
     }
+    if let Some(semantic) = semantic
+        && semantic.is_duplicate(&candidate)
+    {
+        return Ok(());
+    }
 
     append_global(paths, &candidate, &tags)?;
     seen.insert(normalized.clone());
@@ -371,6 +942,189 @@ fn try_capture_candidate(
     Ok(())
 }
 
+type EmbedFuture = Pin<Box<dyn Future<Output = Result<Vec<Vec<f32>>>> + Send>>;
+type EmbedFn = Box<dyn Fn(Vec<String>) -> EmbedFuture + Send + Sync>;
+
+/// Embeddings cache stored in `memory-embeddings.json`, keyed by the
+/// normalized entry content. A different `model` invalidates it.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct EmbeddingCache {
+    model: String,
+    vectors: BTreeMap<String, Vec<f32>>,
+}
+
+/// Embeddings-backed dedup for auto-captured long-term memory
+/// (`memory.semantic_dedup`). [`SemanticDedup::prepare`] embeds the stored
+/// entries and all candidates of one capture in a single request; any
+/// embeddings failure disables it for the rest of the capture, leaving the
+/// exact-match check in place.
+struct SemanticDedup {
+    threshold: f32,
+    embed: EmbedFn,
+    cache: EmbeddingCache,
+    candidates: HashMap<String, Vec<f32>>,
+    disabled: bool,
+}
+
+impl SemanticDedup {
+    /// `None` when the flag is off or the backend has no embeddings API.
+    fn from_config(paths: &AgentPaths) -> Option<Self> {
+        let memory_config = config::load(paths).unwrap_or_default().memory;
+        if !memory_config.semantic_dedup {
+            return None;
+        }
+        let client = ProviderClient::from_paths(paths, None).ok()?;
+        let model = client.embedding_model(memory_config.embedding_model.as_deref())?;
+        let override_model = memory_config.embedding_model;
+        let embed: EmbedFn = Box::new(move |texts| {
+            let client = client.clone();
+            let model = override_model.clone();
+            Box::pin(async move { client.embed(model.as_deref(), &texts).await })
+        });
+        Some(Self::new(
+            paths,
+            model,
+            memory_config.similarity_threshold,
+            embed,
+        ))
+    }
+
+    fn new(paths: &AgentPaths, model: String, threshold: f32, embed: EmbedFn) -> Self {
+        let cache = load_embedding_cache(paths, &model).unwrap_or(EmbeddingCache {
+            model,
+            vectors: BTreeMap::new(),
+        });
+        Self {
+            threshold,
+            embed,
+            cache,
+            candidates: HashMap::new(),
+            disabled: false,
+        }
+    }
+
+    /// Embeds `candidates` together with the stored entries missing from the
+    /// cache, in one request.
+    async fn prepare(&mut self, paths: &AgentPaths, candidates: &[String]) {
+        if self.disabled || candidates.is_empty() {
+            return;
+        }
+        if let Err(err) = self.embed_missing(paths, candidates).await {
+            eprintln!("警告：语义去重不可用，改用精确匹配：{err:#}");
+            self.disabled = true;
+        }
+    }
+
+    async fn embed_missing(&mut self, paths: &AgentPaths, candidates: &[String]) -> Result<()> {
+        let stored = parse_long_term(&fs::read_to_string(&paths.memory_file).unwrap_or_default())
+            .into_iter()
+            .map(|entry| (normalize_for_compare(&entry.content), entry.content))
+            .collect::<BTreeMap<_, _>>();
+        self.cache.vectors.retain(|key, _| stored.contains_key(key));
+
+        let mut keys = Vec::new();
+        let mut texts = Vec::new();
+        for (key, content) in &stored {
+            if !self.cache.vectors.contains_key(key) {
+                keys.push((key.clone(), false));
+                texts.push(content.clone());
+            }
+        }
+        for candidate in candidates {
+            let key = normalize_for_compare(candidate);
+            if !self.candidates.contains_key(&key) && !keys.contains(&(key.clone(), true)) {
+                keys.push((key, true));
+                texts.push(candidate.clone());
+            }
+        }
+        if texts.is_empty() {
+            return Ok(());
+        }
+        let vectors = (self.embed)(texts).await?;
+        if vectors.len() != keys.len() {
+            bail!(
+                "embeddings 响应数量不符：请求 {} 条，返回 {} 条",
+                keys.len(),
+                vectors.len()
+            );
+        }
+        for ((key, is_candidate), vector) in keys.into_iter().zip(vectors) {
+            if is_candidate {
+                self.candidates.insert(key, vector);
+            } else {
+                self.cache.vectors.insert(key, vector);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `candidate` is at least `threshold`-similar to a stored or
+    /// already accepted entry. A candidate that was not prepared is never a
+    /// duplicate; an accepted one is added to the cache.
+    fn is_duplicate(&mut self, candidate: &str) -> bool {
+        if self.disabled {
+            return false;
+        }
+        let key = normalize_for_compare(candidate);
+        let Some(vector) = self.candidates.get(&key) else {
+            return false;
+        };
+        let duplicate = self
+            .cache
+            .vectors
+            .values()
+            .any(|stored| cosine_similarity(stored, vector) >= self.threshold);
+        if !duplicate {
+            self.cache.vectors.insert(key, vector.clone());
+        }
+        duplicate
+    }
+
+    /// Merges the cache into `memory-embeddings.json` under its lock. The
+    /// cache only saves requests, so a failure is reported and ignored.
+    fn save(&self, paths: &AgentPaths) {
+        if self.disabled {
+            return;
+        }
+        let result = (|| -> Result<()> {
+            let _lock = FileLock::acquire(&paths.memory_embeddings_file)?;
+            let mut merged = load_embedding_cache(paths, &self.cache.model).unwrap_or_default();
+            merged.model = self.cache.model.clone();
+            merged.vectors.extend(
+                self.cache
+                    .vectors
+                    .iter()
+                    .map(|(key, vector)| (key.clone(), vector.clone())),
+            );
+            fsutil::write_atomic(
+                &paths.memory_embeddings_file,
+                serde_json::to_string(&merged)?,
+            )
+        })();
+        if let Err(err) = result {
+            eprintln!("警告：保存 embeddings 缓存失败：{err:#}");
+        }
+    }
+}
+
+/// The cache in `memory-embeddings.json` when it was built with `model`.
+fn load_embedding_cache(paths: &AgentPaths, model: &str) -> Option<EmbeddingCache> {
+    fs::read_to_string(&paths.memory_embeddings_file)
+        .ok()
+        .and_then(|raw| serde_json::from_str::<EmbeddingCache>(&raw).ok())
+        .filter(|cache| cache.model == model)
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot = a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denom = norm(a) * norm(b);
+    if denom == 0.0 { 0.0 } else { dot / denom }
+}
+
 fn extract_memory_candidates(input: &str) -> Vec<String> {
     let mut candidates = Vec::new();
     for sentence in split_sentences(input) {
@@ -529,7 +1283,7 @@ fn list_short_term_files(dir: &PathBuf) -> Result<Vec<PathBuf>> {
     Ok(files)
 }
 
-fn is_daily_memory_file(path: &PathBuf) -> bool {
+fn is_daily_memory_file(path: &Path) -> bool {
     let Some(stem) = path.file_stem() else {
         return false;
     };
@@ -644,10 +1398,20 @@ mod tests {
         fs::create_dir_all(&logs_dir).unwrap();
         fs::create_dir_all(&skills_dir).unwrap();
         let memory_file = root.join("MEMORY.md");
+        let memory_embeddings_file = root.join("memory-embeddings.json");
+        let sessions_dir = root.join("sessions");
         let jobs_file = root.join("jobs.json");
+        let reminders_file = root.join("reminders.json");
         let hooks_file = root.join("hooks.json");
         let connect_file = root.join("connect.json");
         let usage_file = root.join("usage.json");
+        let config_file = root.join("config.json");
+        let session_file = root.join("last_session.json");
+        let models_cache_file = root.join("models-cache.json");
+        let hook_signatures_file = root.join("hook-signatures.json");
+        let job_checks_file = root.join("job-checks.json");
+        let job_failures_file = root.join("job-failures.json");
+        let locks_dir = root.join("locks");
         fs::write(
             &memory_file,
             "# GoldAgent 长期记忆\n\n此文件用于保存长期、可复用的记忆。\n\n",
@@ -670,12 +1434,22 @@ mod tests {
             root,
             memory_file,
             memory_dir,
+            memory_embeddings_file,
             jobs_file,
+            reminders_file,
             hooks_file,
             connect_file,
+            models_cache_file,
+            hook_signatures_file,
+            job_checks_file,
+            job_failures_file,
             usage_file,
+            config_file,
+            session_file,
+            sessions_dir,
             logs_dir,
             skills_dir,
+            locks_dir,
         }
     }
 
@@ -692,10 +1466,12 @@ mod tests {
         let _ = fs::remove_dir_all(paths.root);
     }
 
-    #[test]
-    fn captures_event_to_long_term() {
+    #[tokio::test]
+    async fn captures_event_to_long_term() {
         let paths = make_paths();
-        let ok = auto_capture_event(&paths, "skill.new", "用户创建了技能：name=test").unwrap();
+        let ok = auto_capture_event(&paths, "skill.new", "用户创建了技能：name=test")
+            .await
+            .unwrap();
         assert!(ok);
 
         let memory = fs::read_to_string(&paths.memory_file).unwrap();
@@ -705,8 +1481,40 @@ mod tests {
         let _ = fs::remove_dir_all(paths.root);
     }
 
-    #[test]
-    fn promotes_repeated_sentence_to_long_term() {
+    #[tokio::test]
+    async fn caps_auto_captured_entries_per_input() {
+        let paths = make_paths();
+        let input = "这个项目的长期目标是做成桌面应用。\
+我们必须兼容 Windows 平台。\
+我更喜欢简短直接的回答风格。\
+以后的周报一定要附上数据截图。\
+请记住我的工号是 A1024 这件事。";
+
+        let added = auto_capture_long_term(&paths, "chat.turn", input)
+            .await
+            .unwrap();
+        assert_eq!(
+            added,
+            [
+                "请记住我的工号是 A1024 这件事",
+                "我更喜欢简短直接的回答风格",
+                "我们必须兼容 Windows 平台",
+            ]
+        );
+
+        let mut cfg = config::load(&paths).unwrap_or_default();
+        cfg.memory.max_auto_captures = 1;
+        config::save(&paths, &cfg).unwrap();
+        let added = auto_capture_long_term(&paths, "chat.turn", input)
+            .await
+            .unwrap();
+        assert_eq!(added, ["这个项目的长期目标是做成桌面应用"]);
+
+        let _ = fs::remove_dir_all(paths.root);
+    }
+
+    #[tokio::test]
+    async fn promotes_repeated_sentence_to_long_term() {
         let paths = make_paths();
         let sentence = "项目里日志统一写中文";
         for _ in 0..3 {
@@ -718,7 +1526,9 @@ mod tests {
             .unwrap();
         }
 
-        let added = auto_capture_long_term(&paths, "chat.turn", sentence).unwrap();
+        let added = auto_capture_long_term(&paths, "chat.turn", sentence)
+            .await
+            .unwrap();
         assert!(!added.is_empty());
 
         let memory = fs::read_to_string(&paths.memory_file).unwrap();
@@ -772,6 +1582,100 @@ mod tests {
         let _ = fs::remove_dir_all(paths.root);
     }
 
+    #[test]
+    fn repairs_duplicated_title_and_stray_markers() {
+        let paths = make_paths();
+        fs::write(
+            &paths.memory_file,
+            "# GoldAgent 长期记忆\n\n此文件用于保存长期、可复用的记忆。\n\n\
+## mem_1\ntimestamp: t\ntags: none\ncontent:\n保留这条记忆\n\n---\n\n---\n\n\
+# GoldAgent 长期记忆\n\n此文件用于保存长期、可复用的记忆。\n\n\
+## mem_2\ntimestamp: t\ntags: none\ncontent:\n第二条\n\n---\n\n",
+        )
+        .unwrap();
+
+        let report = check(&paths, false).unwrap();
+        assert!(report.anomalies.iter().any(|a| a.contains("重复 2 次")));
+        assert!(
+            report
+                .anomalies
+                .iter()
+                .any(|a| a.contains("多余的结束标记"))
+        );
+        assert!(report.backup_file.is_none());
+
+        let report = check(&paths, true).unwrap();
+        let backup = report.backup_file.unwrap();
+        assert_eq!(
+            fs::read_to_string(&backup)
+                .unwrap()
+                .matches("# GoldAgent 长期记忆")
+                .count(),
+            2
+        );
+
+        let memory = fs::read_to_string(&paths.memory_file).unwrap();
+        assert_eq!(memory.matches("# GoldAgent 长期记忆").count(), 1);
+        assert_eq!(memory.lines().next().unwrap(), "# GoldAgent 长期记忆");
+        assert_eq!(memory.matches("---").count(), 2);
+        assert!(memory.contains("保留这条记忆"));
+        assert!(memory.contains("第二条"));
+        assert!(check(&paths, false).unwrap().anomalies.is_empty());
+
+        let _ = fs::remove_dir_all(paths.root);
+    }
+
+    #[test]
+    fn unmanaged_sections_leave_user_edited_file_untouched() {
+        let paths = make_paths();
+        let mut cfg = config::load(&paths).unwrap_or_default();
+        cfg.memory.manage_capability_sections = false;
+        config::save(&paths, &cfg).unwrap();
+        let edited = "# 我的记忆\n\n只保留我自己的笔记。\n";
+        fs::write(&paths.memory_file, edited).unwrap();
+
+        ensure_capability_declarations(&paths).unwrap();
+        assert_eq!(fs::read_to_string(&paths.memory_file).unwrap(), edited);
+
+        cfg.memory.manage_capability_sections = true;
+        cfg.memory.memory_header = Some("# 团队记忆\n\n共享约定。\n".to_string());
+        config::save(&paths, &cfg).unwrap();
+        fs::write(
+            &paths.memory_file,
+            "# GoldAgent 长期记忆\n\n此文件用于保存长期、可复用的记忆。\n\n",
+        )
+        .unwrap();
+        ensure_capability_declarations(&paths).unwrap();
+        ensure_capability_declarations(&paths).unwrap();
+
+        let memory = fs::read_to_string(&paths.memory_file).unwrap();
+        assert!(memory.starts_with("# 团队记忆\n\n共享约定。\n\n## GoldAgent 能力声明"));
+        assert!(!memory.contains(LONG_TERM_MEMORY_TITLE));
+        assert_eq!(memory.matches("## GoldAgent 能力声明").count(), 1);
+        assert!(check(&paths, false).unwrap().anomalies.is_empty());
+
+        let _ = fs::remove_dir_all(paths.root);
+    }
+
+    #[test]
+    fn unmanaged_sections_still_seed_the_custom_header() {
+        let paths = make_paths();
+        let mut cfg = config::AgentConfig::default();
+        cfg.memory.manage_capability_sections = false;
+        cfg.memory.memory_header = Some("# 团队记忆\n\n共享约定。\n".to_string());
+        config::save(&paths, &cfg).unwrap();
+        let _ = fs::remove_file(&paths.memory_file);
+
+        paths.ensure().unwrap();
+        ensure_capability_declarations(&paths).unwrap();
+
+        let memory = fs::read_to_string(&paths.memory_file).unwrap();
+        assert_eq!(memory, "# 团队记忆\n\n共享约定。\n\n");
+        assert!(check(&paths, false).unwrap().anomalies.is_empty());
+
+        let _ = fs::remove_dir_all(paths.root);
+    }
+
     #[test]
     fn reorders_legacy_content_to_keep_title_first() {
         let paths = make_paths();
@@ -798,4 +1702,191 @@ mod tests {
 
         let _ = fs::remove_dir_all(paths.root);
     }
+
+    #[test]
+    fn search_ranks_matches_and_filters_by_tag() {
+        let paths = make_paths();
+        ensure_capability_declarations(&paths).unwrap();
+        append_global(
+            &paths,
+            "用户偏好：回复使用简体中文",
+            &["preference".to_string()],
+        )
+        .unwrap();
+        let mut raw = fs::read_to_string(&paths.memory_file).unwrap();
+        raw.push_str(
+            "## mem_20260101000000\ntimestamp: 2026-01-01T00:00:00+00:00\ntags: project\ncontent:\nDeploy staging to the eu cluster, not prod.\n\n---\n\n",
+        );
+        fs::write(&paths.memory_file, raw).unwrap();
+        append_short_term(&paths, "run.task", "deploy the staging build tonight").unwrap();
+
+        let hits = search(&paths, "Deploy staging", 10, None).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].id, "mem_20260101000000");
+        assert_eq!(hits[0].tags, ["project"]);
+        assert!(!hits[1].long_term);
+        assert_eq!(hits[1].source.as_deref(), Some("run.task"));
+
+        let by_punctuation = search(&paths, "用户偏好回复", 10, None).unwrap();
+        assert_eq!(by_punctuation.len(), 1);
+        assert!(by_punctuation[0].content.contains("简体中文"));
+
+        let tagged = search(&paths, "staging", 10, Some("PROJECT")).unwrap();
+        assert_eq!(tagged.len(), 1);
+        assert!(tagged[0].long_term);
+        assert!(
+            search(&paths, "staging", 10, Some("preference"))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(search(&paths, "能力声明", 10, None).unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn forget_removes_entries_but_keeps_managed_sections() {
+        let paths = make_paths();
+        ensure_capability_declarations(&paths).unwrap();
+        let mut raw = fs::read_to_string(&paths.memory_file).unwrap();
+        for (id, content) in [
+            ("mem_20260101000000", "API token is abc123"),
+            ("mem_20260102000000", "token rotation happens monthly"),
+            ("mem_20260103000000", "用户喜欢简洁的回复"),
+        ] {
+            raw.push_str(&format!(
+                "## {id}\ntimestamp: 2026-01-01T00:00:00+00:00\ntags: fact\ncontent:\n{content}\n\n---\n\n"
+            ));
+        }
+        fs::write(&paths.memory_file, raw).unwrap();
+
+        let pending = forget(&paths, None, Some("TOKEN"), false).unwrap();
+        assert_eq!(pending.matched.len(), 2);
+        assert_eq!(pending.removed, 0);
+
+        let by_id = forget(&paths, Some("mem_20260101000000"), None, false).unwrap();
+        assert_eq!(by_id.removed, 1);
+        let removed = forget(&paths, None, Some("token"), false).unwrap();
+        assert_eq!(removed.matched, ["mem_20260102000000"]);
+        assert_eq!(removed.removed, 1);
+
+        assert_eq!(
+            forget(&paths, None, Some("能力声明"), true)
+                .unwrap()
+                .removed,
+            0
+        );
+        let rewritten = fs::read_to_string(&paths.memory_file).unwrap();
+        assert!(rewritten.starts_with("# GoldAgent 长期记忆"));
+        assert!(rewritten.contains(CAPABILITY_DECLARATION_TITLE));
+        assert!(rewritten.contains(CONNECT_MEMORY_RULES_TITLE));
+        assert!(rewritten.contains("## mem_20260103000000"));
+        assert!(!rewritten.contains("token"));
+        assert!(find_structure_anomalies(&rewritten, LONG_TERM_MEMORY_TITLE).is_empty());
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn recent_runs_skip_job_and_hook_admin_events() {
+        let paths = make_paths();
+        append_short_term(&paths, "cron.add", "added job job-1").unwrap();
+        append_short_term(&paths, "cron.job-1", "exit=0\nbackup done").unwrap();
+        append_short_term(&paths, "cron.toggle", "disabled job-1").unwrap();
+        append_short_term(&paths, "hook.add", "added hook hook-1").unwrap();
+        append_short_term(&paths, "hook.hook-1", "review: no findings").unwrap();
+        append_short_term(&paths, "chat.turn", "user:\nhello").unwrap();
+
+        let runs = recent_runs(&paths, 10_000).unwrap();
+        assert!(runs.contains("cron.job-1\nexit=0\nbackup done"), "{runs}");
+        assert!(runs.contains("hook.hook-1\nreview: no findings"), "{runs}");
+        for skipped in ["cron.add", "cron.toggle", "hook.add", "chat.turn"] {
+            assert!(!runs.contains(skipped), "{skipped} in {runs}");
+        }
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn export_parses_long_and_short_term_entries() {
+        let paths = make_paths();
+        ensure_capability_declarations(&paths).unwrap();
+        let id = append_global(&paths, "用户偏好中文回复\n第二行", &["preference".into()]).unwrap();
+        append_short_term(&paths, "chat.turn", "user:\nhello").unwrap();
+
+        let export = export(&paths).unwrap();
+        assert_eq!(export.long_term.len(), 1);
+        let entry = &export.long_term[0];
+        assert_eq!(entry.id, id);
+        assert_eq!(entry.tags, ["preference"]);
+        assert_eq!(entry.content, "用户偏好中文回复\n第二行");
+        assert!(!entry.timestamp.is_empty());
+
+        assert_eq!(export.short_term.len(), 1);
+        let day = &export.short_term[0];
+        assert_eq!(day.date, Local::now().format("%Y-%m-%d").to_string());
+        assert_eq!(day.entries[0].source.as_deref(), Some("chat.turn"));
+        assert_eq!(day.entries[0].content, "user:\nhello");
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[tokio::test]
+    async fn semantic_dedup_skips_paraphrases_and_falls_back_on_error() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let paths = make_paths();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&requests);
+        let embed: EmbedFn = Box::new(move |texts| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let vectors = texts
+                .iter()
+                .map(|text| {
+                    if text.contains("日志") && text.contains("中文") {
+                        vec![1.0, 0.05]
+                    } else {
+                        vec![0.0, 1.0]
+                    }
+                })
+                .collect();
+            Box::pin(async move { Ok(vectors) })
+        });
+        let mut semantic = SemanticDedup::new(&paths, "test:model".into(), 0.9, embed);
+        let mut index = String::new();
+        let mut seen = HashSet::new();
+        let mut added = Vec::new();
+        let candidates =
+            ["项目日志用中文", "日志统一写成中文", "周报每周五提交给经理"].map(str::to_string);
+        semantic.prepare(&paths, &candidates).await;
+        for candidate in candidates {
+            try_capture_candidate(
+                &paths,
+                &mut index,
+                &mut seen,
+                &mut added,
+                Some(&mut semantic),
+                candidate,
+                vec!["auto".to_string()],
+            )
+            .unwrap();
+        }
+        assert_eq!(added, ["项目日志用中文", "周报每周五提交给经理"]);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        semantic.save(&paths);
+        let cache = fs::read_to_string(&paths.memory_embeddings_file).unwrap();
+        assert!(cache.contains("test:model"));
+
+        let failing: EmbedFn = Box::new(|_| Box::pin(async { bail!("offline") }));
+        let mut offline = SemanticDedup::new(&paths, "test:model".into(), 0.9, failing);
+        let candidate = "日志全部使用中文记录".to_string();
+        offline
+            .prepare(&paths, std::slice::from_ref(&candidate))
+            .await;
+        assert!(!offline.is_duplicate(&candidate));
+        assert!(offline.disabled);
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
 }
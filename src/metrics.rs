@@ -0,0 +1,229 @@
+use crate::config::AgentPaths;
+use crate::usage::{self, UsageCounter, UsageStats};
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// How long a client may take to send its request line (and to accept the
+/// response) before the connection is dropped.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// In-process counters maintained by `serve`, exported next to usage totals.
+#[derive(Debug, Default)]
+pub struct SchedulerMetrics {
+    job_success: AtomicU64,
+    job_failure: AtomicU64,
+    hook_triggers: AtomicU64,
+}
+
+impl SchedulerMetrics {
+    pub fn record_job(&self, success: bool) {
+        let counter = if success {
+            &self.job_success
+        } else {
+            &self.job_failure
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_hook_trigger(&self) {
+        self.hook_triggers.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Serves `GET /metrics` on `addr` from a background thread; each connection
+/// is handled on its own thread so a slow client cannot stall the others.
+pub fn spawn_server(addr: &str, paths: AgentPaths, metrics: Arc<SchedulerMetrics>) -> Result<()> {
+    let listener =
+        TcpListener::bind(addr).with_context(|| format!("无法监听状态服务地址: {addr}"))?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let paths = paths.clone();
+            let metrics = Arc::clone(&metrics);
+            thread::spawn(move || {
+                let _ = handle_connection(stream, &paths, &metrics);
+            });
+        }
+    });
+    Ok(())
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    paths: &AgentPaths,
+    metrics: &SchedulerMetrics,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+    stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or("");
+    let (status, body) = if path == "/metrics" {
+        let stats = usage::load(&paths.usage_file).unwrap_or_default();
+        ("200 OK", render(&stats, metrics))
+    } else {
+        ("404 Not Found", "not found\n".to_string())
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Renders usage totals and scheduler counters in Prometheus text format.
+pub fn render(stats: &UsageStats, metrics: &SchedulerMetrics) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "# HELP goldagent_requests_total Model requests recorded in usage.json."
+    );
+    let _ = writeln!(out, "# TYPE goldagent_requests_total counter");
+    let _ = writeln!(out, "goldagent_requests_total {}", stats.total.requests);
+
+    write_token_metric(
+        &mut out,
+        "goldagent_input_tokens_total",
+        "Input tokens by provider and model.",
+        stats,
+        |counter| counter.input_tokens,
+    );
+    write_token_metric(
+        &mut out,
+        "goldagent_output_tokens_total",
+        "Output tokens by provider and model.",
+        stats,
+        |counter| counter.output_tokens,
+    );
+
+    let _ = writeln!(
+        out,
+        "# HELP goldagent_job_executions_total Cron job executions since serve started."
+    );
+    let _ = writeln!(out, "# TYPE goldagent_job_executions_total counter");
+    let _ = writeln!(
+        out,
+        "goldagent_job_executions_total{{status=\"success\"}} {}",
+        metrics.job_success.load(Ordering::Relaxed)
+    );
+    let _ = writeln!(
+        out,
+        "goldagent_job_executions_total{{status=\"failure\"}} {}",
+        metrics.job_failure.load(Ordering::Relaxed)
+    );
+    let _ = writeln!(
+        out,
+        "# HELP goldagent_hook_triggers_total Hook triggers since serve started."
+    );
+    let _ = writeln!(out, "# TYPE goldagent_hook_triggers_total counter");
+    let _ = writeln!(
+        out,
+        "goldagent_hook_triggers_total {}",
+        metrics.hook_triggers.load(Ordering::Relaxed)
+    );
+    out
+}
+
+fn write_token_metric(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    stats: &UsageStats,
+    pick: fn(&UsageCounter) -> u64,
+) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} counter");
+    for (key, counter) in &stats.by_model {
+        let (provider, model) = key.split_once(':').unwrap_or((key.as_str(), ""));
+        let _ = writeln!(
+            out,
+            "{name}{{provider=\"{}\",model=\"{}\"}} {}",
+            escape_label(provider),
+            escape_label(model),
+            pick(counter)
+        );
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SchedulerMetrics, render, spawn_server};
+    use crate::config::AgentPaths;
+    use crate::usage::{UsageCounter, UsageStats};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::Arc;
+
+    #[test]
+    fn idle_connection_does_not_block_other_requests() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let root =
+            std::env::temp_dir().join(format!("goldagent-metrics-test-{}", uuid::Uuid::new_v4()));
+        spawn_server(
+            &addr,
+            AgentPaths::from_root(root),
+            Arc::new(SchedulerMetrics::default()),
+        )
+        .unwrap();
+
+        let _idle = TcpStream::connect(&addr).unwrap();
+        let mut client = TcpStream::connect(&addr).unwrap();
+        client
+            .set_read_timeout(Some(std::time::Duration::from_secs(2)))
+            .unwrap();
+        client.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        assert!(response.contains("goldagent_requests_total 0"));
+    }
+
+    #[test]
+    fn renders_usage_and_scheduler_counters() {
+        let mut stats = UsageStats::default();
+        stats.total.requests = 3;
+        stats.by_model.insert(
+            "anthropic:claude-sonnet".to_string(),
+            UsageCounter {
+                requests: 3,
+                input_tokens: 120,
+                output_tokens: 45,
+            },
+        );
+        let metrics = SchedulerMetrics::default();
+        metrics.record_job(true);
+        metrics.record_job(true);
+        metrics.record_job(false);
+        metrics.record_hook_trigger();
+
+        let text = render(&stats, &metrics);
+        let lines = text.lines().collect::<Vec<_>>();
+
+        assert!(lines.contains(&"goldagent_requests_total 3"));
+        assert!(lines.contains(
+            &"goldagent_input_tokens_total{provider=\"anthropic\",model=\"claude-sonnet\"} 120"
+        ));
+        assert!(lines.contains(
+            &"goldagent_output_tokens_total{provider=\"anthropic\",model=\"claude-sonnet\"} 45"
+        ));
+        assert!(lines.contains(&"goldagent_job_executions_total{status=\"success\"} 2"));
+        assert!(lines.contains(&"goldagent_job_executions_total{status=\"failure\"} 1"));
+        assert!(lines.contains(&"goldagent_hook_triggers_total 1"));
+    }
+}
@@ -0,0 +1,86 @@
+/// Line typed at the continuation prompt that submits the collected message.
+pub const END_SENTINEL: &str = "/end";
+
+/// How the user finished a line at the chat prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineInput {
+    /// Plain Enter.
+    Submit(String),
+    /// Alt-Enter: keep the line and continue on the next one.
+    Continue(String),
+}
+
+/// Collects a multiline chat message, kept apart from the terminal so the
+/// submit rules can be tested directly. A line ending in `\` (or entered with
+/// Alt-Enter) starts multiline mode; an empty line or `/end` submits it.
+#[derive(Debug, Clone, Default)]
+pub struct MultilineBuffer {
+    lines: Vec<String>,
+    active: bool,
+}
+
+impl MultilineBuffer {
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Feeds one line; returns the whole message once it is complete.
+    pub fn push(&mut self, input: LineInput) -> Option<String> {
+        let (line, continues) = match input {
+            LineInput::Submit(line) => match line.strip_suffix('\\') {
+                Some(stripped) => (stripped.to_string(), true),
+                None => (line, false),
+            },
+            LineInput::Continue(line) => (line, true),
+        };
+
+        if !self.active {
+            if !continues {
+                return Some(line);
+            }
+            self.active = true;
+            self.lines.push(line);
+            return None;
+        }
+
+        let trimmed = line.trim();
+        if !continues && (trimmed.is_empty() || trimmed == END_SENTINEL) {
+            self.active = false;
+            return Some(std::mem::take(&mut self.lines).join("\n"));
+        }
+        self.lines.push(line);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LineInput, MultilineBuffer};
+
+    #[test]
+    fn trailing_backslash_collects_lines_until_blank_or_end() {
+        let mut buffer = MultilineBuffer::default();
+        assert_eq!(
+            buffer.push(LineInput::Submit("hello".into())),
+            Some("hello".to_string())
+        );
+
+        assert_eq!(buffer.push(LineInput::Submit("第一段\\".into())), None);
+        assert!(buffer.is_active());
+        assert_eq!(buffer.push(LineInput::Submit("  缩进保留".into())), None);
+        assert_eq!(buffer.push(LineInput::Submit("/help".into())), None);
+        assert_eq!(
+            buffer.push(LineInput::Submit(String::new())),
+            Some("第一段\n  缩进保留\n/help".to_string())
+        );
+        assert!(!buffer.is_active());
+
+        assert_eq!(buffer.push(LineInput::Continue("a".into())), None);
+        assert_eq!(buffer.push(LineInput::Continue(String::new())), None);
+        assert_eq!(buffer.push(LineInput::Submit("b".into())), None);
+        assert_eq!(
+            buffer.push(LineInput::Submit(" /end ".into())),
+            Some("a\n\nb".to_string())
+        );
+    }
+}
@@ -18,12 +18,12 @@ pub fn send_notification(title: &str, message: &str) -> bool {
 
     #[cfg(target_os = "linux")]
     {
-        return Command::new("notify-send")
+        Command::new("notify-send")
             .arg(title)
             .arg(message)
             .status()
             .map(|s| s.success())
-            .unwrap_or(false);
+            .unwrap_or(false)
     }
 
     #[cfg(not(any(target_os = "macos", target_os = "linux")))]
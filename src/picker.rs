@@ -0,0 +1,218 @@
+use std::io::{self, Read};
+
+/// Key events understood by the full-screen picker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickerKey {
+    Up,
+    Down,
+    Char(char),
+    Backspace,
+    Enter,
+    Cancel,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PickerOutcome {
+    Pending,
+    /// Index into the original item list.
+    Selected(usize),
+    /// Free text typed when nothing matched and custom values are allowed.
+    Custom(String),
+    Cancelled,
+}
+
+/// Selection state of the arrow-key menu, kept apart from the terminal so the
+/// key handling can be tested directly.
+#[derive(Debug, Clone)]
+pub struct PickerState {
+    items: Vec<String>,
+    filter: String,
+    selected: usize,
+    allow_custom: bool,
+}
+
+impl PickerState {
+    pub fn new(items: Vec<String>, allow_custom: bool) -> Self {
+        Self {
+            items,
+            filter: String::new(),
+            selected: 0,
+            allow_custom,
+        }
+    }
+
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    pub fn item(&self, idx: usize) -> &str {
+        &self.items[idx]
+    }
+
+    /// Indices of items containing the filter (case-insensitive).
+    pub fn matches(&self) -> Vec<usize> {
+        let needle = self.filter.to_lowercase();
+        (0..self.items.len())
+            .filter(|&idx| self.items[idx].to_lowercase().contains(&needle))
+            .collect()
+    }
+
+    /// Position of the highlighted row within [`Self::matches`].
+    pub fn selected(&self) -> Option<usize> {
+        (!self.matches().is_empty()).then_some(self.selected)
+    }
+
+    pub fn apply(&mut self, key: PickerKey) -> PickerOutcome {
+        let len = self.matches().len();
+        match key {
+            PickerKey::Up if len > 0 => {
+                self.selected = if self.selected == 0 {
+                    len - 1
+                } else {
+                    self.selected - 1
+                };
+            }
+            PickerKey::Down if len > 0 => {
+                self.selected = (self.selected + 1) % len;
+            }
+            PickerKey::Up | PickerKey::Down => {}
+            PickerKey::Char(ch) => {
+                self.filter.push(ch);
+                self.selected = 0;
+            }
+            PickerKey::Backspace => {
+                self.filter.pop();
+                self.selected = 0;
+            }
+            PickerKey::Enter => {
+                if let Some(&idx) = self.matches().get(self.selected) {
+                    return PickerOutcome::Selected(idx);
+                }
+                let custom = self.filter.trim();
+                if self.allow_custom && !custom.is_empty() {
+                    return PickerOutcome::Custom(custom.to_string());
+                }
+            }
+            PickerKey::Cancel => return PickerOutcome::Cancelled,
+        }
+        PickerOutcome::Pending
+    }
+}
+
+/// Feeds keys read from `input` to `state` until it settles, calling `render`
+/// before each key. `has_pending` tells a lone Esc (cancel) from the start of
+/// an arrow-key sequence, so it must see what `input` has not consumed yet.
+pub fn drive<R: Read>(
+    state: &mut PickerState,
+    input: &mut R,
+    has_pending: impl Fn(&mut R) -> bool,
+    mut render: impl FnMut(&PickerState) -> io::Result<()>,
+) -> io::Result<PickerOutcome> {
+    let mut pending_utf8 = Vec::<u8>::new();
+    loop {
+        render(state)?;
+        let mut byte = [0u8; 1];
+        if input.read_exact(&mut byte).is_err() {
+            return Ok(PickerOutcome::Cancelled);
+        }
+        let key = match byte[0] {
+            b'\r' | b'\n' => PickerKey::Enter,
+            3 | 4 => PickerKey::Cancel,
+            27 if !has_pending(input) => PickerKey::Cancel,
+            27 => {
+                let mut seq = [0u8; 2];
+                if input.read_exact(&mut seq).is_err() || seq[0] != b'[' {
+                    continue;
+                }
+                match seq[1] {
+                    b'A' => PickerKey::Up,
+                    b'B' => PickerKey::Down,
+                    _ => continue,
+                }
+            }
+            8 | 127 => {
+                pending_utf8.clear();
+                PickerKey::Backspace
+            }
+            b if b < 32 => continue,
+            b => {
+                pending_utf8.push(b);
+                let Ok(piece) = std::str::from_utf8(&pending_utf8) else {
+                    if pending_utf8.len() > 4 {
+                        pending_utf8.clear();
+                    }
+                    continue;
+                };
+                let ch = piece.chars().next().unwrap_or_default();
+                pending_utf8.clear();
+                PickerKey::Char(ch)
+            }
+        };
+        match state.apply(key) {
+            PickerOutcome::Pending => {}
+            outcome => return Ok(outcome),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PickerKey, PickerOutcome, PickerState, drive};
+    use std::io::Cursor;
+
+    fn state(allow_custom: bool) -> PickerState {
+        let items = ["openai", "anthropic", "zhipu", "ollama", "deepseek"]
+            .map(String::from)
+            .to_vec();
+        PickerState::new(items, allow_custom)
+    }
+
+    #[test]
+    fn arrows_wrap_and_filter_narrows_selection() {
+        let mut picker = state(false);
+        assert_eq!(picker.apply(PickerKey::Up), PickerOutcome::Pending);
+        assert_eq!(picker.selected(), Some(4));
+        picker.apply(PickerKey::Down);
+        assert_eq!(picker.apply(PickerKey::Enter), PickerOutcome::Selected(0));
+
+        for ch in "op".chars() {
+            picker.apply(PickerKey::Char(ch));
+        }
+        assert_eq!(picker.matches(), vec![0, 1]);
+        picker.apply(PickerKey::Down);
+        assert_eq!(picker.apply(PickerKey::Enter), PickerOutcome::Selected(1));
+
+        picker.apply(PickerKey::Backspace);
+        assert_eq!(picker.filter(), "o");
+        assert_eq!(picker.selected(), Some(0));
+        assert_eq!(picker.apply(PickerKey::Cancel), PickerOutcome::Cancelled);
+    }
+
+    #[test]
+    fn unmatched_filter_is_custom_only_when_allowed() {
+        let mut strict = state(false);
+        let mut custom = state(true);
+        for ch in "qwen".chars() {
+            strict.apply(PickerKey::Char(ch));
+            custom.apply(PickerKey::Char(ch));
+        }
+        assert_eq!(strict.selected(), None);
+        assert_eq!(strict.apply(PickerKey::Enter), PickerOutcome::Pending);
+        assert_eq!(
+            custom.apply(PickerKey::Enter),
+            PickerOutcome::Custom("qwen".to_string())
+        );
+    }
+
+    #[test]
+    fn arrow_sequences_move_and_lone_escape_cancels() {
+        let run = |keys: &'static [u8]| {
+            let mut input = Cursor::new(keys);
+            let unread = |input: &mut Cursor<&[u8]>| input.position() < keys.len() as u64;
+            drive(&mut state(false), &mut input, unread, |_| Ok(())).unwrap()
+        };
+        assert_eq!(run(b"\x1b[B\r"), PickerOutcome::Selected(1));
+        assert_eq!(run(b"\x1b[A\r"), PickerOutcome::Selected(4));
+        assert_eq!(run(b"\x1b"), PickerOutcome::Cancelled);
+    }
+}
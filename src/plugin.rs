@@ -0,0 +1,72 @@
+use crate::config::AgentPaths;
+use crate::fsutil;
+use anyhow::{Context, Result};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const PLUGIN_PREFIX: &str = "goldagent-";
+
+/// Looks up `goldagent-<name>` in the directories of `path_var` (a PATH-style
+/// list), returning the first executable match.
+pub fn resolve_plugin(name: &str, path_var: &OsStr) -> Option<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return None;
+    }
+    fsutil::find_executable(&format!("{PLUGIN_PREFIX}{name}"), path_var)
+}
+
+/// Runs a resolved plugin with the remaining arguments, exposing the active
+/// GoldAgent home via `GOLDAGENT_HOME`. On unix the current process is
+/// replaced; elsewhere the plugin's exit code is propagated.
+pub fn run_plugin(paths: &AgentPaths, program: &Path, args: &[OsString]) -> Result<()> {
+    let mut command = Command::new(program);
+    command.args(args).env("GOLDAGENT_HOME", &paths.root);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let err = command.exec();
+        Err(err).with_context(|| format!("执行扩展命令失败: {}", program.display()))
+    }
+
+    #[cfg(not(unix))]
+    {
+        let status = command
+            .status()
+            .with_context(|| format!("执行扩展命令失败: {}", program.display()))?;
+        std::process::exit(status.code().unwrap_or(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resolve_plugin;
+    use std::env;
+    use std::fs;
+    use uuid::Uuid;
+
+    #[cfg(unix)]
+    #[test]
+    fn resolves_executable_plugin_on_path() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let empty = env::temp_dir().join(format!("goldagent-plugin-empty-{}", Uuid::new_v4()));
+        let bin = env::temp_dir().join(format!("goldagent-plugin-bin-{}", Uuid::new_v4()));
+        fs::create_dir_all(&empty).unwrap();
+        fs::create_dir_all(&bin).unwrap();
+        let plugin = bin.join("goldagent-foo");
+        fs::write(&plugin, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&plugin, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(bin.join("goldagent-noexec"), "#!/bin/sh\n").unwrap();
+
+        let path_var = env::join_paths([&empty, &bin]).unwrap();
+        assert_eq!(resolve_plugin("foo", &path_var), Some(plugin));
+        assert_eq!(resolve_plugin("bar", &path_var), None);
+        assert_eq!(resolve_plugin("noexec", &path_var), None);
+        assert_eq!(resolve_plugin("../foo", &path_var), None);
+
+        let _ = fs::remove_dir_all(&empty);
+        let _ = fs::remove_dir_all(&bin);
+    }
+}
@@ -1,22 +1,35 @@
 use crate::cli::ConnectCommand;
-use crate::config::AgentPaths;
-use crate::connect::{self, ConnectMode, ConnectProvider, ZhipuApiType};
+use crate::config::{self, AgentPaths, BudgetConfig, ModelProfile};
+use crate::connect::{self, ConnectConfig, ConnectMode, ConnectProvider, ZhipuApiType};
+use crate::fsutil;
+use crate::http_debug::HttpDebugLog;
 use crate::usage::{self, UsageEvent};
 use anyhow::{Context, Result, anyhow, bail};
-use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue};
+use reqwest::StatusCode;
+use reqwest::header::{
+    AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue, RETRY_AFTER,
+};
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
 use tokio::process::Command;
 use uuid::Uuid;
 
+const OPENAI_DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const OLLAMA_DEFAULT_BASE_URL: &str = "http://localhost:11434/v1";
+const DEEPSEEK_CHAT_ENDPOINT: &str = "https://api.deepseek.com/v1/chat/completions";
 const ZHIPU_GENERAL_CHAT_ENDPOINT: &str = "https://open.bigmodel.cn/api/paas/v4/chat/completions";
 const ZHIPU_CODING_CHAT_ENDPOINT: &str =
     "https://open.bigmodel.cn/api/coding/paas/v4/chat/completions";
+const ZHIPU_EMBEDDINGS_ENDPOINT: &str = "https://open.bigmodel.cn/api/paas/v4/embeddings";
 const OPENAI_CODEX_LOGIN_LATEST_MODEL: &str = "gpt-5.3-codex";
 const OPENAI_CODEX_BASE_MODEL: &str = "gpt-5.2-codex";
+/// Sampling temperature used unless a profile or `--temperature` sets one.
+const DEFAULT_TEMPERATURE: f32 = 0.2;
 const OPENAI_CODEX_LOGIN_TIER_MODELS: [&str; 4] = [
     "gpt-5.3-codex@low",
     "gpt-5.3-codex@medium",
@@ -29,8 +42,15 @@ const OPENAI_CODEX_TIER_MODELS: [&str; 4] = [
     "gpt-5.2-codex@high",
     "gpt-5.2-codex@xhigh",
 ];
-
-#[derive(Debug, Clone, Serialize)]
+/// Conservative input budgets (estimated tokens) that leave room for output.
+const DEFAULT_INPUT_TOKEN_BUDGET: usize = 100_000;
+const ANTHROPIC_INPUT_TOKEN_BUDGET: usize = 150_000;
+const TRUNCATION_MARKER: &str = "\n[...内容过长，已截断...]\n";
+const DEFAULT_HTTP_TIMEOUT_SECS: u64 = 120;
+const DEFAULT_HTTP_MAX_RETRIES: u32 = 3;
+const MAX_RETRY_AFTER_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: String,
     pub content: String,
@@ -63,6 +83,22 @@ impl ChatMessage {
 pub struct ProviderClient {
     backend: ModelBackend,
     usage_file: Option<PathBuf>,
+    sampling: Sampling,
+    budget: BudgetConfig,
+}
+
+/// Per-invocation overrides resolved from a [`ModelProfile`].
+#[derive(Debug, Clone, Default)]
+struct Sampling {
+    temperature: Option<f32>,
+    effort: Option<OpenAiReasoningEffort>,
+    stop: Vec<String>,
+    seed: Option<u64>,
+    thinking_budget: Option<u32>,
+    /// Print Anthropic thinking blocks to stderr (`run --show-thinking`).
+    show_thinking: bool,
+    /// `run --json-schema`, sent as OpenAI `response_format`.
+    response_schema: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Clone)]
@@ -73,6 +109,7 @@ enum ModelBackend {
         endpoint: String,
         provider: ConnectProvider,
         zhipu_api_type: Option<ZhipuApiType>,
+        debug_log: Option<HttpDebugLog>,
     },
     CodexExec {
         model: Option<String>,
@@ -81,7 +118,15 @@ enum ModelBackend {
 
 impl ProviderClient {
     pub fn from_paths(paths: &AgentPaths, model_override: Option<String>) -> Result<Self> {
-        let usage_file = Some(paths.usage_file.clone());
+        let mut client = Self::connect_from_paths(paths, model_override)?;
+        // An unreadable config must not lift the budget.
+        client.budget = config::load(paths)
+            .context("无法读取 token 预算配置")?
+            .budget;
+        Ok(client)
+    }
+
+    fn connect_from_paths(paths: &AgentPaths, model_override: Option<String>) -> Result<Self> {
         let cfg = connect::load(paths).unwrap_or_default();
         let env_model = env::var("GOLDAGENT_MODEL").ok();
         let fallback_model = model_override.clone().or_else(|| match cfg.provider {
@@ -95,95 +140,244 @@ impl ProviderClient {
                 let zhipu_api_type = cfg.zhipu_api_type;
                 let env_api_key = env::var(connect::provider_env_var(&provider)).ok();
                 let configured_key = cfg.api_key.clone().or(env_api_key);
-                if let Some(api_key) = configured_key.as_deref() {
-                    if !api_key.trim().is_empty()
+                let usable_key = configured_key.as_deref().filter(|api_key| {
+                    !api_key.trim().is_empty()
                         && connect::validate_api_key(&provider, api_key).is_ok()
-                    {
-                        let model =
-                            model_override
-                                .or(cfg.model)
-                                .or(env_model)
-                                .unwrap_or_else(|| {
-                                    connect::default_model_for_provider(&provider).to_string()
-                                });
-                        return Self::build_api_backend(
-                            api_key,
-                            provider,
-                            model,
-                            usage_file,
-                            Some(zhipu_api_type),
-                        );
-                    }
+                });
+                let api_key = match usable_key {
+                    Some(api_key) => Some(api_key),
+                    None if connect::api_key_optional(&cfg) => Some(""),
+                    None => None,
+                };
+                if let Some(api_key) = api_key {
+                    let model = model_override
+                        .or(cfg.model)
+                        .or(env_model)
+                        .unwrap_or_else(|| {
+                            connect::default_model_for_provider(&provider).to_string()
+                        });
+                    return Self::build_api_backend(
+                        api_key,
+                        provider,
+                        model,
+                        Some(paths),
+                        Some(zhipu_api_type),
+                        cfg.base_url.as_deref(),
+                    );
                 }
             }
             ConnectMode::CodexLogin => {
                 let model = model_override.or(cfg.model).or(env_model);
                 return Ok(Self {
                     backend: ModelBackend::CodexExec { model },
-                    usage_file,
+                    usage_file: Some(paths.usage_file.clone()),
+                    sampling: Sampling::default(),
+                    budget: BudgetConfig::default(),
                 });
             }
         }
 
-        Self::from_env_with_usage(fallback_model, usage_file)
+        Self::from_env_with_paths(fallback_model, Some(paths))
+    }
+
+    /// Applies a resolved `--profile`: its temperature replaces the default
+    /// and its effort replaces any effort suffix on the model name.
+    pub fn with_profile(mut self, profile: &ModelProfile) -> Result<Self> {
+        let effort = match profile.effort.as_deref() {
+            Some(raw) => Some(parse_reasoning_effort(raw).ok_or_else(|| {
+                anyhow!("未知的 effort：{raw}（可选 low / medium / high / xhigh）")
+            })?),
+            None => None,
+        };
+        self.sampling = Sampling {
+            temperature: profile.temperature,
+            effort,
+            stop: validate_stop_sequences(profile.stop.clone())?,
+            seed: profile.seed,
+            thinking_budget: validate_thinking_budget(profile.thinking_budget)?,
+            show_thinking: false,
+            response_schema: None,
+        };
+        Ok(self)
+    }
+
+    /// Replaces the profile's stop sequences with `run --stop` values; an
+    /// empty list keeps the profile's.
+    pub fn with_stop(mut self, stop: Vec<String>) -> Result<Self> {
+        if !stop.is_empty() {
+            self.sampling.stop = validate_stop_sequences(stop)?;
+        }
+        Ok(self)
+    }
+
+    /// Replaces the profile's seed with `run --seed`, if given.
+    pub fn with_seed(mut self, seed: Option<u64>) -> Self {
+        if seed.is_some() {
+            self.sampling.seed = seed;
+        }
+        self
+    }
+
+    /// Replaces the profile's thinking budget with `run --thinking-budget`, if
+    /// given; `show` prints the returned thinking blocks to stderr.
+    pub fn with_thinking(mut self, budget: Option<u32>, show: bool) -> Result<Self> {
+        if budget.is_some() {
+            self.sampling.thinking_budget = validate_thinking_budget(budget)?;
+        }
+        self.sampling.show_thinking = show;
+        Ok(self)
+    }
+
+    /// Whether the backend enforces a JSON schema itself (OpenAI structured
+    /// outputs); other backends only get the schema in the prompt.
+    pub fn supports_response_schema(&self) -> bool {
+        matches!(
+            self.backend,
+            ModelBackend::ApiCompatible {
+                provider: ConnectProvider::OpenAi,
+                ..
+            }
+        )
+    }
+
+    /// Sends `schema` as the request's structured-output format.
+    pub fn with_response_schema(mut self, schema: Option<serde_json::Value>) -> Self {
+        self.sampling.response_schema = schema;
+        self
+    }
+
+    fn report_thinking(&self, output: &ChatApiOutput) {
+        if self.sampling.show_thinking
+            && let Some(thinking) = output.thinking.as_deref()
+        {
+            eprintln!("[thinking]\n{}\n[/thinking]", thinking.trim_end());
+        }
+    }
+
+    /// Prints the backend's `system_fingerprint` for seeded requests so runs
+    /// with the same seed can be checked for reproducibility.
+    fn report_fingerprint(&self, output: &ChatApiOutput) {
+        if let (Some(seed), Some(fingerprint)) = (self.sampling.seed, &output.system_fingerprint) {
+            eprintln!("seed={seed} system_fingerprint={fingerprint}");
+        }
+    }
+
+    /// Rebuilds the client from the saved connection (after `/connect` or
+    /// `/model`), keeping the profile chosen for this session.
+    fn reload(&mut self, paths: &AgentPaths) -> Result<()> {
+        let sampling = std::mem::take(&mut self.sampling);
+        *self = Self::from_paths(paths, None)?;
+        self.sampling = sampling;
+        Ok(())
+    }
+
+    fn temperature(&self) -> f32 {
+        self.sampling.temperature.unwrap_or(DEFAULT_TEMPERATURE)
+    }
+
+    /// Model name and reasoning effort sent to an OpenAI-compatible endpoint.
+    fn openai_compatible_request_model(
+        &self,
+        provider: &ConnectProvider,
+        model: &str,
+    ) -> (String, Option<OpenAiReasoningEffort>) {
+        let (resolved_model, model_effort) = resolve_openai_compatible_model(provider, model);
+        let effort = if matches!(provider, ConnectProvider::OpenAi) {
+            self.sampling.effort.or(model_effort)
+        } else {
+            model_effort
+        };
+        (resolved_model, effort)
     }
 
     #[allow(dead_code)]
     pub fn from_env(model_override: Option<String>) -> Result<Self> {
-        Self::from_env_with_usage(model_override, None)
+        Self::from_env_with_paths(model_override, None)
     }
 
-    fn from_env_with_usage(
+    /// Usage is recorded and HTTP debugging logged under `paths` when given.
+    fn from_env_with_paths(
         model_override: Option<String>,
-        usage_file: Option<PathBuf>,
+        paths: Option<&AgentPaths>,
     ) -> Result<Self> {
         let model = model_override.or_else(|| env::var("GOLDAGENT_MODEL").ok());
 
-        if let Ok(api_key) = env::var("OPENAI_API_KEY") {
-            if !api_key.trim().is_empty() {
-                let direct_model = model.unwrap_or_else(|| "gpt-5.2".to_string());
-                return Self::build_api_backend(
-                    &api_key,
-                    ConnectProvider::OpenAi,
-                    direct_model,
-                    usage_file,
-                    None,
-                );
-            }
+        if let Ok(api_key) = env::var("OPENAI_API_KEY")
+            && !api_key.trim().is_empty()
+        {
+            let direct_model = model.unwrap_or_else(|| "gpt-5.2".to_string());
+            return Self::build_api_backend(
+                &api_key,
+                ConnectProvider::OpenAi,
+                direct_model,
+                paths,
+                None,
+                None,
+            );
         }
 
         Ok(Self {
             backend: ModelBackend::CodexExec { model },
-            usage_file,
+            usage_file: paths.map(|paths| paths.usage_file.clone()),
+            sampling: Sampling::default(),
+            budget: BudgetConfig::default(),
         })
     }
 
     pub async fn chat(&self, messages: &[ChatMessage]) -> Result<String> {
+        self.enforce_budget()?;
+        let (fitted, warning) = fit_messages_to_budget(messages, self.input_token_budget());
+        if let Some(warning) = warning {
+            eprintln!("警告：{warning}");
+        }
+        let messages = fitted.as_slice();
         match &self.backend {
             ModelBackend::ApiCompatible {
                 http,
                 model,
                 endpoint,
                 provider,
+                debug_log,
                 ..
             } => {
+                let debug_log = debug_log.as_ref();
                 let output = match provider {
                     ConnectProvider::Anthropic => {
-                        chat_via_anthropic_api(http, endpoint, model, messages).await?
+                        chat_via_anthropic_api(
+                            http,
+                            endpoint,
+                            model,
+                            messages,
+                            self.temperature(),
+                            &self.sampling.stop,
+                            self.sampling.thinking_budget,
+                            debug_log,
+                        )
+                        .await?
                     }
-                    ConnectProvider::OpenAi | ConnectProvider::Zhipu => {
+                    ConnectProvider::OpenAi
+                    | ConnectProvider::Zhipu
+                    | ConnectProvider::Ollama
+                    | ConnectProvider::DeepSeek => {
                         let (resolved_model, reasoning_effort) =
-                            resolve_openai_compatible_model(provider, model);
+                            self.openai_compatible_request_model(provider, model);
                         chat_via_openai_compatible_api(
                             http,
                             endpoint,
                             &resolved_model,
                             messages,
+                            self.temperature(),
                             reasoning_effort,
+                            &self.sampling.stop,
+                            self.sampling.seed,
+                            self.sampling.response_schema.as_ref(),
+                            debug_log,
                         )
                         .await?
                     }
                 };
+                self.report_fingerprint(&output);
+                self.report_thinking(&output);
                 self.record_usage(UsageEvent {
                     model_key: format!("{}:{model}", provider_key(provider)),
                     input_tokens: output.input_tokens,
@@ -192,7 +386,8 @@ impl ProviderClient {
                 Ok(output.content)
             }
             ModelBackend::CodexExec { model } => {
-                let content = chat_via_codex_exec(messages, model.clone()).await?;
+                let content =
+                    chat_via_codex_exec(messages, model.clone(), self.sampling.effort).await?;
                 let model_key = model
                     .as_deref()
                     .map(|m| format!("codex:{m}"))
@@ -207,6 +402,66 @@ impl ProviderClient {
         }
     }
 
+    /// Like [`chat`](Self::chat) but hands each content delta to `on_delta` as
+    /// it arrives. Only the OpenAI-compatible API backends (OpenAI, Zhipu,
+    /// Ollama, DeepSeek) stream; other backends deliver the whole reply as a single delta.
+    pub async fn chat_stream<F>(&self, messages: &[ChatMessage], mut on_delta: F) -> Result<String>
+    where
+        F: FnMut(&str),
+    {
+        let ModelBackend::ApiCompatible {
+            http,
+            model,
+            endpoint,
+            provider:
+                provider @ (ConnectProvider::OpenAi
+                | ConnectProvider::Zhipu
+                | ConnectProvider::Ollama
+                | ConnectProvider::DeepSeek),
+            debug_log,
+            ..
+        } = &self.backend
+        else {
+            let content = self.chat(messages).await?;
+            on_delta(&content);
+            return Ok(content);
+        };
+
+        self.enforce_budget()?;
+        let (fitted, warning) = fit_messages_to_budget(messages, self.input_token_budget());
+        if let Some(warning) = warning {
+            eprintln!("警告：{warning}");
+        }
+        let (resolved_model, reasoning_effort) =
+            self.openai_compatible_request_model(provider, model);
+        let output = chat_stream_via_openai_compatible_api(
+            http,
+            endpoint,
+            &resolved_model,
+            &fitted,
+            self.temperature(),
+            reasoning_effort,
+            &self.sampling.stop,
+            self.sampling.seed,
+            matches!(provider, ConnectProvider::OpenAi),
+            debug_log.as_ref(),
+            &mut on_delta,
+        )
+        .await?;
+        self.report_fingerprint(&output);
+        self.record_usage(UsageEvent {
+            model_key: format!("{}:{model}", provider_key(provider)),
+            input_tokens: output.input_tokens,
+            output_tokens: output.output_tokens,
+        });
+        Ok(output.content)
+    }
+
+    /// System instruction pinning the assistant's identity to the live backend.
+    pub fn identity_guard(&self, template: Option<&str>) -> String {
+        identity_guard(&self.backend_label(), template)
+    }
+
     pub fn backend_label(&self) -> String {
         match &self.backend {
             ModelBackend::ApiCompatible {
@@ -222,6 +477,8 @@ impl ProviderClient {
                         connect::provider_label(provider),
                         connect::zhipu_api_type_label(kind)
                     )
+                } else if matches!(provider, ConnectProvider::Ollama) {
+                    format!("{} / 本地 / {model}", connect::provider_label(provider))
                 } else {
                     format!("{} / API / {model}", connect::provider_label(provider))
                 }
@@ -233,6 +490,20 @@ impl ProviderClient {
         }
     }
 
+    /// `host:port` of the API endpoint, for connectivity probes; `None` for
+    /// the Codex CLI backend.
+    pub fn endpoint_addr(&self) -> Option<String> {
+        let ModelBackend::ApiCompatible { endpoint, .. } = &self.backend else {
+            return None;
+        };
+        let url = reqwest::Url::parse(endpoint).ok()?;
+        Some(format!(
+            "{}:{}",
+            url.host_str()?,
+            url.port_or_known_default()?
+        ))
+    }
+
     pub fn usage_model_key(&self) -> String {
         match &self.backend {
             ModelBackend::ApiCompatible {
@@ -247,24 +518,44 @@ impl ProviderClient {
         }
     }
 
+    fn input_token_budget(&self) -> usize {
+        match &self.backend {
+            ModelBackend::ApiCompatible {
+                provider: ConnectProvider::Anthropic,
+                ..
+            } => ANTHROPIC_INPUT_TOKEN_BUDGET,
+            _ => DEFAULT_INPUT_TOKEN_BUDGET,
+        }
+    }
+
     fn build_api_backend(
         api_key: &str,
         provider: ConnectProvider,
         model: String,
-        usage_file: Option<PathBuf>,
+        paths: Option<&AgentPaths>,
         zhipu_api_type: Option<ZhipuApiType>,
+        base_url: Option<&str>,
     ) -> Result<Self> {
-        let endpoint = api_endpoint_for_provider(&provider, zhipu_api_type)?;
+        let endpoint = api_endpoint_for_provider(&provider, zhipu_api_type, base_url)?;
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
         match provider {
-            ConnectProvider::OpenAi | ConnectProvider::Zhipu => {
+            ConnectProvider::OpenAi | ConnectProvider::Zhipu | ConnectProvider::DeepSeek => {
                 headers.insert(
                     AUTHORIZATION,
                     HeaderValue::from_str(&format!("Bearer {api_key}"))
                         .map_err(|_| anyhow!("Failed to encode API key header"))?,
                 );
             }
+            ConnectProvider::Ollama => {
+                if !api_key.trim().is_empty() {
+                    headers.insert(
+                        AUTHORIZATION,
+                        HeaderValue::from_str(&format!("Bearer {api_key}"))
+                            .map_err(|_| anyhow!("Failed to encode API key header"))?,
+                    );
+                }
+            }
             ConnectProvider::Anthropic => {
                 headers.insert(
                     HeaderName::from_static("x-api-key"),
@@ -278,9 +569,8 @@ impl ProviderClient {
             }
         }
 
-        let http = reqwest::Client::builder()
-            .default_headers(headers)
-            .build()?;
+        let debug_log = paths.and_then(|paths| HttpDebugLog::from_env(&paths.logs_dir, &headers));
+        let http = api_http_client(headers, request_timeout())?;
         let zhipu_api_type = if matches!(provider, ConnectProvider::Zhipu) {
             Some(zhipu_api_type.unwrap_or(ZhipuApiType::General))
         } else {
@@ -293,11 +583,106 @@ impl ProviderClient {
                 endpoint,
                 provider,
                 zhipu_api_type,
+                debug_log,
             },
-            usage_file,
+            usage_file: paths.map(|paths| paths.usage_file.clone()),
+            sampling: Sampling::default(),
+            budget: BudgetConfig::default(),
         })
     }
 
+    /// `provider:model` label of the embeddings model used for semantic
+    /// memory dedup; `None` when the backend has no embeddings API
+    /// (Anthropic, DeepSeek, Codex login).
+    pub fn embedding_model(&self, model_override: Option<&str>) -> Option<String> {
+        let ModelBackend::ApiCompatible { provider, .. } = &self.backend else {
+            return None;
+        };
+        let default_model = match provider {
+            ConnectProvider::OpenAi => "text-embedding-3-small",
+            ConnectProvider::Zhipu => "embedding-3",
+            ConnectProvider::Ollama => "nomic-embed-text",
+            ConnectProvider::Anthropic | ConnectProvider::DeepSeek => return None,
+        };
+        Some(format!(
+            "{}:{}",
+            provider_key(provider),
+            model_override.unwrap_or(default_model)
+        ))
+    }
+
+    /// Model ids the API reports through its `/models` endpoint.
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        let ModelBackend::ApiCompatible { http, endpoint, .. } = &self.backend else {
+            bail!("登录态模式不支持获取模型列表，请先用 `goldagent connect api` 切换到 API 模式");
+        };
+        let url = models_endpoint(endpoint);
+        let response = http
+            .get(&url)
+            .timeout(request_timeout())
+            .send()
+            .await
+            .with_context(|| format!("请求模型列表失败：{url}"))?;
+        let status = response.status();
+        let body_text = response_text(response, &url).await?;
+        if !status.is_success() {
+            bail!("Models API error {status}: {body_text}");
+        }
+        parse_models_response(&body_text)
+    }
+
+    /// Embeds `texts` through the OpenAI-compatible `/embeddings` endpoint,
+    /// returning one vector per text in order.
+    pub async fn embed(
+        &self,
+        model_override: Option<&str>,
+        texts: &[String],
+    ) -> Result<Vec<Vec<f32>>> {
+        let ModelBackend::ApiCompatible {
+            http,
+            endpoint,
+            provider,
+            ..
+        } = &self.backend
+        else {
+            bail!("当前后端不支持 embeddings");
+        };
+        let label = self
+            .embedding_model(model_override)
+            .ok_or_else(|| anyhow!("当前后端不支持 embeddings"))?;
+        let model = label
+            .split_once(':')
+            .map(|(_, model)| model)
+            .unwrap_or(&label);
+        let url = match provider {
+            ConnectProvider::Zhipu => ZHIPU_EMBEDDINGS_ENDPOINT.to_string(),
+            _ => embeddings_endpoint(endpoint),
+        };
+        let body = serde_json::json!({ "model": model, "input": texts });
+        // Sits on the chat path (memory capture), so it is not retried; the
+        // timeout is the one chat requests use.
+        let response = http
+            .post(&url)
+            .timeout(request_timeout())
+            .json(&body)
+            .send()
+            .await
+            .map_err(|err| request_error(err, &url))?;
+        let status = response.status();
+        let body_text = response_text(response, &url).await?;
+        if !status.is_success() {
+            bail!("Embeddings API error {status}: {body_text}");
+        }
+        parse_embeddings_response(&body_text, texts.len())
+    }
+
+    fn enforce_budget(&self) -> Result<()> {
+        match &self.usage_file {
+            Some(path) => usage::enforce_budget(path, &self.budget),
+            None => Ok(()),
+        }
+    }
+
     fn record_usage(&self, event: UsageEvent) {
         if let Some(path) = &self.usage_file {
             let _ = usage::record(path, &event);
@@ -305,6 +690,48 @@ impl ProviderClient {
     }
 }
 
+/// Whether `chat`/`run` can reach any model at all: a valid API key for the
+/// configured provider, `OPENAI_API_KEY` as the env fallback, or a `codex`
+/// binary for the login mode.
+pub fn has_usable_backend(paths: &AgentPaths) -> bool {
+    let cfg = connect::load(paths).unwrap_or_default();
+    let path_var = env::var_os("PATH").unwrap_or_default();
+    let codex_available = fsutil::find_executable("codex", &path_var).is_some();
+    backend_usable(&cfg, |name| env::var(name).ok(), codex_available)
+}
+
+fn backend_usable(
+    cfg: &ConnectConfig,
+    env_var: impl Fn(&str) -> Option<String>,
+    codex_available: bool,
+) -> bool {
+    if matches!(cfg.mode, ConnectMode::CodexLogin) {
+        return codex_available;
+    }
+    if connect::api_key_optional(cfg) {
+        return true;
+    }
+    let key = cfg
+        .api_key
+        .clone()
+        .or_else(|| env_var(connect::provider_env_var(&cfg.provider)));
+    if key.is_some_and(|key| connect::validate_api_key(&cfg.provider, &key).is_ok()) {
+        return true;
+    }
+    env_var("OPENAI_API_KEY").is_some_and(|key| !key.trim().is_empty()) || codex_available
+}
+
+pub fn print_no_backend_guide() {
+    println!("尚未配置可用的模型后端（没有 API Key，也未找到 `codex` 命令）。");
+    println!("请先完成连接设置，任选其一：");
+    println!(
+        "- API 方式：goldagent connect api <api_key> --provider <openai|anthropic|zhipu|deepseek>"
+    );
+    println!("- 本地模型：启动 Ollama 后执行 goldagent connect api --provider ollama");
+    println!("- 登录方式：安装 Codex CLI 并执行 `codex login`，再运行 goldagent connect login");
+    println!("- 在对话中输入 /connect 进入连接设置");
+}
+
 #[derive(Clone)]
 pub struct HintItem {
     pub label: String,
@@ -320,38 +747,104 @@ pub struct ChatCommandOutcome {
 
 pub type PromptLineFn = fn(&str) -> io::Result<String>;
 
-pub fn handle_connect_command(paths: &AgentPaths, command: ConnectCommand) -> Result<()> {
+pub async fn handle_connect_command(paths: &AgentPaths, command: ConnectCommand) -> Result<()> {
     match command {
-        ConnectCommand::Status => {
+        ConnectCommand::Status { .. } => {
             print_connect_status(paths)?;
         }
         ConnectCommand::Login { model } => {
-            connect::set_login(paths, model)?;
+            let before = connect::load(paths).unwrap_or_default();
+            let after = connect::set_login(paths, model)?;
             let client = ProviderClient::from_paths(paths, None)?;
             println!("已切换连接方式：{}", client.backend_label());
+            print_connect_diff(&before, &after);
         }
         ConnectCommand::Api {
             api_key,
+            api_key_file,
             provider,
             zhipu_api_type,
             model,
+            base_url,
         } => {
             let provider = parse_provider_name(&provider)?;
             let zhipu_api_type = parse_zhipu_api_type_for_cli(&provider, zhipu_api_type)?;
-            connect::set_provider_api(paths, provider, api_key, model, zhipu_api_type)?;
+            let api_key = match api_key_file {
+                Some(file) => connect::read_api_key_file(Path::new(&file))?,
+                None => api_key.unwrap_or_default(),
+            };
+            let before = connect::load(paths).unwrap_or_default();
+            let mut after =
+                connect::set_provider_api(paths, provider, api_key, model, zhipu_api_type)?;
+            if let Some(base_url) = base_url {
+                after = connect::set_base_url(paths, &base_url)?;
+            }
             let client = ProviderClient::from_paths(paths, None)?;
             println!("已切换连接方式：{}", client.backend_label());
+            print_connect_diff(&before, &after);
+        }
+        ConnectCommand::Models => {
+            let cfg = connect::load(paths)?;
+            let client = ProviderClient::from_paths(paths, None)?;
+            let models = client.list_models().await?;
+            if models.is_empty() {
+                println!(
+                    "{} 未返回任何模型。",
+                    connect::provider_label(&cfg.provider)
+                );
+            } else {
+                println!(
+                    "{} 可用模型（{} 个，已缓存用于 /model 补全）：",
+                    connect::provider_label(&cfg.provider),
+                    models.len()
+                );
+                for model in &models {
+                    println!("- {model}");
+                }
+            }
+            connect::save_cached_models(paths, &cfg.provider, models)?;
+        }
+        ConnectCommand::Export { path, with_secrets } => {
+            connect::export(paths, Path::new(&path), with_secrets)?;
+            if with_secrets {
+                println!("已导出连接配置（包含 API Key）：{path}");
+            } else {
+                println!("已导出连接配置（不含 API Key）：{path}");
+            }
+        }
+        ConnectCommand::Import {
+            path,
+            overwrite_key,
+        } => {
+            let before = connect::load(paths).unwrap_or_default();
+            let after = connect::import(paths, Path::new(&path), overwrite_key)?;
+            let client = ProviderClient::from_paths(paths, None)?;
+            println!("已导入连接配置：{}", client.backend_label());
+            print_connect_diff(&before, &after);
         }
     }
     Ok(())
 }
 
+fn print_connect_diff(before: &ConnectConfig, after: &ConnectConfig) {
+    let changes = before.diff(after);
+    if changes.is_empty() {
+        println!("连接配置无变化。");
+    } else {
+        println!("变更：{}", connect::format_diff(&changes));
+    }
+}
+
 pub fn parse_provider_name(name: &str) -> Result<ConnectProvider> {
     match name.trim().to_ascii_lowercase().as_str() {
         "openai" => Ok(ConnectProvider::OpenAi),
         "zhipu" | "glm" => Ok(ConnectProvider::Zhipu),
         "anthropic" | "claude" => Ok(ConnectProvider::Anthropic),
-        other => bail!("不支持的 provider: {other}。可选: openai, zhipu, anthropic"),
+        "ollama" => Ok(ConnectProvider::Ollama),
+        "deepseek" => Ok(ConnectProvider::DeepSeek),
+        other => {
+            bail!("不支持的 provider: {other}。可选: openai, zhipu, anthropic, deepseek, ollama")
+        }
     }
 }
 
@@ -360,13 +853,16 @@ pub fn print_connect_help(paths: &AgentPaths) -> Result<()> {
     println!("- /connect openai");
     println!("- /connect anthropic");
     println!("- /connect zhipu");
+    println!("- /connect deepseek");
+    println!("- /connect ollama");
     println!("统一用法：");
     println!("- /connect <provider>           先选连接方式（api/login）");
-    println!("- /connect openai|anthropic api       进入 API Key 输入流程");
-    println!("- /connect openai|anthropic api <KEY> [model]");
+    println!("- /connect openai|anthropic|deepseek api       进入 API Key 输入流程");
+    println!("- /connect openai|anthropic|deepseek api <KEY> [model]");
     println!("- /connect zhipu api-general [<KEY> [model]]");
     println!("- /connect zhipu api-coding [<KEY> [model]]");
     println!("- /connect openai login [model] 仅 OpenAI 支持登录态");
+    println!("- /connect ollama local [model] 本地 Ollama，无需 API Key");
     println!("通用：");
     println!("- /connect status");
     print_connect_status(paths)?;
@@ -433,6 +929,14 @@ pub fn print_model_overview(paths: &AgentPaths) -> Result<()> {
 }
 
 pub fn print_connect_status(paths: &AgentPaths) -> Result<()> {
+    for line in connect_status_lines(paths)? {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// Lines of `connect status`, shared with the `--watch` view.
+pub fn connect_status_lines(paths: &AgentPaths) -> Result<Vec<String>> {
     let cfg = connect::load(paths)?;
     let client = ProviderClient::from_paths(paths, None)?;
     let usage_stats = usage::load(&paths.usage_file).unwrap_or_default();
@@ -449,54 +953,89 @@ pub fn print_connect_status(paths: &AgentPaths) -> Result<()> {
         .cloned()
         .unwrap_or_default();
 
-    println!("当前连接状态：");
-    println!("- 厂商: {}", connect::provider_label(&cfg.provider));
-    println!("- 模式: {}", connect::mode_label(&cfg.mode));
-    println!("- 生效后端: {}", client.backend_label());
+    let mut lines = vec!["当前连接状态：".to_string()];
+    lines.push(format!(
+        "- 厂商: {}",
+        connect::provider_label(&cfg.provider)
+    ));
+    lines.push(format!("- 模式: {}", connect::mode_label(&cfg.mode)));
+    lines.push(format!("- 生效后端: {}", client.backend_label()));
     if matches!(cfg.provider, ConnectProvider::Zhipu)
         && matches!(cfg.mode, connect::ConnectMode::OpenAIApi)
     {
-        println!(
+        lines.push(format!(
             "- 智谱 API 类型: {}",
             connect::zhipu_api_type_label(cfg.zhipu_api_type)
-        );
+        ));
     }
-    println!(
+    if matches!(cfg.mode, connect::ConnectMode::OpenAIApi) {
+        let default_base_url = match cfg.provider {
+            ConnectProvider::OpenAi => Some(OPENAI_DEFAULT_BASE_URL),
+            ConnectProvider::Ollama => Some(OLLAMA_DEFAULT_BASE_URL),
+            _ => None,
+        };
+        match (cfg.base_url.as_deref(), default_base_url) {
+            (Some(base_url), Some(_)) => lines.push(format!("- Base URL: {base_url}")),
+            (None, Some(default)) => lines.push(format!("- Base URL: {default}（默认）")),
+            _ => {}
+        }
+    }
+    lines.push(format!(
         "- 配置模型: {}",
         cfg.model.as_deref().unwrap_or("默认模型（由后端决定）")
-    );
-    println!("- 账户信息: {}", connect::account_label(&cfg));
+    ));
+    lines.push(format!("- 账户信息: {}", connect::account_label(&cfg)));
     if matches!(cfg.mode, connect::ConnectMode::OpenAIApi) {
         match connect::effective_api_key(&cfg) {
             Some(key) => {
                 if let Err(err) = connect::validate_api_key(&cfg.provider, &key) {
-                    println!("- 警告: 当前 API Key 可能无效：{err}");
+                    lines.push(format!("- 警告: 当前 API Key 可能无效：{err}"));
                 }
             }
+            None if connect::api_key_optional(&cfg) => {}
             None => {
-                println!("- 警告: 当前为 API 模式但未配置 API Key");
+                lines.push("- 警告: 当前为 API 模式但未配置 API Key".to_string());
             }
         }
     }
-    println!(
+    lines.push(format!(
         "- 用量累计: 请求 {} 次, 输入 {} tokens, 输出 {} tokens",
         usage_stats.total.requests, usage_stats.total.input_tokens, usage_stats.total.output_tokens
-    );
-    println!(
+    ));
+    lines.push(format!(
         "- 用量今日({}): 请求 {} 次, 输入 {} tokens, 输出 {} tokens",
         today_key, today.requests, today.input_tokens, today.output_tokens
-    );
-    println!(
+    ));
+    lines.push(format!(
         "- 当前模型用量({}): 请求 {} 次, 输入 {} tokens, 输出 {} tokens",
         current_model_key,
         current_model_usage.requests,
         current_model_usage.input_tokens,
         current_model_usage.output_tokens
-    );
+    ));
+    let budget = config::load(paths)?.budget;
+    if budget.is_set() {
+        let status = usage::budget_status(&budget, &usage_stats, &usage::current_month());
+        let summary = usage::budget_summary(&budget, &status.used);
+        lines.push(format!("- 本月预算: {summary}"));
+        if status.exceeded() {
+            lines.push("- 警告: 本月 token 预算已用尽，新的请求会被拒绝".to_string());
+        } else if status
+            .max_ratio()
+            .is_some_and(|ratio| ratio >= usage::BUDGET_WARN_RATIO)
+        {
+            lines.push(format!(
+                "- 警告: 本月 token 预算已用 {:.0}%",
+                status.max_ratio().unwrap_or_default() * 100.0
+            ));
+        }
+    }
     if matches!(cfg.mode, connect::ConnectMode::CodexLogin) {
-        println!("- 说明: 登录态模式暂无法获取官方 token 用量，tokens 仅在 API 模式下统计。");
+        lines.push(
+            "- 说明: 登录态模式暂无法获取官方 token 用量，tokens 仅在 API 模式下统计。".to_string(),
+        );
     }
-    Ok(())
+    Ok(lines)
 }
 
 pub fn suggested_models(provider: &ConnectProvider) -> Vec<&'static str> {
@@ -506,6 +1045,8 @@ pub fn suggested_models(provider: &ConnectProvider) -> Vec<&'static str> {
             vec!["claude-opus-4-6", "claude-sonnet-4-5", "claude-haiku-4-5"]
         }
         ConnectProvider::Zhipu => vec!["glm-5", "glm-4.7", "glm-4.7-flash"],
+        ConnectProvider::Ollama => vec!["llama3.1", "qwen2.5", "deepseek-r1"],
+        ConnectProvider::DeepSeek => vec!["deepseek-chat", "deepseek-reasoner"],
     }
 }
 
@@ -546,6 +1087,8 @@ pub fn connect_hint_items(rest: &str) -> Vec<HintItem> {
             "智谱 GLM（api-general/api-coding）",
             "/connect zhipu ",
         ),
+        ("deepseek", "DeepSeek（api）", "/connect deepseek "),
+        ("ollama", "Ollama 本地模型（local）", "/connect ollama "),
         ("status", "查看连接/模型/账户/用量", "/connect status"),
     ];
 
@@ -581,6 +1124,7 @@ pub fn connect_hint_items(rest: &str) -> Vec<HintItem> {
                     "api-coding" => {
                         format!("/connect {} api-coding ", provider_command_name(&provider))
                     }
+                    "local" => format!("/connect {} local ", provider_command_name(&provider)),
                     _ => format!("/connect {} ", provider_command_name(&provider)),
                 };
                 let desc = match *method {
@@ -588,6 +1132,7 @@ pub fn connect_hint_items(rest: &str) -> Vec<HintItem> {
                     "api" => "使用 API Key",
                     "api-general" => "普通 API（/api/paas）",
                     "api-coding" => "Coding Plan API（/api/coding/paas）",
+                    "local" => "本地 Ollama（无需 API Key）",
                     _ => "",
                 };
                 HintItem {
@@ -612,7 +1157,7 @@ pub fn connect_hint_items(rest: &str) -> Vec<HintItem> {
         if items.is_empty() {
             items.push(HintItem {
                 label: "未匹配到 connect 子命令".to_string(),
-                desc: "可选: openai / anthropic / zhipu / status".to_string(),
+                desc: "可选: openai / anthropic / zhipu / deepseek / ollama / status".to_string(),
                 completion: "/connect ".to_string(),
             });
         }
@@ -628,7 +1173,7 @@ pub fn connect_hint_items(rest: &str) -> Vec<HintItem> {
         None => {
             return vec![HintItem {
                 label: "connect".to_string(),
-                desc: "可选: openai / anthropic / zhipu / status".to_string(),
+                desc: "可选: openai / anthropic / zhipu / deepseek / ollama / status".to_string(),
                 completion: "/connect ".to_string(),
             }];
         }
@@ -652,7 +1197,7 @@ pub fn connect_hint_items(rest: &str) -> Vec<HintItem> {
         ];
     }
 
-    if tokens.len() == 2 && !methods.iter().any(|m| *m == method_token) {
+    if tokens.len() == 2 && !methods.contains(&method_token) {
         let mut items = methods
             .iter()
             .filter(|method| method.starts_with(method_token))
@@ -662,6 +1207,7 @@ pub fn connect_hint_items(rest: &str) -> Vec<HintItem> {
                     "api" => format!("/connect {provider_cmd} api "),
                     "api-general" => format!("/connect {provider_cmd} api-general "),
                     "api-coding" => format!("/connect {provider_cmd} api-coding "),
+                    "local" => format!("/connect {provider_cmd} local "),
                     _ => format!("/connect {provider_cmd} "),
                 };
                 let desc = match *method {
@@ -669,6 +1215,7 @@ pub fn connect_hint_items(rest: &str) -> Vec<HintItem> {
                     "api" => "使用 API Key",
                     "api-general" => "普通 API（/api/paas）",
                     "api-coding" => "Coding Plan API（/api/coding/paas）",
+                    "local" => "本地 Ollama（无需 API Key）",
                     _ => "",
                 };
                 HintItem {
@@ -789,6 +1336,31 @@ pub fn connect_hint_items(rest: &str) -> Vec<HintItem> {
             }
             return items;
         }
+        "local" => {
+            if !matches!(provider, ConnectProvider::Ollama) {
+                return vec![HintItem {
+                    label: provider_cmd.to_string(),
+                    desc: format!("可选方式: {}", methods.join(" / ")),
+                    completion: format!("/connect {provider_cmd} "),
+                }];
+            }
+            let model_prefix = tokens.get(2).copied().unwrap_or_default();
+            let mut items = vec![HintItem {
+                label: "执行切换".to_string(),
+                desc: "回车切换到本地 Ollama".to_string(),
+                completion: format!("/connect {provider_cmd} local {model_prefix}"),
+            }];
+            for model in suggested_models(&provider) {
+                if model.starts_with(model_prefix) {
+                    items.push(HintItem {
+                        label: model.to_string(),
+                        desc: "Ollama 本地模型".to_string(),
+                        completion: format!("/connect {provider_cmd} local {model}"),
+                    });
+                }
+            }
+            return items;
+        }
         "api-general" | "api-coding" | "general" | "coding" | "coding-plan" => {
             if !matches!(provider, ConnectProvider::Zhipu) {
                 return vec![HintItem {
@@ -888,6 +1460,11 @@ pub fn model_hint_items(paths: &AgentPaths, rest: &str) -> Vec<HintItem> {
     if !models.iter().any(|m| m == &current) {
         models.insert(0, current.clone());
     }
+    for live in connect::load_cached_models(paths, &cfg.provider) {
+        if !models.contains(&live) {
+            models.push(live);
+        }
+    }
 
     let mut items = models
         .iter()
@@ -982,6 +1559,25 @@ pub fn handle_connect_chat_command(
                         client_changed: true,
                     });
                 }
+                "local" => {
+                    if !matches!(provider, ConnectProvider::Ollama) {
+                        println!("{} 不支持该连接方式。", connect::provider_label(&provider));
+                        return Ok(ChatCommandOutcome {
+                            handled: true,
+                            client_changed: false,
+                        });
+                    }
+                    let model = prompt_line(&format!(
+                        "请输入模型（可选，回车默认 {}）: ",
+                        connect::default_model_for_provider(&provider)
+                    ))?;
+                    let model = Some(model.trim().to_string()).filter(|m| !m.is_empty());
+                    let changed = connect_ollama_local(paths, client, model);
+                    return Ok(ChatCommandOutcome {
+                        handled: true,
+                        client_changed: changed,
+                    });
+                }
                 "api" => {
                     if matches!(provider, ConnectProvider::Zhipu) {
                         println!("智谱请使用 `api-general` 或 `api-coding`。");
@@ -1052,6 +1648,21 @@ pub fn handle_connect_chat_command(
                 client_changed: true,
             })
         }
+        Some("local") => {
+            if !matches!(provider, ConnectProvider::Ollama) {
+                println!("{} 不支持该连接方式。", connect::provider_label(&provider));
+                return Ok(ChatCommandOutcome {
+                    handled: true,
+                    client_changed: false,
+                });
+            }
+            let model = parts.next().map(str::to_string);
+            let changed = connect_ollama_local(paths, client, model);
+            Ok(ChatCommandOutcome {
+                handled: true,
+                client_changed: changed,
+            })
+        }
         Some("api") => {
             if matches!(provider, ConnectProvider::Zhipu) {
                 println!("智谱请使用 `api-general` 或 `api-coding`。");
@@ -1175,9 +1786,7 @@ pub fn handle_model_chat_command(
                 client_changed: false,
             });
         }
-        connect::set_model(paths, Some(model.to_string()))?;
-        *client = ProviderClient::from_paths(paths, None)?;
-        println!("已切换模型：{}", client.backend_label());
+        switch_model(paths, client, model)?;
         return Ok(ChatCommandOutcome {
             handled: true,
             client_changed: true,
@@ -1210,17 +1819,13 @@ pub fn handle_model_chat_command(
                     client_changed: false,
                 });
             }
-            connect::set_model(paths, Some(target.to_string()))?;
-            *client = ProviderClient::from_paths(paths, None)?;
-            println!("已切换模型：{}", client.backend_label());
+            switch_model(paths, client, target)?;
             return Ok(ChatCommandOutcome {
                 handled: true,
                 client_changed: true,
             });
         }
-        connect::set_model(paths, Some(model.to_string()))?;
-        *client = ProviderClient::from_paths(paths, None)?;
-        println!("已切换模型：{}", client.backend_label());
+        switch_model(paths, client, model)?;
         return Ok(ChatCommandOutcome {
             handled: true,
             client_changed: true,
@@ -1230,32 +1835,114 @@ pub fn handle_model_chat_command(
     Ok(ChatCommandOutcome::default())
 }
 
+fn switch_model(paths: &AgentPaths, client: &mut ProviderClient, model: &str) -> Result<()> {
+    let before = connect::load(paths).unwrap_or_default();
+    let after = connect::set_model(paths, Some(model.to_string()))?;
+    client.reload(paths)?;
+    println!("已切换模型：{}", client.backend_label());
+    print_connect_diff(&before, &after);
+    Ok(())
+}
+
 fn provider_command_name(provider: &ConnectProvider) -> &'static str {
     match provider {
         ConnectProvider::OpenAi => "openai",
         ConnectProvider::Anthropic => "anthropic",
         ConnectProvider::Zhipu => "zhipu",
+        ConnectProvider::Ollama => "ollama",
+        ConnectProvider::DeepSeek => "deepseek",
+    }
+}
+
+/// Providers offered by the interactive `/connect` picker, in display order.
+pub const PICKER_PROVIDERS: [ConnectProvider; 5] = [
+    ConnectProvider::OpenAi,
+    ConnectProvider::Anthropic,
+    ConnectProvider::Zhipu,
+    ConnectProvider::DeepSeek,
+    ConnectProvider::Ollama,
+];
+
+/// Provider, connect method and model picked in the interactive menu.
+#[derive(Debug, Clone)]
+pub struct ConnectChoice {
+    pub provider: ConnectProvider,
+    pub method: &'static str,
+    pub model: String,
+}
+
+/// Model candidates for `method`; login mode lists the Codex variants.
+pub fn picker_models(provider: &ConnectProvider, method: &str) -> Vec<&'static str> {
+    if method == "login" {
+        suggested_login_models(provider)
+    } else {
+        suggested_models(provider)
+    }
+}
+
+/// Applies a picker choice through the same paths as `/connect <provider>
+/// <method>`; API methods still prompt for the key.
+pub fn apply_connect_choice(
+    paths: &AgentPaths,
+    client: &mut ProviderClient,
+    choice: ConnectChoice,
+    prompt_line: PromptLineFn,
+) -> Result<bool> {
+    let model = Some(choice.model);
+    let result = match choice.method {
+        "login" => connect_openai_login(paths, client, model),
+        "local" => return Ok(connect_ollama_local(paths, client, model)),
+        method => {
+            let env_var = connect::provider_env_var(&choice.provider);
+            let api_key = prompt_line(&format!(
+                "请输入 {env_var}（留空取消，@/path/to/file 从文件读取）: "
+            ))?;
+            if api_key.trim().is_empty() {
+                println!("已取消连接。");
+                return Ok(false);
+            }
+            connect_provider_api(
+                paths,
+                client,
+                choice.provider,
+                api_key.trim().to_string(),
+                model,
+                parse_zhipu_api_type_from_method(method),
+            )
+        }
+    };
+    if let Err(err) = result {
+        println!("连接失败：{err}");
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+pub fn connect_method_label(method: &str) -> &'static str {
+    match method {
+        "login" => "login（登录态）",
+        "api" => "api（API Key）",
+        "api-general" => "api-general（普通 API）",
+        "api-coding" => "api-coding（Coding Plan API）",
+        "local" => "local（本地 Ollama，无需 API Key）",
+        _ => "",
     }
 }
 
-fn connect_methods_for_provider(provider: &ConnectProvider) -> &'static [&'static str] {
+pub fn connect_methods_for_provider(provider: &ConnectProvider) -> &'static [&'static str] {
     match provider {
         ConnectProvider::OpenAi => &["login", "api"],
         ConnectProvider::Anthropic => &["api"],
         ConnectProvider::Zhipu => &["api-general", "api-coding"],
+        ConnectProvider::Ollama => &["local"],
+        ConnectProvider::DeepSeek => &["api"],
     }
 }
 
 fn print_provider_connect_methods(provider: &ConnectProvider) {
     println!("{} 连接方式：", connect::provider_label(provider));
     for method in connect_methods_for_provider(provider) {
-        match *method {
-            "login" => println!("- login（登录态）"),
-            "api" => println!("- api（API Key）"),
-            "api-general" => println!("- api-general（普通 API）"),
-            "api-coding" => println!("- api-coding（Coding Plan API）"),
-            _ => {}
-        }
+        println!("- {}", connect_method_label(method));
     }
 }
 
@@ -1264,9 +1951,11 @@ fn connect_openai_login(
     client: &mut ProviderClient,
     model: Option<String>,
 ) -> Result<()> {
-    connect::set_login(paths, model)?;
-    *client = ProviderClient::from_paths(paths, None)?;
+    let before = connect::load(paths).unwrap_or_default();
+    let after = connect::set_login(paths, model)?;
+    client.reload(paths)?;
     println!("已切换连接方式：{}", client.backend_label());
+    print_connect_diff(&before, &after);
     Ok(())
 }
 
@@ -1278,12 +1967,36 @@ fn connect_provider_api(
     model: Option<String>,
     zhipu_api_type: Option<ZhipuApiType>,
 ) -> Result<()> {
-    connect::set_provider_api(paths, provider, api_key, model, zhipu_api_type)?;
-    *client = ProviderClient::from_paths(paths, None)?;
+    let api_key = connect::resolve_api_key_input(&api_key)?;
+    let before = connect::load(paths).unwrap_or_default();
+    let after = connect::set_provider_api(paths, provider, api_key, model, zhipu_api_type)?;
+    client.reload(paths)?;
     println!("已切换连接方式：{}", client.backend_label());
+    print_connect_diff(&before, &after);
     Ok(())
 }
 
+/// Switches to the local Ollama server; prints the failure instead of
+/// returning it so the chat loop keeps running. Returns whether it switched.
+fn connect_ollama_local(
+    paths: &AgentPaths,
+    client: &mut ProviderClient,
+    model: Option<String>,
+) -> bool {
+    let result = connect_provider_api(
+        paths,
+        client,
+        ConnectProvider::Ollama,
+        String::new(),
+        model,
+        None,
+    );
+    if let Err(err) = &result {
+        println!("连接失败：{err}");
+    }
+    result.is_ok()
+}
+
 fn connect_provider_api_interactive(
     paths: &AgentPaths,
     client: &mut ProviderClient,
@@ -1292,7 +2005,9 @@ fn connect_provider_api_interactive(
     prompt_line: PromptLineFn,
 ) -> Result<bool> {
     let env_var = connect::provider_env_var(&provider);
-    let api_key = prompt_line(&format!("请输入 {env_var}（留空取消）: "))?;
+    let api_key = prompt_line(&format!(
+        "请输入 {env_var}（留空取消，@/path/to/file 从文件读取）: "
+    ))?;
     let api_key = api_key.trim().to_string();
     if api_key.is_empty() {
         println!("已取消连接。");
@@ -1492,34 +2207,106 @@ fn codex_cli_model_and_effort(model: &str) -> (String, Option<OpenAiReasoningEff
     (model.to_string(), None)
 }
 
-async fn chat_via_openai_compatible_api(
-    http: &reqwest::Client,
-    endpoint: &str,
+fn chat_completion_request(
     model: &str,
     messages: &[ChatMessage],
+    temperature: f32,
     reasoning_effort: Option<OpenAiReasoningEffort>,
-) -> Result<ChatApiOutput> {
-    let body = ChatCompletionRequest {
+    stop: &[String],
+    seed: Option<u64>,
+    stream: bool,
+) -> ChatCompletionRequest {
+    ChatCompletionRequest {
         model: model.to_string(),
         messages: messages.to_vec(),
-        temperature: 0.2,
+        temperature,
         reasoning: reasoning_effort.map(|effort| ChatReasoning {
             effort: effort.as_str().to_string(),
         }),
-    };
+        stop: (!stop.is_empty()).then(|| stop.to_vec()),
+        seed,
+        stream,
+        stream_options: None,
+        response_format: None,
+    }
+}
 
-    let response = http
-        .post(endpoint)
-        .json(&body)
-        .send()
-        .await
-        .with_context(|| format!("Failed to call API: {endpoint}"))?;
-    let status = response.status();
-    let body_text = response.text().await.unwrap_or_default();
-    let mut parsed = if status.is_success() {
-        serde_json::from_str::<ChatCompletionResponse>(&body_text).with_context(|| {
-            format!("Failed to parse OpenAI chat completion response: {body_text}")
-        })
+/// Most providers (OpenAI included) accept at most four stop sequences.
+const MAX_STOP_SEQUENCES: usize = 4;
+
+/// `max_tokens` of Anthropic requests, and of those with extended thinking,
+/// whose budget has to fit below it.
+const ANTHROPIC_MAX_TOKENS: u32 = 2_048;
+const ANTHROPIC_THINKING_MAX_TOKENS: u32 = 16_000;
+/// Smallest `budget_tokens` Anthropic accepts.
+const MIN_THINKING_BUDGET: u32 = 1_024;
+
+fn validate_thinking_budget(budget: Option<u32>) -> Result<Option<u32>> {
+    if let Some(budget) = budget
+        && !(MIN_THINKING_BUDGET..ANTHROPIC_THINKING_MAX_TOKENS).contains(&budget)
+    {
+        bail!(
+            "thinking budget 需在 {MIN_THINKING_BUDGET} 到 {} 之间（须小于 max_tokens={ANTHROPIC_THINKING_MAX_TOKENS}），当前为 {budget}",
+            ANTHROPIC_THINKING_MAX_TOKENS - 1
+        );
+    }
+    Ok(budget)
+}
+
+fn validate_stop_sequences(stop: Vec<String>) -> Result<Vec<String>> {
+    if stop.len() > MAX_STOP_SEQUENCES {
+        bail!(
+            "stop 序列最多 {MAX_STOP_SEQUENCES} 个，当前为 {} 个",
+            stop.len()
+        );
+    }
+    if stop.iter().any(String::is_empty) {
+        bail!("stop 序列不能为空字符串");
+    }
+    Ok(stop)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn chat_via_openai_compatible_api(
+    http: &reqwest::Client,
+    endpoint: &str,
+    model: &str,
+    messages: &[ChatMessage],
+    temperature: f32,
+    reasoning_effort: Option<OpenAiReasoningEffort>,
+    stop: &[String],
+    seed: Option<u64>,
+    response_schema: Option<&serde_json::Value>,
+    debug_log: Option<&HttpDebugLog>,
+) -> Result<ChatApiOutput> {
+    let response_format = || response_schema.map(ChatResponseFormat::json_schema);
+    let body = ChatCompletionRequest {
+        response_format: response_format(),
+        ..chat_completion_request(
+            model,
+            messages,
+            temperature,
+            reasoning_effort,
+            stop,
+            seed,
+            false,
+        )
+    };
+
+    if let Some(log) = debug_log {
+        log.log_request(endpoint, &body);
+    }
+    let response = send_with_retry(endpoint, || {
+        http.post(endpoint).timeout(request_timeout()).json(&body)
+    })
+    .await?;
+    let status = response.status();
+    let body_text = response_text(response, endpoint).await?;
+    if let Some(log) = debug_log {
+        log.log_response(endpoint, status, &body_text);
+    }
+    let mut parsed = if status.is_success() {
+        parse_chat_completion_response(&body_text)
     } else {
         bail!("API error {status}: {body_text}");
     };
@@ -1527,60 +2314,252 @@ async fn chat_via_openai_compatible_api(
         let lower = body_text.to_ascii_lowercase();
         if lower.contains("reasoning") || lower.contains("effort") {
             let fallback_body = ChatCompletionRequest {
-                model: model.to_string(),
-                messages: messages.to_vec(),
-                temperature: 0.2,
-                reasoning: None,
+                response_format: response_format(),
+                ..chat_completion_request(model, messages, temperature, None, stop, seed, false)
             };
-            let fallback_response = http
-                .post(endpoint)
-                .json(&fallback_body)
-                .send()
-                .await
-                .with_context(|| format!("Failed to call API: {endpoint}"))?;
+            if let Some(log) = debug_log {
+                log.log_request(endpoint, &fallback_body);
+            }
+            let fallback_response = send_with_retry(endpoint, || {
+                http.post(endpoint)
+                    .timeout(request_timeout())
+                    .json(&fallback_body)
+            })
+            .await?;
             let status = fallback_response.status();
-            let fallback_text = fallback_response.text().await.unwrap_or_default();
+            let fallback_text = response_text(fallback_response, endpoint).await?;
+            if let Some(log) = debug_log {
+                log.log_response(endpoint, status, &fallback_text);
+            }
             parsed = if status.is_success() {
-                serde_json::from_str::<ChatCompletionResponse>(&fallback_text).with_context(|| {
-                    format!("Failed to parse OpenAI chat completion response: {fallback_text}")
-                })
+                parse_chat_completion_response(&fallback_text)
             } else {
                 bail!("API error {status}: {fallback_text}");
             };
         }
     }
-    let parsed = parsed?;
+    parsed
+}
 
-    let content = parsed
-        .choices
+#[allow(clippy::too_many_arguments)]
+async fn chat_stream_via_openai_compatible_api(
+    http: &reqwest::Client,
+    endpoint: &str,
+    model: &str,
+    messages: &[ChatMessage],
+    temperature: f32,
+    reasoning_effort: Option<OpenAiReasoningEffort>,
+    stop: &[String],
+    seed: Option<u64>,
+    request_usage: bool,
+    debug_log: Option<&HttpDebugLog>,
+    on_delta: &mut dyn FnMut(&str),
+) -> Result<ChatApiOutput> {
+    let mut reasoning_effort = reasoning_effort;
+    let mut response = loop {
+        let body = ChatCompletionRequest {
+            stream_options: request_usage.then_some(ChatStreamOptions {
+                include_usage: true,
+            }),
+            ..chat_completion_request(
+                model,
+                messages,
+                temperature,
+                reasoning_effort,
+                stop,
+                seed,
+                true,
+            )
+        };
+        if let Some(log) = debug_log {
+            log.log_request(endpoint, &body);
+        }
+        // No overall timeout: the client's read timeout bounds each wait for
+        // the next chunk, so long replies keep streaming.
+        let response = send_with_retry(endpoint, || http.post(endpoint).json(&body)).await?;
+        let status = response.status();
+        if status.is_success() {
+            break response;
+        }
+        let text = response_text(response, endpoint).await?;
+        if let Some(log) = debug_log {
+            log.log_response(endpoint, status, &text);
+        }
+        let lower = text.to_ascii_lowercase();
+        if reasoning_effort.is_some() && (lower.contains("reasoning") || lower.contains("effort")) {
+            reasoning_effort = None;
+            continue;
+        }
+        bail!("API error {status}: {text}");
+    };
+
+    let status = response.status();
+    let mut parser = SseParser::default();
+    let mut output = ChatApiOutput::default();
+    let mut raw_events = String::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|err| request_error(err, endpoint))?
+    {
+        for data in parser.push(&chunk) {
+            if debug_log.is_some() {
+                raw_events.push_str(&data);
+                raw_events.push('\n');
+            }
+            apply_stream_event(&data, &mut output, on_delta)?;
+        }
+    }
+    if let Some(log) = debug_log {
+        log.log_response(endpoint, status, &raw_events);
+    }
+    if output.content.trim().is_empty() {
+        bail!("流式响应未返回文本内容");
+    }
+    Ok(output)
+}
+
+/// Incremental `text/event-stream` splitter: feeds raw bytes, yields the
+/// payload of each complete `data:` line. Bytes are buffered until a newline
+/// so multi-byte characters split across chunks stay intact.
+#[derive(Debug, Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(bytes);
+        let mut events = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line = self.buffer.drain(..=pos).collect::<Vec<_>>();
+            let line = String::from_utf8_lossy(&line);
+            if let Some(data) = line.trim_end().strip_prefix("data:") {
+                events.push(data.trim_start().to_string());
+            }
+        }
+        events
+    }
+}
+
+/// Applies one SSE `data:` payload: forwards the content delta and keeps the
+/// latest `usage` block (usually only on the final chunk).
+fn apply_stream_event(
+    data: &str,
+    output: &mut ChatApiOutput,
+    on_delta: &mut dyn FnMut(&str),
+) -> Result<()> {
+    if data.is_empty() || data == "[DONE]" {
+        return Ok(());
+    }
+    let value = serde_json::from_str::<serde_json::Value>(data)
+        .with_context(|| format!("Failed to parse stream chunk as JSON: {data}"))?;
+    if let Some(delta) = value
+        .pointer("/choices/0/delta/content")
+        .and_then(completion_content_text)
+        && !delta.is_empty()
+    {
+        on_delta(&delta);
+        output.content.push_str(&delta);
+    }
+    if let Some(fingerprint) = system_fingerprint(&value) {
+        output.system_fingerprint = Some(fingerprint);
+    }
+    if let Some(usage) = value.get("usage").filter(|usage| usage.is_object()) {
+        let read = |key: &str| usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+        output.input_tokens = read("prompt_tokens");
+        output.output_tokens = read("completion_tokens");
+    }
+    Ok(())
+}
+
+/// Parses an OpenAI-compatible completion body. Deliberately lenient because
+/// some compatible endpoints (notably Zhipu coding) omit `usage`, add extra
+/// fields, or return the text under `delta`/`text` instead of `message`.
+/// Errors name the exact field that could not be read.
+fn parse_chat_completion_response(body: &str) -> Result<ChatApiOutput> {
+    let value = serde_json::from_str::<serde_json::Value>(body)
+        .with_context(|| format!("Failed to parse chat completion response as JSON: {body}"))?;
+    let choices = value
+        .get("choices")
+        .ok_or_else(|| anyhow!("Chat completion response is missing `choices`: {body}"))?;
+    let first = choices
+        .as_array()
+        .ok_or_else(|| anyhow!("Chat completion field `choices` is not an array: {body}"))?
         .first()
-        .and_then(|choice| choice.message.content.clone())
-        .ok_or_else(|| anyhow!("OpenAI response did not include a message content"))?;
+        .ok_or_else(|| anyhow!("Chat completion field `choices` is empty: {body}"))?;
 
-    let input_tokens = parsed
-        .usage
-        .as_ref()
-        .map(|usage| usage.prompt_tokens)
-        .unwrap_or(0);
-    let output_tokens = parsed
-        .usage
-        .as_ref()
-        .map(|usage| usage.completion_tokens)
-        .unwrap_or(0);
+    let (field, raw_content) = ["message", "delta"]
+        .iter()
+        .find_map(|key| {
+            first
+                .get(*key)
+                .and_then(|m| m.get("content"))
+                .filter(|c| !c.is_null())
+                .map(|c| (format!("choices[0].{key}.content"), c))
+        })
+        .or_else(|| {
+            first
+                .get("text")
+                .filter(|c| !c.is_null())
+                .map(|c| ("choices[0].text".to_string(), c))
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "Chat completion field `choices[0]` has no `message.content`, `delta.content` or `text`: {body}"
+            )
+        })?;
+    let content = completion_content_text(raw_content).ok_or_else(|| {
+        anyhow!(
+            "Chat completion field `{field}` is neither a string nor a list of text parts: {body}"
+        )
+    })?;
+
+    let usage = value.get("usage");
+    let usage_field = |name: &str| {
+        usage
+            .and_then(|u| u.get(name))
+            .and_then(serde_json::Value::as_u64)
+            .unwrap_or(0)
+    };
 
     Ok(ChatApiOutput {
         content,
-        input_tokens,
-        output_tokens,
+        input_tokens: usage_field("prompt_tokens"),
+        output_tokens: usage_field("completion_tokens"),
+        system_fingerprint: system_fingerprint(&value),
+        thinking: None,
     })
 }
 
-async fn chat_via_anthropic_api(
-    http: &reqwest::Client,
-    endpoint: &str,
+fn system_fingerprint(value: &serde_json::Value) -> Option<String> {
+    value
+        .get("system_fingerprint")
+        .and_then(serde_json::Value::as_str)
+        .filter(|fingerprint| !fingerprint.is_empty())
+        .map(str::to_string)
+}
+
+fn completion_content_text(value: &serde_json::Value) -> Option<String> {
+    if let Some(text) = value.as_str() {
+        return Some(text.to_string());
+    }
+    let parts = value.as_array()?;
+    let text = parts
+        .iter()
+        .filter_map(|part| part.as_str().or_else(|| part.get("text")?.as_str()))
+        .collect::<Vec<_>>()
+        .join("");
+    Some(text)
+}
+
+fn anthropic_request(
     model: &str,
     messages: &[ChatMessage],
-) -> Result<ChatApiOutput> {
+    temperature: f32,
+    stop: &[String],
+    thinking_budget: Option<u32>,
+) -> Result<AnthropicMessagesRequest> {
     let mut system_parts = Vec::new();
     let mut anthropic_messages = Vec::new();
 
@@ -1599,35 +2578,65 @@ async fn chat_via_anthropic_api(
         bail!("Anthropic 请求缺少 user/assistant 消息");
     }
 
-    let body = AnthropicMessagesRequest {
+    Ok(AnthropicMessagesRequest {
         model: model.to_string(),
-        max_tokens: 2_048,
-        temperature: 0.2,
+        max_tokens: if thinking_budget.is_some() {
+            ANTHROPIC_THINKING_MAX_TOKENS
+        } else {
+            ANTHROPIC_MAX_TOKENS
+        },
+        // Extended thinking only accepts the default temperature.
+        temperature: if thinking_budget.is_some() {
+            1.0
+        } else {
+            temperature
+        },
         system: if system_parts.is_empty() {
             None
         } else {
             Some(system_parts.join("\n\n"))
         },
         messages: anthropic_messages,
-    };
+        stop_sequences: (!stop.is_empty()).then(|| stop.to_vec()),
+        thinking: thinking_budget.map(|budget_tokens| AnthropicThinking {
+            kind: "enabled",
+            budget_tokens,
+        }),
+    })
+}
 
-    let response = http
-        .post(endpoint)
-        .json(&body)
-        .send()
-        .await
-        .with_context(|| format!("Failed to call API: {endpoint}"))?;
+#[allow(clippy::too_many_arguments)]
+async fn chat_via_anthropic_api(
+    http: &reqwest::Client,
+    endpoint: &str,
+    model: &str,
+    messages: &[ChatMessage],
+    temperature: f32,
+    stop: &[String],
+    thinking_budget: Option<u32>,
+    debug_log: Option<&HttpDebugLog>,
+) -> Result<ChatApiOutput> {
+    let body = anthropic_request(model, messages, temperature, stop, thinking_budget)?;
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let text = response.text().await.unwrap_or_default();
+    if let Some(log) = debug_log {
+        log.log_request(endpoint, &body);
+    }
+    let response = send_with_retry(endpoint, || {
+        http.post(endpoint).timeout(request_timeout()).json(&body)
+    })
+    .await?;
+
+    let status = response.status();
+    let text = response_text(response, endpoint).await?;
+    if let Some(log) = debug_log {
+        log.log_response(endpoint, status, &text);
+    }
+    if !status.is_success() {
         bail!("API error {status}: {text}");
     }
 
-    let parsed: AnthropicMessagesResponse = response
-        .json()
-        .await
-        .context("Failed to parse Anthropic messages response")?;
+    let parsed: AnthropicMessagesResponse =
+        serde_json::from_str(&text).context("Failed to parse Anthropic messages response")?;
 
     let content = parsed
         .content
@@ -1635,6 +2644,12 @@ async fn chat_via_anthropic_api(
         .filter_map(|block| block.text.clone())
         .collect::<Vec<_>>()
         .join("");
+    let thinking = parsed
+        .content
+        .iter()
+        .filter_map(|block| block.thinking.as_deref())
+        .collect::<Vec<_>>()
+        .join("\n\n");
 
     if content.trim().is_empty() {
         bail!("Anthropic 响应未返回文本内容");
@@ -1655,32 +2670,234 @@ async fn chat_via_anthropic_api(
         content,
         input_tokens,
         output_tokens,
+        system_fingerprint: None,
+        thinking: (!thinking.trim().is_empty()).then_some(thinking),
     })
 }
 
+/// Rough token estimate: CJK characters count as one token each, everything
+/// else as a quarter token (about four ASCII characters per token).
+fn estimate_tokens(text: &str) -> usize {
+    let (wide, narrow) = text.chars().fold((0usize, 0usize), |(wide, narrow), ch| {
+        if ch.is_ascii() {
+            (wide, narrow + 1)
+        } else {
+            (wide + 1, narrow)
+        }
+    });
+    wide + narrow.div_ceil(4)
+}
+
+fn estimate_messages_tokens(messages: &[ChatMessage]) -> usize {
+    messages.iter().map(|m| estimate_tokens(&m.content)).sum()
+}
+
+/// Shrinks a request to fit `budget`. Older history is dropped first, then
+/// the system/memory portion is cut down to a quarter of the budget, and only
+/// then is the latest user message itself truncated. Returns a warning
+/// describing what was removed when anything changed.
+fn fit_messages_to_budget(
+    messages: &[ChatMessage],
+    budget: usize,
+) -> (Vec<ChatMessage>, Option<String>) {
+    let original = estimate_messages_tokens(messages);
+    if original <= budget {
+        return (messages.to_vec(), None);
+    }
+
+    // Works in place so system messages keep their position among the history.
+    let mut fitted = messages.to_vec();
+    let is_system = |m: &ChatMessage| m.role == "system";
+    let mut notes = Vec::new();
+
+    let mut dropped = 0usize;
+    while fitted.iter().filter(|m| !is_system(m)).count() > 1
+        && estimate_messages_tokens(&fitted) > budget
+        && let Some(oldest) = fitted.iter().position(|m| !is_system(m))
+    {
+        fitted.remove(oldest);
+        dropped += 1;
+    }
+    // Anthropic rejects a history that opens with an assistant turn, so keep
+    // dropping until the oldest remaining turn is the user's.
+    while dropped > 0
+        && fitted.iter().filter(|m| !is_system(m)).count() > 1
+        && let Some(oldest) = fitted.iter().position(|m| !is_system(m))
+        && fitted[oldest].role != "user"
+    {
+        fitted.remove(oldest);
+        dropped += 1;
+    }
+    if dropped > 0 {
+        notes.push(format!("丢弃了 {dropped} 条较早的对话历史"));
+    }
+
+    let system_cap = budget / 4;
+    let system_count = fitted.iter().filter(|m| is_system(m)).count();
+    let system_tokens = fitted
+        .iter()
+        .filter(|m| is_system(m))
+        .map(|m| estimate_tokens(&m.content))
+        .sum::<usize>();
+    if estimate_messages_tokens(&fitted) > budget && system_tokens > system_cap {
+        let per_message = system_cap / system_count.max(1);
+        for message in fitted.iter_mut().filter(|m| is_system(m)) {
+            message.content = truncate_to_tokens(&message.content, per_message);
+        }
+        notes.push("截断了系统提示与记忆上下文".to_string());
+    }
+
+    if let Some(last) = fitted.iter().rposition(|m| !is_system(m)) {
+        let others = estimate_messages_tokens(&fitted) - estimate_tokens(&fitted[last].content);
+        let allowed = budget.saturating_sub(others);
+        if estimate_tokens(&fitted[last].content) > allowed {
+            fitted[last].content = truncate_to_tokens(&fitted[last].content, allowed);
+            notes.push("截断了本次输入内容".to_string());
+        }
+    }
+
+    let warning = format!(
+        "请求约 {original} tokens，超出模型输入预算 {budget}，已{}后发送（约 {} tokens）。",
+        notes.join("、"),
+        estimate_messages_tokens(&fitted)
+    );
+    (fitted, Some(warning))
+}
+
+/// Keeps the head of `text` within roughly `max_tokens`, appending a marker.
+fn truncate_to_tokens(text: &str, max_tokens: usize) -> String {
+    let marker_tokens = estimate_tokens(TRUNCATION_MARKER);
+    let target = max_tokens.saturating_sub(marker_tokens);
+    let mut used = 0usize;
+    let mut ascii_run = 0usize;
+    let mut out = String::new();
+    for ch in text.chars() {
+        if ch.is_ascii() {
+            ascii_run += 1;
+            if ascii_run % 4 == 1 {
+                used += 1;
+            }
+        } else {
+            used += 1;
+        }
+        if used > target {
+            break;
+        }
+        out.push(ch);
+    }
+    out.push_str(TRUNCATION_MARKER);
+    out
+}
+
 fn provider_key(provider: &ConnectProvider) -> &'static str {
     match provider {
         ConnectProvider::OpenAi => "openai",
         ConnectProvider::Anthropic => "anthropic",
         ConnectProvider::Zhipu => "zhipu",
+        ConnectProvider::Ollama => "ollama",
+        ConnectProvider::DeepSeek => "deepseek",
+    }
+}
+
+/// Joins `/chat/completions` onto an OpenAI-compatible base URL, leaving a
+/// URL that already names the endpoint unchanged.
+fn openai_chat_endpoint(base_url: &str) -> String {
+    let base = base_url.trim().trim_end_matches('/');
+    if base.ends_with("/chat/completions") {
+        base.to_string()
+    } else {
+        format!("{base}/chat/completions")
+    }
+}
+
+/// `/models` next to a chat endpoint (`/chat/completions` or Anthropic's
+/// `/messages`).
+fn models_endpoint(chat_endpoint: &str) -> String {
+    let base = chat_endpoint
+        .strip_suffix("/chat/completions")
+        .or_else(|| chat_endpoint.strip_suffix("/messages"))
+        .unwrap_or(chat_endpoint);
+    format!("{}/models", base.trim_end_matches('/'))
+}
+
+/// Ids from the `{"data": [{"id": ...}]}` list shared by OpenAI-compatible
+/// APIs and Anthropic, sorted and deduplicated.
+fn parse_models_response(raw: &str) -> Result<Vec<String>> {
+    let value: serde_json::Value =
+        serde_json::from_str(raw).context("Failed to parse models response")?;
+    let mut models = value
+        .get("data")
+        .and_then(serde_json::Value::as_array)
+        .ok_or_else(|| anyhow!("Models response has no `data` list"))?
+        .iter()
+        .filter_map(|item| item.get("id").and_then(serde_json::Value::as_str))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    models.sort();
+    models.dedup();
+    Ok(models)
+}
+
+/// Swaps the `/chat/completions` suffix of an OpenAI-compatible endpoint for
+/// `/embeddings`.
+fn embeddings_endpoint(chat_endpoint: &str) -> String {
+    let base = chat_endpoint
+        .strip_suffix("/chat/completions")
+        .unwrap_or(chat_endpoint);
+    format!("{}/embeddings", base.trim_end_matches('/'))
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingsResponse {
+    data: Vec<EmbeddingItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingItem {
+    #[serde(default)]
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+fn parse_embeddings_response(body: &str, expected: usize) -> Result<Vec<Vec<f32>>> {
+    let mut parsed = serde_json::from_str::<EmbeddingsResponse>(body)
+        .with_context(|| format!("无法解析 embeddings 响应：{body}"))?;
+    if parsed.data.len() != expected {
+        bail!(
+            "embeddings 响应条数不符：期望 {expected}，实际 {}",
+            parsed.data.len()
+        );
     }
+    parsed.data.sort_by_key(|item| item.index);
+    Ok(parsed.data.into_iter().map(|item| item.embedding).collect())
 }
 
 fn api_endpoint_for_provider(
     provider: &ConnectProvider,
     zhipu_api_type: Option<ZhipuApiType>,
+    base_url: Option<&str>,
 ) -> Result<String> {
     match provider {
-        ConnectProvider::OpenAi => Ok("https://api.openai.com/v1/chat/completions".to_string()),
+        ConnectProvider::OpenAi => Ok(openai_chat_endpoint(
+            base_url.unwrap_or(OPENAI_DEFAULT_BASE_URL),
+        )),
         ConnectProvider::Zhipu => match zhipu_api_type.unwrap_or(ZhipuApiType::General) {
             ZhipuApiType::General => Ok(ZHIPU_GENERAL_CHAT_ENDPOINT.to_string()),
             ZhipuApiType::Coding => Ok(ZHIPU_CODING_CHAT_ENDPOINT.to_string()),
         },
         ConnectProvider::Anthropic => Ok("https://api.anthropic.com/v1/messages".to_string()),
+        ConnectProvider::DeepSeek => Ok(DEEPSEEK_CHAT_ENDPOINT.to_string()),
+        ConnectProvider::Ollama => Ok(openai_chat_endpoint(
+            base_url.unwrap_or(OLLAMA_DEFAULT_BASE_URL),
+        )),
     }
 }
 
-async fn chat_via_codex_exec(messages: &[ChatMessage], model: Option<String>) -> Result<String> {
+async fn chat_via_codex_exec(
+    messages: &[ChatMessage],
+    model: Option<String>,
+    effort_override: Option<OpenAiReasoningEffort>,
+) -> Result<String> {
     let output_file = env::temp_dir().join(format!("goldagent-codex-{}.txt", Uuid::new_v4()));
     let prompt = build_codex_prompt(messages);
 
@@ -1693,20 +2910,50 @@ async fn chat_via_codex_exec(messages: &[ChatMessage], model: Option<String>) ->
         .arg("--output-last-message")
         .arg(&output_file);
 
+    let mut reasoning_effort = None;
     if let Some(model) = model {
-        let (resolved_model, reasoning_effort) = codex_cli_model_and_effort(&model);
+        let (resolved_model, model_effort) = codex_cli_model_and_effort(&model);
         cmd.arg("--model").arg(resolved_model);
-        if let Some(effort) = reasoning_effort {
-            cmd.arg("-c")
-                .arg(format!("model_reasoning_effort=\"{}\"", effort.as_str()));
-        }
+        reasoning_effort = model_effort;
+    }
+    if let Some(effort) = effort_override.or(reasoning_effort) {
+        cmd.arg("-c")
+            .arg(format!("model_reasoning_effort=\"{}\"", effort.as_str()));
     }
     cmd.arg(prompt);
 
-    let output = cmd
-        .output()
-        .await
+    run_codex_process(cmd, &output_file, request_timeout()).await
+}
+
+/// Removes the `--output-last-message` file however the run ends.
+struct RemoveOnDrop<'a>(&'a Path);
+
+impl Drop for RemoveOnDrop<'_> {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(self.0);
+    }
+}
+
+/// Runs a prepared `codex exec` and reads its last message from
+/// `output_file`. Dropping the future (Ctrl-C in chat, or `timeout`) kills
+/// the child and removes the file.
+async fn run_codex_process(
+    mut cmd: Command,
+    output_file: &Path,
+    timeout: Duration,
+) -> Result<String> {
+    let _cleanup = RemoveOnDrop(output_file);
+    let child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
         .context("Failed to execute `codex`. Install Codex CLI or set OPENAI_API_KEY.")?;
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| anyhow!(timeout_message(timeout)))?
+        .context("Failed to wait for `codex`.")?;
 
     if !output.status.success() {
         let stdout = String::from_utf8_lossy(&output.stdout);
@@ -1718,9 +2965,8 @@ async fn chat_via_codex_exec(messages: &[ChatMessage], model: Option<String>) ->
         );
     }
 
-    let response = fs::read_to_string(&output_file)
+    let response = fs::read_to_string(output_file)
         .with_context(|| format!("Failed to read Codex output file {}", output_file.display()))?;
-    let _ = fs::remove_file(&output_file);
 
     let trimmed = response.trim().to_string();
     if trimmed.is_empty() {
@@ -1754,33 +3000,51 @@ struct ChatCompletionRequest {
     temperature: f32,
     #[serde(skip_serializing_if = "Option::is_none")]
     reasoning: Option<ChatReasoning>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<ChatStreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<ChatResponseFormat>,
 }
 
 #[derive(Debug, Serialize)]
-struct ChatReasoning {
-    effort: String,
+struct ChatStreamOptions {
+    include_usage: bool,
 }
 
-#[derive(Debug, Deserialize)]
-struct ChatCompletionResponse {
-    choices: Vec<ChatChoice>,
-    usage: Option<ChatUsage>,
+#[derive(Debug, Serialize)]
+struct ChatResponseFormat {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    json_schema: ChatJsonSchema,
 }
 
-#[derive(Debug, Deserialize)]
-struct ChatChoice {
-    message: ChatResponseMessage,
+#[derive(Debug, Serialize)]
+struct ChatJsonSchema {
+    name: &'static str,
+    schema: serde_json::Value,
 }
 
-#[derive(Debug, Deserialize)]
-struct ChatResponseMessage {
-    content: Option<String>,
+impl ChatResponseFormat {
+    fn json_schema(schema: &serde_json::Value) -> Self {
+        Self {
+            kind: "json_schema",
+            json_schema: ChatJsonSchema {
+                name: "response",
+                schema: schema.clone(),
+            },
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct ChatUsage {
-    prompt_tokens: u64,
-    completion_tokens: u64,
+#[derive(Debug, Serialize)]
+struct ChatReasoning {
+    effort: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -1791,6 +3055,17 @@ struct AnthropicMessagesRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     system: Option<String>,
     messages: Vec<AnthropicMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop_sequences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking: Option<AnthropicThinking>,
+}
+
+#[derive(Debug, Serialize)]
+struct AnthropicThinking {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    budget_tokens: u32,
 }
 
 #[derive(Debug, Serialize)]
@@ -1808,6 +3083,8 @@ struct AnthropicMessagesResponse {
 #[derive(Debug, Deserialize)]
 struct AnthropicContentBlock {
     text: Option<String>,
+    /// Present on `thinking` blocks when extended thinking is enabled.
+    thinking: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -1816,8 +3093,862 @@ struct AnthropicUsage {
     output_tokens: u64,
 }
 
+#[derive(Default)]
 struct ChatApiOutput {
     content: String,
     input_tokens: u64,
     output_tokens: u64,
+    /// Backend configuration id OpenAI returns alongside seeded requests.
+    system_fingerprint: Option<String>,
+    /// Anthropic extended-thinking text, when requested.
+    thinking: Option<String>,
+}
+
+/// Per-request timeout for API calls and `codex exec`, from
+/// `GOLDAGENT_HTTP_TIMEOUT_SECS` (default 120s).
+fn request_timeout() -> Duration {
+    Duration::from_secs(parse_timeout_secs(
+        env::var("GOLDAGENT_HTTP_TIMEOUT_SECS").ok().as_deref(),
+    ))
+}
+
+fn parse_timeout_secs(raw: Option<&str>) -> u64 {
+    raw.and_then(|value| value.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_HTTP_TIMEOUT_SECS)
+}
+
+fn timeout_message(timeout: Duration) -> String {
+    format!(
+        "请求超时（{}s），请检查网络或降低上下文长度",
+        timeout.as_secs()
+    )
+}
+
+/// HTTP client for the API backends. `timeout` bounds connecting and each
+/// read (an idle stream); non-streaming requests also set it as their total
+/// timeout with `RequestBuilder::timeout`.
+fn api_http_client(headers: HeaderMap, timeout: Duration) -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .default_headers(headers)
+        .connect_timeout(timeout)
+        .read_timeout(timeout)
+        .build()?)
+}
+
+/// Reads a response body, reporting read failures instead of treating them
+/// as an empty body.
+async fn response_text(response: reqwest::Response, endpoint: &str) -> Result<String> {
+    response
+        .text()
+        .await
+        .map_err(|err| request_error(err, endpoint))
+}
+
+fn request_error(err: reqwest::Error, endpoint: &str) -> anyhow::Error {
+    if err.is_timeout() {
+        anyhow!(timeout_message(request_timeout()))
+    } else {
+        anyhow::Error::new(err).context(format!("Failed to call API: {endpoint}"))
+    }
+}
+
+/// Sends a request, retrying HTTP 429/500/502/503/504 up to
+/// `GOLDAGENT_HTTP_MAX_RETRIES` times (default 3) with exponential backoff
+/// (1s, 2s, 4s, plus jitter) or the server's `Retry-After`. Other statuses,
+/// including 400/401/403, are returned immediately for the caller to report.
+async fn send_with_retry(
+    endpoint: &str,
+    build: impl Fn() -> reqwest::RequestBuilder,
+) -> Result<reqwest::Response> {
+    let max_retries = parse_max_retries(env::var("GOLDAGENT_HTTP_MAX_RETRIES").ok().as_deref());
+    let mut attempt = 0u32;
+    loop {
+        let response = build()
+            .send()
+            .await
+            .map_err(|err| request_error(err, endpoint))?;
+        let status = response.status();
+        if attempt >= max_retries || !is_retryable_status(status) {
+            return Ok(response);
+        }
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok());
+        let jitter_ms = (Uuid::new_v4().as_u128() % 250) as u64;
+        let delay = retry_delay(attempt, retry_after, jitter_ms);
+        attempt += 1;
+        eprintln!(
+            "API 返回 {status}，{:.1}s 后重试（{attempt}/{max_retries}）",
+            delay.as_secs_f32()
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+fn parse_max_retries(raw: Option<&str>) -> u32 {
+    raw.and_then(|value| value.trim().parse::<u32>().ok())
+        .unwrap_or(DEFAULT_HTTP_MAX_RETRIES)
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 500 | 502 | 503 | 504)
+}
+
+fn retry_delay(attempt: u32, retry_after: Option<&str>, jitter_ms: u64) -> Duration {
+    if let Some(secs) = retry_after.and_then(|value| value.trim().parse::<u64>().ok()) {
+        return Duration::from_secs(secs.min(MAX_RETRY_AFTER_SECS));
+    }
+    Duration::from_secs(1 << attempt.min(6)) + Duration::from_millis(jitter_ms)
+}
+
+const DEFAULT_IDENTITY_GUARD: &str = "Identity: you are GoldAgent running on {backend}.\n\
+If asked about model/backend identity, answer strictly based on this backend.\n\
+Memory context may mention models or backends used in earlier sessions; treat those as history and never as your own identity.\n\
+Never claim a fixed model family unless it matches {backend}.";
+
+/// Renders the identity guard, substituting `{backend}` in a custom template
+/// or the built-in text.
+pub fn identity_guard(backend_label: &str, template: Option<&str>) -> String {
+    template
+        .map(str::trim)
+        .filter(|template| !template.is_empty())
+        .unwrap_or(DEFAULT_IDENTITY_GUARD)
+        .replace("{backend}", backend_label)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ChatApiOutput, ChatMessage, ConnectConfig, ConnectMode, ConnectProvider, ProviderClient,
+        SseParser, anthropic_request, api_endpoint_for_provider, api_http_client,
+        apply_stream_event, backend_usable, chat_completion_request,
+        chat_stream_via_openai_compatible_api, embeddings_endpoint, estimate_messages_tokens,
+        estimate_tokens, fit_messages_to_budget, identity_guard, is_retryable_status,
+        model_hint_items, models_endpoint, parse_chat_completion_response,
+        parse_embeddings_response, parse_max_retries, parse_models_response, parse_provider_name,
+        parse_timeout_secs, request_error, retry_delay, send_with_retry, suggested_models,
+    };
+    use crate::config::{self, AgentConfig, AgentPaths, ChatConfig};
+    use crate::connect;
+    use crate::response_format::WrapperFilter;
+    use crate::usage;
+    use reqwest::StatusCode;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn seed_is_sent_to_openai_requests_when_set() {
+        let profile: config::ModelProfile = serde_json::from_str(r#"{"seed": 7}"#).unwrap();
+        let client = ProviderClient::build_api_backend(
+            "sk-test",
+            ConnectProvider::OpenAi,
+            "gpt-5.2".to_string(),
+            None,
+            None,
+            None,
+        )
+        .unwrap()
+        .with_profile(&profile)
+        .unwrap();
+        let body = |client: &ProviderClient| {
+            let request = chat_completion_request(
+                "gpt-5.2",
+                &[ChatMessage::user("hi")],
+                0.2,
+                None,
+                &[],
+                client.sampling.seed,
+                false,
+            );
+            serde_json::to_value(request).unwrap()
+        };
+        assert_eq!(body(&client)["seed"], 7);
+        let client = client.with_seed(Some(42));
+        assert_eq!(body(&client)["seed"], 42);
+        let unseeded = ProviderClient::build_api_backend(
+            "sk-test",
+            ConnectProvider::OpenAi,
+            "gpt-5.2".to_string(),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        assert!(body(&unseeded).get("seed").is_none());
+
+        let output = parse_chat_completion_response(
+            r#"{"system_fingerprint": "fp_abc", "choices": [{"message": {"content": "ok"}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(output.system_fingerprint.as_deref(), Some("fp_abc"));
+    }
+
+    #[test]
+    fn stop_sequences_reach_openai_and_anthropic_requests() {
+        let client = ProviderClient::build_api_backend(
+            "sk-ant-test",
+            ConnectProvider::Anthropic,
+            "claude-test".to_string(),
+            None,
+            None,
+            None,
+        )
+        .unwrap()
+        .with_stop(vec!["###".to_string(), "\nEND".to_string()])
+        .unwrap();
+        let stop = &client.sampling.stop;
+        let messages = [ChatMessage::system("sys"), ChatMessage::user("hi")];
+
+        let openai = chat_completion_request("gpt-5.2", &messages, 0.2, None, stop, None, false);
+        let openai = serde_json::to_value(openai).unwrap();
+        assert_eq!(openai["stop"], serde_json::json!(["###", "\nEND"]));
+        let anthropic = anthropic_request("claude-test", &messages, 0.2, stop, None).unwrap();
+        let anthropic = serde_json::to_value(anthropic).unwrap();
+        assert_eq!(
+            anthropic["stop_sequences"],
+            serde_json::json!(["###", "\nEND"])
+        );
+
+        let plain = chat_completion_request("gpt-5.2", &messages, 0.2, None, &[], None, false);
+        assert!(serde_json::to_value(plain).unwrap().get("stop").is_none());
+        let plain = anthropic_request("claude-test", &messages, 0.2, &[], None).unwrap();
+        assert!(
+            serde_json::to_value(plain)
+                .unwrap()
+                .get("stop_sequences")
+                .is_none()
+        );
+
+        let too_many = vec!["a".to_string(); 5];
+        assert!(client.clone().with_stop(too_many).is_err());
+        assert!(client.with_stop(vec![String::new()]).is_err());
+    }
+
+    #[test]
+    fn thinking_field_is_sent_only_with_a_budget() {
+        let client = ProviderClient::build_api_backend(
+            "sk-ant-test",
+            ConnectProvider::Anthropic,
+            "claude-test".to_string(),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let messages = [ChatMessage::system("sys"), ChatMessage::user("hi")];
+
+        let plain = anthropic_request("claude-test", &messages, 0.2, &[], None).unwrap();
+        let plain = serde_json::to_value(plain).unwrap();
+        assert!(plain.get("thinking").is_none());
+        assert_eq!(plain["max_tokens"], 2_048);
+
+        let client = client.with_thinking(Some(4_000), false).unwrap();
+        let budget = client.sampling.thinking_budget;
+        let thinking = anthropic_request("claude-test", &messages, 0.2, &[], budget).unwrap();
+        let thinking = serde_json::to_value(thinking).unwrap();
+        assert_eq!(
+            thinking["thinking"],
+            serde_json::json!({"type": "enabled", "budget_tokens": 4000})
+        );
+        assert_eq!(thinking["max_tokens"], 16_000);
+        assert_eq!(thinking["temperature"], 1.0);
+
+        assert!(client.clone().with_thinking(Some(512), false).is_err());
+        assert!(client.with_thinking(Some(16_000), false).is_err());
+    }
+
+    #[test]
+    fn profile_temperature_and_effort_reach_the_request() {
+        let cfg: AgentConfig = serde_json::from_str(
+            r#"{"profiles": {"creative": {"temperature": 1.0}, "draft": {"effort": "low"}}}"#,
+        )
+        .unwrap();
+        let client = || {
+            ProviderClient::build_api_backend(
+                "sk-test",
+                ConnectProvider::OpenAi,
+                "gpt-5.2".to_string(),
+                None,
+                None,
+                None,
+            )
+            .unwrap()
+        };
+        let request_body = |profile: &config::ModelProfile| {
+            let client = client().with_profile(profile).unwrap();
+            let (model, effort) =
+                client.openai_compatible_request_model(&ConnectProvider::OpenAi, "gpt-5.2");
+            let body = chat_completion_request(
+                &model,
+                &[],
+                client.temperature(),
+                effort,
+                &[],
+                None,
+                false,
+            );
+            serde_json::to_value(body).unwrap()
+        };
+
+        let creative = config::resolve_profile(&cfg, Some("creative"), None, None).unwrap();
+        assert_eq!(request_body(&creative)["temperature"], 1.0);
+
+        let default = config::resolve_profile(&cfg, None, None, None).unwrap();
+        let body = request_body(&default);
+        assert!((body["temperature"].as_f64().unwrap() - 0.2).abs() < 1e-6);
+        assert!(body.get("reasoning").is_none());
+
+        let review = config::resolve_profile(&cfg, Some("review"), Some(0.0), None).unwrap();
+        let body = request_body(&review);
+        assert_eq!(body["temperature"], 0.0);
+        assert_eq!(body["reasoning"]["effort"], "high");
+
+        assert!(config::resolve_profile(&cfg, Some("missing"), None, None).is_err());
+        let bad_effort =
+            config::resolve_profile(&cfg, None, None, Some("max".to_string())).unwrap();
+        assert!(client().with_profile(&bad_effort).is_err());
+    }
+
+    #[test]
+    fn backs_off_exponentially_unless_retry_after_is_given() {
+        assert_eq!(retry_delay(0, None, 0), Duration::from_secs(1));
+        assert_eq!(retry_delay(1, None, 0), Duration::from_secs(2));
+        assert_eq!(retry_delay(2, None, 100), Duration::from_millis(4_100));
+        assert_eq!(retry_delay(2, Some("7"), 100), Duration::from_secs(7));
+        assert_eq!(retry_delay(0, Some("3600"), 0), Duration::from_secs(60));
+        assert_eq!(parse_max_retries(None), 3);
+        assert_eq!(parse_max_retries(Some("0")), 0);
+    }
+
+    #[test]
+    fn retries_only_transient_statuses() {
+        for code in [429, 500, 502, 503, 504] {
+            assert!(is_retryable_status(StatusCode::from_u16(code).unwrap()));
+        }
+        for code in [400, 401, 403, 404] {
+            assert!(!is_retryable_status(StatusCode::from_u16(code).unwrap()));
+        }
+    }
+
+    /// Serves the given raw responses in order, one per connection.
+    fn serve_responses(responses: Vec<&'static str>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!(
+            "http://{}/v1/chat/completions",
+            listener.local_addr().unwrap()
+        );
+        thread::spawn(move || {
+            for response in responses {
+                let Ok((mut stream, _)) = listener.accept() else {
+                    return;
+                };
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf);
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        endpoint
+    }
+
+    #[tokio::test]
+    async fn retries_503_and_returns_the_eventual_success() {
+        let endpoint = serve_responses(vec![
+            "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+        ]);
+        let http = reqwest::Client::new();
+        let response = send_with_retry(&endpoint, || http.post(&endpoint))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_auth_failures() {
+        let endpoint = serve_responses(vec![
+            "HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+        ]);
+        let http = reqwest::Client::new();
+        let response = send_with_retry(&endpoint, || http.post(&endpoint))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn parses_http_timeout_override() {
+        assert_eq!(parse_timeout_secs(None), 120);
+        assert_eq!(parse_timeout_secs(Some(" 30 ")), 30);
+        assert_eq!(parse_timeout_secs(Some("0")), 120);
+        assert_eq!(parse_timeout_secs(Some("abc")), 120);
+    }
+
+    // Reads the child's state from /proc.
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn cancelling_codex_run_kills_child_and_removes_output_file() {
+        let dir = std::env::temp_dir().join(format!(
+            "goldagent-codex-cancel-test-{}",
+            uuid::Uuid::new_v4()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pid_file = dir.join("pid");
+        let output_file = dir.join("last-message.txt");
+        std::fs::write(&output_file, "partial").unwrap();
+
+        // Stand-in for a long `codex exec`: records its pid, then hangs.
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c")
+            .arg(format!("echo $$ > '{}'; exec sleep 30", pid_file.display()));
+        let mut run = Box::pin(super::run_codex_process(
+            cmd,
+            &output_file,
+            Duration::from_secs(60),
+        ));
+        let pid = loop {
+            tokio::select! {
+                result = &mut run => panic!("codex stand-in finished early: {result:?}"),
+                _ = tokio::time::sleep(Duration::from_millis(20)) => {}
+            }
+            if let Some(pid) = std::fs::read_to_string(&pid_file)
+                .ok()
+                .and_then(|raw| raw.trim().parse::<u32>().ok())
+            {
+                break pid;
+            }
+        };
+        drop(run);
+
+        assert!(!output_file.exists());
+        let stat = format!("/proc/{pid}/stat");
+        let mut exited = false;
+        for _ in 0..100 {
+            // A killed child is gone or a zombie awaiting the reaper.
+            match std::fs::read_to_string(&stat) {
+                Err(_) => exited = true,
+                Ok(raw) => {
+                    exited = raw
+                        .rsplit(')')
+                        .next()
+                        .is_some_and(|rest| rest.trim_start().starts_with('Z'))
+                }
+            }
+            if exited {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(exited, "sleep stand-in (pid {pid}) survived cancellation");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn streaming_run_assembles_reply_from_deltas_and_records_final_usage() {
+        let body = "data: {\"choices\":[{\"delta\":{\"content\":\"第一段，\"}}]}\n\n\
+data: {\"choices\":[{\"delta\":{\"content\":\"第二段。\"}}]}\n\n\
+data: {\"choices\":[],\"usage\":{\"prompt_tokens\":12,\"completion_tokens\":5}}\n\n\
+data: [DONE]\n\n";
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        let endpoint = serve_responses(vec![Box::leak(response.into_boxed_str())]);
+        let base_url = endpoint.trim_end_matches("/chat/completions");
+        let paths = AgentPaths::from_root(std::env::temp_dir().join(format!(
+            "goldagent-stream-usage-test-{}",
+            uuid::Uuid::new_v4()
+        )));
+        let client = ProviderClient::build_api_backend(
+            "sk-test",
+            ConnectProvider::OpenAi,
+            "gpt-5.2".to_string(),
+            Some(&paths),
+            None,
+            Some(base_url),
+        )
+        .unwrap();
+
+        let mut deltas = Vec::new();
+        let reply = client
+            .chat_stream(&[ChatMessage::user("hi")], |delta| {
+                deltas.push(delta.to_string())
+            })
+            .await
+            .unwrap();
+        assert_eq!(deltas, ["第一段，", "第二段。"]);
+        assert_eq!(reply, "第一段，第二段。");
+
+        let stats = usage::load(&paths.usage_file).unwrap();
+        assert_eq!(stats.total.input_tokens, 12);
+        assert_eq!(stats.total.output_tokens, 5);
+        let _ = std::fs::remove_dir_all(&paths.root);
+    }
+
+    #[tokio::test]
+    async fn streamed_run_prints_what_the_buffered_run_prints() {
+        let body = "data: {\"choices\":[{\"delta\":{\"content\":\"Here is the answer:\\n\\n结论\"}}]}\n\n\
+data: {\"choices\":[{\"delta\":{\"content\":\"：可以。\\n\\nHope this helps!\"}}]}\n\n\
+data: [DONE]\n\n";
+        let streamed = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        let json = "{\"choices\":[{\"message\":{\"content\":\"Here is the answer:\\n\\n结论：可以。\\n\\nHope this helps!\"}}]}";
+        let buffered = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{json}",
+            json.len()
+        );
+        let endpoint = serve_responses(vec![
+            Box::leak(streamed.into_boxed_str()),
+            Box::leak(buffered.into_boxed_str()),
+        ]);
+        let client = ProviderClient::build_api_backend(
+            "sk-test",
+            ConnectProvider::OpenAi,
+            "gpt-5.2".to_string(),
+            None,
+            None,
+            Some(endpoint.trim_end_matches("/chat/completions")),
+        )
+        .unwrap();
+        let filter = WrapperFilter::from_config(&ChatConfig::default());
+        let messages = [ChatMessage::user("hi")];
+
+        let mut printer = filter.stream();
+        let mut printed = String::new();
+        let raw = client
+            .chat_stream(&messages, |delta| printed.push_str(&printer.push(delta)))
+            .await
+            .unwrap();
+        printed.push_str(&printer.finish());
+
+        let reply = filter.apply(&client.chat(&messages).await.unwrap());
+        assert_eq!(reply, "结论：可以。");
+        assert_eq!(filter.apply(&raw), reply);
+        assert_eq!(printed, format!("{reply}\n"));
+    }
+
+    #[tokio::test]
+    async fn stream_outlasting_the_timeout_is_not_cut_off_while_chunks_arrive() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!(
+            "http://{}/v1/chat/completions",
+            listener.local_addr().unwrap()
+        );
+        thread::spawn(move || {
+            let Ok((mut stream, _)) = listener.accept() else {
+                return;
+            };
+            let mut buf = [0u8; 4096];
+            let _ = stream.read(&mut buf);
+            let _ = stream.write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n",
+            );
+            for word in ["a", "b", "c", "d"] {
+                thread::sleep(Duration::from_millis(400));
+                let event =
+                    format!("data: {{\"choices\":[{{\"delta\":{{\"content\":\"{word}\"}}}}]}}\n\n");
+                let _ = stream.write_all(event.as_bytes());
+            }
+        });
+
+        // Four chunks 400ms apart take longer than the 1s timeout in total.
+        let http =
+            api_http_client(reqwest::header::HeaderMap::new(), Duration::from_secs(1)).unwrap();
+        let output = chat_stream_via_openai_compatible_api(
+            &http,
+            &endpoint,
+            "gpt-5.2",
+            &[ChatMessage::user("hi")],
+            0.7,
+            None,
+            &[],
+            None,
+            false,
+            None,
+            &mut |_| {},
+        )
+        .await
+        .unwrap();
+        assert_eq!(output.content, "abcd");
+    }
+
+    #[tokio::test]
+    async fn hung_endpoint_surfaces_timeout_message() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!(
+            "http://{}/v1/chat/completions",
+            listener.local_addr().unwrap()
+        );
+        thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf);
+                thread::sleep(Duration::from_secs(2));
+            }
+        });
+
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        let err = http.post(&endpoint).send().await.unwrap_err();
+        let message = request_error(err, &endpoint).to_string();
+        assert!(message.starts_with("请求超时"), "{message}");
+    }
+
+    #[test]
+    fn identity_guard_names_live_backend_and_distrusts_memory() {
+        let guard = identity_guard("智谱 / API(通用) / glm-5", None);
+        assert!(guard.contains("running on 智谱 / API(通用) / glm-5"));
+        assert!(guard.contains("Memory context may mention models"));
+        assert!(!guard.contains("{backend}"));
+
+        let custom = identity_guard("OpenAI / API / gpt-5", Some("只以 {backend} 身份回答。"));
+        assert_eq!(custom, "只以 OpenAI / API / gpt-5 身份回答。");
+    }
+
+    #[test]
+    fn streams_deltas_across_split_chunks() {
+        let mut parser = SseParser::default();
+        let mut output = ChatApiOutput::default();
+        let mut deltas = Vec::new();
+        let stream = "data: {\"choices\":[{\"delta\":{\"content\":\"你好\"}}]}\n\n\
+data: {\"choices\":[{\"delta\":{\"content\":\", world\"}}]}\n\n\
+data: {\"choices\":[],\"usage\":{\"prompt_tokens\":7,\"completion_tokens\":3}}\n\n\
+data: [DONE]\n\n"
+            .as_bytes();
+        // Split inside the multi-byte "你" to exercise byte buffering.
+        for chunk in [&stream[..40], &stream[40..]] {
+            for data in parser.push(chunk) {
+                apply_stream_event(&data, &mut output, &mut |delta: &str| {
+                    deltas.push(delta.to_string())
+                })
+                .unwrap();
+            }
+        }
+        assert_eq!(deltas, ["你好", ", world"]);
+        assert_eq!(output.content, "你好, world");
+        assert_eq!((output.input_tokens, output.output_tokens), (7, 3));
+    }
+
+    #[test]
+    fn parses_deepseek_provider_and_validates_its_key() {
+        let provider = parse_provider_name("DeepSeek").unwrap();
+        assert_eq!(provider, ConnectProvider::DeepSeek);
+        assert!(connect::validate_api_key(&provider, "sk-0123456789abcdef0123").is_ok());
+        assert!(connect::validate_api_key(&provider, "0123456789abcdef0123").is_err());
+        assert_eq!(
+            suggested_models(&provider),
+            ["deepseek-chat", "deepseek-reasoner"]
+        );
+    }
+
+    #[test]
+    fn malformed_config_refuses_to_build_a_client() {
+        let paths = AgentPaths::from_root(
+            std::env::temp_dir().join(format!("goldagent-budget-test-{}", uuid::Uuid::new_v4())),
+        );
+        std::fs::create_dir_all(&paths.root).unwrap();
+        std::fs::write(&paths.config_file, r#"{"budget": {"monthly_tokens": "#).unwrap();
+
+        let err = ProviderClient::from_paths(&paths, None).unwrap_err();
+        assert!(format!("{err:#}").contains("解析配置文件失败"), "{err:#}");
+        let _ = std::fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn model_completion_includes_cached_live_models() {
+        let paths = AgentPaths::from_root(
+            std::env::temp_dir().join(format!("goldagent-models-test-{}", uuid::Uuid::new_v4())),
+        );
+        std::fs::create_dir_all(&paths.root).unwrap();
+        let labels = |rest| {
+            model_hint_items(&paths, rest)
+                .into_iter()
+                .map(|item| item.label)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(labels("gpt-4.1-m"), ["gpt-4.1-m"]);
+
+        let body = r#"{"object": "list", "data": [{"id": "gpt-5.2"}, {"id": "gpt-4.1-mini"}, {"id": "gpt-4.1-mini"}]}"#;
+        let live = parse_models_response(body).unwrap();
+        assert_eq!(live, ["gpt-4.1-mini", "gpt-5.2"]);
+        connect::save_cached_models(&paths, &ConnectProvider::OpenAi, live).unwrap();
+
+        assert_eq!(labels("gpt-4.1-m"), ["gpt-4.1-mini"]);
+        assert!(!suggested_models(&ConnectProvider::OpenAi).contains(&"gpt-4.1-mini"));
+        assert_eq!(
+            labels("gpt-5.2")
+                .iter()
+                .filter(|label| *label == "gpt-5.2")
+                .count(),
+            1
+        );
+        assert!(connect::load_cached_models(&paths, &ConnectProvider::Anthropic).is_empty());
+
+        assert_eq!(
+            models_endpoint("https://api.anthropic.com/v1/messages"),
+            "https://api.anthropic.com/v1/models"
+        );
+        assert_eq!(
+            models_endpoint("http://localhost:11434/v1/chat/completions"),
+            "http://localhost:11434/v1/models"
+        );
+        let _ = std::fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn parses_embeddings_in_index_order() {
+        assert_eq!(
+            embeddings_endpoint("http://localhost:11434/v1/chat/completions"),
+            "http://localhost:11434/v1/embeddings"
+        );
+        let body = r#"{"data": [{"index": 1, "embedding": [0.0, 1.0]}, {"index": 0, "embedding": [1.0, 0.0]}]}"#;
+        let vectors = parse_embeddings_response(body, 2).unwrap();
+        assert_eq!(vectors, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+        assert!(parse_embeddings_response(body, 3).is_err());
+    }
+
+    #[test]
+    fn derives_openai_endpoint_from_base_url() {
+        let endpoint =
+            |base_url| api_endpoint_for_provider(&ConnectProvider::OpenAi, None, base_url).unwrap();
+        assert_eq!(endpoint(None), "https://api.openai.com/v1/chat/completions");
+        assert_eq!(
+            endpoint(Some("http://localhost:4000/v1/")),
+            "http://localhost:4000/v1/chat/completions"
+        );
+        assert_eq!(
+            endpoint(Some("https://gw.example.com/v1/chat/completions")),
+            "https://gw.example.com/v1/chat/completions"
+        );
+        let deepseek = api_endpoint_for_provider(&ConnectProvider::DeepSeek, None, None).unwrap();
+        assert_eq!(deepseek, "https://api.deepseek.com/v1/chat/completions");
+        let anthropic = api_endpoint_for_provider(
+            &ConnectProvider::Anthropic,
+            None,
+            Some("http://localhost:4000/v1"),
+        )
+        .unwrap();
+        assert_eq!(anthropic, "https://api.anthropic.com/v1/messages");
+    }
+
+    #[test]
+    fn no_backend_usable_when_nothing_configured() {
+        let cfg = ConnectConfig::default();
+        assert!(!backend_usable(&cfg, |_| None, false));
+        assert!(backend_usable(&cfg, |_| None, true));
+
+        let api_cfg = ConnectConfig {
+            mode: ConnectMode::OpenAIApi,
+            ..ConnectConfig::default()
+        };
+        assert!(!backend_usable(&api_cfg, |_| None, false));
+        assert!(backend_usable(
+            &api_cfg,
+            |name| (name == "OPENAI_API_KEY").then(|| "sk-test".to_string()),
+            false
+        ));
+    }
+
+    #[test]
+    fn keeps_messages_within_budget_untouched() {
+        let messages = vec![ChatMessage::system("sys"), ChatMessage::user("hello")];
+        let (fitted, warning) = fit_messages_to_budget(&messages, 1_000);
+        assert!(warning.is_none());
+        assert_eq!(fitted.len(), 2);
+        assert_eq!(fitted[1].content, "hello");
+    }
+
+    #[test]
+    fn truncates_oversized_user_message_with_warning() {
+        let huge = "x".repeat(40_000);
+        let messages = vec![
+            ChatMessage::system("memory context"),
+            ChatMessage::user("old question"),
+            ChatMessage::assistant("old answer"),
+            ChatMessage::user(huge),
+        ];
+        let (fitted, warning) = fit_messages_to_budget(&messages, 1_000);
+        let warning = warning.expect("oversized request should warn");
+        assert!(warning.contains("截断了本次输入内容"));
+        assert!(warning.contains("丢弃了"));
+        assert_eq!(fitted.first().map(|m| m.role.as_str()), Some("system"));
+        assert_eq!(fitted[0].content, "memory context");
+        assert_eq!(fitted.len(), 2);
+        assert!(estimate_messages_tokens(&fitted) <= 1_000);
+    }
+
+    #[test]
+    fn trims_system_portion_before_user_input() {
+        let messages = vec![
+            ChatMessage::system("m".repeat(8_000)),
+            ChatMessage::user("short question"),
+        ];
+        let (fitted, warning) = fit_messages_to_budget(&messages, 1_000);
+        assert!(warning.unwrap().contains("系统提示"));
+        assert_eq!(fitted[1].content, "short question");
+        assert!(estimate_tokens(&fitted[0].content) <= 250);
+    }
+
+    #[test]
+    fn keeps_system_messages_in_place_when_fitting() {
+        let messages = vec![
+            ChatMessage::system("memory context"),
+            ChatMessage::user("o".repeat(8_000)),
+            ChatMessage::assistant("old answer"),
+            ChatMessage::system("summary of earlier turns"),
+            ChatMessage::user("latest question"),
+        ];
+        let (fitted, warning) = fit_messages_to_budget(&messages, 1_000);
+        assert!(warning.unwrap().contains("丢弃了 2 条"));
+        let order = fitted
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            [
+                "memory context",
+                "summary of earlier turns",
+                "latest question"
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_zhipu_style_response_without_usage() {
+        let body = r#"{"id":"1","request_id":"abc","created":1,"choices":[{"index":0,"finish_reason":"stop","message":{"role":"assistant","content":"你好"}}]}"#;
+        let out = parse_chat_completion_response(body).expect("lenient parse should succeed");
+        assert_eq!(out.content, "你好");
+        assert_eq!(out.input_tokens, 0);
+        assert_eq!(out.output_tokens, 0);
+    }
+
+    #[test]
+    fn parses_delta_content_and_usage() {
+        let body = r#"{"choices":[{"delta":{"content":"hi"}}],"usage":{"prompt_tokens":3,"completion_tokens":1}}"#;
+        let out = parse_chat_completion_response(body).expect("delta parse should succeed");
+        assert_eq!(out.content, "hi");
+        assert_eq!(out.input_tokens, 3);
+        assert_eq!(out.output_tokens, 1);
+    }
+
+    #[test]
+    fn names_offending_field_on_parse_failure() {
+        let err = parse_chat_completion_response(r#"{"data":[]}"#)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("`choices`"));
+        let err = parse_chat_completion_response(r#"{"choices":[{"message":{"content":42}}]}"#)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("`choices[0].message.content`"));
+    }
 }
@@ -0,0 +1,184 @@
+use crate::config::AgentPaths;
+use crate::jobs::{self, Job};
+use crate::scheduler;
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Duration, Local, Timelike};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use uuid::Uuid;
+
+/// Oldest entries are dropped beyond this many reminders.
+const MAX_REMINDERS: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReminderStatus {
+    Pending,
+    Acknowledged,
+    Snoozed,
+}
+
+impl ReminderStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Pending => "待确认",
+            Self::Acknowledged => "已确认",
+            Self::Snoozed => "已推迟",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reminder {
+    pub id: String,
+    pub message: String,
+    pub fired_at: String,
+    pub status: ReminderStatus,
+    /// Follow-up fire time when snoozed.
+    #[serde(default)]
+    pub snoozed_until: Option<String>,
+    /// One-shot cron job that re-fires the reminder.
+    #[serde(default)]
+    pub followup_job_id: Option<String>,
+}
+
+pub fn load(paths: &AgentPaths) -> Result<Vec<Reminder>> {
+    let raw = fs::read_to_string(&paths.reminders_file).unwrap_or_else(|_| "[]".to_string());
+    serde_json::from_str(&raw).with_context(|| {
+        format!(
+            "Failed to parse reminders file {}",
+            paths.reminders_file.display()
+        )
+    })
+}
+
+fn save(paths: &AgentPaths, reminders: &[Reminder]) -> Result<()> {
+    let serialized = serde_json::to_string_pretty(reminders)?;
+    fs::write(&paths.reminders_file, serialized)?;
+    Ok(())
+}
+
+/// Records a reminder that just fired; it stays pending until acknowledged
+/// or snoozed.
+pub fn record_fired(paths: &AgentPaths, message: &str, now: DateTime<Local>) -> Result<Reminder> {
+    let mut reminders = load(paths)?;
+    let reminder = Reminder {
+        id: Uuid::new_v4().simple().to_string()[..8].to_string(),
+        message: message.to_string(),
+        fired_at: now.to_rfc3339(),
+        status: ReminderStatus::Pending,
+        snoozed_until: None,
+        followup_job_id: None,
+    };
+    reminders.push(reminder.clone());
+    if reminders.len() > MAX_REMINDERS {
+        let excess = reminders.len() - MAX_REMINDERS;
+        reminders.drain(..excess);
+    }
+    save(paths, &reminders)?;
+    Ok(reminder)
+}
+
+pub fn acknowledge(paths: &AgentPaths, id: &str) -> Result<Option<Reminder>> {
+    let mut reminders = load(paths)?;
+    let Some(reminder) = reminders.iter_mut().find(|r| r.id == id) else {
+        return Ok(None);
+    };
+    reminder.status = ReminderStatus::Acknowledged;
+    let updated = reminder.clone();
+    save(paths, &reminders)?;
+    Ok(Some(updated))
+}
+
+/// Re-schedules the reminder `minutes` from `now` as a one-shot cron job
+/// running `goldagent remind`.
+pub fn snooze(
+    paths: &AgentPaths,
+    id: &str,
+    minutes: u32,
+    now: DateTime<Local>,
+) -> Result<Option<(Reminder, Job)>> {
+    if minutes == 0 {
+        bail!("推迟时间至少 1 分钟");
+    }
+    let mut reminders = load(paths)?;
+    let Some(reminder) = reminders.iter_mut().find(|r| r.id == id) else {
+        return Ok(None);
+    };
+
+    let at = (now + Duration::minutes(i64::from(minutes)))
+        .with_second(0)
+        .and_then(|at| at.with_nanosecond(0))
+        .context("无法计算推迟时间")?;
+    let job = jobs::add_job(
+        paths,
+        jobs::NewJob {
+            schedule: jobs::one_shot_schedule(at),
+            command: scheduler::build_goldagent_remind_command(&reminder.message),
+            name: Some(format!("snooze-{}", reminder.id)),
+            ..jobs::NewJob::default()
+        },
+    )?;
+    reminder.status = ReminderStatus::Snoozed;
+    reminder.snoozed_until = Some(at.to_rfc3339());
+    reminder.followup_job_id = Some(job.id.clone());
+    let updated = reminder.clone();
+    save(paths, &reminders)?;
+    Ok(Some((updated, job)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReminderStatus, acknowledge, load, record_fired, snooze};
+    use crate::config::AgentPaths;
+    use crate::jobs;
+    use chrono::{Local, TimeZone};
+    use std::fs;
+    use uuid::Uuid;
+
+    fn temp_paths() -> AgentPaths {
+        let root =
+            std::env::temp_dir().join(format!("goldagent-reminders-test-{}", Uuid::new_v4()));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(&paths.root).unwrap();
+        paths
+    }
+
+    #[test]
+    fn snooze_creates_one_shot_followup_job() {
+        let paths = temp_paths();
+        let now = Local.with_ymd_and_hms(2026, 10, 18, 9, 30, 15).unwrap();
+        let reminder = record_fired(&paths, "喝水", now).unwrap();
+
+        let (snoozed, job) = snooze(&paths, &reminder.id, 10, now).unwrap().unwrap();
+        assert_eq!(snoozed.status, ReminderStatus::Snoozed);
+        assert_eq!(job.schedule, "at@2026-10-18 09:40");
+        assert_eq!(job.command, "goldagent remind \"喝水\"");
+        assert_eq!(snoozed.followup_job_id.as_deref(), Some(job.id.as_str()));
+
+        let fires = jobs::upcoming_fires(&jobs::load_jobs(&paths).unwrap(), 5, now).unwrap();
+        assert_eq!(fires.len(), 1, "one-shot job must fire exactly once");
+        assert_eq!(
+            fires[0].at,
+            Local.with_ymd_and_hms(2026, 10, 18, 9, 40, 0).unwrap()
+        );
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn acknowledges_pending_reminder() {
+        let paths = temp_paths();
+        let reminder = record_fired(&paths, "开会", Local::now()).unwrap();
+        assert_eq!(reminder.status, ReminderStatus::Pending);
+
+        acknowledge(&paths, &reminder.id).unwrap().unwrap();
+        assert_eq!(
+            load(&paths).unwrap()[0].status,
+            ReminderStatus::Acknowledged
+        );
+        assert!(acknowledge(&paths, "missing").unwrap().is_none());
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+}
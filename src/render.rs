@@ -0,0 +1,351 @@
+use crate::config::ChatConfig;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Below this many columns wrapping does more harm than good.
+const MIN_WRAP_COLUMNS: usize = 20;
+
+const CODE_STYLE: &str = "\x1b[48;5;236m";
+const FENCE_STYLE: &str = "\x1b[2m";
+const HEADER_STYLE: &str = "\x1b[1;96m";
+const BOLD_ON: &str = "\x1b[1m";
+const BOLD_OFF: &str = "\x1b[22m";
+const RESET: &str = "\x1b[0m";
+
+/// Set by the global `--no-color` flag.
+static NO_COLOR: AtomicBool = AtomicBool::new(false);
+
+pub fn disable_color() {
+    NO_COLOR.store(true, Ordering::Relaxed);
+}
+
+/// Whether answers get ANSI markdown styling: only when stdout is a terminal
+/// and neither `--no-color`, `GOLDAGENT_NO_COLOR` nor `NO_COLOR` is set.
+pub fn color_enabled() -> bool {
+    let env_disabled = ["GOLDAGENT_NO_COLOR", "NO_COLOR"]
+        .iter()
+        .any(|name| env::var(name).is_ok_and(|value| !value.is_empty()));
+    !NO_COLOR.load(Ordering::Relaxed) && !env_disabled && stdout_is_tty()
+}
+
+#[cfg(unix)]
+fn stdout_is_tty() -> bool {
+    unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
+#[cfg(not(unix))]
+fn stdout_is_tty() -> bool {
+    false
+}
+
+/// Layout of the `goldagent:` answer block, taken from `config.json`.
+#[derive(Debug, Clone)]
+pub struct BlockStyle {
+    pub prefix: String,
+    pub footer: Option<String>,
+    /// Target width for wrapping; `None` leaves long lines untouched.
+    pub width: Option<usize>,
+    /// Style code blocks, `**bold**` and `#` headers with ANSI codes.
+    pub markdown: bool,
+}
+
+impl BlockStyle {
+    pub fn from_config(chat: &ChatConfig) -> Self {
+        Self {
+            prefix: chat.assistant_prefix.clone(),
+            footer: chat.assistant_footer.clone().filter(|f| !f.is_empty()),
+            width: if chat.wrap_output {
+                terminal_width()
+            } else {
+                None
+            },
+            markdown: color_enabled(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineKind {
+    Prose,
+    Header,
+    Fence,
+    Code,
+}
+
+/// Formats an answer line by line, wrapping prose to the style width while
+/// leaving fenced code blocks untouched.
+#[derive(Debug)]
+pub struct BlockPrinter {
+    style: BlockStyle,
+    rows: usize,
+    in_code_block: bool,
+}
+
+impl BlockPrinter {
+    pub fn new(style: BlockStyle) -> Self {
+        Self {
+            style,
+            rows: 0,
+            in_code_block: false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows == 0
+    }
+
+    pub fn render_line(&mut self, line: &str) -> Vec<String> {
+        let indent = " ".repeat(display_width(&self.style.prefix));
+        let is_fence = line.trim_start().starts_with("```");
+        let (kind, text) = if is_fence {
+            (LineKind::Fence, line)
+        } else if self.in_code_block {
+            (LineKind::Code, line)
+        } else {
+            match header_text(line).filter(|_| self.style.markdown) {
+                Some(text) => (LineKind::Header, text),
+                None => (LineKind::Prose, line),
+            }
+        };
+        let bodies = match self.style.width {
+            Some(width) if matches!(kind, LineKind::Prose | LineKind::Header) => {
+                wrap_text(text, width.saturating_sub(indent.len()))
+            }
+            _ => vec![text.to_string()],
+        };
+        let rows = bodies
+            .into_iter()
+            .enumerate()
+            .map(|(idx, body)| {
+                let lead = if self.rows == 0 && idx == 0 {
+                    self.style.prefix.as_str()
+                } else {
+                    indent.as_str()
+                };
+                if self.style.markdown {
+                    format!("{lead}{}", paint(kind, &body))
+                } else {
+                    format!("{lead}{body}")
+                }
+            })
+            .collect::<Vec<_>>();
+        if is_fence {
+            self.in_code_block = !self.in_code_block;
+        }
+        self.rows += rows.len();
+        rows
+    }
+
+    /// Row printed for an answer without any content.
+    pub fn empty_row(&self) -> String {
+        self.style.prefix.trim_end().to_string()
+    }
+
+    pub fn footer(&self) -> Option<&str> {
+        self.style.footer.as_deref()
+    }
+}
+
+/// Text of a `#`…`######` header line, without the markers.
+fn header_text(line: &str) -> Option<&str> {
+    let hashes = line.len() - line.trim_start_matches('#').len();
+    let rest = &line[hashes..];
+    ((1..=6).contains(&hashes) && rest.starts_with(' ')).then(|| rest.trim())
+}
+
+fn paint(kind: LineKind, body: &str) -> String {
+    match kind {
+        LineKind::Prose => render_bold(body),
+        LineKind::Header => format!("{HEADER_STYLE}{body}{RESET}"),
+        LineKind::Fence => format!("{FENCE_STYLE}{body}{RESET}"),
+        LineKind::Code => format!("{CODE_STYLE}{body}{RESET}"),
+    }
+}
+
+/// Replaces matched `**` pairs with bold on/off codes; an unmatched trailing
+/// `**` is kept as typed.
+fn render_bold(text: &str) -> String {
+    let parts = text.split("**").collect::<Vec<_>>();
+    let paired = (parts.len() - 1) / 2 * 2;
+    let mut out = String::with_capacity(text.len());
+    for (idx, part) in parts.iter().enumerate() {
+        if idx > paired {
+            out.push_str("**");
+        } else if idx > 0 {
+            out.push_str(if idx % 2 == 1 { BOLD_ON } else { BOLD_OFF });
+        }
+        out.push_str(part);
+    }
+    out
+}
+
+/// Splits `line` into rows of at most `available` columns, breaking at the
+/// last space when possible, otherwise mid-word (CJK text).
+fn wrap_text(line: &str, available: usize) -> Vec<String> {
+    if available < MIN_WRAP_COLUMNS || display_width(line) <= available {
+        return vec![line.to_string()];
+    }
+
+    let mut rows = Vec::new();
+    let mut current = String::new();
+    let mut current_width = 0;
+    for ch in line.chars() {
+        let ch_width = char_width(ch);
+        if current_width + ch_width > available {
+            let carry = match current.rfind(' ').filter(|&at| at > 0) {
+                Some(at) => {
+                    let rest = current[at + 1..].to_string();
+                    current.truncate(at);
+                    rest
+                }
+                None => String::new(),
+            };
+            rows.push(current.trim_end().to_string());
+            current_width = display_width(&carry);
+            current = carry;
+        }
+        if current.is_empty() && ch == ' ' && !rows.is_empty() {
+            continue;
+        }
+        current.push(ch);
+        current_width += ch_width;
+    }
+    if !current.is_empty() {
+        rows.push(current);
+    }
+    rows
+}
+
+pub fn terminal_width() -> Option<usize> {
+    #[cfg(unix)]
+    {
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        let rc = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+        if rc == 0 && size.ws_col > 0 {
+            return Some(size.ws_col as usize);
+        }
+    }
+    env::var("COLUMNS")
+        .ok()?
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|&columns| columns > 0)
+}
+
+pub fn display_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// Terminal columns taken by `ch`: CJK and emoji are double width.
+fn char_width(ch: char) -> usize {
+    match ch as u32 {
+        0..=0x1F | 0x7F => 0,
+        0x1100..=0x115F
+        | 0x2E80..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockPrinter, BlockStyle, display_width};
+
+    /// Rows of a single-line answer printed under `prefix` at `width`.
+    fn wrap_with_prefix(line: &str, prefix: &str, width: usize) -> Vec<String> {
+        BlockPrinter::new(BlockStyle {
+            prefix: prefix.to_string(),
+            footer: None,
+            width: Some(width),
+            markdown: false,
+        })
+        .render_line(line)
+    }
+
+    fn markdown_printer() -> BlockPrinter {
+        BlockPrinter::new(BlockStyle {
+            prefix: "> ".to_string(),
+            footer: None,
+            width: None,
+            markdown: true,
+        })
+    }
+
+    #[test]
+    fn wraps_long_line_under_prefix() {
+        let line = "the quick brown fox jumps over the lazy dog and keeps running far away";
+        let rows = wrap_with_prefix(line, "goldagent: ", 40);
+
+        assert!(rows.len() > 1);
+        assert!(rows[0].starts_with("goldagent: the quick"));
+        for row in &rows {
+            assert!(display_width(row) <= 40, "{row:?}");
+        }
+        for row in &rows[1..] {
+            assert!(row.starts_with("           ") && !row[11..].starts_with(' '));
+        }
+        let joined = rows
+            .iter()
+            .map(|row| row.trim())
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(joined, format!("goldagent: {line}"));
+
+        let cjk = wrap_with_prefix(&"记忆".repeat(20), "| ", 30);
+        assert!(cjk.len() > 1 && cjk.iter().all(|row| display_width(row) <= 30));
+    }
+
+    #[test]
+    fn leaves_code_blocks_unwrapped() {
+        let mut printer = BlockPrinter::new(BlockStyle {
+            prefix: "goldagent: ".to_string(),
+            footer: None,
+            width: Some(40),
+            markdown: false,
+        });
+        let long = "let value = compute(alpha, beta, gamma, delta);";
+        assert_eq!(printer.render_line("```rust").len(), 1);
+        assert_eq!(printer.render_line(long).len(), 1);
+        assert_eq!(printer.render_line("```").len(), 1);
+        assert!(printer.render_line(long).len() > 1);
+    }
+
+    #[test]
+    fn styles_markdown_only_when_enabled() {
+        let mut printer = markdown_printer();
+        assert_eq!(
+            printer.render_line("## 总结"),
+            vec!["> \x1b[1;96m总结\x1b[0m"]
+        );
+        assert_eq!(
+            printer.render_line("use **cargo** and **fmt** **here"),
+            vec!["  use \x1b[1mcargo\x1b[22m and \x1b[1mfmt\x1b[22m **here"]
+        );
+        assert_eq!(printer.render_line("```sh"), vec!["  \x1b[2m```sh\x1b[0m"]);
+        assert_eq!(
+            printer.render_line("# **not** a header"),
+            vec!["  \x1b[48;5;236m# **not** a header\x1b[0m"]
+        );
+        printer.render_line("```");
+        assert_eq!(printer.render_line("#hashtag"), vec!["  #hashtag"]);
+
+        let mut plain = BlockPrinter::new(BlockStyle {
+            markdown: false,
+            ..markdown_printer().style
+        });
+        for line in ["## 总结", "use **cargo**", "```sh", "# code", "```"] {
+            assert_eq!(
+                plain.render_line(line)[0].trim_start_matches("> ").trim(),
+                line
+            );
+        }
+    }
+}
@@ -0,0 +1,603 @@
+use crate::config::ChatConfig;
+use crate::provider::ChatMessage;
+use anyhow::{Context, Result, anyhow};
+use clap::ValueEnum;
+use regex::Regex;
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputValidation {
+    Json,
+    /// JSON that also has to satisfy a `run --json-schema` schema.
+    Schema(Arc<JsonSchema>),
+}
+
+/// Errors listed when a response fails its schema; the rest are counted.
+const MAX_SCHEMA_ERRORS: usize = 5;
+
+/// A compiled `run --json-schema` file.
+pub struct JsonSchema {
+    schema: Value,
+    validator: jsonschema::Validator,
+}
+
+impl JsonSchema {
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("读取 JSON Schema 失败：{}", path.display()))?;
+        let schema = serde_json::from_str(&raw)
+            .with_context(|| format!("JSON Schema 不是合法的 JSON：{}", path.display()))?;
+        Self::new(schema)
+    }
+
+    pub fn new(schema: Value) -> Result<Self> {
+        let validator = jsonschema::validator_for(&schema)
+            .map_err(|err| anyhow!("无效的 JSON Schema：{err}"))?;
+        Ok(Self { schema, validator })
+    }
+
+    pub fn schema(&self) -> &Value {
+        &self.schema
+    }
+
+    fn validate(&self, instance: &Value) -> Result<(), String> {
+        let errors = self
+            .validator
+            .iter_errors(instance)
+            .map(|err| {
+                let path = err.instance_path.to_string();
+                if path.is_empty() {
+                    err.to_string()
+                } else {
+                    format!("{path}: {err}")
+                }
+            })
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            return Ok(());
+        }
+        let mut message = errors
+            .iter()
+            .take(MAX_SCHEMA_ERRORS)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("；");
+        if errors.len() > MAX_SCHEMA_ERRORS {
+            message.push_str(&format!("（另有 {} 处）", errors.len() - MAX_SCHEMA_ERRORS));
+        }
+        Err(format!("不符合 JSON Schema：{message}"))
+    }
+}
+
+impl fmt::Debug for JsonSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonSchema")
+            .field("schema", &self.schema)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for JsonSchema {
+    fn eq(&self, other: &Self) -> bool {
+        self.schema == other.schema
+    }
+}
+
+impl Eq for JsonSchema {}
+
+/// Output format requested via `--format` on `run`/`chat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ResponseFormat {
+    Json,
+    Markdown,
+    Plain,
+}
+
+/// Per-request output constraints (`--max-words`, `--format`,
+/// `--json-schema`) that are turned into a system-prompt instruction.
+#[derive(Debug, Clone, Default)]
+pub struct ResponseConstraints {
+    pub max_words: Option<usize>,
+    pub format: Option<ResponseFormat>,
+    pub schema: Option<Arc<JsonSchema>>,
+    /// The schema goes out in the provider's structured-output field, so the
+    /// prompt only asks for JSON instead of repeating it.
+    pub native_schema: bool,
+}
+
+impl ResponseConstraints {
+    pub fn instruction(&self) -> Option<String> {
+        let mut lines = Vec::new();
+        if let Some(max_words) = self.max_words {
+            lines.push(format!(
+                "- Keep the answer under {max_words} words (for Chinese, under {max_words} characters)."
+            ));
+        }
+        match (&self.schema, self.format) {
+            (Some(schema), _) if !self.native_schema => lines.push(format!(
+                "- Respond with a single JSON value only, no prose and no code fences, that validates against this JSON Schema:\n{}",
+                schema.schema()
+            )),
+            (Some(_), _) | (None, Some(ResponseFormat::Json)) => lines.push(
+                "- Respond with a single valid JSON value only: no prose, no code fences."
+                    .to_string(),
+            ),
+            (None, Some(ResponseFormat::Markdown)) => {
+                lines.push("- Format the answer as Markdown.".to_string())
+            }
+            (None, Some(ResponseFormat::Plain)) => {
+                lines.push("- Respond in plain text without Markdown syntax.".to_string())
+            }
+            (None, None) => {}
+        }
+        if lines.is_empty() {
+            return None;
+        }
+        Some(format!("Response constraints:\n{}", lines.join("\n")))
+    }
+
+    /// Appends the constraint instruction, if any, to a system prompt.
+    pub fn apply_to_system(&self, system: String) -> String {
+        match self.instruction() {
+            Some(instruction) => format!("{system}\n\n{instruction}"),
+            None => system,
+        }
+    }
+
+    /// JSON output, and a schema when given, is validated and repaired once;
+    /// other formats are not machine-checkable.
+    pub fn validation(&self) -> Option<OutputValidation> {
+        if let Some(schema) = &self.schema {
+            return Some(OutputValidation::Schema(schema.clone()));
+        }
+        matches!(self.format, Some(ResponseFormat::Json)).then_some(OutputValidation::Json)
+    }
+}
+
+/// Error returned by [`run_with_self_correction`] when the output still
+/// fails validation after every correction.
+#[derive(Debug)]
+pub struct ValidationFailed {
+    pub corrections: u8,
+    pub reason: String,
+}
+
+impl fmt::Display for ValidationFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "输出在 {} 次自我修正后仍未通过校验：{}",
+            self.corrections, self.reason
+        )
+    }
+}
+
+impl std::error::Error for ValidationFailed {}
+
+/// Calls the model and, while the output fails `validation`, feeds the error
+/// back as a follow-up turn up to `max_corrections` times.
+pub async fn run_with_self_correction<F, Fut>(
+    mut messages: Vec<ChatMessage>,
+    validation: Option<OutputValidation>,
+    max_corrections: u8,
+    mut chat: F,
+) -> Result<String>
+where
+    F: FnMut(Vec<ChatMessage>) -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    let mut attempt = 0u8;
+    loop {
+        let response = chat(messages.clone()).await?;
+        let Some(validation) = &validation else {
+            return Ok(response);
+        };
+        let err = match validate_output(validation, &response) {
+            Ok(()) => return Ok(response),
+            Err(err) => err,
+        };
+        if attempt >= max_corrections {
+            if max_corrections == 0 {
+                return Ok(response);
+            }
+            return Err(ValidationFailed {
+                corrections: max_corrections,
+                reason: err,
+            }
+            .into());
+        }
+        attempt += 1;
+        messages.push(ChatMessage::assistant(response));
+        messages.push(ChatMessage::user(format!(
+            "上一次输出未通过校验：{err}\n请修正后重新输出完整结果，不要附加解释。"
+        )));
+    }
+}
+
+fn validate_output(validation: &OutputValidation, output: &str) -> Result<(), String> {
+    let value = serde_json::from_str::<Value>(strip_code_fence(output))
+        .map_err(|err| format!("不是合法的 JSON（{err}）"))?;
+    match validation {
+        OutputValidation::Json => Ok(()),
+        OutputValidation::Schema(schema) => schema.validate(&value),
+    }
+}
+
+fn strip_code_fence(output: &str) -> &str {
+    let trimmed = output.trim();
+    let Some(rest) = trimmed.strip_prefix("```") else {
+        return trimmed;
+    };
+    let body = rest.split_once('\n').map(|(_, body)| body).unwrap_or("");
+    body.strip_suffix("```").unwrap_or(body).trim()
+}
+
+/// Fence languages that mark a whole answer as wrapped rather than as code.
+const WRAPPER_FENCE_LANGS: &[&str] = &["", "text", "txt", "markdown", "md", "plain"];
+
+/// Post-processing that removes wrapper lines some backends (notably Codex)
+/// put around an answer, plus a single outer fence around the whole answer.
+#[derive(Debug, Clone, Default)]
+pub struct WrapperFilter {
+    enabled: bool,
+    patterns: Vec<Regex>,
+}
+
+impl WrapperFilter {
+    /// Builds the filter from `chat.strip_wrappers`/`chat.wrapper_patterns`;
+    /// invalid patterns are reported and skipped.
+    pub fn from_config(config: &ChatConfig) -> Self {
+        let patterns = config
+            .wrapper_patterns
+            .iter()
+            .filter_map(|pattern| match Regex::new(pattern) {
+                Ok(regex) => Some(regex),
+                Err(err) => {
+                    eprintln!("警告：忽略无效的 chat.wrapper_patterns 条目 `{pattern}`：{err}");
+                    None
+                }
+            })
+            .collect();
+        Self {
+            enabled: config.strip_wrappers,
+            patterns,
+        }
+    }
+
+    pub fn apply(&self, content: &str) -> String {
+        if !self.enabled {
+            return content.to_string();
+        }
+        let mut lines = content.trim().lines().collect::<Vec<_>>();
+        while lines.first().is_some_and(|line| self.is_wrapper_line(line)) {
+            lines.remove(0);
+            trim_blank_edges(&mut lines);
+        }
+        while lines.last().is_some_and(|line| self.is_wrapper_line(line)) {
+            lines.pop();
+            trim_blank_edges(&mut lines);
+        }
+        match unwrap_outer_fence(&lines) {
+            Some(inner) => inner.join("\n").trim().to_string(),
+            None => lines.join("\n"),
+        }
+    }
+
+    fn is_wrapper_line(&self, line: &str) -> bool {
+        let line = line.trim();
+        !line.is_empty() && self.patterns.iter().any(|regex| regex.is_match(line))
+    }
+
+    /// Starts filtering a streamed reply; see [`WrapperStream`].
+    pub fn stream(&self) -> WrapperStream<'_> {
+        WrapperStream {
+            filter: self,
+            raw: String::new(),
+            partial: String::new(),
+            held: Vec::new(),
+            emitted: String::new(),
+            started: false,
+            skipped_wrapper: false,
+            hold_all: false,
+        }
+    }
+}
+
+/// [`WrapperFilter::apply`] for a reply that arrives in deltas. Everything
+/// returned by [`push`](Self::push) followed by [`finish`](Self::finish) is
+/// exactly `apply` on the whole reply plus a newline, i.e. what a buffered
+/// `println!` prints. Complete lines are released once no later text can
+/// strip them: the last content line and any wrapper or blank lines after it
+/// wait for more content, and a reply opening with a fence that may wrap the
+/// whole answer is held until the end.
+pub struct WrapperStream<'a> {
+    filter: &'a WrapperFilter,
+    raw: String,
+    partial: String,
+    held: Vec<String>,
+    emitted: String,
+    started: bool,
+    skipped_wrapper: bool,
+    hold_all: bool,
+}
+
+impl WrapperStream<'_> {
+    /// Feeds one delta and returns the text that can be printed now.
+    pub fn push(&mut self, delta: &str) -> String {
+        self.raw.push_str(delta);
+        if !self.filter.enabled {
+            return delta.to_string();
+        }
+        self.partial.push_str(delta);
+        let mut out = String::new();
+        while let Some(pos) = self.partial.find('\n') {
+            let line = self.partial[..pos].trim_end_matches('\r').to_string();
+            self.partial.drain(..=pos);
+            self.take_line(line, &mut out);
+        }
+        self.emitted.push_str(&out);
+        out
+    }
+
+    fn take_line(&mut self, line: String, out: &mut String) {
+        let skippable = line.trim().is_empty() || self.filter.is_wrapper_line(&line);
+        if !self.started {
+            // Leading blank and wrapper lines are always stripped; the
+            // reply's leading whitespace only when no wrapper came first.
+            if skippable {
+                self.skipped_wrapper |= !line.trim().is_empty();
+                return;
+            }
+            self.started = true;
+            self.hold_all = opens_wrapper_fence(&line);
+            if self.skipped_wrapper {
+                self.held.push(line);
+            } else {
+                self.held.push(line.trim_start().to_string());
+            }
+            return;
+        }
+        if !skippable && !self.hold_all {
+            for held in self.held.drain(..) {
+                out.push_str(&held);
+                out.push('\n');
+            }
+        }
+        self.held.push(line);
+    }
+
+    /// Returns the rest of the filtered reply once the stream has ended.
+    pub fn finish(self) -> String {
+        if !self.filter.enabled {
+            return "\n".to_string();
+        }
+        let filtered = self.filter.apply(&self.raw);
+        let rest = filtered
+            .strip_prefix(self.emitted.as_str())
+            .unwrap_or_default();
+        format!("{rest}\n")
+    }
+}
+
+/// Whether `line` opens a fence [`unwrap_outer_fence`] may treat as a wrapper.
+fn opens_wrapper_fence(line: &str) -> bool {
+    let line = line.trim();
+    let ticks = line.len() - line.trim_start_matches('`').len();
+    ticks >= 3 && WRAPPER_FENCE_LANGS.contains(&line[ticks..].trim().to_ascii_lowercase().as_str())
+}
+
+fn trim_blank_edges(lines: &mut Vec<&str>) {
+    while lines.first().is_some_and(|line| line.trim().is_empty()) {
+        lines.remove(0);
+    }
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+}
+
+/// Returns the body of a fence that encloses the whole answer. Inner blocks
+/// must open with a language tag and close with a bare fence; a bare fence at
+/// the top level means the outer block ends early, so nothing is unwrapped.
+fn unwrap_outer_fence<'a>(lines: &[&'a str]) -> Option<Vec<&'a str>> {
+    let (first, rest) = lines.split_first()?;
+    let (last, body) = rest.split_last()?;
+    let first = first.trim();
+    let ticks = first.len() - first.trim_start_matches('`').len();
+    if ticks < 3 || last.trim() != &first[..ticks] {
+        return None;
+    }
+    let lang = first[ticks..].trim().to_ascii_lowercase();
+    if !WRAPPER_FENCE_LANGS.contains(&lang.as_str()) {
+        return None;
+    }
+    if ticks == 3 {
+        let mut inside = false;
+        for line in body {
+            let line = line.trim();
+            let Some(info) = line.strip_prefix("```") else {
+                continue;
+            };
+            match (inside, info.trim().is_empty()) {
+                (false, false) => inside = true,
+                (true, true) => inside = false,
+                (false, true) => return None,
+                (true, false) => {}
+            }
+        }
+        if inside {
+            return None;
+        }
+    }
+    Some(body.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        JsonSchema, ResponseConstraints, ResponseFormat, ValidationFailed, WrapperFilter,
+        run_with_self_correction,
+    };
+    use crate::config::ChatConfig;
+    use crate::provider::ChatMessage;
+    use serde_json::json;
+    use std::cell::Cell;
+    use std::sync::Arc;
+
+    #[test]
+    fn json_format_adds_instruction() {
+        let constraints = ResponseConstraints {
+            max_words: Some(100),
+            format: Some(ResponseFormat::Json),
+            ..ResponseConstraints::default()
+        };
+        let system = constraints.apply_to_system("You are GoldAgent.".to_string());
+        assert!(system.starts_with("You are GoldAgent."));
+        assert!(system.contains("under 100 words"));
+        assert!(system.contains("valid JSON"));
+        assert!(ResponseConstraints::default().instruction().is_none());
+    }
+
+    #[tokio::test]
+    async fn unparseable_json_triggers_one_repair_attempt() {
+        let constraints = ResponseConstraints {
+            max_words: None,
+            format: Some(ResponseFormat::Json),
+            ..ResponseConstraints::default()
+        };
+        let calls = Cell::new(0);
+        let output = run_with_self_correction(
+            vec![ChatMessage::user("list two colors")],
+            constraints.validation(),
+            1,
+            |messages| {
+                calls.set(calls.get() + 1);
+                let attempt = calls.get();
+                async move {
+                    if attempt == 1 {
+                        Ok("Sure! red and blue".to_string())
+                    } else {
+                        assert_eq!(messages.len(), 3);
+                        Ok("[\"red\", \"blue\"]".to_string())
+                    }
+                }
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(calls.get(), 2);
+        assert_eq!(output, "[\"red\", \"blue\"]");
+    }
+
+    #[tokio::test]
+    async fn output_still_invalid_after_repair_is_a_validation_failure() {
+        let constraints = ResponseConstraints {
+            format: Some(ResponseFormat::Json),
+            ..ResponseConstraints::default()
+        };
+        let err = run_with_self_correction(
+            vec![ChatMessage::user("list two colors")],
+            constraints.validation(),
+            1,
+            |_| async { Ok("red and blue".to_string()) },
+        )
+        .await
+        .unwrap_err();
+        assert!(err.is::<ValidationFailed>(), "{err}");
+    }
+
+    #[tokio::test]
+    async fn schema_violation_triggers_one_retry() {
+        let schema = JsonSchema::new(json!({
+            "type": "object",
+            "required": ["name", "age"],
+            "properties": {"name": {"type": "string"}, "age": {"type": "integer"}}
+        }))
+        .unwrap();
+        let mut constraints = ResponseConstraints {
+            schema: Some(Arc::new(schema)),
+            ..ResponseConstraints::default()
+        };
+        let system = constraints.apply_to_system("You are GoldAgent.".to_string());
+        assert!(system.contains("validates against this JSON Schema"));
+        assert!(system.contains("\"required\":[\"name\",\"age\"]"));
+        constraints.native_schema = true;
+        let native = constraints.apply_to_system(String::new());
+        assert!(native.contains("valid JSON"));
+        assert!(!native.contains("required"));
+
+        let calls = Cell::new(0);
+        let output = run_with_self_correction(
+            vec![ChatMessage::user("extract: Ada, 36")],
+            constraints.validation(),
+            1,
+            |messages| {
+                calls.set(calls.get() + 1);
+                let attempt = calls.get();
+                async move {
+                    if attempt == 1 {
+                        Ok(r#"{"name": "Ada", "age": "36"}"#.to_string())
+                    } else {
+                        let feedback = &messages.last().unwrap().content;
+                        assert!(feedback.contains("不符合 JSON Schema"), "{feedback}");
+                        assert!(feedback.contains("/age"), "{feedback}");
+                        Ok(r#"{"name": "Ada", "age": 36}"#.to_string())
+                    }
+                }
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(calls.get(), 2);
+        assert_eq!(output, r#"{"name": "Ada", "age": 36}"#);
+    }
+
+    #[test]
+    fn outer_text_fence_is_unwrapped_and_inner_code_kept() {
+        let filter = WrapperFilter::from_config(&ChatConfig::default());
+        let raw = "Here is the answer:\n\n```text\n步骤如下：\n\n```rust\nfn main() {}\n```\n\n完成。\n```\n";
+        assert_eq!(
+            filter.apply(raw),
+            "步骤如下：\n\n```rust\nfn main() {}\n```\n\n完成。"
+        );
+
+        // Two separate blocks are not one wrapper; a lone code block is code.
+        let split = "```text\na\n```\nb\n```text\nc\n```";
+        assert_eq!(filter.apply(split), split);
+        let code = "```rust\nfn main() {}\n```";
+        assert_eq!(filter.apply(code), code);
+    }
+
+    #[test]
+    fn streamed_output_matches_the_buffered_filter() {
+        let replies = [
+            "Here is the answer:\n\n  第一行  \n第二行\n\nHope this helps!\n",
+            "```text\n步骤如下：\n```rust\nfn main() {}\n```\n完成。\n```",
+            "结论：可以。\n以下是补充：\n细节。   ",
+            "\n\n",
+        ];
+        for enabled in [true, false] {
+            let config = ChatConfig {
+                strip_wrappers: enabled,
+                ..ChatConfig::default()
+            };
+            let filter = WrapperFilter::from_config(&config);
+            for reply in replies {
+                for size in [1, 3, 7, reply.len().max(1)] {
+                    let chars = reply.chars().collect::<Vec<_>>();
+                    let mut stream = filter.stream();
+                    let mut printed = String::new();
+                    for chunk in chars.chunks(size) {
+                        printed.push_str(&stream.push(&chunk.iter().collect::<String>()));
+                    }
+                    printed.push_str(&stream.finish());
+                    assert_eq!(printed, format!("{}\n", filter.apply(reply)), "{reply:?}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use crate::config::AgentPaths;
+use crate::fsutil;
+use crate::shell;
+use chrono::Local;
+use serde::Serialize;
+use std::env;
+use std::path::PathBuf;
+
+/// One job or hook execution in `logs/scheduler.jsonl`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunRecord<'a> {
+    pub ts: String,
+    pub kind: &'static str,
+    pub id: &'a str,
+    pub name: &'a str,
+    pub status: &'static str,
+    pub exit_code: Option<i32>,
+    /// 1-based attempt within the run's retries.
+    pub attempt: u8,
+    pub error: Option<String>,
+}
+
+impl<'a> RunRecord<'a> {
+    /// Record of an attempt that ended with `result` (the exit code, if a
+    /// command ran); the exit code of a failed command is recovered from
+    /// [`shell::CommandFailed`].
+    pub fn new(
+        kind: &'static str,
+        id: &'a str,
+        name: &'a str,
+        attempt: u8,
+        result: Result<Option<i32>, &anyhow::Error>,
+    ) -> Self {
+        let (status, exit_code, error) = match result {
+            Ok(exit_code) => ("success", exit_code, None),
+            Err(err) => (
+                "failed",
+                err.downcast_ref::<shell::CommandFailed>()
+                    .map(|failed| failed.exit_code),
+                Some(err.to_string()),
+            ),
+        };
+        Self {
+            ts: Local::now().to_rfc3339(),
+            kind,
+            id,
+            name,
+            status,
+            exit_code,
+            attempt,
+            error,
+        }
+    }
+}
+
+/// Opt-in (`GOLDAGENT_LOG_JSON=1`) machine-readable stream of scheduler runs,
+/// kept alongside the short-term memory entries.
+pub fn enabled() -> bool {
+    env::var("GOLDAGENT_LOG_JSON")
+        .map(|value| matches!(value.trim(), "1" | "true" | "yes" | "on"))
+        .unwrap_or(false)
+}
+
+pub fn log_path(paths: &AgentPaths) -> PathBuf {
+    paths.logs_dir.join("scheduler.jsonl")
+}
+
+/// Appends `record` as one JSON line when [`enabled`]; failures to write are
+/// ignored like the memory log's.
+pub fn append(paths: &AgentPaths, record: &RunRecord) {
+    if enabled() {
+        let _ = append_line(paths, record);
+    }
+}
+
+fn append_line(paths: &AgentPaths, record: &RunRecord) -> anyhow::Result<()> {
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    fsutil::append_locked(&log_path(paths), line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RunRecord, append_line, log_path};
+    use crate::config::AgentPaths;
+    use crate::shell;
+    use serde_json::Value;
+    use std::fs;
+
+    #[test]
+    fn writes_one_json_line_per_attempt_with_exit_code() {
+        let root =
+            std::env::temp_dir().join(format!("goldagent-run-log-test-{}", uuid::Uuid::new_v4()));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(&paths.logs_dir).unwrap();
+
+        let failed = anyhow::Error::new(shell::CommandFailed {
+            exit_code: 3,
+            stdout: String::new(),
+            stderr: "boom".to_string(),
+        });
+        append_line(
+            &paths,
+            &RunRecord::new("cron", "j1", "nightly", 1, Err(&failed)),
+        )
+        .unwrap();
+        append_line(
+            &paths,
+            &RunRecord::new("cron", "j1", "nightly", 2, Ok(Some(0))),
+        )
+        .unwrap();
+        let other = anyhow::anyhow!("rules file missing");
+        append_line(
+            &paths,
+            &RunRecord::new("hook", "h1", "repo", 1, Err(&other)),
+        )
+        .unwrap();
+
+        let lines = fs::read_to_string(log_path(&paths))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["kind"], "cron");
+        assert_eq!(lines[0]["status"], "failed");
+        assert_eq!(lines[0]["exit_code"], 3);
+        assert_eq!(lines[0]["attempt"], 1);
+        assert!(lines[0]["error"].as_str().unwrap().contains("boom"));
+        assert_eq!(lines[1]["status"], "success");
+        assert_eq!(lines[1]["exit_code"], 0);
+        assert!(lines[1]["error"].is_null());
+        assert_eq!(lines[2]["kind"], "hook");
+        assert!(lines[2]["exit_code"].is_null());
+        assert!(lines[2]["ts"].as_str().is_some());
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+}
@@ -1,31 +1,89 @@
-use crate::config::AgentPaths;
+use crate::config::{self, AgentPaths, SchedulerConfig};
+use crate::escalation;
+use crate::fsutil;
 use crate::hooks::{self, Hook, HookSource};
 use crate::jobs::{self, Job};
 use crate::memory;
+use crate::metrics::{self, SchedulerMetrics};
 use crate::provider::{ChatMessage, ProviderClient};
+use crate::redact;
+use crate::run_log::{self, RunRecord};
 use crate::shell;
-use anyhow::Result;
-use chrono::Local;
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local};
 use cron::Schedule;
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsStr;
 use std::fs;
+use std::future::Future;
 use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Instant;
 use tokio::signal;
+use tokio::sync::Mutex;
 use tokio::time::{Duration, sleep};
+use uuid::Uuid;
 
-pub async fn serve(paths: AgentPaths) -> Result<()> {
-    let Some(_pid_guard) = SchedulerPidGuard::acquire(&paths)? else {
-        if let Some(pid) = running_pid(&paths)? {
-            println!("GoldAgent scheduler already running (pid={pid}).");
-        } else {
-            println!("GoldAgent scheduler already running.");
-        }
-        return Ok(());
+/// Starts the scheduler. Missing shell/git/p4 binaries needed by the loaded
+/// jobs and hooks are reported up front; with `strict` they abort startup.
+/// With `once` the due jobs run and each hook is polled a single time, then
+/// it returns; no pid file is taken, so it can run beside a daemon.
+pub async fn serve(paths: AgentPaths, strict: bool, once: bool) -> Result<()> {
+    let _pid_guard = if once {
+        None
+    } else {
+        let Some(guard) = SchedulerPidGuard::acquire(&paths)? else {
+            if let Some(pid) = running_pid(&paths)? {
+                println!("GoldAgent scheduler already running (pid={pid}).");
+            } else {
+                println!("GoldAgent scheduler already running.");
+            }
+            return Ok(());
+        };
+        Some(guard)
     };
 
     let jobs = jobs::load_jobs(&paths)?;
     let hooks = hooks::load_hooks(&paths)?;
+    let scheduler_config = config::load(&paths).unwrap_or_default().scheduler;
+    let hook_jitter_percent = scheduler_config.hook_jitter_percent;
+    let metrics = Arc::new(SchedulerMetrics::default());
+    if !once && let Some(addr) = scheduler_config.status_addr.as_deref() {
+        match metrics::spawn_server(addr, paths.clone(), metrics.clone()) {
+            Ok(()) => println!("Status server listening on http://{addr}/metrics"),
+            Err(err) => eprintln!("Status server disabled: {err:#}"),
+        }
+    }
+    let gate = NetworkGate::new(scheduler_config);
+    let shutdown = Shutdown::default();
+
+    let path_var = env::var_os("PATH").unwrap_or_default();
+    let missing = required_binaries(&jobs, &hooks, &shell::resolve_shell())
+        .into_iter()
+        .filter(|binary| !binary_available(&binary.program, &path_var))
+        .collect::<Vec<_>>();
+    for binary in &missing {
+        eprintln!(
+            "Warning: `{}` not found; needed by {}",
+            binary.program,
+            binary.used_by.join(", ")
+        );
+    }
+    if strict && !missing.is_empty() {
+        let programs = missing
+            .iter()
+            .map(|binary| binary.program.as_str())
+            .collect::<Vec<_>>();
+        bail!(
+            "Refusing to start scheduler: missing required binaries ({}). Install them or fix PATH, or start without --strict.",
+            programs.join(", ")
+        );
+    }
 
     if jobs.is_empty() && hooks.is_empty() {
         println!(
@@ -39,10 +97,24 @@ pub async fn serve(paths: AgentPaths) -> Result<()> {
         );
     }
 
+    if once {
+        run_once(&paths, jobs, hooks, &gate, &metrics, Local::now()).await;
+        return Ok(());
+    }
+
     for job in jobs.into_iter().filter(|j| j.enabled) {
         let paths_clone = paths.clone();
+        let gate = gate.clone();
+        let metrics = metrics.clone();
+        let shutdown = shutdown.clone();
         tokio::spawn(async move {
-            if let Err(err) = run_job_loop(paths_clone, job).await {
+            if let Some(_running) = shutdown.begin() {
+                if run_missed_one_shot(&paths_clone, &job, &gate, &metrics).await {
+                    return;
+                }
+                run_on_start(&paths_clone, &job, &gate, &metrics).await;
+            }
+            if let Err(err) = run_job_loop(paths_clone, job, gate, metrics, shutdown).await {
                 eprintln!("Scheduler task exited with error: {err}");
             }
         });
@@ -50,19 +122,111 @@ pub async fn serve(paths: AgentPaths) -> Result<()> {
 
     for hook in hooks.into_iter().filter(|h| h.enabled) {
         let paths_clone = paths.clone();
+        let gate = gate.clone();
+        let metrics = metrics.clone();
+        let shutdown = shutdown.clone();
         tokio::spawn(async move {
-            if let Err(err) = run_hook_loop(paths_clone, hook).await {
+            if let Err(err) = run_hook_loop(
+                paths_clone,
+                hook,
+                gate,
+                metrics,
+                hook_jitter_percent,
+                shutdown,
+            )
+            .await
+            {
                 eprintln!("Hook watcher exited with error: {err}");
             }
         });
     }
 
     println!("GoldAgent scheduler is running. Press Ctrl+C to stop.");
-    signal::ctrl_c().await?;
+    stop_signal().await?;
+    shutdown.stop();
+    let running = shutdown.running();
+    if running > 0 {
+        println!(
+            "等待 {running} 个任务完成...（最长 {}s，再按 Ctrl+C 立即退出）",
+            SHUTDOWN_GRACE.as_secs()
+        );
+        let finished = tokio::select! {
+            finished = shutdown.drain(SHUTDOWN_GRACE) => finished,
+            _ = stop_signal() => false,
+        };
+        if !finished {
+            eprintln!("Exiting with {} task(s) still running.", shutdown.running());
+        }
+    }
     println!("GoldAgent scheduler stopped.");
     Ok(())
 }
 
+/// Resolves on Ctrl+C or, on Unix, on the SIGTERM that `daemon stop` sends.
+async fn stop_signal() -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let mut terminate = signal::unix::signal(signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            result = signal::ctrl_c() => result,
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    signal::ctrl_c().await
+}
+
+/// How long Ctrl+C or SIGTERM waits for running jobs and hook commands to
+/// finish.
+pub(crate) const SHUTDOWN_GRACE: Duration = Duration::from_secs(30);
+
+/// Tracks job and hook executions in progress. Once stopped, no new
+/// execution starts and the loops exit at their next turn.
+#[derive(Clone, Default)]
+struct Shutdown {
+    stopping: Arc<AtomicBool>,
+    running: Arc<AtomicUsize>,
+}
+
+/// Marks one execution as running until dropped.
+struct Running(Arc<AtomicUsize>);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Shutdown {
+    /// Registers an execution, or `None` once shutdown has begun.
+    fn begin(&self) -> Option<Running> {
+        self.running.fetch_add(1, Ordering::SeqCst);
+        let running = Running(self.running.clone());
+        (!self.stopping.load(Ordering::SeqCst)).then_some(running)
+    }
+
+    fn stop(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+    }
+
+    fn running(&self) -> usize {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Waits up to `timeout` for running executions to finish; returns
+    /// whether they all did.
+    async fn drain(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.running() > 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        true
+    }
+}
+
 pub fn running_pid(paths: &AgentPaths) -> Result<Option<u32>> {
     let pid_file = scheduler_pid_file(paths);
     let raw = match fs::read_to_string(&pid_file) {
@@ -77,7 +241,7 @@ pub fn running_pid(paths: &AgentPaths) -> Result<Option<u32>> {
         return Ok(None);
     };
 
-    if process_is_alive(pid) {
+    if process_is_alive(pid) && process_is_scheduler(pid) {
         Ok(Some(pid))
     } else {
         let _ = fs::remove_file(&pid_file);
@@ -85,6 +249,70 @@ pub fn running_pid(paths: &AgentPaths) -> Result<Option<u32>> {
     }
 }
 
+/// Read-only view of the scheduler pid file for `scheduler pid show`.
+#[derive(Debug, Clone)]
+pub struct PidFileStatus {
+    pub path: PathBuf,
+    pub contents: String,
+    pub pid: Option<u32>,
+    pub alive: bool,
+    pub is_scheduler: bool,
+}
+
+pub fn pid_file_status(paths: &AgentPaths) -> Result<Option<PidFileStatus>> {
+    let path = scheduler_pid_file(paths);
+    let contents = match fs::read_to_string(&path) {
+        Ok(value) => value.trim().to_string(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let pid = contents.parse::<u32>().ok();
+    let alive = pid.is_some_and(process_is_alive);
+    let is_scheduler = alive && pid.is_some_and(process_is_scheduler);
+    Ok(Some(PidFileStatus {
+        path,
+        contents,
+        pid,
+        alive,
+        is_scheduler,
+    }))
+}
+
+/// Removes the pid file regardless of its contents. Returns whether a file
+/// was present.
+pub fn clear_pid_file(paths: &AgentPaths) -> Result<bool> {
+    match fs::remove_file(scheduler_pid_file(paths)) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Guards against pid reuse: a live pid only counts as the scheduler when its
+/// command line is a `goldagent ... serve` invocation. Where the command line
+/// cannot be read (non-Linux), liveness alone is trusted.
+fn process_is_scheduler(pid: u32) -> bool {
+    match fs::read(format!("/proc/{pid}/cmdline")) {
+        Ok(cmdline) => is_scheduler_cmdline(&cmdline),
+        Err(_) => !cfg!(target_os = "linux"),
+    }
+}
+
+fn is_scheduler_cmdline(cmdline: &[u8]) -> bool {
+    let mut args = cmdline
+        .split(|byte| *byte == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(String::from_utf8_lossy);
+    let Some(program) = args.next() else {
+        return false;
+    };
+    let program_name = Path::new(program.as_ref())
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    program_name.starts_with("goldagent") && args.any(|arg| arg == "serve")
+}
+
 fn scheduler_pid_file(paths: &AgentPaths) -> PathBuf {
     paths.root.join("scheduler.pid")
 }
@@ -134,65 +362,469 @@ fn process_is_alive(_pid: u32) -> bool {
     false
 }
 
-async fn run_job_loop(paths: AgentPaths, job: Job) -> Result<()> {
+/// How long a successful readiness check is reused before probing again.
+const NETWORK_CHECK_TTL: Duration = Duration::from_secs(60);
+
+/// Holds back network-dependent work until a connectivity probe succeeds.
+/// Shared by all job/hook tasks; concurrent callers wait on one probe, and a
+/// success is reused for [`NETWORK_CHECK_TTL`] while a network lost later is
+/// still noticed. When disabled in config it is always open.
+#[derive(Clone)]
+struct NetworkGate {
+    config: SchedulerConfig,
+    online_at: Arc<Mutex<Option<Instant>>>,
+}
+
+impl NetworkGate {
+    fn new(config: SchedulerConfig) -> Self {
+        Self {
+            config,
+            online_at: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    async fn wait_ready(&self) {
+        if !self.config.wait_for_network {
+            return;
+        }
+        // Held across the probe so a burst of jobs shares its result.
+        let mut online_at = self.online_at.lock().await;
+        if online_at.is_some_and(|at| at.elapsed() < NETWORK_CHECK_TTL) {
+            return;
+        }
+        let addr = self.config.network_probe_addr.clone();
+        let max_wait = Duration::from_secs(self.config.network_wait_max_secs);
+        let online =
+            wait_for_network(|| probe_tcp(addr.clone()), max_wait, Duration::from_secs(1)).await;
+        if online {
+            *online_at = Some(Instant::now());
+        } else {
+            eprintln!(
+                "Network probe {} still failing after {}s, running anyway.",
+                self.config.network_probe_addr, self.config.network_wait_max_secs
+            );
+        }
+    }
+}
+
+/// Polls `probe` with exponential backoff (capped at 30s per step) until it
+/// succeeds or `max_wait` has been spent sleeping.
+async fn wait_for_network<F, Fut>(mut probe: F, max_wait: Duration, initial_delay: Duration) -> bool
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    let mut delay = initial_delay;
+    let mut waited = Duration::ZERO;
+    loop {
+        if probe().await {
+            return true;
+        }
+        if waited >= max_wait {
+            return false;
+        }
+        let step = delay.min(max_wait - waited);
+        sleep(step).await;
+        waited += step;
+        delay = (delay * 2).min(Duration::from_secs(30));
+    }
+}
+
+async fn probe_tcp(addr: String) -> bool {
+    probe_tcp_latency(addr).await.is_some()
+}
+
+/// Time taken to open a TCP connection to `addr` (3s timeout), or `None` if
+/// it cannot be reached.
+pub async fn probe_tcp_latency(addr: String) -> Option<Duration> {
+    tokio::task::spawn_blocking(move || {
+        let sock = addr.to_socket_addrs().ok()?.next()?;
+        let started = Instant::now();
+        TcpStream::connect_timeout(&sock, Duration::from_secs(3)).ok()?;
+        Some(started.elapsed())
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+fn command_needs_network(command: &str) -> bool {
+    let trimmed = command.trim_start();
+    trimmed.starts_with("goldagent run ") || trimmed.starts_with("goldagent skill run ")
+}
+
+/// Executes a `run_on_start` job once before its schedule loop begins.
+/// Returns whether the job ran.
+async fn run_on_start(
+    paths: &AgentPaths,
+    job: &Job,
+    gate: &NetworkGate,
+    metrics: &SchedulerMetrics,
+) -> bool {
+    if !job.run_on_start {
+        return false;
+    }
+    println!("Running job {} ({}) on startup.", job.id, job.name);
+    metrics.record_job(execute_with_retry(paths, job, gate).await);
+    true
+}
+
+/// Runs a one-shot job whose time passed while the scheduler was down, which
+/// also removes it from `jobs.json`. Returns whether it ran.
+async fn run_missed_one_shot(
+    paths: &AgentPaths,
+    job: &Job,
+    gate: &NetworkGate,
+    metrics: &SchedulerMetrics,
+) -> bool {
+    match one_shot_missed(job, Local::now()) {
+        Ok(true) => {
+            println!("Running missed one-shot job {} ({}).", job.id, job.name);
+            run_scheduled_job(paths, job, gate, metrics).await;
+            true
+        }
+        Ok(false) => false,
+        Err(err) => {
+            eprintln!("Job {} ({}) skipped: {err}", job.id, job.name);
+            false
+        }
+    }
+}
+
+/// Whether `job` is one-shot and its single fire is already behind `now`.
+fn one_shot_missed(job: &Job, now: DateTime<Local>) -> Result<bool> {
+    if !job.once {
+        return Ok(false);
+    }
     let normalized = jobs::normalize_schedule(&job.schedule)?;
     let schedule = Schedule::from_str(&normalized)?;
-    let mut upcoming = schedule.after(&Local::now());
+    Ok(jobs::fires_after(job, &schedule, now)?.next().is_none())
+}
 
-    loop {
-        let Some(next) = upcoming.next() else {
+/// Longest single sleep in the job loop: the wall clock is re-read at least
+/// this often, so suspend, clock changes and DST shifts are noticed.
+const CLOCK_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Wall-clock drift over one sleep that is reported as a clock jump.
+const CLOCK_JUMP_THRESHOLD_SECS: i64 = 30;
+
+async fn run_job_loop(
+    paths: AgentPaths,
+    job: Job,
+    gate: NetworkGate,
+    metrics: Arc<SchedulerMetrics>,
+    shutdown: Shutdown,
+) -> Result<()> {
+    let normalized = jobs::normalize_schedule(&job.schedule)?;
+    let schedule = Schedule::from_str(&normalized)?;
+    let mut last_fire = None;
+
+    while let Some(next) = next_fire(&job, &schedule, Local::now(), last_fire)? {
+        wait_until(&job, next).await;
+
+        let Some(_running) = shutdown.begin() else {
             break;
         };
+        if !run_scheduled_job(&paths, &job, &gate, &metrics).await {
+            break;
+        }
+        last_fire = Some(next);
+    }
+
+    Ok(())
+}
+
+/// Runs one scheduled fire of `job` and records it. One-shot jobs are
+/// removed afterwards; returns whether the job stays scheduled.
+async fn run_scheduled_job(
+    paths: &AgentPaths,
+    job: &Job,
+    gate: &NetworkGate,
+    metrics: &SchedulerMetrics,
+) -> bool {
+    let success = execute_with_retry(paths, job, gate).await;
+    metrics.record_job(success);
+    let threshold = if job.onefail {
+        1
+    } else {
+        config::load(paths)
+            .unwrap_or_default()
+            .scheduler
+            .notify_after_failures
+    };
+    if let Err(err) = escalation::track_job_run(paths, job, success, threshold) {
+        eprintln!(
+            "Job {} ({}): failed to track failures: {err:#}",
+            job.id, job.name
+        );
+    }
+    if job.once {
+        let _ = jobs::remove_job(paths, &job.id);
+        return false;
+    }
+    true
+}
+
+/// How far back the first `serve --once` pass for a job looks for a fire;
+/// later passes continue from where the previous pass stopped.
+const ONCE_FIRST_PASS_WINDOW_SECS: i64 = 60;
+
+/// Whether `job` has a fire in `(since, now]`.
+fn job_is_due(job: &Job, since: DateTime<Local>, now: DateTime<Local>) -> Result<bool> {
+    let normalized = jobs::normalize_schedule(&job.schedule)?;
+    let schedule = Schedule::from_str(&normalized)?;
+    Ok(jobs::fires_after(job, &schedule, since)?
+        .next()
+        .is_some_and(|next| next <= now))
+}
 
-        let now = Local::now();
-        if next > now {
-            let wait = (next - now)
-                .to_std()
-                .unwrap_or_else(|_| Duration::from_secs(0));
-            sleep(wait).await;
+/// One pass of `serve --once`: runs the enabled jobs with a fire since the
+/// previous pass (each at most once) and one-shot jobs whose time has
+/// passed, then polls every enabled hook once against the signature stored
+/// by the previous pass.
+async fn run_once(
+    paths: &AgentPaths,
+    jobs: Vec<Job>,
+    hooks: Vec<Hook>,
+    gate: &NetworkGate,
+    metrics: &SchedulerMetrics,
+    now: DateTime<Local>,
+) {
+    let mut checked = load_state_map(&paths.job_checks_file);
+    checked.retain(|id, _| jobs.iter().any(|job| &job.id == id));
+    let first_pass = now - chrono::Duration::seconds(ONCE_FIRST_PASS_WINDOW_SECS);
+    for job in jobs.into_iter().filter(|j| j.enabled) {
+        let since = checked
+            .get(&job.id)
+            .and_then(|raw| DateTime::parse_from_rfc3339(raw).ok())
+            .map_or(first_pass, |at| at.with_timezone(&Local))
+            .min(now);
+        checked.insert(job.id.clone(), now.to_rfc3339());
+        let due =
+            job_is_due(&job, since, now).and_then(|due| Ok(due || one_shot_missed(&job, now)?));
+        match due {
+            Ok(true) => {
+                println!("Running due job {} ({}).", job.id, job.name);
+                if !run_scheduled_job(paths, &job, gate, metrics).await {
+                    checked.remove(&job.id);
+                }
+            }
+            Ok(false) => {}
+            Err(err) => eprintln!("Job {} ({}) skipped: {err}", job.id, job.name),
         }
+    }
+    if let Err(err) = save_state_map(&paths.job_checks_file, &checked) {
+        eprintln!("Failed to save job check times: {err:#}");
+    }
 
-        execute_with_retry(&paths, &job).await;
+    let mut signatures = load_state_map(&paths.hook_signatures_file);
+    for hook in hooks.into_iter().filter(|h| h.enabled) {
+        let mut last_seen = signatures.get(&hook.id).cloned().unwrap_or_default();
+        match poll_hook(paths, &hook, gate, metrics, &mut last_seen).await {
+            Ok(()) => {
+                signatures.insert(hook.id.clone(), last_seen);
+            }
+            Err(err) => eprintln!("Hook {} ({}) poll failed: {err}", hook.id, hook.name),
+        }
     }
+    if let Err(err) = save_state_map(&paths.hook_signatures_file, &signatures) {
+        eprintln!("Failed to save hook signatures: {err:#}");
+    }
+}
 
-    Ok(())
+fn load_state_map(path: &Path) -> BTreeMap<String, String> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn save_state_map(path: &Path, map: &BTreeMap<String, String>) -> Result<()> {
+    let raw = serde_json::to_string_pretty(map)?;
+    fsutil::write_atomic(path, raw)
+}
+
+/// Next fire time, recomputed from the wall clock rather than from a stale
+/// iterator: fires missed while suspended collapse into the one already run,
+/// and a clock moved back never repeats `last_fire`.
+fn next_fire(
+    job: &Job,
+    schedule: &Schedule,
+    now: DateTime<Local>,
+    last_fire: Option<DateTime<Local>>,
+) -> Result<Option<DateTime<Local>>> {
+    let after = last_fire.map_or(now, |last| last.max(now));
+    Ok(jobs::fires_after(job, schedule, after)?.next())
+}
+
+/// Sleeps until `next` by the wall clock in steps of at most
+/// [`CLOCK_CHECK_INTERVAL`], logging clock jumps seen between steps.
+async fn wait_until(job: &Job, next: DateTime<Local>) {
+    loop {
+        let before = Local::now();
+        let Some(wait) = remaining_wait(next, before) else {
+            return;
+        };
+        sleep(wait).await;
+        if let Some(jump) = clock_jump(before, wait, Local::now()) {
+            eprintln!(
+                "Job {} ({}): clock jumped by {}s, rechecking next run at {}",
+                job.id,
+                job.name,
+                jump.num_seconds(),
+                next.format("%Y-%m-%d %H:%M:%S")
+            );
+        }
+    }
 }
 
-async fn execute_with_retry(paths: &AgentPaths, job: &Job) {
+/// How long to sleep before checking the clock again, or `None` once `next`
+/// is due.
+fn remaining_wait(next: DateTime<Local>, now: DateTime<Local>) -> Option<Duration> {
+    let wait = (next - now).to_std().ok()?;
+    (!wait.is_zero()).then(|| wait.min(CLOCK_CHECK_INTERVAL))
+}
+
+/// Difference between the wall clock after a sleep and where it should be,
+/// when it exceeds [`CLOCK_JUMP_THRESHOLD_SECS`].
+fn clock_jump(
+    before: DateTime<Local>,
+    slept: Duration,
+    after: DateTime<Local>,
+) -> Option<chrono::Duration> {
+    let drift = after - before - chrono::Duration::from_std(slept).ok()?;
+    (drift.num_seconds().abs() >= CLOCK_JUMP_THRESHOLD_SECS).then_some(drift)
+}
+
+async fn execute_with_retry(paths: &AgentPaths, job: &Job, gate: &NetworkGate) -> bool {
     let effective_command = effective_job_command(&job.command);
+    if command_needs_network(&effective_command) {
+        gate.wait_ready().await;
+    }
 
     for attempt in 0..=job.retry_max {
-        let result = shell::run_shell_command(&effective_command, false).await;
-
-        match result {
-            Ok(output) => {
-                let log_line = format!(
-                    "job={} name={} status=success code={}\nstdout:\n{}\nstderr:\n{}",
-                    job.id, job.name, output.exit_code, output.stdout, output.stderr
-                );
-                let _ = memory::append_short_term(paths, &format!("cron.{}", job.id), &log_line);
-                return;
-            }
+        match execute_once(
+            paths,
+            job,
+            &effective_command,
+            attempt,
+            job.retry_max + 1,
+            false,
+        )
+        .await
+        {
+            Ok(_) => return true,
             Err(err) => {
-                let is_last = attempt == job.retry_max;
-                let log_line = format!(
-                    "job={} name={} status=failed attempt={}/{}\nerror={}",
-                    job.id,
-                    job.name,
-                    attempt + 1,
-                    job.retry_max + 1,
-                    err
-                );
-                let _ = memory::append_short_term(paths, &format!("cron.{}", job.id), &log_line);
-
-                if is_last {
+                if err.is::<shell::PolicyViolation>() {
+                    eprintln!("Job {} ({}) rejected: {err}", job.id, job.name);
+                    return false;
+                }
+                if attempt == job.retry_max {
                     eprintln!("Job {} ({}) failed after retries: {err}", job.id, job.name);
-                    return;
+                    return false;
                 }
                 sleep(Duration::from_secs(3)).await;
             }
         }
     }
+    false
+}
+
+/// Runs `job` once right now, bypassing its schedule and retries; `force`
+/// also skips `scheduler.command_policy`.
+pub async fn run_job_now(paths: &AgentPaths, job: &Job, force: bool) -> Result<shell::ShellOutput> {
+    let effective_command = effective_job_command(&job.command);
+    execute_once(paths, job, &effective_command, 0, 1, force).await
+}
+
+/// Executes a single attempt of `job` and logs the outcome under `cron.<id>`.
+async fn execute_once(
+    paths: &AgentPaths,
+    job: &Job,
+    command: &str,
+    attempt: u8,
+    attempts: u8,
+    force: bool,
+) -> Result<shell::ShellOutput> {
+    let source = format!("cron.{}", job.id);
+    match shell::run_scheduled_command(paths, command, force, job.cwd.as_deref().map(Path::new))
+        .await
+    {
+        Ok(output) => {
+            let log_line = format!(
+                "job={} name={} status=success code={}\nstdout:\n{}\nstderr:\n{}",
+                job.id, job.name, output.exit_code, output.stdout, output.stderr
+            );
+            let _ = memory::append_short_term(paths, &source, &log_line);
+            run_log::append(
+                paths,
+                &RunRecord::new(
+                    "cron",
+                    &job.id,
+                    &job.name,
+                    attempt + 1,
+                    Ok(Some(output.exit_code)),
+                ),
+            );
+            Ok(output)
+        }
+        Err(err) => {
+            let log_line = format!(
+                "job={} name={} status=failed attempt={}/{}\nerror={}",
+                job.id,
+                job.name,
+                attempt + 1,
+                attempts,
+                err
+            );
+            let _ = memory::append_short_term(paths, &source, &log_line);
+            run_log::append(
+                paths,
+                &RunRecord::new("cron", &job.id, &job.name, attempt + 1, Err(&err)),
+            );
+            Err(err)
+        }
+    }
+}
+
+/// External program that enabled jobs or hooks cannot run without.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequiredBinary {
+    pub program: String,
+    /// `cron <id>` / `hook <id>` entries that depend on it.
+    pub used_by: Vec<String>,
+}
+
+/// Programs the enabled `jobs` and `hooks` need: cron commands run through
+/// `shell`, hooks poll with git or p4 and use the shell for their command
+/// (or `on_findings` in review mode).
+pub fn required_binaries(jobs: &[Job], hooks: &[Hook], shell: &str) -> Vec<RequiredBinary> {
+    let mut required = BTreeMap::<String, Vec<String>>::new();
+    let mut need = |program: &str, user: String| {
+        required.entry(program.to_string()).or_default().push(user);
+    };
+    for job in jobs.iter().filter(|job| job.enabled) {
+        need(shell, format!("cron {}", job.id));
+    }
+    for hook in hooks.iter().filter(|hook| hook.enabled) {
+        let user = format!("hook {}", hook.id);
+        need(hook.source.as_str(), user.clone());
+        if hook.rules_file.is_none() || hook.on_findings.is_some() {
+            need(shell, user);
+        }
+    }
+    required
+        .into_iter()
+        .map(|(program, used_by)| RequiredBinary { program, used_by })
+        .collect()
+}
+
+/// Whether `program` exists: paths are checked directly, bare names are
+/// looked up in `path_var`.
+fn binary_available(program: &str, path_var: &OsStr) -> bool {
+    if program.contains('/') {
+        return Path::new(program).is_file();
+    }
+    fsutil::find_executable(program, path_var).is_some()
 }
 
 fn effective_job_command(command: &str) -> String {
@@ -232,6 +864,15 @@ fn unescape_quoted(input: &str) -> String {
     out
 }
 
+/// Spreads `interval_secs` by up to ±`jitter_percent`%, where `unit` is a
+/// uniform sample in `[0, 1)`. Never returns less than one second.
+fn jittered_interval(interval_secs: u64, jitter_percent: u8, unit: f64) -> Duration {
+    let base = interval_secs as f64;
+    let spread = base * f64::from(jitter_percent.min(100)) / 100.0;
+    let secs = base + spread * (unit.clamp(0.0, 1.0) * 2.0 - 1.0);
+    Duration::from_secs_f64(secs.max(1.0))
+}
+
 fn is_reminder_message(message: &str) -> bool {
     let trimmed = message.trim();
     trimmed.starts_with("提醒")
@@ -239,13 +880,20 @@ fn is_reminder_message(message: &str) -> bool {
         || trimmed.to_ascii_lowercase().starts_with("remind")
 }
 
-fn build_goldagent_remind_command(message: &str) -> String {
+pub fn build_goldagent_remind_command(message: &str) -> String {
     let normalized = message.replace(['\r', '\n'], " ");
     let escaped = normalized.replace('\\', "\\\\").replace('"', "\\\"");
     format!("goldagent remind \"{}\"", escaped.trim())
 }
 
-async fn run_hook_loop(paths: AgentPaths, hook: Hook) -> Result<()> {
+async fn run_hook_loop(
+    paths: AgentPaths,
+    hook: Hook,
+    gate: NetworkGate,
+    metrics: Arc<SchedulerMetrics>,
+    jitter_percent: u8,
+    shutdown: Shutdown,
+) -> Result<()> {
     let mut last_seen = match hooks::read_signature(&hook).await {
         Ok(signature) => signature,
         Err(err) => {
@@ -258,34 +906,45 @@ async fn run_hook_loop(paths: AgentPaths, hook: Hook) -> Result<()> {
     };
 
     loop {
-        sleep(Duration::from_secs(hook.interval_secs)).await;
-        match hooks::read_signature(&hook).await {
-            Ok(current) => {
-                if last_seen.is_empty() {
-                    last_seen = current;
-                    continue;
-                }
+        let unit = (Uuid::new_v4().as_u128() % 10_000) as f64 / 10_000.0;
+        sleep(jittered_interval(hook.interval_secs, jitter_percent, unit)).await;
+        let Some(_running) = shutdown.begin() else {
+            return Ok(());
+        };
+        if let Err(err) = poll_hook(&paths, &hook, &gate, &metrics, &mut last_seen).await {
+            eprintln!("Hook {} ({}) poll failed: {err}", hook.id, hook.name);
+        }
+    }
+}
 
-                if current != last_seen {
-                    if hook.rules_file.is_some() {
-                        execute_llm_hook(&paths, &hook, &last_seen, &current).await;
-                    } else {
-                        execute_hook_with_retry(&paths, &hook, &last_seen, &current).await;
-                    }
-                    last_seen = current;
-                }
-            }
-            Err(err) => {
-                eprintln!("Hook {} ({}) poll failed: {err}", hook.id, hook.name);
-            }
+/// Reads the hook's signature once and triggers it when it moved away from
+/// `last_seen`, which is then updated. An empty `last_seen` only records the
+/// baseline.
+async fn poll_hook(
+    paths: &AgentPaths,
+    hook: &Hook,
+    gate: &NetworkGate,
+    metrics: &SchedulerMetrics,
+    last_seen: &mut String,
+) -> Result<()> {
+    let current = hooks::read_signature(hook).await?;
+    if !last_seen.is_empty() && current != *last_seen {
+        metrics.record_hook_trigger();
+        if hook.rules_file.is_some() {
+            gate.wait_ready().await;
+            execute_llm_hook(paths, hook, last_seen, &current).await;
+        } else {
+            execute_hook_with_retry(paths, hook, last_seen, &current).await;
         }
     }
+    *last_seen = current;
+    Ok(())
 }
 
 async fn execute_hook_with_retry(paths: &AgentPaths, hook: &Hook, previous: &str, current: &str) {
     let command = hooks::render_command_template(hook, previous, current);
     for attempt in 0..=hook.retry_max {
-        let result = shell::run_shell_command(&command, false).await;
+        let result = shell::run_scheduled_command(paths, &command, false, hook.command_cwd()).await;
 
         match result {
             Ok(output) => {
@@ -301,6 +960,16 @@ async fn execute_hook_with_retry(paths: &AgentPaths, hook: &Hook, previous: &str
                     output.stderr
                 );
                 let _ = memory::append_short_term(paths, &format!("hook.{}", hook.id), &log_line);
+                run_log::append(
+                    paths,
+                    &RunRecord::new(
+                        "hook",
+                        &hook.id,
+                        &hook.name,
+                        attempt + 1,
+                        Ok(Some(output.exit_code)),
+                    ),
+                );
                 return;
             }
             Err(err) => {
@@ -318,7 +987,15 @@ async fn execute_hook_with_retry(paths: &AgentPaths, hook: &Hook, previous: &str
                     err
                 );
                 let _ = memory::append_short_term(paths, &format!("hook.{}", hook.id), &log_line);
+                run_log::append(
+                    paths,
+                    &RunRecord::new("hook", &hook.id, &hook.name, attempt + 1, Err(&err)),
+                );
 
+                if err.is::<shell::PolicyViolation>() {
+                    eprintln!("Hook {} ({}) rejected: {err}", hook.id, hook.name);
+                    return;
+                }
                 if is_last {
                     eprintln!(
                         "Hook {} ({}) failed after retries: {err}",
@@ -333,12 +1010,16 @@ async fn execute_hook_with_retry(paths: &AgentPaths, hook: &Hook, previous: &str
 }
 
 async fn execute_llm_hook(paths: &AgentPaths, hook: &Hook, prev: &str, curr: &str) {
-    let rules_path =
-        resolve_relative_to_target(&hook.target, hook.rules_file.as_deref().unwrap_or(""));
+    let rules_path = resolve_rules_path(hook);
     let prompt = match std::fs::read_to_string(&rules_path) {
         Ok(content) => content,
         Err(e) => {
-            eprintln!("[hook {}] 读取规则文件失败 {}: {e}", hook.id, rules_path.display());
+            eprintln!(
+                "[hook {}] 读取规则文件失败 {}: {e}",
+                hook.id,
+                rules_path.display()
+            );
+            log_llm_hook(paths, hook, Err(&e.into()));
             return;
         }
     };
@@ -362,6 +1043,7 @@ async fn execute_llm_hook(paths: &AgentPaths, hook: &Hook, prev: &str, curr: &st
         Ok(c) => c,
         Err(e) => {
             eprintln!("[hook {}] LLM client error: {e}", hook.id);
+            log_llm_hook(paths, hook, Err(&e));
             return;
         }
     };
@@ -369,13 +1051,38 @@ async fn execute_llm_hook(paths: &AgentPaths, hook: &Hook, prev: &str, curr: &st
         Ok(r) => r,
         Err(e) => {
             eprintln!("[hook {}] LLM call failed: {e}", hook.id);
+            log_llm_hook(paths, hook, Err(&e));
             return;
         }
     };
+    log_llm_hook(paths, hook, Ok(()));
+    let redact = config::load(paths)
+        .unwrap_or_default()
+        .scheduler
+        .redact_review_reports;
+    let response = review_text(response, redact);
 
     let report_path = resolve_report_path(hook);
     append_review_report(&report_path, &hook.source, prev, curr, &response);
 
+    if let Some(command) =
+        hooks::render_on_findings_command(hook, &response, prev, curr, &report_path)
+    {
+        let outcome =
+            match shell::run_scheduled_command(paths, &command, false, hook.command_cwd()).await {
+                Ok(_) => "status=success".to_string(),
+                Err(e) => {
+                    eprintln!("[hook {}] on_findings command failed: {e}", hook.id);
+                    format!("status=failed error={e}")
+                }
+            };
+        let _ = memory::append_short_term(
+            paths,
+            &format!("hook.{}", hook.id),
+            &format!("on_findings {outcome}\ncommand={command}"),
+        );
+    }
+
     let summary = format!(
         "hook.{} llm-review: {} → {}\nreport: {}\nresponse(截断): {}",
         hook.id,
@@ -387,11 +1094,21 @@ async fn execute_llm_hook(paths: &AgentPaths, hook: &Hook, prev: &str, curr: &st
     let _ = memory::append_short_term(paths, &format!("hook.{}", hook.id), &summary);
 }
 
+/// LLM reviews run once, without a command exit code.
+fn log_llm_hook(paths: &AgentPaths, hook: &Hook, result: Result<(), &anyhow::Error>) {
+    let result = result.map(|()| None);
+    run_log::append(
+        paths,
+        &RunRecord::new("hook", &hook.id, &hook.name, 1, result),
+    );
+}
+
 async fn fetch_diff(hook: &Hook, prev: &str, curr: &str) -> Option<String> {
     let escaped_target = hook.target.replace('\'', "'\"'\"'");
     let cmd = match hook.source {
         HookSource::Git => format!("git -C '{escaped_target}' diff {prev} {curr}"),
         HookSource::P4 => format!("p4 describe -du {curr}"),
+        HookSource::Hg => format!("hg -R '{escaped_target}' diff -r {prev} -r {curr}"),
     };
     match shell::run_shell_command_lenient(&cmd).await {
         Ok(out) if !out.stdout.trim().is_empty() => Some(truncate_str(out.stdout, 8000)),
@@ -399,7 +1116,13 @@ async fn fetch_diff(hook: &Hook, prev: &str, curr: &str) -> Option<String> {
     }
 }
 
-fn resolve_report_path(hook: &Hook) -> PathBuf {
+/// Rules file of an LLM-review hook, relative paths resolved against the target.
+pub fn resolve_rules_path(hook: &Hook) -> PathBuf {
+    resolve_relative_to_target(&hook.target, hook.rules_file.as_deref().unwrap_or(""))
+}
+
+/// Where an LLM-review hook appends its report.
+pub fn resolve_report_path(hook: &Hook) -> PathBuf {
     if let Some(ref path) = hook.report_file {
         resolve_relative_to_target(&hook.target, path)
     } else {
@@ -416,19 +1139,26 @@ fn resolve_relative_to_target(target: &str, path: &str) -> PathBuf {
     }
 }
 
+/// LLM review output as persisted, with secrets scrubbed when `redact`.
+fn review_text(response: String, redact: bool) -> String {
+    if redact {
+        redact::scrub_secrets(&response)
+    } else {
+        response
+    }
+}
+
 fn append_review_report(path: &Path, source: &HookSource, prev: &str, curr: &str, response: &str) {
     let ts = chrono::Local::now().to_rfc3339();
     let identity = match source {
-        HookSource::Git => format!(
+        HookSource::Git | HookSource::Hg => format!(
             "{} → {}",
             &prev[..7.min(prev.len())],
             &curr[..7.min(curr.len())]
         ),
         HookSource::P4 => format!("CL {} → {}", prev, curr),
     };
-    let entry = format!(
-        "## {ts} | {identity}\n\n**LLM 审查结果：**\n\n{response}\n\n---\n\n"
-    );
+    let entry = format!("## {ts} | {identity}\n\n**LLM 审查结果：**\n\n{response}\n\n---\n\n");
     use std::io::Write;
     if let Ok(mut f) = std::fs::OpenOptions::new()
         .create(true)
@@ -449,7 +1179,38 @@ fn truncate_str(s: String, max: usize) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::effective_job_command;
+    use super::{
+        CLOCK_CHECK_INTERVAL, NETWORK_CHECK_TTL, NetworkGate, Shutdown, append_review_report,
+        binary_available, clock_jump, command_needs_network, effective_job_command,
+        is_scheduler_cmdline, jittered_interval, job_is_due, next_fire, remaining_wait,
+        required_binaries, resolve_report_path, resolve_rules_path, review_text, run_job_now,
+        run_missed_one_shot, run_on_start, run_once, wait_for_network,
+    };
+    use crate::config::{self, AgentPaths, SchedulerConfig};
+    use crate::hooks::{FindingSeverity, Hook, HookSource};
+    use crate::jobs::{self, Job};
+    use crate::metrics::SchedulerMetrics;
+    use crate::shell;
+    use chrono::{Local, TimeZone};
+    use cron::Schedule;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+    use tokio::time::Duration;
+
+    #[test]
+    fn hook_interval_jitter_stays_within_bounds() {
+        for unit in [0.0, 0.25, 0.5, 0.75, 0.9999] {
+            let interval = jittered_interval(30, 10, unit);
+            assert!(interval >= Duration::from_secs(27), "{interval:?}");
+            assert!(interval <= Duration::from_secs(33), "{interval:?}");
+        }
+        assert_eq!(jittered_interval(30, 0, 0.9), Duration::from_secs(30));
+        assert_eq!(jittered_interval(1, 100, 0.0), Duration::from_secs(1));
+    }
 
     #[test]
     fn upgrades_legacy_run_reminder_command() {
@@ -462,4 +1223,613 @@ mod tests {
         let out = effective_job_command("goldagent run \"总结今天工作\"");
         assert_eq!(out, "goldagent run \"总结今天工作\"");
     }
+
+    #[tokio::test]
+    async fn network_wait_retries_until_probe_succeeds() {
+        let attempts = AtomicUsize::new(0);
+        let online = wait_for_network(
+            || {
+                let n = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+                async move { n >= 3 }
+            },
+            Duration::from_secs(1),
+            Duration::from_millis(1),
+        )
+        .await;
+        assert!(online);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn network_wait_gives_up_after_budget() {
+        let online = wait_for_network(
+            || async { false },
+            Duration::from_millis(5),
+            Duration::from_millis(1),
+        )
+        .await;
+        assert!(!online);
+    }
+
+    #[tokio::test]
+    async fn network_gate_reuses_a_recent_check() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let gate = NetworkGate::new(SchedulerConfig {
+            wait_for_network: true,
+            network_probe_addr: listener.local_addr().unwrap().to_string(),
+            network_wait_max_secs: 5,
+            ..SchedulerConfig::default()
+        });
+        gate.wait_ready().await;
+        assert!(gate.online_at.lock().await.is_some());
+
+        drop(listener);
+        let started = Instant::now();
+        gate.wait_ready().await;
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        *gate.online_at.lock().await = Some(Instant::now() - NETWORK_CHECK_TTL);
+        let offline = NetworkGate {
+            config: SchedulerConfig {
+                network_wait_max_secs: 0,
+                ..gate.config.clone()
+            },
+            ..gate.clone()
+        };
+        offline.wait_ready().await;
+        assert!(
+            gate.online_at
+                .lock()
+                .await
+                .is_some_and(|at| at.elapsed() >= NETWORK_CHECK_TTL),
+            "a failed probe must not be reused"
+        );
+    }
+
+    #[test]
+    fn recognizes_scheduler_cmdline() {
+        assert!(is_scheduler_cmdline(b"/usr/local/bin/goldagent\0serve\0"));
+        assert!(is_scheduler_cmdline(b"goldagent\0--verbose\0serve\0"));
+        assert!(!is_scheduler_cmdline(b"/usr/local/bin/goldagent\0chat\0"));
+        assert!(!is_scheduler_cmdline(b"/usr/bin/python3\0serve\0"));
+        assert!(!is_scheduler_cmdline(b""));
+    }
+
+    #[test]
+    fn only_llm_commands_need_network() {
+        assert!(command_needs_network("goldagent run \"总结今天工作\""));
+        assert!(command_needs_network(
+            "goldagent skill run daily-summary \"x\""
+        ));
+        assert!(!command_needs_network("goldagent remind \"提醒我喝水\""));
+        assert!(!command_needs_network("echo hi"));
+    }
+
+    #[tokio::test]
+    async fn run_job_now_executes_once_and_logs_like_scheduler() {
+        let root =
+            std::env::temp_dir().join(format!("goldagent-cron-run-test-{}", uuid::Uuid::new_v4()));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(&paths.memory_dir).unwrap();
+        let job = Job {
+            id: "job-1".to_string(),
+            name: "debug".to_string(),
+            schedule: "0 9 * * *".to_string(),
+            command: "echo cron-run-ok".to_string(),
+            enabled: true,
+            retry_max: 3,
+            created_at: Local::now().to_rfc3339(),
+            timezone: None,
+            once: false,
+            run_on_start: false,
+            cwd: None,
+            onefail: false,
+        };
+
+        let output = run_job_now(&paths, &job, false).await.unwrap();
+        assert_eq!(output.stdout.trim(), "cron-run-ok");
+
+        let daily = paths
+            .memory_dir
+            .join(format!("{}.md", Local::now().format("%Y-%m-%d")));
+        let log = fs::read_to_string(daily).unwrap();
+        assert!(log.contains("source: cron.job-1"));
+        assert!(log.contains("status=success code=0"));
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[tokio::test]
+    async fn job_and_git_hook_commands_run_in_their_working_directory() {
+        let root =
+            std::env::temp_dir().join(format!("goldagent-cwd-test-{}", uuid::Uuid::new_v4()));
+        let paths = AgentPaths::from_root(root);
+        let workdir = paths.root.join("work");
+        fs::create_dir_all(&workdir).unwrap();
+        fs::create_dir_all(&paths.memory_dir).unwrap();
+        let workdir_str = workdir.to_string_lossy().into_owned();
+
+        let job = jobs::add_job(
+            &paths,
+            jobs::NewJob {
+                schedule: "0 9 * * *".to_string(),
+                command: "pwd".to_string(),
+                cwd: Some(workdir_str.clone()),
+                ..jobs::NewJob::default()
+            },
+        )
+        .unwrap();
+        let output = run_job_now(&paths, &job, false).await.unwrap();
+        let expected = fs::canonicalize(&workdir).unwrap();
+        assert_eq!(Path::new(output.stdout.trim()), expected);
+
+        let mut hook = Hook {
+            id: "h1".to_string(),
+            name: "h1".to_string(),
+            source: HookSource::Git,
+            target: workdir_str,
+            reference: None,
+            interval_secs: 30,
+            command: "pwd".to_string(),
+            enabled: true,
+            retry_max: 0,
+            created_at: Local::now().to_rfc3339(),
+            rules_file: None,
+            report_file: None,
+            on_findings: None,
+            on_findings_min_severity: FindingSeverity::default(),
+            cwd: None,
+        };
+        assert_eq!(hook.command_cwd(), Some(workdir.as_path()));
+        hook.source = HookSource::P4;
+        assert_eq!(hook.command_cwd(), None);
+        hook.cwd = Some("/tmp".to_string());
+        assert_eq!(hook.command_cwd(), Some(Path::new("/tmp")));
+
+        assert!(
+            jobs::add_job(
+                &paths,
+                jobs::NewJob {
+                    schedule: "0 9 * * *".to_string(),
+                    command: "pwd".to_string(),
+                    cwd: Some(paths.root.join("missing").to_string_lossy().into_owned()),
+                    ..jobs::NewJob::default()
+                },
+            )
+            .is_err()
+        );
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn review_report_is_written_with_secrets_scrubbed() {
+        let dir = std::env::temp_dir().join(format!(
+            "goldagent-review-redact-test-{}",
+            uuid::Uuid::new_v4()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let report = dir.join("goldagent-review.md");
+        let response =
+            "[HIGH] config.rs:12 提交了密钥 sk-live-0123456789abcdefABCD，请改为读取环境变量"
+                .to_string();
+
+        append_review_report(
+            &report,
+            &HookSource::Git,
+            "a1b2c3d4e5",
+            "f6e7d8c9b0",
+            &review_text(response.clone(), true),
+        );
+        let written = fs::read_to_string(&report).unwrap();
+        assert!(
+            !written.contains("sk-live-0123456789abcdefABCD"),
+            "{written}"
+        );
+        assert!(written.contains("提交了密钥 [REDACTED]，请改为读取环境变量"));
+        assert!(written.contains("a1b2c3d → f6e7d8c"));
+
+        assert_eq!(review_text(response.clone(), false), response);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn resolves_review_paths_against_hook_target() {
+        let mut hook = Hook {
+            id: "h1".to_string(),
+            name: "review".to_string(),
+            source: HookSource::Git,
+            target: "/srv/repo".to_string(),
+            reference: None,
+            interval_secs: 30,
+            command: String::new(),
+            enabled: true,
+            retry_max: 0,
+            created_at: Local::now().to_rfc3339(),
+            rules_file: Some("review-rules.md".to_string()),
+            report_file: None,
+            on_findings: None,
+            on_findings_min_severity: FindingSeverity::default(),
+            cwd: None,
+        };
+        assert_eq!(
+            resolve_rules_path(&hook),
+            Path::new("/srv/repo/review-rules.md")
+        );
+        assert_eq!(
+            resolve_report_path(&hook),
+            Path::new("/srv/repo/goldagent-review.md")
+        );
+        hook.report_file = Some("/var/reports/review.md".to_string());
+        assert_eq!(
+            resolve_report_path(&hook),
+            Path::new("/var/reports/review.md")
+        );
+    }
+
+    #[tokio::test]
+    async fn command_policy_rejects_disallowed_interpreter_unless_forced() {
+        let root = std::env::temp_dir().join(format!(
+            "goldagent-command-policy-test-{}",
+            uuid::Uuid::new_v4()
+        ));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(&paths.memory_dir).unwrap();
+        let mut config = config::AgentConfig::default();
+        config.scheduler.command_policy.allow = vec!["goldagent".into(), "git".into()];
+        config::save(&paths, &config).unwrap();
+        let mut job = Job {
+            id: "job-policy".to_string(),
+            name: "script".to_string(),
+            schedule: "0 9 * * *".to_string(),
+            command: "python3 -c 'print(1)'".to_string(),
+            enabled: true,
+            retry_max: 0,
+            created_at: Local::now().to_rfc3339(),
+            timezone: None,
+            once: false,
+            run_on_start: false,
+            cwd: None,
+            onefail: false,
+        };
+
+        let err = run_job_now(&paths, &job, false).await.unwrap_err();
+        assert!(err.is::<shell::PolicyViolation>());
+        assert!(err.to_string().contains("`python3` is not permitted"));
+        let daily = paths
+            .memory_dir
+            .join(format!("{}.md", Local::now().format("%Y-%m-%d")));
+        assert!(fs::read_to_string(daily).unwrap().contains("status=failed"));
+
+        job.command = "echo forced".to_string();
+        assert!(run_job_now(&paths, &job, false).await.is_err());
+        let output = run_job_now(&paths, &job, true).await.unwrap();
+        assert_eq!(output.stdout.trim(), "forced");
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    fn daily_job(schedule: &str) -> (Job, Schedule) {
+        let job = Job {
+            id: "job-clock".to_string(),
+            name: "clock".to_string(),
+            schedule: schedule.to_string(),
+            command: "echo tick".to_string(),
+            enabled: true,
+            retry_max: 0,
+            created_at: Local::now().to_rfc3339(),
+            timezone: None,
+            once: false,
+            run_on_start: false,
+            cwd: None,
+            onefail: false,
+        };
+        let normalized = jobs::normalize_schedule(schedule).unwrap();
+        (job, Schedule::from_str(&normalized).unwrap())
+    }
+
+    #[test]
+    fn clock_jump_past_fire_time_makes_job_due_once() {
+        let (job, schedule) = daily_job("0 9 * * *");
+        let before = Local.with_ymd_and_hms(2025, 1, 6, 8, 0, 0).unwrap();
+        let next = next_fire(&job, &schedule, before, None).unwrap().unwrap();
+        assert_eq!(next, Local.with_ymd_and_hms(2025, 1, 6, 9, 0, 0).unwrap());
+
+        let wait = remaining_wait(next, before).unwrap();
+        assert_eq!(wait, CLOCK_CHECK_INTERVAL);
+        assert!(clock_jump(before, wait, before + chrono::Duration::seconds(61)).is_none());
+
+        // Suspended for three days during a one-minute sleep.
+        let woke = before + chrono::Duration::days(3);
+        assert!(clock_jump(before, wait, woke).is_some());
+        assert_eq!(remaining_wait(next, woke), None);
+
+        // Missed days are not replayed one after another.
+        let following = next_fire(&job, &schedule, woke, Some(next))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            following,
+            Local.with_ymd_and_hms(2025, 1, 9, 9, 0, 0).unwrap()
+        );
+
+        // A clock moved back before the last run does not repeat it.
+        let rewound = next - chrono::Duration::minutes(30);
+        let after_rewind = next_fire(&job, &schedule, rewound, Some(next))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            after_rewind,
+            Local.with_ymd_and_hms(2025, 1, 7, 9, 0, 0).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_running_executions_and_refuses_new_ones() {
+        let shutdown = Shutdown::default();
+        let running = shutdown.begin().expect("not stopping yet");
+        let held = shutdown.begin().expect("not stopping yet");
+        assert_eq!(shutdown.running(), 2);
+        drop(held);
+
+        shutdown.stop();
+        assert!(shutdown.begin().is_none());
+        assert_eq!(shutdown.running(), 1);
+        assert!(!shutdown.drain(Duration::from_millis(150)).await);
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            drop(running);
+        });
+        assert!(shutdown.drain(Duration::from_secs(5)).await);
+        assert_eq!(shutdown.running(), 0);
+    }
+
+    #[tokio::test]
+    async fn run_on_start_job_executes_once_at_startup() {
+        let root = std::env::temp_dir().join(format!(
+            "goldagent-run-on-start-test-{}",
+            uuid::Uuid::new_v4()
+        ));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(&paths.memory_dir).unwrap();
+        let gate = NetworkGate::new(SchedulerConfig::default());
+        let metrics = SchedulerMetrics::default();
+        let mut job = Job {
+            id: "job-start".to_string(),
+            name: "catch-up".to_string(),
+            schedule: "0 9 * * *".to_string(),
+            command: "echo started".to_string(),
+            enabled: true,
+            retry_max: 0,
+            created_at: Local::now().to_rfc3339(),
+            timezone: None,
+            once: false,
+            run_on_start: false,
+            cwd: None,
+            onefail: false,
+        };
+
+        assert!(!run_on_start(&paths, &job, &gate, &metrics).await);
+        job.run_on_start = true;
+        assert!(run_on_start(&paths, &job, &gate, &metrics).await);
+
+        let daily = paths
+            .memory_dir
+            .join(format!("{}.md", Local::now().format("%Y-%m-%d")));
+        let log = fs::read_to_string(daily).unwrap();
+        assert_eq!(log.matches("source: cron.job-start").count(), 1);
+        assert!(log.contains("stdout:\nstarted"));
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[tokio::test]
+    async fn once_pass_runs_only_jobs_due_within_the_window() {
+        let job = |id: &str, schedule: &str| Job {
+            id: id.to_string(),
+            name: id.to_string(),
+            schedule: schedule.to_string(),
+            command: format!("echo ran-{id}"),
+            enabled: true,
+            retry_max: 0,
+            created_at: Local::now().to_rfc3339(),
+            timezone: None,
+            once: false,
+            run_on_start: false,
+            cwd: None,
+            onefail: false,
+        };
+        let nine = Local.with_ymd_and_hms(2025, 1, 6, 9, 0, 0).unwrap();
+        let minutes = chrono::Duration::minutes;
+        let daily = job("daily", "0 9 * * *");
+        assert!(job_is_due(&daily, nine - minutes(1), nine).unwrap());
+        assert!(!job_is_due(&daily, nine, nine + minutes(1)).unwrap());
+        assert!(!job_is_due(&daily, nine - minutes(2), nine - minutes(1)).unwrap());
+
+        let root = std::env::temp_dir().join(format!(
+            "goldagent-serve-once-test-{}",
+            uuid::Uuid::new_v4()
+        ));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(&paths.memory_dir).unwrap();
+        let gate = NetworkGate::new(SchedulerConfig::default());
+        let metrics = SchedulerMetrics::default();
+        let every_minute = job("minutely", "* * * * *");
+        let far_off = job("far", "at@2000-01-01 00:00");
+        let mut disabled = job("off", "* * * * *");
+        disabled.enabled = false;
+
+        run_once(
+            &paths,
+            vec![every_minute, far_off, disabled],
+            Vec::new(),
+            &gate,
+            &metrics,
+            Local::now(),
+        )
+        .await;
+
+        let daily_log = paths
+            .memory_dir
+            .join(format!("{}.md", Local::now().format("%Y-%m-%d")));
+        let log = fs::read_to_string(daily_log).unwrap();
+        assert!(log.contains("stdout:\nran-minutely"));
+        assert!(!log.contains("ran-far"));
+        assert!(!log.contains("ran-off"));
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[tokio::test]
+    async fn consecutive_once_passes_run_each_fire_once() {
+        let root = std::env::temp_dir().join(format!(
+            "goldagent-serve-once-repeat-test-{}",
+            uuid::Uuid::new_v4()
+        ));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(&paths.memory_dir).unwrap();
+        let gate = NetworkGate::new(SchedulerConfig::default());
+        let metrics = SchedulerMetrics::default();
+        let daily = Job {
+            id: "daily".to_string(),
+            name: "daily".to_string(),
+            schedule: "0 9 * * *".to_string(),
+            command: "echo ran-daily".to_string(),
+            enabled: true,
+            retry_max: 0,
+            created_at: Local::now().to_rfc3339(),
+            timezone: None,
+            once: false,
+            run_on_start: false,
+            cwd: None,
+            onefail: false,
+        };
+        let nine = Local.with_ymd_and_hms(2025, 1, 6, 9, 0, 0).unwrap();
+        for offset in [-30, 30, 90] {
+            let now = nine + chrono::Duration::seconds(offset);
+            run_once(
+                &paths,
+                vec![daily.clone()],
+                Vec::new(),
+                &gate,
+                &metrics,
+                now,
+            )
+            .await;
+        }
+
+        let daily_log = paths
+            .memory_dir
+            .join(format!("{}.md", Local::now().format("%Y-%m-%d")));
+        let log = fs::read_to_string(daily_log).unwrap();
+        assert_eq!(log.matches("stdout:\nran-daily").count(), 1);
+        assert!(paths.job_checks_file.exists());
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[tokio::test]
+    async fn missed_one_shot_runs_once_and_is_removed() {
+        let root = std::env::temp_dir().join(format!(
+            "goldagent-missed-one-shot-test-{}",
+            uuid::Uuid::new_v4()
+        ));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(&paths.memory_dir).unwrap();
+        let job = jobs::add_job(
+            &paths,
+            jobs::NewJob {
+                schedule: "at@2025-06-01 09:00".to_string(),
+                command: "echo ran-missed".to_string(),
+                ..jobs::NewJob::default()
+            },
+        )
+        .unwrap();
+        let gate = NetworkGate::new(SchedulerConfig::default());
+        let metrics = SchedulerMetrics::default();
+
+        assert!(run_missed_one_shot(&paths, &job, &gate, &metrics).await);
+        assert!(jobs::load_jobs(&paths).unwrap().is_empty());
+        let daily_log = paths
+            .memory_dir
+            .join(format!("{}.md", Local::now().format("%Y-%m-%d")));
+        let log = fs::read_to_string(daily_log).unwrap();
+        assert!(log.contains("ran-missed"));
+
+        let upcoming = Job {
+            schedule: "at@2999-06-01 09:00".to_string(),
+            ..job
+        };
+        assert!(!run_missed_one_shot(&paths, &upcoming, &gate, &metrics).await);
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn required_binaries_follow_enabled_jobs_and_hooks() {
+        let job = |id: &str, enabled| Job {
+            id: id.to_string(),
+            name: id.to_string(),
+            schedule: "0 9 * * *".to_string(),
+            command: "echo hi".to_string(),
+            enabled,
+            retry_max: 0,
+            created_at: Local::now().to_rfc3339(),
+            timezone: None,
+            once: false,
+            run_on_start: false,
+            cwd: None,
+            onefail: false,
+        };
+        let hook = |id: &str, source, rules_file: Option<&str>| Hook {
+            id: id.to_string(),
+            name: id.to_string(),
+            source,
+            target: "/repo".to_string(),
+            reference: None,
+            interval_secs: 30,
+            command: "echo changed".to_string(),
+            enabled: true,
+            retry_max: 0,
+            created_at: Local::now().to_rfc3339(),
+            rules_file: rules_file.map(str::to_string),
+            report_file: None,
+            on_findings: None,
+            on_findings_min_severity: FindingSeverity::default(),
+            cwd: None,
+        };
+
+        let jobs = [job("j1", true), job("j2", false)];
+        let hooks = [
+            hook("h1", HookSource::Git, None),
+            hook("h2", HookSource::P4, Some("rules.md")),
+        ];
+        let required = required_binaries(&jobs, &hooks, "/bin/zsh")
+            .into_iter()
+            .map(|binary| (binary.program, binary.used_by))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            required,
+            vec![
+                (
+                    "/bin/zsh".to_string(),
+                    vec!["cron j1".to_string(), "hook h1".to_string()]
+                ),
+                ("git".to_string(), vec!["hook h1".to_string()]),
+                ("p4".to_string(), vec!["hook h2".to_string()]),
+            ]
+        );
+        assert!(required_binaries(&[job("off", false)], &[], "/bin/sh").is_empty());
+
+        let dir = std::env::temp_dir().join(format!("goldagent-bin-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let p4 = dir.join("p4");
+        fs::write(&p4, "").unwrap();
+        fs::set_permissions(&p4, fs::Permissions::from_mode(0o755)).unwrap();
+        let path_var = dir.clone().into_os_string();
+        assert!(binary_available("p4", &path_var));
+        assert!(!binary_available("git-not-installed", &path_var));
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
@@ -0,0 +1,439 @@
+use crate::config::AgentPaths;
+use crate::fsutil;
+use crate::provider::ChatMessage;
+use anyhow::{Context, Result, bail};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Writes the chat history to `last_session.json` atomically.
+pub fn save(paths: &AgentPaths, messages: &[ChatMessage]) -> Result<()> {
+    let raw = serde_json::to_string_pretty(messages)?;
+    fsutil::write_atomic(&paths.session_file, format!("{raw}\n"))
+        .with_context(|| format!("写入会话文件失败: {}", paths.session_file.display()))
+}
+
+/// Loads the last saved chat history, if any.
+pub fn load(paths: &AgentPaths) -> Result<Option<Vec<ChatMessage>>> {
+    if !paths.session_file.exists() {
+        return Ok(None);
+    }
+    let raw = fs::read_to_string(&paths.session_file)
+        .with_context(|| format!("读取会话文件失败: {}", paths.session_file.display()))?;
+    let messages = serde_json::from_str::<Vec<ChatMessage>>(&raw)
+        .with_context(|| format!("解析会话文件失败: {}", paths.session_file.display()))?;
+    Ok(Some(messages))
+}
+
+/// Saves `messages` as the named session `sessions/<name>.json`, leaving out
+/// the system prompt, which is rebuilt from live memory on load. Returns the
+/// number of turns saved.
+pub fn save_named(paths: &AgentPaths, name: &str, messages: &[ChatMessage]) -> Result<usize> {
+    let path = named_session_file(paths, name)?;
+    let history = messages
+        .iter()
+        .skip_while(|message| message.role == "system" && !is_summary_note(message))
+        .cloned()
+        .collect::<Vec<_>>();
+    fs::create_dir_all(&paths.sessions_dir)
+        .with_context(|| format!("创建会话目录失败: {}", paths.sessions_dir.display()))?;
+    let raw = serde_json::to_string_pretty(&history)?;
+    fsutil::write_atomic(&path, format!("{raw}\n"))
+        .with_context(|| format!("写入会话文件失败: {}", path.display()))?;
+    Ok(count_turns(&history))
+}
+
+/// Loads the named session saved by [`save_named`].
+pub fn load_named(paths: &AgentPaths, name: &str) -> Result<Vec<ChatMessage>> {
+    let path = named_session_file(paths, name)?;
+    if !path.exists() {
+        bail!("没有名为 {name} 的会话（/sessions 查看已保存的会话）");
+    }
+    let raw = fs::read_to_string(&path)
+        .with_context(|| format!("读取会话文件失败: {}", path.display()))?;
+    serde_json::from_str::<Vec<ChatMessage>>(&raw)
+        .with_context(|| format!("解析会话文件失败: {}", path.display()))
+}
+
+/// Saved session names with their turn counts, sorted by name.
+pub fn list_named(paths: &AgentPaths) -> Result<Vec<(String, usize)>> {
+    if !paths.sessions_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut sessions = Vec::new();
+    for entry in fs::read_dir(&paths.sessions_dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let Some(name) = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+        else {
+            continue;
+        };
+        let turns = fs::read_to_string(&path)
+            .ok()
+            .and_then(|raw| serde_json::from_str::<Vec<ChatMessage>>(&raw).ok())
+            .map(|messages| count_turns(&messages))
+            .unwrap_or(0);
+        sessions.push((name, turns));
+    }
+    sessions.sort();
+    Ok(sessions)
+}
+
+fn count_turns(messages: &[ChatMessage]) -> usize {
+    messages
+        .iter()
+        .filter(|message| message.role == "user")
+        .count()
+}
+
+fn named_session_file(paths: &AgentPaths, name: &str) -> Result<PathBuf> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || matches!(ch, '-' | '_' | '.'))
+        && !name.starts_with('.');
+    if !valid {
+        bail!("会话名只能包含字母、数字、-、_ 和 .（且不能以 . 开头）：{name}");
+    }
+    Ok(paths.sessions_dir.join(format!("{name}.json")))
+}
+
+/// Saved history trimmed to the last turns for re-hydration on resume.
+#[derive(Debug, Clone)]
+pub struct RestoredSession {
+    pub messages: Vec<ChatMessage>,
+    pub dropped_turns: usize,
+}
+
+/// Keeps the last `window` turns (a user message plus the replies after it)
+/// of `saved`, dropping stored system prompts. `window == 0` keeps everything.
+/// With `summarize`, the dropped turns are replaced by one system note listing
+/// their opening user prompts.
+pub fn restore_window(saved: Vec<ChatMessage>, window: usize, summarize: bool) -> RestoredSession {
+    let history = saved
+        .into_iter()
+        .filter(|message| message.role != "system")
+        .collect::<Vec<_>>();
+    let turn_starts = history
+        .iter()
+        .enumerate()
+        .filter(|(_, message)| message.role == "user")
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+    if window == 0 || turn_starts.len() <= window {
+        return RestoredSession {
+            messages: history,
+            dropped_turns: 0,
+        };
+    }
+
+    let dropped_turns = turn_starts.len() - window;
+    let cut = turn_starts[dropped_turns];
+    let mut messages = Vec::with_capacity(history.len() - cut + 1);
+    if summarize {
+        let prompts = history[..cut]
+            .iter()
+            .filter(|message| message.role == "user")
+            .map(|message| {
+                let line = message.content.lines().next().unwrap_or_default();
+                format!(
+                    "- {}",
+                    line.chars().take(SUMMARY_PROMPT_CHARS).collect::<String>()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        messages.push(ChatMessage::system(format!(
+            "更早的 {dropped_turns} 轮对话已省略，当时用户的问题依次为：\n{prompts}"
+        )));
+    }
+    messages.extend(history.into_iter().skip(cut));
+    RestoredSession {
+        messages,
+        dropped_turns,
+    }
+}
+
+/// Characters kept from each dropped user prompt in the resume summary.
+const SUMMARY_PROMPT_CHARS: usize = 60;
+
+/// How the chat history is shrunk once it exceeds the turn limit, chosen
+/// with `GOLDAGENT_COMPACT=summarize|drop`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompactMode {
+    /// Discard the oldest messages.
+    #[default]
+    Drop,
+    /// Ask the model to summarize the discarded messages into a note.
+    Summarize,
+}
+
+impl CompactMode {
+    pub fn from_env() -> Self {
+        parse_compact_mode(env::var("GOLDAGENT_COMPACT").ok().as_deref())
+    }
+}
+
+fn parse_compact_mode(raw: Option<&str>) -> CompactMode {
+    match raw
+        .map(|value| value.trim().to_ascii_lowercase())
+        .as_deref()
+    {
+        Some("summarize") => CompactMode::Summarize,
+        _ => CompactMode::Drop,
+    }
+}
+
+/// Marks the system note that holds the rolling summary of compacted turns.
+const SUMMARY_NOTE_PREFIX: &str = "此前对话的摘要（较早的消息已压缩）：\n";
+/// Characters of each dropped message sent to the summarizer.
+const SUMMARY_INPUT_CHARS: usize = 1500;
+
+pub fn is_summary_note(message: &ChatMessage) -> bool {
+    message.role == "system" && message.content.starts_with(SUMMARY_NOTE_PREFIX)
+}
+
+/// Removes the messages beyond the last `max_non_system` (after the system
+/// prompt and any summary note) and returns them, previous note first.
+pub fn take_overflow(messages: &mut Vec<ChatMessage>, max_non_system: usize) -> Vec<ChatMessage> {
+    let has_note = messages.get(1).is_some_and(is_summary_note);
+    let start = if has_note { 2 } else { 1 };
+    if messages.len() <= start + max_non_system {
+        return Vec::new();
+    }
+    let cut = messages.len() - max_non_system;
+    let mut dropped = messages.drain(start..cut).collect::<Vec<_>>();
+    if has_note {
+        dropped.insert(0, messages.remove(1));
+    }
+    dropped
+}
+
+/// Request asking the model to fold `dropped` (including an earlier
+/// summary) into one short summary.
+pub fn summary_request(dropped: &[ChatMessage]) -> Vec<ChatMessage> {
+    let transcript = dropped
+        .iter()
+        .map(|message| {
+            if is_summary_note(message) {
+                format!(
+                    "earlier summary: {}",
+                    &message.content[SUMMARY_NOTE_PREFIX.len()..]
+                )
+            } else {
+                let content = message
+                    .content
+                    .chars()
+                    .take(SUMMARY_INPUT_CHARS)
+                    .collect::<String>();
+                format!("{}: {content}", message.role)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    vec![
+        ChatMessage::system("你负责压缩对话历史，只输出摘要本身。"),
+        ChatMessage::user(format!(
+            "请把以下对话总结为不超过 200 字的中文要点，保留用户的目标、偏好、约束和已达成的结论：\n\n{transcript}"
+        )),
+    ]
+}
+
+/// Puts `summary` right after the system prompt as the summary note.
+pub fn insert_summary_note(messages: &mut Vec<ChatMessage>, summary: &str) {
+    let note = ChatMessage::system(format!("{SUMMARY_NOTE_PREFIX}{}", summary.trim()));
+    messages.insert(1.min(messages.len()), note);
+}
+
+/// Saves the session every `every_turns` completed turns so a crash loses at
+/// most that many turns. Disabled when `every_turns` is 0 or privacy mode is on.
+#[derive(Debug, Clone)]
+pub struct Autosaver {
+    every_turns: u32,
+    enabled: bool,
+    turns_since_save: u32,
+}
+
+impl Autosaver {
+    pub fn new(every_turns: u32, privacy_mode: bool) -> Self {
+        Self {
+            every_turns,
+            enabled: !privacy_mode,
+            turns_since_save: 0,
+        }
+    }
+
+    /// Persisting is allowed at all (also governs the save on exit).
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Call after each completed turn; returns whether the session was saved.
+    pub fn after_turn(&mut self, paths: &AgentPaths, messages: &[ChatMessage]) -> Result<bool> {
+        if !self.enabled || self.every_turns == 0 {
+            return Ok(false);
+        }
+        self.turns_since_save += 1;
+        if self.turns_since_save < self.every_turns {
+            return Ok(false);
+        }
+        save(paths, messages)?;
+        self.turns_since_save = 0;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Autosaver, CompactMode, insert_summary_note, is_summary_note, list_named, load, load_named,
+        parse_compact_mode, restore_window, save_named, summary_request, take_overflow,
+    };
+    use crate::config::AgentPaths;
+    use crate::provider::ChatMessage;
+    use std::env;
+    use std::fs;
+    use uuid::Uuid;
+
+    fn temp_paths() -> AgentPaths {
+        let root = env::temp_dir().join(format!("goldagent-session-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        AgentPaths::from_root(root)
+    }
+
+    fn forty_turn_session() -> Vec<ChatMessage> {
+        let mut saved = vec![ChatMessage::system("old system prompt")];
+        for turn in 0..40 {
+            saved.push(ChatMessage::user(format!("question {turn}")));
+            saved.push(ChatMessage::assistant(format!("answer {turn}")));
+        }
+        saved
+    }
+
+    #[test]
+    fn resume_window_keeps_system_and_last_turns() {
+        let restored = restore_window(forty_turn_session(), 10, false);
+        let mut messages = vec![ChatMessage::system("current system prompt")];
+        messages.extend(restored.messages);
+
+        assert_eq!(restored.dropped_turns, 30);
+        assert_eq!(messages.len(), 1 + 10 * 2);
+        assert_eq!(messages[0].content, "current system prompt");
+        assert_eq!(messages[1].content, "question 30");
+        assert_eq!(messages[20].content, "answer 39");
+
+        let summarized = restore_window(forty_turn_session(), 10, true);
+        assert_eq!(summarized.messages.len(), 1 + 10 * 2);
+        assert_eq!(summarized.messages[0].role, "system");
+        assert!(summarized.messages[0].content.contains("- question 29"));
+        assert!(!summarized.messages[0].content.contains("question 30"));
+
+        assert_eq!(
+            restore_window(forty_turn_session(), 0, true).messages.len(),
+            80
+        );
+    }
+
+    #[test]
+    fn autosaves_after_configured_turns() {
+        let paths = temp_paths();
+        let mut saver = Autosaver::new(2, false);
+        let mut messages = vec![ChatMessage::system("sys")];
+
+        messages.push(ChatMessage::user("q1"));
+        messages.push(ChatMessage::assistant("a1"));
+        assert!(!saver.after_turn(&paths, &messages).unwrap());
+        assert!(!paths.session_file.exists());
+
+        messages.push(ChatMessage::user("q2"));
+        messages.push(ChatMessage::assistant("a2"));
+        assert!(saver.after_turn(&paths, &messages).unwrap());
+
+        let saved = load(&paths).unwrap().unwrap();
+        let contents = saved.iter().map(|m| m.content.as_str()).collect::<Vec<_>>();
+        assert_eq!(contents, ["sys", "q1", "a1", "q2", "a2"]);
+        assert_eq!(saved[3].role, "user");
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn privacy_mode_never_autosaves() {
+        let paths = temp_paths();
+        let mut saver = Autosaver::new(1, true);
+        let messages = vec![ChatMessage::user("secret")];
+        assert!(!saver.after_turn(&paths, &messages).unwrap());
+        assert!(!paths.session_file.exists());
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn summarize_compaction_keeps_a_rolling_summary_note() {
+        assert_eq!(parse_compact_mode(None), CompactMode::Drop);
+        assert_eq!(parse_compact_mode(Some("drop")), CompactMode::Drop);
+        assert_eq!(
+            parse_compact_mode(Some(" Summarize ")),
+            CompactMode::Summarize
+        );
+
+        let mut messages = vec![ChatMessage::system("prompt")];
+        for i in 0..5 {
+            messages.push(ChatMessage::user(format!("q{i}")));
+            messages.push(ChatMessage::assistant(format!("a{i}")));
+        }
+        assert!(take_overflow(&mut messages.clone(), 10).is_empty());
+
+        let dropped = take_overflow(&mut messages, 4);
+        assert_eq!(dropped.len(), 6);
+        assert_eq!(dropped[0].content, "q0");
+        insert_summary_note(&mut messages, "用户在问 q0 到 q2");
+        assert_eq!(messages.len(), 1 + 1 + 4);
+        assert!(is_summary_note(&messages[1]));
+        assert_eq!(messages[2].content, "q3");
+
+        messages.push(ChatMessage::user("q5"));
+        messages.push(ChatMessage::assistant("a5"));
+        let dropped = take_overflow(&mut messages, 4);
+        assert!(is_summary_note(&dropped[0]));
+        assert_eq!(dropped.len(), 3);
+        let request = summary_request(&dropped);
+        assert!(
+            request[1]
+                .content
+                .contains("earlier summary: 用户在问 q0 到 q2")
+        );
+        assert!(request[1].content.contains("user: q3"));
+        assert_eq!(messages[0].content, "prompt");
+        assert_eq!(messages[1].content, "q4");
+    }
+
+    #[test]
+    fn named_sessions_round_trip_without_system_prompt() {
+        let paths = temp_paths();
+        let messages = vec![
+            ChatMessage::system("memory context"),
+            ChatMessage::user("q1"),
+            ChatMessage::assistant("a1"),
+            ChatMessage::user("q2"),
+            ChatMessage::assistant("a2"),
+        ];
+        assert_eq!(save_named(&paths, "work-1", &messages).unwrap(), 2);
+        save_named(&paths, "scratch", &messages[..3]).unwrap();
+
+        let loaded = load_named(&paths, "work-1").unwrap();
+        assert_eq!(loaded.len(), 4);
+        assert_eq!(loaded[0].content, "q1");
+        assert_eq!(
+            list_named(&paths).unwrap(),
+            [("scratch".to_string(), 1), ("work-1".to_string(), 2)]
+        );
+        assert!(load_named(&paths, "missing").is_err());
+        assert!(save_named(&paths, "../escape", &messages).is_err());
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+}
@@ -1,4 +1,11 @@
-use anyhow::{Result, bail};
+use crate::config::{self, AgentPaths, CommandPolicy};
+use crate::memory;
+use crate::provider::ChatMessage;
+use anyhow::{Context, Result, bail};
+use std::env;
+use std::fmt;
+use std::future::Future;
+use std::path::{Path, PathBuf};
 use tokio::process::Command;
 
 #[derive(Debug, Clone)]
@@ -8,19 +15,111 @@ pub struct ShellOutput {
     pub stderr: String,
 }
 
+/// Error returned by [`run_shell_command`] when [`is_dangerous`] rejects a
+/// command and `force` was not set.
+#[derive(Debug)]
+pub struct BlockedCommand {
+    pub command: String,
+    pub reason: DangerReason,
+}
+
+impl BlockedCommand {
+    /// The `goldagent shell` invocation that would run the command anyway.
+    pub fn force_invocation(&self) -> String {
+        format!("goldagent shell {} --force", quote(&self.command))
+    }
+}
+
+impl fmt::Display for BlockedCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Blocked potentially dangerous command ({}). Re-run with --force if this is intentional.",
+            self.reason
+        )
+    }
+}
+
+impl std::error::Error for BlockedCommand {}
+
+/// Error returned when a command runs but exits unsuccessfully.
+#[derive(Debug)]
+pub struct CommandFailed {
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl fmt::Display for CommandFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Command failed with code {}.\nstdout:\n{}\nstderr:\n{}",
+            self.exit_code, self.stdout, self.stderr
+        )
+    }
+}
+
+impl std::error::Error for CommandFailed {}
+
 pub async fn run_shell_command(command: &str, force: bool) -> Result<ShellOutput> {
-    if is_dangerous(command) && !force {
-        bail!("Blocked potentially dangerous command. Re-run with --force if this is intentional.");
+    run_shell_command_in(command, force, None).await
+}
+
+/// Like [`run_shell_command`], but runs in `cwd` instead of the process's
+/// working directory when given.
+pub async fn run_shell_command_in(
+    command: &str,
+    force: bool,
+    cwd: Option<&Path>,
+) -> Result<ShellOutput> {
+    run_with_shell(&resolve_shell(), command, force, cwd, false).await
+}
+
+/// Runs `command` through `shell -lc`; callers pick the shell, normally
+/// with [`resolve_shell`]. With `own_group` the command gets its own process
+/// group, so a Ctrl+C at the terminal running the scheduler does not reach it.
+async fn run_with_shell(
+    shell: &str,
+    command: &str,
+    force: bool,
+    cwd: Option<&Path>,
+    own_group: bool,
+) -> Result<ShellOutput> {
+    if let Some(reason) = is_dangerous(command)
+        && !force
+    {
+        return Err(BlockedCommand {
+            command: command.to_string(),
+            reason,
+        }
+        .into());
     }
 
-    let output = Command::new("zsh").arg("-lc").arg(command).output().await?;
+    let mut process = Command::new(shell);
+    process.arg("-lc").arg(command);
+    if let Some(cwd) = cwd {
+        process.current_dir(cwd);
+    }
+    if own_group {
+        detach_process_group(&mut process);
+    }
+    let output = process.output().await.with_context(|| match cwd {
+        Some(cwd) => format!("Failed to run command in {}", cwd.display()),
+        None => "Failed to run command".to_string(),
+    })?;
 
     let exit_code = output.status.code().unwrap_or(-1);
     let stdout = String::from_utf8_lossy(&output.stdout).to_string();
     let stderr = String::from_utf8_lossy(&output.stderr).to_string();
 
     if !output.status.success() {
-        bail!("Command failed with code {exit_code}.\nstdout:\n{stdout}\nstderr:\n{stderr}");
+        return Err(CommandFailed {
+            exit_code,
+            stdout,
+            stderr,
+        }
+        .into());
     }
 
     Ok(ShellOutput {
@@ -30,13 +129,303 @@ pub async fn run_shell_command(command: &str, force: bool) -> Result<ShellOutput
     })
 }
 
+/// Error returned by [`run_scheduled_command`] when `scheduler.command_policy`
+/// does not permit one of the programs a command starts.
+#[derive(Debug)]
+pub struct PolicyViolation {
+    pub program: String,
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` is not permitted by scheduler.command_policy. Add it to `allow` (or remove it from `deny`) in config.json, or run the job manually with `goldagent cron run <id> --force`.",
+            self.program
+        )
+    }
+}
+
+impl std::error::Error for PolicyViolation {}
+
+/// Runs a command on behalf of the scheduler, first checking every program
+/// it starts against `scheduler.command_policy` unless `force` is set. An
+/// unreadable config refuses the command rather than skipping the policy.
+pub async fn run_scheduled_command(
+    paths: &AgentPaths,
+    command: &str,
+    force: bool,
+    cwd: Option<&Path>,
+) -> Result<ShellOutput> {
+    if !force {
+        let policy = config::load(paths)
+            .context("Refusing to run the command: scheduler.command_policy could not be loaded")?
+            .scheduler
+            .command_policy;
+        if let Some(violation) = policy_violation(command, &policy) {
+            return Err(violation.into());
+        }
+    }
+    run_with_shell(&resolve_shell(), command, false, cwd, true).await
+}
+
+#[cfg(unix)]
+fn detach_process_group(process: &mut Command) {
+    process.process_group(0);
+}
+
+#[cfg(not(unix))]
+fn detach_process_group(_process: &mut Command) {}
+
+fn policy_violation(command: &str, policy: &CommandPolicy) -> Option<PolicyViolation> {
+    if policy.allow.is_empty() && policy.deny.is_empty() {
+        return None;
+    }
+    command_programs(command)
+        .into_iter()
+        .find(|program| {
+            policy.deny.contains(program)
+                || (!policy.allow.is_empty() && !policy.allow.contains(program))
+        })
+        .map(|program| PolicyViolation { program })
+}
+
+/// Wrappers that start the program named after them (and their own flags).
+const COMMAND_WRAPPERS: [&str; 6] = ["sudo", "doas", "env", "command", "exec", "nohup"];
+
+/// Shell builtins that cannot start another program; never checked against
+/// the policy, so `cd dir && make` works under an allowlist of `make`.
+const POLICY_EXEMPT_BUILTINS: [&str; 13] = [
+    "cd", "pushd", "popd", "export", "unset", "set", "umask", "true", "false", ":", "exit", "wait",
+    "shift",
+];
+
+/// Basenames of the programs started by each segment of `command` (split on
+/// `;`, `&`, `|`, parentheses, backticks and newlines outside quotes),
+/// skipping leading `NAME=value` assignments. A wrapper such as `sudo` is
+/// reported together with the program it wraps.
+fn command_programs(command: &str) -> Vec<String> {
+    let mut segments = vec![String::new()];
+    let mut quote = None;
+    for ch in command.chars() {
+        match (quote, ch) {
+            (Some(open), _) if ch == open => quote = None,
+            (None, '"' | '\'') => quote = Some(ch),
+            (None, ';' | '&' | '|' | '(' | ')' | '`' | '\n') => {
+                segments.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        if let Some(segment) = segments.last_mut() {
+            segment.push(ch);
+        }
+    }
+    let mut programs = Vec::new();
+    for segment in &segments {
+        let mut after_wrapper = false;
+        let tokens = segment
+            .split_whitespace()
+            .skip_while(|token| is_env_assignment(token));
+        for token in tokens {
+            // A wrapper's flags (and `env`'s assignments) precede the program.
+            if after_wrapper && (token.starts_with('-') || is_env_assignment(token)) {
+                continue;
+            }
+            let token = token.trim_matches(['"', '\'']);
+            let program = token.rsplit('/').next().unwrap_or(token);
+            if program.is_empty() || POLICY_EXEMPT_BUILTINS.contains(&program) {
+                break;
+            }
+            programs.push(program.to_string());
+            if !COMMAND_WRAPPERS.contains(&program) {
+                break;
+            }
+            after_wrapper = true;
+        }
+    }
+    programs
+}
+
+fn is_env_assignment(token: &str) -> bool {
+    token.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty()
+            && !name.starts_with(|ch: char| ch.is_ascii_digit())
+            && name
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+    })
+}
+
+/// Runs a command typed by the user via `goldagent shell`. A blocked command
+/// is recorded in short-term memory and the error spells out the exact
+/// `--force` re-run.
+pub async fn run_manual_command(
+    paths: &AgentPaths,
+    command: &str,
+    force: bool,
+) -> Result<ShellOutput> {
+    let home = dirs::home_dir();
+    run_manual_command_with(
+        paths,
+        command,
+        force,
+        home.as_deref(),
+        |command| async move { run_shell_command(&command, force).await },
+    )
+    .await
+}
+
+/// [`run_manual_command`] with the home directory the danger gate checks
+/// against and the command runner injected; `run` is only called for
+/// commands the gate lets through.
+async fn run_manual_command_with<F, Fut>(
+    paths: &AgentPaths,
+    command: &str,
+    force: bool,
+    home: Option<&Path>,
+    run: F,
+) -> Result<ShellOutput>
+where
+    F: FnOnce(String) -> Fut,
+    Fut: Future<Output = Result<ShellOutput>>,
+{
+    if let Some(reason) = danger_reason(command, home)
+        && !force
+    {
+        let blocked = BlockedCommand {
+            command: command.to_string(),
+            reason,
+        };
+        let _ = memory::append_short_term(
+            paths,
+            "shell.blocked",
+            &format!("$ {command}\nreason={}", blocked.reason),
+        );
+        bail!(
+            "{blocked}\n确认无误后可执行：{}",
+            blocked.force_invocation()
+        );
+    }
+    run(command.to_string()).await
+}
+
+/// Most trailing characters of command output kept in the chat context.
+const CONTEXT_OUTPUT_CHARS: usize = 4000;
+
+/// Runs `command` for the chat `/shell` command and appends its output to
+/// `messages` so the next turn can refer to it. Goes through the same danger
+/// gate as `goldagent shell` (without `--force`); nothing is added when the
+/// command is blocked or fails.
+pub async fn run_into_context(
+    paths: &AgentPaths,
+    command: &str,
+    messages: &mut Vec<ChatMessage>,
+) -> Result<ShellOutput> {
+    let home = dirs::home_dir();
+    run_into_context_with(
+        paths,
+        command,
+        messages,
+        home.as_deref(),
+        |command| async move { run_shell_command(&command, false).await },
+    )
+    .await
+}
+
+/// [`run_into_context`] with the danger gate's home directory and the
+/// command runner injected, as in [`run_manual_command_with`].
+async fn run_into_context_with<F, Fut>(
+    paths: &AgentPaths,
+    command: &str,
+    messages: &mut Vec<ChatMessage>,
+    home: Option<&Path>,
+    run: F,
+) -> Result<ShellOutput>
+where
+    F: FnOnce(String) -> Fut,
+    Fut: Future<Output = Result<ShellOutput>>,
+{
+    let command = command.trim();
+    if command.is_empty() {
+        bail!("命令不能为空");
+    }
+    let output = run_manual_command_with(paths, command, false, home, run).await?;
+    memory::append_short_term(paths, "shell.manual", &format!("$ {command}"))?;
+    messages.push(ChatMessage::user(context_message(command, &output)));
+    Ok(output)
+}
+
+fn context_message(command: &str, output: &ShellOutput) -> String {
+    let mut combined = output.stdout.trim_end().to_string();
+    if !output.stderr.trim().is_empty() {
+        combined.push_str(&format!("\n[stderr]\n{}", output.stderr.trim_end()));
+    }
+    let total = combined.chars().count();
+    let body = if total > CONTEXT_OUTPUT_CHARS {
+        let tail = combined
+            .chars()
+            .skip(total - CONTEXT_OUTPUT_CHARS)
+            .collect::<String>();
+        format!(
+            "…（已省略前 {} 个字符）\n{tail}",
+            total - CONTEXT_OUTPUT_CHARS
+        )
+    } else if combined.is_empty() {
+        "（无输出）".to_string()
+    } else {
+        combined
+    };
+    format!(
+        "我在本地执行了命令（退出码 {}），输出如下，后续提问可能会引用它：\n$ {command}\n```\n{body}\n```",
+        output.exit_code
+    )
+}
+
+/// Asks the model to explain `command` and assess its risk without running
+/// it. The local danger heuristics are passed along as extra context.
+pub async fn explain_command<F, Fut>(command: &str, chat: F) -> Result<String>
+where
+    F: FnOnce(Vec<ChatMessage>) -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    let command = command.trim();
+    if command.is_empty() {
+        bail!("命令不能为空");
+    }
+    let heuristic = match is_dangerous(command) {
+        Some(reason) => format!("本地安全检查：命中危险规则（{reason}），默认会被拦截。"),
+        None => "本地安全检查：未命中已知危险规则（不代表一定安全）。".to_string(),
+    };
+    let messages = vec![
+        ChatMessage::system(
+            "你是命令行安全助手。用户只想了解命令，不会执行它。\n\
+             请用通俗的中文说明：\n\
+             1. 这条命令做什么（逐个解释关键参数）\n\
+             2. 风险等级（低/中/高）及原因，可能影响的文件或系统状态\n\
+             3. 如有更安全的替代写法，给出建议",
+        ),
+        ChatMessage::user(format!("命令：\n```\n{command}\n```\n{heuristic}")),
+    ];
+    chat(messages).await
+}
+
+/// Single-quotes `raw` for POSIX shells.
+pub fn quote(raw: &str) -> String {
+    let escaped = raw.replace('\'', "'\"'\"'");
+    format!("'{escaped}'")
+}
+
 /// Like `run_shell_command` but does not bail on non-zero exit codes.
 /// Used by hook diff fetching where a partial/empty diff is acceptable.
 pub async fn run_shell_command_lenient(command: &str) -> Result<ShellOutput> {
-    if is_dangerous(command) {
-        bail!("Blocked potentially dangerous command.");
+    if let Some(reason) = is_dangerous(command) {
+        bail!("Blocked potentially dangerous command ({reason}).");
     }
-    let output = Command::new("zsh").arg("-lc").arg(command).output().await?;
+    let mut process = Command::new(resolve_shell());
+    process.arg("-lc").arg(command);
+    detach_process_group(&mut process);
+    let output = process.output().await?;
     Ok(ShellOutput {
         exit_code: output.status.code().unwrap_or(-1),
         stdout: String::from_utf8_lossy(&output.stdout).to_string(),
@@ -44,16 +433,482 @@ pub async fn run_shell_command_lenient(command: &str) -> Result<ShellOutput> {
     })
 }
 
-fn is_dangerous(command: &str) -> bool {
-    let lowered = command.to_lowercase();
-    [
-        "rm -rf /",
-        "mkfs",
-        "shutdown",
-        "reboot",
-        "dd if=",
-        ":(){:|:&};:",
-    ]
-    .iter()
-    .any(|pattern| lowered.contains(pattern))
+/// Shell used for `-lc` invocations: `GOLDAGENT_SHELL`, then `$SHELL`, then
+/// `/bin/bash`, then `/bin/sh`, skipping candidates that do not exist.
+pub fn resolve_shell() -> String {
+    pick_shell(
+        env::var("GOLDAGENT_SHELL").ok(),
+        env::var("SHELL").ok(),
+        |path| Path::new(path).exists(),
+    )
+}
+
+fn pick_shell(
+    configured: Option<String>,
+    login_shell: Option<String>,
+    exists: impl Fn(&str) -> bool,
+) -> String {
+    configured
+        .into_iter()
+        .chain(login_shell)
+        .map(|shell| shell.trim().to_string())
+        .filter(|shell| !shell.is_empty())
+        .chain(["/bin/bash".to_string()])
+        .find(|shell| exists(shell))
+        .unwrap_or_else(|| "/bin/sh".to_string())
+}
+
+/// Rule that made [`is_dangerous`] reject a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DangerReason {
+    RecursiveRemoveHome,
+    FormatFilesystem,
+    PowerControl,
+    RawDiskWrite,
+    ForkBomb,
+    RecursiveChmodRoot,
+    MoveRoot,
+}
+
+impl fmt::Display for DangerReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Self::RecursiveRemoveHome => {
+                "recursive rm of /, the home directory or one of its parents"
+            }
+            Self::FormatFilesystem => "mkfs formats a filesystem",
+            Self::PowerControl => "shutdown/reboot",
+            Self::RawDiskWrite => "raw write to a disk device",
+            Self::ForkBomb => "fork bomb",
+            Self::RecursiveChmodRoot => "recursive chmod of /",
+            Self::MoveRoot => "mv of / or /*",
+        };
+        f.write_str(text)
+    }
+}
+
+fn is_dangerous(command: &str) -> Option<DangerReason> {
+    danger_reason(command, dirs::home_dir().as_deref())
+}
+
+fn danger_reason(command: &str, home: Option<&Path>) -> Option<DangerReason> {
+    let normalized = command.split_whitespace().collect::<Vec<_>>().join(" ");
+    let lowered = normalized.to_lowercase();
+    let compact = lowered.replace(' ', "");
+
+    if compact.contains(":(){:|:&};:") {
+        return Some(DangerReason::ForkBomb);
+    }
+    if lowered.contains("mkfs") {
+        return Some(DangerReason::FormatFilesystem);
+    }
+    if lowered.contains("shutdown") || lowered.contains("reboot") {
+        return Some(DangerReason::PowerControl);
+    }
+    if lowered.contains("dd if=") || compact.contains(">/dev/sd") || compact.contains(">/dev/nvme")
+    {
+        return Some(DangerReason::RawDiskWrite);
+    }
+
+    let separated = command.replace(['&', '|', '(', ')', '`', '\n'], ";");
+    for segment in separated.split(';') {
+        let tokens = segment.split_whitespace().collect::<Vec<_>>();
+        let Some((program, args)) = split_program(&tokens) else {
+            continue;
+        };
+        let (flags, targets): (Vec<&str>, Vec<&str>) =
+            args.iter().partition(|arg| arg.starts_with('-'));
+        let recursive = flags.iter().any(|flag| {
+            *flag == "--recursive" || (!flag.starts_with("--") && flag.contains(['r', 'R']))
+        });
+        let reason = match program {
+            "rm" if recursive => targets
+                .iter()
+                .any(|target| at_or_above_home(target, home))
+                .then_some(DangerReason::RecursiveRemoveHome),
+            "chmod" if recursive => targets
+                .iter()
+                .any(|target| is_root(target))
+                .then_some(DangerReason::RecursiveChmodRoot),
+            "mv" => targets
+                .iter()
+                .any(|target| is_root(target))
+                .then_some(DangerReason::MoveRoot),
+            _ => None,
+        };
+        if reason.is_some() {
+            return reason;
+        }
+    }
+    None
+}
+
+/// Skips wrappers such as `sudo` (and their flags) and returns the basename of
+/// the actual program, so `/bin/rm` and `sudo rm` are treated like `rm`.
+fn split_program<'a>(tokens: &'a [&'a str]) -> Option<(&'a str, &'a [&'a str])> {
+    let start = tokens.iter().position(|token| {
+        !matches!(
+            *token,
+            "sudo" | "doas" | "env" | "command" | "exec" | "nohup"
+        ) && !token.starts_with('-')
+    })?;
+    let program = tokens[start].rsplit('/').next().unwrap_or(tokens[start]);
+    Some((program, &tokens[start + 1..]))
+}
+
+fn is_root(target: &str) -> bool {
+    let trimmed = target.trim_matches(['"', '\'']);
+    trimmed
+        .trim_end_matches('*')
+        .trim_end_matches('/')
+        .is_empty()
+        && trimmed.starts_with('/')
+}
+
+fn at_or_above_home(target: &str, home: Option<&Path>) -> bool {
+    let trimmed = target.trim_matches(['"', '\'']);
+    let base = trimmed.trim_end_matches('*').trim_end_matches('/');
+    if is_root(trimmed) || matches!(base, "~" | "$HOME" | "${HOME}") {
+        return true;
+    }
+    let Some(home) = home else {
+        return false;
+    };
+    let expanded = match base.strip_prefix("~/") {
+        Some(rest) => home.join(rest),
+        None => PathBuf::from(base),
+    };
+    expanded.is_absolute() && home.starts_with(&expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DangerReason, ShellOutput, danger_reason, explain_command, pick_shell, policy_violation,
+        resolve_shell, run_into_context_with, run_manual_command_with, run_scheduled_command,
+        run_shell_command, run_with_shell,
+    };
+    use crate::config::{AgentPaths, CommandPolicy};
+    use crate::provider::ChatMessage;
+    use anyhow::Result;
+    use chrono::Local;
+    use std::fs;
+    use std::path::Path;
+    use uuid::Uuid;
+
+    const HOME: &str = "/home/alice";
+
+    fn reason(command: &str) -> Option<DangerReason> {
+        danger_reason(command, Some(Path::new(HOME)))
+    }
+
+    /// Runner for commands the gate must block; nothing is ever executed.
+    async fn never_run(command: String) -> Result<ShellOutput> {
+        panic!("`{command}` got past the danger gate");
+    }
+
+    fn temp_paths() -> AgentPaths {
+        let root = std::env::temp_dir().join(format!("goldagent-shell-test-{}", Uuid::new_v4()));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(&paths.memory_dir).unwrap();
+        paths
+    }
+
+    #[test]
+    fn command_policy_checks_every_program_outside_quotes() {
+        let policy = CommandPolicy {
+            allow: vec!["goldagent".into(), "git".into()],
+            deny: Vec::new(),
+        };
+        let program = |command: &str| policy_violation(command, &policy).map(|v| v.program);
+
+        assert_eq!(program("goldagent run \"整理 a | b; c\""), None);
+        assert_eq!(
+            program("LANG=C /usr/bin/git pull && goldagent remind x"),
+            None
+        );
+        assert_eq!(program("python3 -c 'print(1)'").as_deref(), Some("python3"));
+        assert_eq!(program("git log | sudo -E bash").as_deref(), Some("sudo"));
+        assert_eq!(program("echo $(perl -e 1)").as_deref(), Some("echo"));
+        assert_eq!(program("cd /srv/repo && git pull"), None);
+
+        let with_wrapper = CommandPolicy {
+            allow: vec!["sudo".into(), "git".into()],
+            deny: Vec::new(),
+        };
+        assert!(policy_violation("sudo -E git pull", &with_wrapper).is_none());
+        assert_eq!(
+            policy_violation("sudo rm -rf build", &with_wrapper).map(|v| v.program),
+            Some("rm".to_string())
+        );
+
+        let deny_only = CommandPolicy {
+            allow: Vec::new(),
+            deny: vec!["curl".into(), "sudo".into()],
+        };
+        assert!(policy_violation("echo hi", &deny_only).is_none());
+        assert!(policy_violation("echo hi; curl x", &deny_only).is_some());
+        assert!(policy_violation("nohup env A=1 curl x", &deny_only).is_some());
+        assert!(policy_violation("sudo ls", &deny_only).is_some());
+        assert!(policy_violation("python3 x", &CommandPolicy::default()).is_none());
+    }
+
+    #[test]
+    fn blocks_rm_of_root_despite_spacing_and_prefixes() {
+        for command in [
+            "rm  -rf  /",
+            "/bin/rm -rf /",
+            "sudo rm -rf /*",
+            "rm -r -f /",
+        ] {
+            assert_eq!(
+                reason(command),
+                Some(DangerReason::RecursiveRemoveHome),
+                "{command}"
+            );
+        }
+    }
+
+    #[test]
+    fn blocks_rm_of_home_and_its_parents() {
+        for command in [
+            "rm -rf ~",
+            "rm -rf ~/",
+            "rm -rf $HOME",
+            "rm -rf \"${HOME}\"",
+            "sudo rm -rf /home",
+            "rm -rf /home/alice/",
+            "cd /tmp && rm -rf /home/alice",
+        ] {
+            assert_eq!(
+                reason(command),
+                Some(DangerReason::RecursiveRemoveHome),
+                "{command}"
+            );
+        }
+    }
+
+    #[test]
+    fn allows_rm_below_home_or_in_tmp() {
+        assert_eq!(reason("rm -rf /tmp/x"), None);
+        assert_eq!(reason("rm -rf ~/project/target"), None);
+        assert_eq!(reason("rm /home/alice/notes.txt"), None);
+    }
+
+    #[test]
+    fn blocks_raw_disk_writes() {
+        assert_eq!(
+            reason("cat img > /dev/sda"),
+            Some(DangerReason::RawDiskWrite)
+        );
+        assert_eq!(
+            reason("dd  if=/dev/zero of=/dev/sda"),
+            Some(DangerReason::RawDiskWrite)
+        );
+    }
+
+    #[test]
+    fn blocks_recursive_chmod_of_root() {
+        assert_eq!(
+            reason("chmod -R 000 /"),
+            Some(DangerReason::RecursiveChmodRoot)
+        );
+        assert_eq!(reason("chmod -R 755 ./build"), None);
+    }
+
+    #[test]
+    fn blocks_moving_root_contents() {
+        assert_eq!(reason("mv /* /tmp/trash"), Some(DangerReason::MoveRoot));
+        assert_eq!(reason("mv a.txt b.txt"), None);
+    }
+
+    #[test]
+    fn keeps_existing_patterns() {
+        assert_eq!(
+            reason("mkfs.ext4 /dev/sdb1"),
+            Some(DangerReason::FormatFilesystem)
+        );
+        assert_eq!(reason("sudo  reboot"), Some(DangerReason::PowerControl));
+        assert_eq!(reason(":(){ :|:& };:"), Some(DangerReason::ForkBomb));
+    }
+
+    #[tokio::test]
+    async fn explain_asks_model_and_never_runs_command() {
+        let marker = std::env::temp_dir().join(format!("goldagent-explain-{}", Uuid::new_v4()));
+        let command = format!("touch {} && rm -rf /", marker.display());
+
+        let explanation = explain_command(&command, |messages| async move {
+            assert_eq!(messages.len(), 2);
+            let prompt = &messages[1].content;
+            assert!(prompt.contains("touch"));
+            assert!(prompt.contains("命中危险规则"));
+            Ok("删除整个根目录，风险：高".to_string())
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(explanation, "删除整个根目录，风险：高");
+        assert!(!marker.exists());
+    }
+
+    #[tokio::test]
+    async fn shell_output_is_added_to_chat_history() {
+        let paths = temp_paths();
+        let home = Some(Path::new(HOME));
+        let mut messages = vec![ChatMessage::system("system")];
+
+        let echo = |command: String| async move {
+            assert_eq!(command, "echo hi");
+            Ok(ShellOutput {
+                exit_code: 0,
+                stdout: "hi\n".to_string(),
+                stderr: String::new(),
+            })
+        };
+        run_into_context_with(&paths, "echo hi", &mut messages, home, echo)
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].role, "user");
+        assert!(messages[1].content.contains("$ echo hi"));
+        assert!(messages[1].content.contains("\nhi\n"));
+
+        assert!(
+            run_into_context_with(&paths, "rm -rf ~/", &mut messages, home, never_run)
+                .await
+                .is_err()
+        );
+        assert_eq!(
+            messages.len(),
+            2,
+            "blocked commands must not reach the model"
+        );
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[tokio::test]
+    async fn blocked_manual_command_suggests_force_rerun_and_is_audited() {
+        let paths = temp_paths();
+
+        let err =
+            run_manual_command_with(&paths, "rm -rf ~/", false, Some(Path::new(HOME)), never_run)
+                .await
+                .unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.contains("goldagent shell 'rm -rf ~/' --force"),
+            "{message}"
+        );
+
+        let daily = paths
+            .memory_dir
+            .join(format!("{}.md", Local::now().format("%Y-%m-%d")));
+        let audit = fs::read_to_string(daily).unwrap();
+        assert!(audit.contains("shell.blocked"));
+        assert!(audit.contains("$ rm -rf ~/"));
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[tokio::test]
+    async fn malformed_config_refuses_scheduled_commands() {
+        let root = std::env::temp_dir().join(format!("goldagent-shell-test-{}", Uuid::new_v4()));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(&paths.root).unwrap();
+        fs::write(&paths.config_file, "{ \"scheduler\": ").unwrap();
+        let marker = paths.root.join("ran");
+
+        let command = format!("touch {}", marker.display());
+        let err = run_scheduled_command(&paths, &command, false, None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("command_policy"), "{err}");
+        assert!(!marker.exists());
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[tokio::test]
+    async fn bail_message_names_the_tripped_rule() {
+        let paths = temp_paths();
+        let err = run_manual_command_with(
+            &paths,
+            "rm -rf ~ --help-not-run",
+            false,
+            Some(Path::new(HOME)),
+            never_run,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("recursive rm"));
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn falls_back_through_shell_candidates() {
+        let only_sh = |path: &str| path == "/bin/sh";
+        assert_eq!(
+            pick_shell(
+                Some("/opt/zsh".into()),
+                Some("/usr/bin/zsh".into()),
+                only_sh
+            ),
+            "/bin/sh"
+        );
+        let has_bash = |path: &str| path == "/bin/bash";
+        assert_eq!(
+            pick_shell(None, Some("/usr/bin/zsh".into()), has_bash),
+            "/bin/bash"
+        );
+        let all = |_: &str| true;
+        assert_eq!(
+            pick_shell(Some("/bin/sh".into()), Some("/bin/zsh".into()), all),
+            "/bin/sh"
+        );
+    }
+
+    /// Sets an environment variable until dropped, then restores it.
+    struct ScopedEnv {
+        key: &'static str,
+        previous: Option<std::ffi::OsString>,
+    }
+
+    impl ScopedEnv {
+        fn set(key: &'static str, value: &str) -> Self {
+            let previous = std::env::var_os(key);
+            // SAFETY: only this test touches GOLDAGENT_SHELL, and any other
+            // test resolving the shell meanwhile gets /bin/sh, a valid choice.
+            unsafe { std::env::set_var(key, value) };
+            Self { key, previous }
+        }
+    }
+
+    impl Drop for ScopedEnv {
+        fn drop(&mut self) {
+            // SAFETY: see `ScopedEnv::set`.
+            unsafe {
+                match &self.previous {
+                    Some(value) => std::env::set_var(self.key, value),
+                    None => std::env::remove_var(self.key),
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn goldagent_shell_overrides_the_login_shell() {
+        let _shell = ScopedEnv::set("GOLDAGENT_SHELL", "/bin/sh");
+        assert_eq!(resolve_shell(), "/bin/sh");
+        let output = run_shell_command("echo hi", false).await.unwrap();
+        assert_eq!(output.stdout.trim(), "hi");
+    }
+
+    #[tokio::test]
+    async fn runs_command_with_the_picked_shell() {
+        let output = run_with_shell("/bin/sh", "echo hi", false, None, false)
+            .await
+            .unwrap();
+        assert_eq!(output.stdout.trim(), "hi");
+    }
 }
@@ -1,9 +1,94 @@
-use crate::config::AgentPaths;
+use crate::config::{self, AgentPaths};
+use crate::fsutil::{self, FileLock};
 use crate::memory;
 use crate::provider::{ChatMessage, ProviderClient};
-use anyhow::{Result, bail};
+use crate::response_format::{OutputValidation, run_with_self_correction};
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::future::Future;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use uuid::Uuid;
+
+/// Self-correction rounds used by `--repeat-on-fail` when the skill does not
+/// declare `max_self_corrections`.
+const DEFAULT_MAX_SELF_CORRECTIONS: u8 = 2;
+/// Characters of memory or run logs injected by `--context`.
+const SKILL_CONTEXT_CHARS: usize = 3_000;
+
+/// Recent data `skill run --context` puts into the skill prompt.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SkillContext {
+    /// Tail of long-term and recent short-term memory.
+    #[default]
+    Memory,
+    /// Recent cron job and hook run logs.
+    Runs,
+    /// No extra context.
+    None,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SkillMetadata {
+    pub output_validation: Option<OutputValidation>,
+    pub max_self_corrections: Option<u8>,
+    /// Model the skill was written for (`- 模型：...`); informational only.
+    pub model: Option<String>,
+    /// Shortest time between two runs (`- min_interval_secs: 600`), for
+    /// skills that call paid or rate-limited services from cron or hooks.
+    pub min_interval_secs: Option<u64>,
+}
+
+/// One entry of the optional front-matter `params:` list in `SKILL.md`:
+///
+/// ```text
+/// ---
+/// params:
+///   - name: date
+///     required: true
+///     description: 会议日期
+/// ---
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SkillParam {
+    pub name: String,
+    pub required: bool,
+    pub description: String,
+}
+
+/// Per-skill usage counter kept next to `SKILL.md`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SkillStats {
+    pub runs: u64,
+    pub last_run_at: Option<String>,
+}
+
+/// Everything `skill info` shows about one skill.
+#[derive(Debug, Clone)]
+pub struct SkillDetails {
+    pub info: SkillInfo,
+    pub parameters: Vec<String>,
+    pub metadata: SkillMetadata,
+    pub stats: SkillStats,
+}
+
+const SKILL_STATS_FILE: &str = ".stats.json";
+/// Lock taken by [`record_run`] next to [`SKILL_STATS_FILE`].
+const SKILL_STATS_LOCK: &str = ".stats.json.lock";
+
+/// Limits for one skill run. Every model call counts as a step.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SkillBudget {
+    pub timeout: Option<Duration>,
+    pub max_steps: Option<u32>,
+}
 
 #[derive(Debug, Clone)]
 pub struct SkillInfo {
@@ -12,13 +97,69 @@ pub struct SkillInfo {
     pub path: PathBuf,
 }
 
+/// Cached scan of one skills directory. Adding or removing a skill changes the
+/// directory mtime and forces a rescan; edits inside an existing `SKILL.md`
+/// are picked up once the entry is older than [`SKILL_CACHE_TTL`].
+struct SkillCacheEntry {
+    modified: SystemTime,
+    loaded_at: Instant,
+    skills: Vec<SkillInfo>,
+    /// Scans of this directory so far, for tests of the cache.
+    #[cfg(test)]
+    scans: usize,
+}
+
+const SKILL_CACHE_TTL: Duration = Duration::from_secs(5);
+
+static SKILL_CACHE: Mutex<Option<HashMap<PathBuf, SkillCacheEntry>>> = Mutex::new(None);
+
+/// Lists installed skills. Called on every keystroke by the inline hints, so
+/// results are cached per directory (see [`SkillCacheEntry`]).
 pub fn list_skills(paths: &AgentPaths) -> Result<Vec<SkillInfo>> {
-    if !paths.skills_dir.exists() {
+    let dir = &paths.skills_dir;
+    let Ok(modified) = fs::metadata(dir).and_then(|meta| meta.modified()) else {
         return Ok(Vec::new());
+    };
+
+    let mut cache = SKILL_CACHE
+        .lock()
+        .unwrap_or_else(|poison| poison.into_inner());
+    let entries = cache.get_or_insert_with(HashMap::new);
+    if let Some(entry) = entries.get(dir)
+        && entry.modified == modified
+        && entry.loaded_at.elapsed() < SKILL_CACHE_TTL
+    {
+        return Ok(entry.skills.clone());
     }
 
+    let skills = scan_skills(dir)?;
+    #[cfg(test)]
+    let scans = entries.get(dir).map_or(0, |entry| entry.scans) + 1;
+    entries.insert(
+        dir.clone(),
+        SkillCacheEntry {
+            modified,
+            loaded_at: Instant::now(),
+            skills: skills.clone(),
+            #[cfg(test)]
+            scans,
+        },
+    );
+    Ok(skills)
+}
+
+fn invalidate_skill_cache(dir: &Path) {
+    let mut cache = SKILL_CACHE
+        .lock()
+        .unwrap_or_else(|poison| poison.into_inner());
+    if let Some(entries) = cache.as_mut() {
+        entries.remove(dir);
+    }
+}
+
+fn scan_skills(dir: &Path) -> Result<Vec<SkillInfo>> {
     let mut skills = Vec::new();
-    for entry in fs::read_dir(&paths.skills_dir)? {
+    for entry in fs::read_dir(dir)? {
         let entry = entry?;
         let path = entry.path();
         if !path.is_dir() {
@@ -27,12 +168,27 @@ pub fn list_skills(paths: &AgentPaths) -> Result<Vec<SkillInfo>> {
 
         let name = entry.file_name().to_string_lossy().to_string();
         let skill_md = path.join("SKILL.md");
-        if !skill_md.exists() {
+        // Dot directories are imports in progress, never skills.
+        if name.starts_with('.') || !skill_md.exists() {
             continue;
         }
 
         let content = fs::read_to_string(&skill_md).unwrap_or_default();
-        let description = extract_description(&content);
+        let mut description = extract_description(&content);
+        let params = parse_params(&content);
+        if !params.is_empty() {
+            let names: Vec<String> = params
+                .iter()
+                .map(|param| {
+                    if param.required {
+                        format!("{}*", param.name)
+                    } else {
+                        param.name.clone()
+                    }
+                })
+                .collect();
+            description = format!("{description}（参数：{}）", names.join(", "));
+        }
 
         skills.push(SkillInfo {
             name,
@@ -90,14 +246,355 @@ pub fn create_skill(paths: &AgentPaths, name: &str) -> Result<PathBuf> {
 3. 风险与跟进：...\n"
     );
     fs::write(&skill_file, template)?;
+    invalidate_skill_cache(&paths.skills_dir);
     Ok(skill_file)
 }
 
+/// Skill seeded by `AgentPaths::ensure`; removing or renaming it needs
+/// `--force` (it is re-seeded on the next start).
+pub const SEEDED_SKILL: &str = "daily-summary";
+
+/// Files a skill directory may contain for `skill remove` without `--force`.
+const REMOVABLE_SKILL_FILES: [&str; 3] = ["SKILL.md", SKILL_STATS_FILE, SKILL_STATS_LOCK];
+
+/// Directory of installed skill `name`, rejecting names that would point
+/// outside the skills directory.
+fn existing_skill_dir(paths: &AgentPaths, name: &str) -> Result<PathBuf> {
+    let name = name.trim();
+    if name.is_empty() || name != normalize_skill_name(name) || matches!(name, "." | "..") {
+        bail!("无效的技能名称：{name}");
+    }
+    let dir = paths.skills_dir.join(name);
+    if !dir.join("SKILL.md").is_file() {
+        bail!("Skill `{name}` not found in {}", paths.skills_dir.display());
+    }
+    Ok(dir)
+}
+
+fn ensure_not_seeded(name: &str, force: bool) -> Result<()> {
+    if name.trim() == SEEDED_SKILL && !force {
+        bail!("`{SEEDED_SKILL}` 是内置技能，如确认修改请加 --force");
+    }
+    Ok(())
+}
+
+/// Deletes skill `name`. Without `force` the directory may only contain
+/// `SKILL.md` and its run stats, so scripts or notes kept next to a skill are
+/// not removed by accident.
+pub fn remove_skill(paths: &AgentPaths, name: &str, force: bool) -> Result<PathBuf> {
+    let dir = existing_skill_dir(paths, name)?;
+    ensure_not_seeded(name, force)?;
+    if !force {
+        let mut extra = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if !entry.file_type()?.is_file() || !REMOVABLE_SKILL_FILES.contains(&file_name.as_str())
+            {
+                extra.push(file_name);
+            }
+        }
+        if !extra.is_empty() {
+            extra.sort();
+            bail!(
+                "技能目录 {} 还包含其他文件：{}，如确认删除请加 --force",
+                dir.display(),
+                extra.join(", ")
+            );
+        }
+    }
+    fs::remove_dir_all(&dir).with_context(|| format!("删除技能目录失败: {}", dir.display()))?;
+    invalidate_skill_cache(&paths.skills_dir);
+    Ok(dir)
+}
+
+/// Renames skill `from` to the normalized form of `to` and returns the new
+/// directory.
+pub fn rename_skill(paths: &AgentPaths, from: &str, to: &str, force: bool) -> Result<PathBuf> {
+    let source = existing_skill_dir(paths, from)?;
+    ensure_not_seeded(from, force)?;
+    let to = normalize_skill_name(to);
+    if to.is_empty() || matches!(to.as_str(), "." | "..") {
+        bail!("无效的技能名称：{to}");
+    }
+    let target = paths.skills_dir.join(&to);
+    if target.exists() {
+        bail!("技能 `{to}` 已存在");
+    }
+    fs::rename(&source, &target).with_context(|| {
+        format!(
+            "重命名技能目录失败: {} -> {}",
+            source.display(),
+            target.display()
+        )
+    })?;
+    invalidate_skill_cache(&paths.skills_dir);
+    Ok(target)
+}
+
+/// First line of a `skill export` bundle: `<!-- goldagent-skill v1 name=... -->`.
+const SKILL_BUNDLE_HEADER: &str = "<!-- goldagent-skill v1 name=";
+
+/// Packages skill `name` into one portable text file. Every file of the skill
+/// directory (except run stats) follows a `<!-- file PATH bytes=N -->` line,
+/// so contents are copied byte for byte and the bundle still reads as Markdown.
+pub fn export_skill(paths: &AgentPaths, name: &str) -> Result<String> {
+    let dir = existing_skill_dir(paths, name)?;
+    let mut files = Vec::new();
+    collect_skill_files(&dir, &dir, &mut files)?;
+    files.sort();
+
+    let mut bundle = format!("{SKILL_BUNDLE_HEADER}{} -->\n", name.trim());
+    for relative in files {
+        let path = dir.join(&relative);
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("仅支持导出文本文件: {}", path.display()))?;
+        bundle.push_str(&format!(
+            "<!-- file {relative} bytes={} -->\n{content}\n",
+            content.len()
+        ));
+    }
+    Ok(bundle)
+}
+
+fn collect_skill_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_skill_files(root, &path, files)?;
+        } else if file_type.is_file() {
+            let relative = path
+                .strip_prefix(root)?
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            if relative != SKILL_STATS_FILE && relative != SKILL_STATS_LOCK {
+                files.push(relative);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Unpacks a bundle written by [`export_skill`] into the skills directory and
+/// returns the new skill directory. An existing skill of the same name is
+/// only replaced with `force`. The bundle is unpacked next to the skills
+/// first and renamed into place, so a failed import leaves the old skill.
+pub fn import_skill(paths: &AgentPaths, bundle: &str, force: bool) -> Result<PathBuf> {
+    let (name, files) = parse_skill_bundle(bundle)?;
+    if !files.iter().any(|(path, _)| path == "SKILL.md") {
+        bail!("技能包缺少 SKILL.md");
+    }
+
+    let dir = paths.skills_dir.join(&name);
+    if dir.exists() && !force {
+        bail!("技能 `{name}` 已存在，如需覆盖请加 --force");
+    }
+    let staging = paths
+        .skills_dir
+        .join(format!(".import-{name}-{}", Uuid::new_v4()));
+    if let Err(err) = unpack_skill_files(&staging, files) {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(err);
+    }
+
+    let replaced = paths
+        .skills_dir
+        .join(format!(".replaced-{name}-{}", Uuid::new_v4()));
+    let had_old = dir.exists();
+    if had_old {
+        fs::rename(&dir, &replaced)
+            .with_context(|| format!("移开已有技能目录失败: {}", dir.display()))?;
+    }
+    if let Err(err) = fs::rename(&staging, &dir) {
+        if had_old {
+            let _ = fs::rename(&replaced, &dir);
+        }
+        let _ = fs::remove_dir_all(&staging);
+        return Err(err).with_context(|| format!("写入技能目录失败: {}", dir.display()));
+    }
+    if had_old {
+        let _ = fs::remove_dir_all(&replaced);
+    }
+    invalidate_skill_cache(&paths.skills_dir);
+    Ok(dir)
+}
+
+fn unpack_skill_files(dir: &Path, files: Vec<(String, &str)>) -> Result<()> {
+    for (relative, content) in files {
+        let path = dir.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, content).with_context(|| format!("写入失败: {}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn parse_skill_bundle(bundle: &str) -> Result<(String, Vec<(String, &str)>)> {
+    let (header, mut rest) = bundle.split_once('\n').unwrap_or((bundle, ""));
+    let name = header
+        .trim()
+        .strip_prefix(SKILL_BUNDLE_HEADER)
+        .and_then(|rest| rest.strip_suffix("-->"))
+        .map(|name| normalize_skill_name(name.trim()))
+        .ok_or_else(|| anyhow!("不是 GoldAgent 技能包（缺少 `{SKILL_BUNDLE_HEADER}...` 开头）"))?;
+    if name.is_empty() || matches!(name.as_str(), "." | "..") {
+        bail!("技能包中的技能名称无效：{name}");
+    }
+
+    let mut files = Vec::new();
+    while !rest.trim().is_empty() {
+        let (line, after) = rest.split_once('\n').unwrap_or((rest, ""));
+        let (relative, bytes) = line
+            .trim()
+            .strip_prefix("<!-- file ")
+            .and_then(|line| line.strip_suffix(" -->"))
+            .and_then(|line| line.rsplit_once(" bytes="))
+            .ok_or_else(|| anyhow!("技能包格式错误：{line}"))?;
+        let bytes = bytes
+            .parse::<usize>()
+            .map_err(|_| anyhow!("技能包格式错误：{line}"))?;
+        let content = after
+            .get(..bytes)
+            .ok_or_else(|| anyhow!("技能包中 {relative} 的内容不完整"))?;
+        let relative = relative.trim();
+        let safe = !relative.is_empty()
+            && relative.split('/').all(|part| {
+                !part.is_empty() && !matches!(part, "." | "..") && !part.contains('\\')
+            });
+        if !safe {
+            bail!("技能包中的文件路径不安全：{relative}");
+        }
+        if matches!(relative, SKILL_STATS_FILE | SKILL_STATS_LOCK) {
+            bail!("技能包不能包含 {relative}：运行统计只属于本机");
+        }
+        files.push((relative.to_string(), content));
+        rest = after[bytes..].strip_prefix('\n').unwrap_or(&after[bytes..]);
+    }
+    Ok((name, files))
+}
+
+fn stats_file(paths: &AgentPaths, name: &str) -> PathBuf {
+    paths.skills_dir.join(name).join(SKILL_STATS_FILE)
+}
+
+pub fn load_stats(paths: &AgentPaths, name: &str) -> Result<SkillStats> {
+    let path = stats_file(paths, name);
+    match fs::read_to_string(&path) {
+        Ok(raw) => serde_json::from_str(&raw)
+            .with_context(|| format!("Failed to parse skill stats {}", path.display())),
+        Err(_) => Ok(SkillStats::default()),
+    }
+}
+
+/// Rejects a run of skill `name` that starts less than `min_interval_secs`
+/// after its last run; otherwise records `now` as the last run before the
+/// model is called, under the stats lock, so concurrent or failing runs
+/// cannot slip past the cooldown.
+pub fn claim_cooldown(
+    paths: &AgentPaths,
+    name: &str,
+    min_interval_secs: Option<u64>,
+    now: DateTime<Local>,
+) -> Result<()> {
+    let Some(min_interval_secs) = min_interval_secs else {
+        return Ok(());
+    };
+    let path = stats_file(paths, name);
+    let _lock = FileLock::acquire(&path)?;
+    let mut stats = load_stats(paths, name)?;
+    if let Some(last_run) = stats
+        .last_run_at
+        .as_deref()
+        .and_then(|raw| DateTime::parse_from_rfc3339(raw).ok())
+    {
+        let elapsed = (now - last_run.with_timezone(&Local)).num_seconds().max(0) as u64;
+        if elapsed < min_interval_secs {
+            bail!(
+                "技能 `{name}` 冷却中：最小运行间隔 {min_interval_secs} 秒，距上次运行 {elapsed} 秒，请 {} 秒后再试",
+                min_interval_secs - elapsed
+            );
+        }
+    }
+    stats.last_run_at = Some(now.to_rfc3339());
+    fsutil::write_atomic(&path, serde_json::to_string_pretty(&stats)?)
+}
+
+/// Increments the run counter of skill `name`.
+pub fn record_run(paths: &AgentPaths, name: &str) -> Result<SkillStats> {
+    let path = stats_file(paths, name);
+    let _lock = FileLock::acquire(&path)?;
+    let mut stats = load_stats(paths, name)?;
+    stats.runs += 1;
+    stats.last_run_at = Some(Local::now().to_rfc3339());
+    fsutil::write_atomic(&path, serde_json::to_string_pretty(&stats)?)?;
+    Ok(stats)
+}
+
+pub fn skill_info(paths: &AgentPaths, name: &str) -> Result<SkillDetails> {
+    let path = paths.skills_dir.join(name).join("SKILL.md");
+    if !path.exists() {
+        bail!("Skill `{name}` not found in {}", paths.skills_dir.display());
+    }
+    let content = fs::read_to_string(&path)?;
+    let params = parse_params(&content);
+    let parameters = if params.is_empty() {
+        extract_section(&content, "输入")
+    } else {
+        params
+            .iter()
+            .map(|param| {
+                let mut line = param.name.clone();
+                if param.required {
+                    line.push_str("（必填）");
+                }
+                if !param.description.is_empty() {
+                    line.push('：');
+                    line.push_str(&param.description);
+                }
+                line
+            })
+            .collect()
+    };
+    Ok(SkillDetails {
+        info: SkillInfo {
+            name: name.to_string(),
+            description: extract_description(&content),
+            path,
+        },
+        parameters,
+        metadata: parse_metadata(&content),
+        stats: load_stats(paths, name)?,
+    })
+}
+
+/// Bullet lines of a `标题：` section in `SKILL.md`, up to the next blank line.
+fn extract_section(content: &str, title: &str) -> Vec<String> {
+    content
+        .lines()
+        .skip_while(|line| {
+            let line = line.trim();
+            line.strip_prefix(title)
+                .is_none_or(|rest| !matches!(rest, "：" | ":"))
+        })
+        .skip(1)
+        .take_while(|line| !line.trim().is_empty())
+        .map(|line| line.trim().trim_start_matches("- ").to_string())
+        .collect()
+}
+
 pub async fn run_skill(
     paths: &AgentPaths,
     client: &ProviderClient,
     name: &str,
     input: &str,
+    repeat_on_fail: bool,
+    budget: SkillBudget,
+    context: SkillContext,
 ) -> Result<String> {
     let skill_file = paths.skills_dir.join(name).join("SKILL.md");
     if !skill_file.exists() {
@@ -105,23 +602,359 @@ pub async fn run_skill(
     }
 
     let skill_content = fs::read_to_string(&skill_file)?;
-    let memory_context = memory::tail_context(paths, 3_000)?;
+    let metadata = parse_metadata(&skill_content);
+    // Invalid parameters are rejected before the run counts against the cooldown.
+    let parameters = parameter_section(name, &parse_params(&skill_content), input)?;
+    claim_cooldown(paths, name, metadata.min_interval_secs, Local::now())?;
+    let guard_template = config::load(paths).unwrap_or_default().chat.identity_guard;
+    let mut system = skill_system_prompt(
+        paths,
+        &skill_content,
+        &client.backend_label(),
+        &client.identity_guard(guard_template.as_deref()),
+        context,
+    )?;
+    if let Some(parameters) = parameters {
+        system.push_str("\n\n");
+        system.push_str(&parameters);
+    }
 
-    let system = format!(
-        "You are GoldAgent.\n\
-Current backend: {}.\n\
-If asked about model/backend identity, answer strictly based on Current backend, not historical memory.\n\n\
-Skill definition:\n{skill_content}\n\nMemory context:\n{memory_context}\n\n\
-Follow the skill faithfully and produce a concise response.",
-        client.backend_label()
-    );
+    let max_corrections = if repeat_on_fail {
+        metadata
+            .max_self_corrections
+            .unwrap_or(DEFAULT_MAX_SELF_CORRECTIONS)
+    } else {
+        0
+    };
 
     let messages = vec![ChatMessage::system(system), ChatMessage::user(input)];
-    let response = client.chat(&messages).await?;
-    Ok(response)
+    run_with_budget(
+        messages,
+        metadata.output_validation,
+        max_corrections,
+        budget,
+        |messages| async move { client.chat(&messages).await },
+    )
+    .await
+}
+
+/// Structured `Parameters:` prompt section for a skill that declares
+/// front-matter params and receives `key=value` input. Returns `None` when
+/// nothing is declared or the input is free text, which is then passed as-is.
+pub fn parameter_section(name: &str, params: &[SkillParam], input: &str) -> Result<Option<String>> {
+    if params.is_empty() {
+        return Ok(None);
+    }
+    let Some(values) = parse_key_values(input) else {
+        return Ok(None);
+    };
+
+    let missing: Vec<&str> = params
+        .iter()
+        .filter(|param| param.required && !values.iter().any(|(key, _)| key == &param.name))
+        .map(|param| param.name.as_str())
+        .collect();
+    if !missing.is_empty() {
+        bail!("技能 `{name}` 缺少必填参数：{}", missing.join(", "));
+    }
+
+    let mut section = String::from("Parameters:");
+    for param in params {
+        let value = values
+            .iter()
+            .find(|(key, _)| key == &param.name)
+            .map_or("(not provided)", |(_, value)| value.as_str());
+        section.push_str(&format!("\n- {}: {value}", param.name));
+    }
+    for (key, value) in &values {
+        if !params.iter().any(|param| &param.name == key) {
+            section.push_str(&format!("\n- {key}: {value}"));
+        }
+    }
+    Ok(Some(section))
+}
+
+/// Splits `date=2024-05-01 title="周会 纪要"` into pairs. Returns `None` unless
+/// every whitespace-separated token is a `key=value` pair.
+fn parse_key_values(input: &str) -> Option<Vec<(String, String)>> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    for ch in input.trim().chars() {
+        match ch {
+            '"' => quoted = !quoted,
+            ch if ch.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            ch => token.push(ch),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    if tokens.is_empty() {
+        return None;
+    }
+
+    tokens
+        .into_iter()
+        .map(|token| {
+            let (key, value) = token.split_once('=')?;
+            let valid_key = !key.is_empty()
+                && key
+                    .chars()
+                    .all(|ch| ch.is_alphanumeric() || ch == '_' || ch == '-');
+            valid_key.then(|| (key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Splits a leading `---` front-matter block off `SKILL.md`, returning the
+/// block (if any) and the remaining body.
+fn split_front_matter(content: &str) -> (Option<&str>, &str) {
+    let Some(rest) = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))
+    else {
+        return (None, content);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (None, content)
+}
+
+/// Reads the front-matter `params:` list. Only the subset of YAML used for
+/// the list is understood: `- name: x` items with indented `required:` and
+/// `description:` keys, or bare `- x` names.
+pub fn parse_params(content: &str) -> Vec<SkillParam> {
+    let Some(front_matter) = split_front_matter(content).0 else {
+        return Vec::new();
+    };
+
+    let mut params: Vec<SkillParam> = Vec::new();
+    let mut in_params = false;
+    for line in front_matter.lines() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        if !line.starts_with([' ', '\t', '-']) {
+            in_params = line.trim_end() == "params:";
+            continue;
+        }
+        if !in_params {
+            continue;
+        }
+
+        let mut entry = line.trim();
+        if let Some(item) = entry.strip_prefix('-') {
+            params.push(SkillParam::default());
+            entry = item.trim();
+            if !entry.contains(':') {
+                if let Some(param) = params.last_mut() {
+                    param.name = unquote(entry).to_string();
+                }
+                continue;
+            }
+        }
+        let (Some(param), Some((key, value))) = (params.last_mut(), entry.split_once(':')) else {
+            continue;
+        };
+        let value = unquote(value.trim());
+        match key.trim() {
+            "name" => param.name = value.to_string(),
+            "required" => {
+                param.required = matches!(value.to_ascii_lowercase().as_str(), "true" | "yes")
+            }
+            "description" => param.description = value.to_string(),
+            _ => {}
+        }
+    }
+    params.retain(|param| !param.name.is_empty());
+    params
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .or_else(|| {
+            value
+                .strip_prefix('\'')
+                .and_then(|value| value.strip_suffix('\''))
+        })
+        .unwrap_or(value)
+}
+
+/// Resolves a `skill run` input: `-` reads `stdin` (unavailable in chat,
+/// where it is `None`), `@path` reads a file, a leading `@@` stands for a
+/// literal `@`, and anything else is used as-is.
+pub fn resolve_skill_input(raw: &str, stdin: Option<&mut dyn Read>) -> Result<String> {
+    let trimmed = raw.trim();
+    if trimmed.starts_with("@@") {
+        return Ok(raw.replacen("@@", "@", 1));
+    }
+    let input = if trimmed == "-" {
+        let Some(stdin) = stdin else {
+            bail!("此处不支持从标准输入读取技能输入，可改用 @文件路径");
+        };
+        let mut input = String::new();
+        stdin
+            .read_to_string(&mut input)
+            .context("读取标准输入失败")?;
+        input
+    } else if let Some(path) = trimmed.strip_prefix('@') {
+        let path = Path::new(path.trim());
+        fs::read_to_string(path)
+            .with_context(|| format!("读取技能输入文件失败: {}", path.display()))?
+    } else {
+        return Ok(raw.to_string());
+    };
+    if input.trim().is_empty() {
+        bail!("技能输入为空：{trimmed}");
+    }
+    Ok(input)
+}
+
+fn skill_system_prompt(
+    paths: &AgentPaths,
+    skill_content: &str,
+    backend: &str,
+    identity_guard: &str,
+    context: SkillContext,
+) -> Result<String> {
+    let context_section = match context {
+        SkillContext::Memory => format!(
+            "Memory context:\n{}\n\n",
+            memory::tail_context(paths, SKILL_CONTEXT_CHARS)?
+        ),
+        SkillContext::Runs => {
+            let runs = memory::recent_runs(paths, SKILL_CONTEXT_CHARS)?;
+            let runs = if runs.is_empty() {
+                "(no recent runs)".to_string()
+            } else {
+                runs
+            };
+            format!("Recent job and hook runs:\n{runs}\n\n")
+        }
+        SkillContext::None => String::new(),
+    };
+    Ok(format!(
+        "You are GoldAgent.\n\
+Current backend: {backend}.\n\n\
+Skill definition:\n{skill_content}\n\n{context_section}\
+{identity_guard}\n\
+Follow the skill faithfully and produce a concise response."
+    ))
+}
+
+/// Runs the self-correction loop under `budget`. When the step or time budget
+/// is exceeded, the error carries the last model output as partial progress.
+async fn run_with_budget<F, Fut>(
+    messages: Vec<ChatMessage>,
+    validation: Option<OutputValidation>,
+    max_corrections: u8,
+    budget: SkillBudget,
+    chat: F,
+) -> Result<String>
+where
+    F: Fn(Vec<ChatMessage>) -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    let steps = Cell::new(0u32);
+    let partial = RefCell::new(None::<String>);
+    let exceeded = Cell::new(false);
+    let run = run_with_self_correction(messages, validation, max_corrections, |messages| {
+        steps.set(steps.get() + 1);
+        let over_budget = budget.max_steps.is_some_and(|max| steps.get() > max);
+        let call = (!over_budget).then(|| chat(messages));
+        let (partial, exceeded) = (&partial, &exceeded);
+        async move {
+            let Some(call) = call else {
+                exceeded.set(true);
+                bail!("step budget exhausted");
+            };
+            let output = call.await?;
+            partial.replace(Some(output.clone()));
+            Ok(output)
+        }
+    });
+
+    let result = match budget.timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, run).await {
+            Ok(result) => result,
+            Err(_) => {
+                return Err(budget_error(
+                    format!("Skill exceeded time budget ({}s)", timeout.as_secs()),
+                    partial.take(),
+                ));
+            }
+        },
+        None => run.await,
+    };
+    if exceeded.get() {
+        let max = budget.max_steps.unwrap_or_default();
+        return Err(budget_error(
+            format!("Skill exceeded step budget ({max} model calls)"),
+            partial.take(),
+        ));
+    }
+    result
+}
+
+fn budget_error(message: String, partial: Option<String>) -> anyhow::Error {
+    match partial {
+        Some(partial) => anyhow!("{message}.\n部分结果：\n{partial}"),
+        None => anyhow!("{message}."),
+    }
+}
+
+/// Reads optional metadata lines such as `- 输出校验：json` /
+/// `- output_validation: json`, `- max_self_corrections: 3` and
+/// `- min_interval_secs: 600`.
+pub fn parse_metadata(content: &str) -> SkillMetadata {
+    let mut metadata = SkillMetadata::default();
+    for line in content.lines() {
+        let trimmed = line.trim().trim_start_matches("- ");
+        let Some((key, value)) = trimmed.split_once('：').or_else(|| trimmed.split_once(':'))
+        else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "输出校验" | "output_validation" if value.eq_ignore_ascii_case("json") => {
+                metadata.output_validation = Some(OutputValidation::Json);
+            }
+            "模型" | "model" if !value.is_empty() => {
+                metadata.model = Some(value.to_string());
+            }
+            "最大自我修正次数" | "max_self_corrections" => {
+                if let Ok(n) = value.parse::<u8>() {
+                    metadata.max_self_corrections = Some(n);
+                }
+            }
+            "最小运行间隔秒数" | "min_interval_secs" => {
+                if let Ok(secs) = value.parse::<u64>() {
+                    metadata.min_interval_secs = Some(secs);
+                }
+            }
+            _ => {}
+        }
+    }
+    metadata
 }
 
 fn extract_description(content: &str) -> String {
+    let content = split_front_matter(content).1;
     for line in content.lines() {
         let trimmed = line.trim();
         if let Some(value) = trimmed.strip_prefix("- 描述：") {
@@ -147,8 +980,402 @@ fn extract_description(content: &str) -> String {
 }
 
 fn normalize_skill_name(name: &str) -> String {
-    name.trim()
-        .replace(' ', "-")
-        .replace('/', "-")
-        .replace('\\', "-")
+    name.trim().replace([' ', '/', '\\'], "-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        SKILL_CACHE, SkillBudget, SkillContext, SkillParam, claim_cooldown, create_skill,
+        export_skill, import_skill, list_skills, load_stats, parameter_section, parse_metadata,
+        parse_params, record_run, remove_skill, rename_skill, resolve_skill_input, run_with_budget,
+        skill_info, skill_system_prompt,
+    };
+    use crate::config::AgentPaths;
+    use crate::memory;
+    use crate::provider::ChatMessage;
+    use crate::response_format::{OutputValidation, run_with_self_correction};
+    use chrono::Local;
+    use std::cell::Cell;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+    use uuid::Uuid;
+
+    fn scan_count(dir: &Path) -> usize {
+        SKILL_CACHE
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|entries| entries.get(dir))
+            .map_or(0, |entry| entry.scans)
+    }
+
+    #[test]
+    fn runs_context_injects_recent_run_logs_into_prompt() {
+        let root = env::temp_dir().join(format!("goldagent-skill-context-test-{}", Uuid::new_v4()));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(&paths.memory_dir).unwrap();
+        memory::append_short_term(&paths, "chat", "user prefers short answers").unwrap();
+        memory::append_short_term(
+            &paths,
+            "cron.job-1",
+            "job=job-1 name=backup status=success code=0",
+        )
+        .unwrap();
+        memory::append_short_term(
+            &paths,
+            "hook.hook-1",
+            "hook=hook-1 name=repo status=failed attempt=1/1",
+        )
+        .unwrap();
+
+        let prompt =
+            |context| skill_system_prompt(&paths, "# daily", "openai", "guard", context).unwrap();
+        let runs = prompt(SkillContext::Runs);
+        assert!(runs.contains("Recent job and hook runs:"));
+        assert!(runs.contains("job=job-1 name=backup status=success"));
+        assert!(runs.contains("hook=hook-1 name=repo status=failed"));
+        assert!(!runs.contains("user prefers short answers"));
+        assert!(runs.find("job=job-1") < runs.find("hook=hook-1"));
+
+        assert!(prompt(SkillContext::Memory).contains("user prefers short answers"));
+        let none = prompt(SkillContext::None);
+        assert!(!none.contains("job=job-1") && !none.contains("Memory context"));
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn skill_info_reports_metadata_and_run_count() {
+        let root = env::temp_dir().join(format!("goldagent-skills-test-{}", Uuid::new_v4()));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(&paths.skills_dir).unwrap();
+        let skill_file = create_skill(&paths, "daily").unwrap();
+        let content = fs::read_to_string(&skill_file).unwrap();
+        fs::write(
+            &skill_file,
+            content.replace("- 版本：v1\n", "- 版本：v1\n- 模型：gpt-5.2\n"),
+        )
+        .unwrap();
+
+        assert_eq!(skill_info(&paths, "daily").unwrap().stats.runs, 0);
+        record_run(&paths, "daily").unwrap();
+        record_run(&paths, "daily").unwrap();
+
+        let details = skill_info(&paths, "daily").unwrap();
+        assert_eq!(details.stats.runs, 2);
+        assert!(details.stats.last_run_at.is_some());
+        assert_eq!(details.metadata.model.as_deref(), Some("gpt-5.2"));
+        assert_eq!(
+            details.parameters,
+            vec![
+                "用户输入：自然语言或结构化参数。",
+                "上下文：可选的记忆、系统状态或外部事件。"
+            ]
+        );
+        assert!(skill_info(&paths, "missing").is_err());
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn cooldown_rejects_a_second_rapid_run() {
+        let root = env::temp_dir().join(format!("goldagent-skills-test-{}", Uuid::new_v4()));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(&paths.skills_dir).unwrap();
+        let skill_file = create_skill(&paths, "paid-api").unwrap();
+        let content = fs::read_to_string(&skill_file).unwrap();
+        fs::write(
+            &skill_file,
+            content.replace("- 版本：v1\n", "- 版本：v1\n- min_interval_secs: 600\n"),
+        )
+        .unwrap();
+        let cooldown = skill_info(&paths, "paid-api")
+            .unwrap()
+            .metadata
+            .min_interval_secs;
+        assert_eq!(cooldown, Some(600));
+
+        let now = Local::now();
+        // The claim alone starts the cooldown, even if the run never finishes.
+        claim_cooldown(&paths, "paid-api", cooldown, now).unwrap();
+        let err = claim_cooldown(&paths, "paid-api", cooldown, Local::now())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("冷却中"), "{err}");
+        assert_eq!(load_stats(&paths, "paid-api").unwrap().runs, 0);
+        let later = now + chrono::Duration::seconds(601);
+        claim_cooldown(&paths, "paid-api", cooldown, later).unwrap();
+        assert!(claim_cooldown(&paths, "paid-api", cooldown, later).is_err());
+        claim_cooldown(&paths, "paid-api", None, Local::now()).unwrap();
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn skill_input_reads_stdin_dash_and_at_files() {
+        let mut stdin = std::io::Cursor::new("会议记录\n第二行\n");
+        assert_eq!(
+            resolve_skill_input("-", Some(&mut stdin)).unwrap(),
+            "会议记录\n第二行\n"
+        );
+        assert!(resolve_skill_input("-", None).is_err());
+        let mut empty = std::io::Cursor::new("  \n");
+        assert!(resolve_skill_input("-", Some(&mut empty)).is_err());
+
+        let file = env::temp_dir().join(format!("goldagent-skill-input-{}.txt", Uuid::new_v4()));
+        fs::write(&file, "transcript body").unwrap();
+        let from_file = resolve_skill_input(&format!("@{}", file.display()), None).unwrap();
+        assert_eq!(from_file, "transcript body");
+        assert!(resolve_skill_input("@/nonexistent/goldagent-input.txt", None).is_err());
+        assert_eq!(
+            resolve_skill_input("@@alice 请跟进", None).unwrap(),
+            "@alice 请跟进"
+        );
+        assert_eq!(
+            resolve_skill_input("plain text", None).unwrap(),
+            "plain text"
+        );
+        let _ = fs::remove_file(&file);
+    }
+
+    #[test]
+    fn front_matter_params_validate_key_value_input() {
+        let root = env::temp_dir().join(format!("goldagent-skills-test-{}", Uuid::new_v4()));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(paths.skills_dir.join("minutes")).unwrap();
+        let content = "---\nparams:\n  - name: date\n    required: true\n    description: 会议日期\n  - tone\n---\n# minutes\n\n- 描述：整理会议纪要\n";
+        fs::write(paths.skills_dir.join("minutes/SKILL.md"), content).unwrap();
+
+        let params = parse_params(content);
+        assert_eq!(
+            params,
+            vec![
+                SkillParam {
+                    name: "date".to_string(),
+                    required: true,
+                    description: "会议日期".to_string(),
+                },
+                SkillParam {
+                    name: "tone".to_string(),
+                    ..SkillParam::default()
+                },
+            ]
+        );
+
+        let section = parameter_section("minutes", &params, r#"date=2024-05-01 topic="周会 纪要""#)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            section,
+            "Parameters:\n- date: 2024-05-01\n- tone: (not provided)\n- topic: 周会 纪要"
+        );
+        let err = parameter_section("minutes", &params, "tone=brief").unwrap_err();
+        assert!(err.to_string().contains("缺少必填参数：date"));
+        // Free text and skills without front matter are passed through as-is.
+        assert_eq!(
+            parameter_section("minutes", &params, "整理今天的会").unwrap(),
+            None
+        );
+        assert!(parse_params("# plain\n\n- 描述：x\n").is_empty());
+
+        let skills = list_skills(&paths).unwrap();
+        assert_eq!(skills[0].description, "整理会议纪要（参数：date*, tone）");
+        assert_eq!(
+            skill_info(&paths, "minutes").unwrap().parameters,
+            vec!["date（必填）：会议日期", "tone"]
+        );
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn remove_and_rename_guard_seeded_skill_and_extra_files() {
+        let root = env::temp_dir().join(format!("goldagent-skills-test-{}", Uuid::new_v4()));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(&paths.skills_dir).unwrap();
+        create_skill(&paths, "daily-summary").unwrap();
+        create_skill(&paths, "draft").unwrap();
+        record_run(&paths, "draft").unwrap();
+
+        let renamed = rename_skill(&paths, "draft", "weekly report", false).unwrap();
+        assert_eq!(renamed, paths.skills_dir.join("weekly-report"));
+        assert!(renamed.join("SKILL.md").is_file());
+        assert!(!paths.skills_dir.join("draft").exists());
+        assert!(rename_skill(&paths, "weekly-report", "daily-summary", false).is_err());
+        assert!(rename_skill(&paths, "../skills", "x", false).is_err());
+
+        fs::write(renamed.join("helper.sh"), "echo hi").unwrap();
+        let err = remove_skill(&paths, "weekly-report", false).unwrap_err();
+        assert!(err.to_string().contains("helper.sh"));
+        remove_skill(&paths, "weekly-report", true).unwrap();
+        assert!(!renamed.exists());
+
+        let err = remove_skill(&paths, "daily-summary", false).unwrap_err();
+        assert!(err.to_string().contains("--force"));
+        assert!(rename_skill(&paths, "daily-summary", "mine", false).is_err());
+        remove_skill(&paths, "daily-summary", true).unwrap();
+        assert!(list_skills(&paths).unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn exported_skill_imports_into_another_home() {
+        let source = AgentPaths::from_root(
+            env::temp_dir().join(format!("goldagent-skills-test-{}", Uuid::new_v4())),
+        );
+        let target = AgentPaths::from_root(
+            env::temp_dir().join(format!("goldagent-skills-test-{}", Uuid::new_v4())),
+        );
+        fs::create_dir_all(&target.skills_dir).unwrap();
+        let skill_file = create_skill(&source, "meeting notes").unwrap();
+        let skill_dir = skill_file.parent().unwrap();
+        fs::create_dir_all(skill_dir.join("scripts")).unwrap();
+        fs::write(
+            skill_dir.join("scripts/fetch.sh"),
+            "echo '<!-- file x bytes=1 -->'",
+        )
+        .unwrap();
+        record_run(&source, "meeting-notes").unwrap();
+
+        let bundle = export_skill(&source, "meeting-notes").unwrap();
+        assert!(bundle.starts_with("<!-- goldagent-skill v1 name=meeting-notes -->\n"));
+        assert!(!bundle.contains(".stats.json"));
+
+        let dir = import_skill(&target, &bundle, false).unwrap();
+        assert_eq!(dir, target.skills_dir.join("meeting-notes"));
+        assert_eq!(
+            fs::read_to_string(dir.join("SKILL.md")).unwrap(),
+            fs::read_to_string(&skill_file).unwrap()
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("scripts/fetch.sh")).unwrap(),
+            "echo '<!-- file x bytes=1 -->'"
+        );
+        assert_eq!(list_skills(&target).unwrap()[0].name, "meeting-notes");
+
+        let err = import_skill(&target, &bundle, false).unwrap_err();
+        assert!(err.to_string().contains("--force"));
+        import_skill(&target, &bundle, true).unwrap();
+        let entries = fs::read_dir(&target.skills_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(entries, ["meeting-notes"]);
+
+        let with_stats = "<!-- goldagent-skill v1 name=meeting-notes -->\n\
+<!-- file SKILL.md bytes=2 -->\nhi\n<!-- file .stats.json bytes=2 -->\n{}\n";
+        let err = import_skill(&target, with_stats, true).unwrap_err();
+        assert!(err.to_string().contains(".stats.json"), "{err}");
+        assert!(dir.join("scripts/fetch.sh").exists());
+
+        let unsafe_bundle =
+            "<!-- goldagent-skill v1 name=x -->\n<!-- file ../evil bytes=2 -->\nhi\n";
+        assert!(import_skill(&target, unsafe_bundle, false).is_err());
+        let missing = "<!-- goldagent-skill v1 name=x -->\n<!-- file a.md bytes=2 -->\nhi\n";
+        let err = import_skill(&target, missing, false).unwrap_err();
+        assert!(err.to_string().contains("SKILL.md"));
+
+        let _ = fs::remove_dir_all(&source.root);
+        let _ = fs::remove_dir_all(&target.root);
+    }
+
+    #[test]
+    fn repeated_listing_reads_skills_once_until_a_skill_is_added() {
+        let root = env::temp_dir().join(format!("goldagent-skills-test-{}", Uuid::new_v4()));
+        let paths = AgentPaths::from_root(root);
+        fs::create_dir_all(paths.skills_dir.join("alpha")).unwrap();
+        fs::write(paths.skills_dir.join("alpha/SKILL.md"), "- 描述：first\n").unwrap();
+
+        assert_eq!(list_skills(&paths).unwrap().len(), 1);
+        assert_eq!(list_skills(&paths).unwrap().len(), 1);
+        assert_eq!(scan_count(&paths.skills_dir), 1);
+
+        create_skill(&paths, "beta").unwrap();
+        let names = list_skills(&paths)
+            .unwrap()
+            .into_iter()
+            .map(|skill| skill.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["alpha", "beta"]);
+
+        let _ = fs::remove_dir_all(&paths.root);
+    }
+
+    #[test]
+    fn parses_validation_metadata() {
+        let content = "元信息：\n- 名称：demo\n- 输出校验：json\n- max_self_corrections: 3\n";
+        let metadata = parse_metadata(content);
+        assert_eq!(metadata.output_validation, Some(OutputValidation::Json));
+        assert_eq!(metadata.max_self_corrections, Some(3));
+    }
+
+    #[tokio::test]
+    async fn halts_skill_that_exceeds_step_budget() {
+        let calls = Cell::new(0);
+        let budget = SkillBudget {
+            timeout: None,
+            max_steps: Some(2),
+        };
+        let err = run_with_budget(
+            vec![ChatMessage::user("give me json")],
+            Some(OutputValidation::Json),
+            5,
+            budget,
+            |_| {
+                calls.set(calls.get() + 1);
+                let attempt = calls.get();
+                async move { Ok(format!("still not json #{attempt}")) }
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(calls.get(), 2);
+        let message = err.to_string();
+        assert!(message.contains("exceeded step budget"), "{message}");
+        assert!(message.contains("still not json #2"), "{message}");
+    }
+
+    #[tokio::test]
+    async fn halts_skill_that_exceeds_time_budget() {
+        let budget = SkillBudget {
+            timeout: Some(std::time::Duration::from_millis(20)),
+            max_steps: None,
+        };
+        let err = run_with_budget(vec![ChatMessage::user("hi")], None, 0, budget, |_| async {
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            Ok("late".to_string())
+        })
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("exceeded time budget"));
+    }
+
+    #[tokio::test]
+    async fn self_correction_recovers_from_invalid_json() {
+        let calls = Cell::new(0);
+        let output = run_with_self_correction(
+            vec![ChatMessage::user("give me json")],
+            Some(OutputValidation::Json),
+            2,
+            |messages| {
+                calls.set(calls.get() + 1);
+                let attempt = calls.get();
+                async move {
+                    if attempt == 1 {
+                        Ok("not json".to_string())
+                    } else {
+                        assert!(messages.last().unwrap().content.contains("JSON"));
+                        Ok("```json\n{\"ok\": true}\n```".to_string())
+                    }
+                }
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(calls.get(), 2);
+        assert!(output.contains("\"ok\": true"));
+    }
 }
@@ -0,0 +1,129 @@
+use crate::usage::UsageCounter;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Probe results kept for the latency trend line.
+const LATENCY_HISTORY: usize = 8;
+
+/// Outcome of one connectivity probe; `latency` is `None` when it failed.
+#[derive(Debug, Clone)]
+pub struct Probe {
+    pub addr: String,
+    pub latency: Option<Duration>,
+}
+
+/// State carried across `connect status --watch` refreshes, kept apart from
+/// the terminal so each frame can be tested directly.
+#[derive(Debug, Default)]
+pub struct StatusWatch {
+    baseline: Option<UsageCounter>,
+    previous: Option<UsageCounter>,
+    latencies: VecDeque<Option<Duration>>,
+}
+
+impl StatusWatch {
+    /// Assembles one refresh: the `connect status` lines, usage added since
+    /// the last refresh and since watching started, and the probe trend.
+    pub fn frame(
+        &mut self,
+        status: Vec<String>,
+        total: &UsageCounter,
+        probe: Option<Probe>,
+    ) -> Vec<String> {
+        let baseline = self.baseline.get_or_insert_with(|| total.clone());
+        let since_start = usage_delta(baseline, total);
+        let since_last = usage_delta(self.previous.as_ref().unwrap_or(baseline), total);
+        self.previous = Some(total.clone());
+
+        let mut lines = status;
+        lines.push(format!("- 本次刷新新增: {}", format_delta(&since_last)));
+        lines.push(format!("- 监看期间新增: {}", format_delta(&since_start)));
+        if let Some(probe) = probe {
+            if self.latencies.len() == LATENCY_HISTORY {
+                self.latencies.pop_front();
+            }
+            self.latencies.push_back(probe.latency);
+            let trend = self
+                .latencies
+                .iter()
+                .map(|latency| match latency {
+                    Some(latency) => format!("{}ms", latency.as_millis()),
+                    None => "超时".to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(" → ");
+            lines.push(format!("- 连接延迟({}): {trend}", probe.addr));
+        }
+        lines
+    }
+}
+
+/// Growth from `from` to `to`; a usage reset in between counts as zero.
+fn usage_delta(from: &UsageCounter, to: &UsageCounter) -> UsageCounter {
+    UsageCounter {
+        requests: to.requests.saturating_sub(from.requests),
+        input_tokens: to.input_tokens.saturating_sub(from.input_tokens),
+        output_tokens: to.output_tokens.saturating_sub(from.output_tokens),
+    }
+}
+
+fn format_delta(delta: &UsageCounter) -> String {
+    format!(
+        "请求 +{} 次, 输入 +{} tokens, 输出 +{} tokens",
+        delta.requests, delta.input_tokens, delta.output_tokens
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Probe, StatusWatch};
+    use crate::usage::UsageCounter;
+    use std::time::Duration;
+
+    fn counter(requests: u64, input_tokens: u64, output_tokens: u64) -> UsageCounter {
+        UsageCounter {
+            requests,
+            input_tokens,
+            output_tokens,
+        }
+    }
+
+    fn probe(latency_ms: Option<u64>) -> Option<Probe> {
+        Some(Probe {
+            addr: "api.example.com:443".to_string(),
+            latency: latency_ms.map(Duration::from_millis),
+        })
+    }
+
+    #[test]
+    fn frames_report_usage_deltas_and_latency_trend() {
+        let mut watch = StatusWatch::default();
+        let status = || vec!["当前连接状态：".to_string()];
+
+        let first = watch.frame(status(), &counter(10, 1_000, 500), probe(Some(42)));
+        assert_eq!(
+            first,
+            [
+                "当前连接状态：",
+                "- 本次刷新新增: 请求 +0 次, 输入 +0 tokens, 输出 +0 tokens",
+                "- 监看期间新增: 请求 +0 次, 输入 +0 tokens, 输出 +0 tokens",
+                "- 连接延迟(api.example.com:443): 42ms",
+            ]
+        );
+
+        watch.frame(status(), &counter(12, 1_300, 650), probe(None));
+        let third = watch.frame(status(), &counter(13, 1_350, 700), probe(Some(38)));
+        assert_eq!(
+            &third[1..],
+            [
+                "- 本次刷新新增: 请求 +1 次, 输入 +50 tokens, 输出 +50 tokens",
+                "- 监看期间新增: 请求 +3 次, 输入 +350 tokens, 输出 +200 tokens",
+                "- 连接延迟(api.example.com:443): 42ms → 超时 → 38ms",
+            ]
+        );
+
+        let without_probe = watch.frame(status(), &counter(0, 0, 0), None);
+        assert_eq!(without_probe.len(), 3);
+        assert!(without_probe[1].ends_with("请求 +0 次, 输入 +0 tokens, 输出 +0 tokens"));
+    }
+}
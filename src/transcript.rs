@@ -0,0 +1,107 @@
+use crate::config::AgentPaths;
+use crate::fsutil;
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// Appends one `--append` section: a dated `##` header naming the source,
+/// the prompt as a quote, then the response.
+pub fn append_entry(
+    paths: &AgentPaths,
+    path: &Path,
+    title: &str,
+    prompt: &str,
+    response: &str,
+    now: DateTime<Local>,
+) -> Result<()> {
+    let quoted = prompt
+        .trim()
+        .lines()
+        .map(|line| format!("> {line}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let section = format!(
+        "## {} · {title}\n\n{quoted}\n\n{}\n\n",
+        now.format("%Y-%m-%d %H:%M:%S"),
+        response.trim_end()
+    );
+    fsutil::append_with_lock(path, &lock_target(paths, path), section)
+}
+
+/// Lock for appends to `path`, kept in `locks_dir` rather than next to the
+/// user's file and keyed by its absolute path, so every process appending to
+/// the same file shares it.
+fn lock_target(paths: &AgentPaths, path: &Path) -> PathBuf {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let absolute = fs::canonicalize(dir)
+        .unwrap_or_else(|_| dir.to_path_buf())
+        .join(path.file_name().unwrap_or_default());
+    let mut hasher = DefaultHasher::new();
+    absolute.hash(&mut hasher);
+    paths
+        .locks_dir
+        .join(format!("append-{:016x}", hasher.finish()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{append_entry, lock_target};
+    use crate::config::AgentPaths;
+    use chrono::{Local, TimeZone};
+    use std::fs;
+    use uuid::Uuid;
+
+    #[test]
+    fn appends_timestamped_sections_in_order() {
+        let dir =
+            std::env::temp_dir().join(format!("goldagent-transcript-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let paths = AgentPaths::from_root(dir.join("home"));
+        let path = dir.join("report.md");
+        let first = Local.with_ymd_and_hms(2026, 10, 18, 9, 0, 0).unwrap();
+        let second = Local.with_ymd_and_hms(2026, 10, 18, 9, 5, 30).unwrap();
+
+        append_entry(&paths, &path, "run", "总结今天", "完成了三件事", first).unwrap();
+        append_entry(
+            &paths,
+            &path,
+            "skill daily-summary",
+            "明天计划",
+            "写测试",
+            second,
+        )
+        .unwrap();
+
+        let transcript = fs::read_to_string(&path).unwrap();
+        let headers = transcript
+            .lines()
+            .filter(|line| line.starts_with("## "))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            headers,
+            [
+                "## 2026-10-18 09:00:00 · run",
+                "## 2026-10-18 09:05:30 · skill daily-summary"
+            ]
+        );
+        let first_at = transcript.find("完成了三件事").unwrap();
+        let second_at = transcript.find("写测试").unwrap();
+        assert!(first_at < second_at);
+        assert!(transcript.contains("> 明天计划"));
+
+        assert!(!dir.join("report.md.lock").exists());
+        assert!(lock_target(&paths, &path).starts_with(&paths.locks_dir));
+        assert_eq!(
+            lock_target(&paths, &path),
+            lock_target(&paths, &dir.join(".").join("report.md"))
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,7 +1,11 @@
-use anyhow::{Context, Result};
-use chrono::Local;
+use crate::config::BudgetConfig;
+use crate::fsutil::{self, FileLock};
+use anyhow::{Context, Result, bail};
+use chrono::{Local, NaiveDate};
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::env;
 use std::fs;
 use std::path::Path;
 
@@ -23,6 +27,10 @@ pub struct UsageStats {
     pub by_day: BTreeMap<String, UsageCounter>,
     #[serde(default)]
     pub by_model: BTreeMap<String, UsageCounter>,
+    /// Per-day model breakdown, so reports can filter models by date and
+    /// price each day. Only present for usage recorded after it was added.
+    #[serde(default)]
+    pub by_day_model: BTreeMap<String, BTreeMap<String, UsageCounter>>,
     #[serde(default)]
     pub updated_at: Option<String>,
 }
@@ -48,20 +56,31 @@ pub fn load(path: &Path) -> Result<UsageStats> {
 
 pub fn save(path: &Path, stats: &UsageStats) -> Result<()> {
     let raw = serde_json::to_string_pretty(stats)?;
-    fs::write(path, format!("{raw}\n"))
+    fsutil::write_atomic(path, format!("{raw}\n"))
         .with_context(|| format!("写入用量文件失败: {}", path.display()))?;
     Ok(())
 }
 
+/// Adds one request to the usage file. The read-modify-write cycle runs under
+/// a file lock because chat and the scheduler may record usage concurrently.
 pub fn record(path: &Path, event: &UsageEvent) -> Result<()> {
+    let _lock = FileLock::acquire(path)?;
     let mut stats = load(path).unwrap_or_default();
 
     add_counter(&mut stats.total, event);
 
     let day_key = Local::now().format("%Y-%m-%d").to_string();
-    let day = stats.by_day.entry(day_key).or_default();
+    let day = stats.by_day.entry(day_key.clone()).or_default();
     add_counter(day, event);
 
+    let day_model = stats
+        .by_day_model
+        .entry(day_key)
+        .or_default()
+        .entry(event.model_key.clone())
+        .or_default();
+    add_counter(day_model, event);
+
     let model = stats.by_model.entry(event.model_key.clone()).or_default();
     add_counter(model, event);
 
@@ -70,8 +89,523 @@ pub fn record(path: &Path, event: &UsageEvent) -> Result<()> {
     Ok(())
 }
 
+/// Part of `usage.json` cleared by `goldagent usage reset`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ResetScope {
+    #[default]
+    All,
+    Total,
+    /// Daily totals, together with their per-model breakdown.
+    ByDay,
+    ByModel,
+}
+
+impl ResetScope {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::All => "全部用量统计",
+            Self::Total => "累计用量（total）",
+            Self::ByDay => "按日期统计（by_day）",
+            Self::ByModel => "按模型统计（by_model）",
+        }
+    }
+}
+
+/// Clears `scope` and stamps `updated_at` with the reset time.
+pub fn reset(path: &Path, scope: ResetScope) -> Result<UsageStats> {
+    let _lock = FileLock::acquire(path)?;
+    let mut stats = match scope {
+        ResetScope::All => UsageStats::default(),
+        _ => load(path)?,
+    };
+    match scope {
+        ResetScope::All => {}
+        ResetScope::Total => stats.total = UsageCounter::default(),
+        ResetScope::ByDay => {
+            stats.by_day.clear();
+            stats.by_day_model.clear();
+        }
+        ResetScope::ByModel => stats.by_model.clear(),
+    }
+    stats.updated_at = Some(Local::now().to_rfc3339());
+    save(path, &stats)?;
+    Ok(stats)
+}
+
+/// Returns the `limit` heaviest entries of a `by_model`/`by_day` map, ordered
+/// by output tokens (the costlier side), then input tokens.
+pub fn top_entries(
+    entries: &BTreeMap<String, UsageCounter>,
+    limit: usize,
+) -> Vec<(&str, &UsageCounter)> {
+    let mut ranked = entries
+        .iter()
+        .map(|(key, counter)| (key.as_str(), counter))
+        .collect::<Vec<_>>();
+    ranked.sort_by(|(_, a), (_, b)| {
+        b.output_tokens
+            .cmp(&a.output_tokens)
+            .then(b.input_tokens.cmp(&a.input_tokens))
+    });
+    ranked.truncate(limit);
+    ranked
+}
+
+/// Share of a monthly limit at which `connect status` starts warning.
+pub const BUDGET_WARN_RATIO: f64 = 0.8;
+
+/// Usage of the calendar month `month` (`YYYY-MM`), summed from `by_day`.
+pub fn month_usage(stats: &UsageStats, month: &str) -> UsageCounter {
+    let mut counter = UsageCounter::default();
+    for (_, day) in stats.by_day.iter().filter(|(day, _)| {
+        day.strip_prefix(month)
+            .is_some_and(|rest| rest.starts_with('-'))
+    }) {
+        merge_counter(&mut counter, day);
+    }
+    counter
+}
+
+pub fn current_month() -> String {
+    Local::now().format("%Y-%m").to_string()
+}
+
+#[derive(Debug, Clone)]
+pub struct BudgetStatus {
+    pub used: UsageCounter,
+    pub input_ratio: Option<f64>,
+    pub output_ratio: Option<f64>,
+}
+
+impl BudgetStatus {
+    pub fn exceeded(&self) -> bool {
+        self.max_ratio().is_some_and(|ratio| ratio >= 1.0)
+    }
+
+    pub fn max_ratio(&self) -> Option<f64> {
+        match (self.input_ratio, self.output_ratio) {
+            (Some(input), Some(output)) => Some(input.max(output)),
+            (ratio, None) | (None, ratio) => ratio,
+        }
+    }
+}
+
+pub fn budget_status(budget: &BudgetConfig, stats: &UsageStats, month: &str) -> BudgetStatus {
+    let used = month_usage(stats, month);
+    let ratio = |used: u64, limit: Option<u64>| {
+        limit.map(|limit| {
+            if limit == 0 {
+                f64::INFINITY
+            } else {
+                used as f64 / limit as f64
+            }
+        })
+    };
+    BudgetStatus {
+        input_ratio: ratio(used.input_tokens, budget.monthly_input_tokens),
+        output_ratio: ratio(used.output_tokens, budget.monthly_output_tokens),
+        used,
+    }
+}
+
+/// Refuses a new request once this month's usage has reached a configured
+/// limit, unless `GOLDAGENT_BUDGET_OVERRIDE=1` is set.
+pub fn enforce_budget(path: &Path, budget: &BudgetConfig) -> Result<()> {
+    if !budget.is_set() || env::var("GOLDAGENT_BUDGET_OVERRIDE").is_ok_and(|v| v.trim() == "1") {
+        return Ok(());
+    }
+    let stats = load(path)?;
+    let status = budget_status(budget, &stats, &current_month());
+    if status.exceeded() {
+        bail!(
+            "本月 token 预算已用尽（{}）。如需继续，请调整 config.json 中的 budget 或设置 GOLDAGENT_BUDGET_OVERRIDE=1",
+            budget_summary(budget, &status.used)
+        );
+    }
+    Ok(())
+}
+
+/// `输入 used/limit, 输出 used/limit` for the limits that are set.
+pub fn budget_summary(budget: &BudgetConfig, used: &UsageCounter) -> String {
+    let mut parts = Vec::new();
+    if let Some(limit) = budget.monthly_input_tokens {
+        parts.push(format!("输入 {}/{limit}", used.input_tokens));
+    }
+    if let Some(limit) = budget.monthly_output_tokens {
+        parts.push(format!("输出 {}/{limit}", used.output_tokens));
+    }
+    parts.join(", ")
+}
+
+/// USD per 1K tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_1k: f64,
+    pub output_per_1k: f64,
+}
+
+/// Rough list prices keyed by `provider:model` prefix; the longest matching
+/// prefix wins. Override or extend them with `prices.json`.
+const BUILTIN_PRICES: &[(&str, f64, f64)] = &[
+    ("openai:gpt-4o-mini", 0.000_15, 0.000_6),
+    ("openai:gpt-4o", 0.002_5, 0.01),
+    ("openai:gpt-4.1-mini", 0.000_4, 0.001_6),
+    ("openai:gpt-4.1", 0.002, 0.008),
+    ("openai:gpt-5", 0.001_25, 0.01),
+    ("anthropic:claude-3-5-haiku", 0.000_8, 0.004),
+    ("anthropic:claude-haiku", 0.001, 0.005),
+    ("anthropic:claude-sonnet", 0.003, 0.015),
+    ("anthropic:claude-opus", 0.015, 0.075),
+    ("zhipu:glm-4-flash", 0.0, 0.0),
+    ("zhipu:glm", 0.000_7, 0.000_7),
+    ("deepseek:deepseek-chat", 0.000_27, 0.001_1),
+    ("deepseek:deepseek-reasoner", 0.000_55, 0.002_19),
+    ("ollama:", 0.0, 0.0),
+];
+
+/// Built-in prices merged with the entries of a JSON file of the form
+/// `{"openai:gpt-4o": {"input_per_1k": 0.0025, "output_per_1k": 0.01}}`.
+pub fn load_prices(path: &Path) -> Result<BTreeMap<String, ModelPrice>> {
+    let mut prices = BUILTIN_PRICES
+        .iter()
+        .map(|&(prefix, input_per_1k, output_per_1k)| {
+            (
+                prefix.to_string(),
+                ModelPrice {
+                    input_per_1k,
+                    output_per_1k,
+                },
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
+    if path.exists() {
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("读取价格文件失败: {}", path.display()))?;
+        let overrides = serde_json::from_str::<BTreeMap<String, ModelPrice>>(&raw)
+            .with_context(|| format!("解析价格文件失败: {}", path.display()))?;
+        prices.extend(overrides);
+    }
+    Ok(prices)
+}
+
+pub fn price_for<'a>(
+    prices: &'a BTreeMap<String, ModelPrice>,
+    model_key: &str,
+) -> Option<&'a ModelPrice> {
+    prices
+        .iter()
+        .filter(|(prefix, _)| model_key.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, price)| price)
+}
+
+pub fn estimate_cost(price: &ModelPrice, counter: &UsageCounter) -> f64 {
+    counter.input_tokens as f64 / 1000.0 * price.input_per_1k
+        + counter.output_tokens as f64 / 1000.0 * price.output_per_1k
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageRow {
+    pub key: String,
+    #[serde(flatten)]
+    pub counter: UsageCounter,
+    /// Estimated USD; `None` when a model has no known price or the day
+    /// predates the per-model breakdown.
+    pub cost: Option<f64>,
+}
+
+/// One row per day on or after `since`. A day is priced from its model
+/// breakdown and left without a cost if any of its models is unpriced.
+pub fn report_by_day(
+    stats: &UsageStats,
+    since: Option<NaiveDate>,
+    prices: &BTreeMap<String, ModelPrice>,
+) -> Vec<UsageRow> {
+    stats
+        .by_day
+        .iter()
+        .filter(|(day, _)| day_on_or_after(day, since))
+        .map(|(day, counter)| {
+            let cost = stats.by_day_model.get(day).and_then(|models| {
+                models.iter().try_fold(0.0, |sum, (model, counter)| {
+                    price_for(prices, model).map(|price| sum + estimate_cost(price, counter))
+                })
+            });
+            UsageRow {
+                key: day.clone(),
+                counter: counter.clone(),
+                cost,
+            }
+        })
+        .collect()
+}
+
+/// One row per model. Without `since` this is the all-time total; with it
+/// only days that carry a per-model breakdown can be counted.
+pub fn report_by_model(
+    stats: &UsageStats,
+    since: Option<NaiveDate>,
+    prices: &BTreeMap<String, ModelPrice>,
+) -> Vec<UsageRow> {
+    let totals = match since {
+        None => stats.by_model.clone(),
+        Some(_) => {
+            let mut totals = BTreeMap::<String, UsageCounter>::new();
+            for (_, models) in stats
+                .by_day_model
+                .iter()
+                .filter(|(day, _)| day_on_or_after(day, since))
+            {
+                for (model, counter) in models {
+                    merge_counter(totals.entry(model.clone()).or_default(), counter);
+                }
+            }
+            totals
+        }
+    };
+    totals
+        .into_iter()
+        .map(|(model, counter)| UsageRow {
+            cost: price_for(prices, &model).map(|price| estimate_cost(price, &counter)),
+            key: model,
+            counter,
+        })
+        .collect()
+}
+
+/// Sums report rows; the cost is only known when every row has one.
+pub fn report_total(rows: &[UsageRow]) -> UsageRow {
+    let mut counter = UsageCounter::default();
+    for row in rows {
+        merge_counter(&mut counter, &row.counter);
+    }
+    UsageRow {
+        key: "total".to_string(),
+        counter,
+        cost: rows
+            .iter()
+            .try_fold(0.0, |sum, row| row.cost.map(|cost| sum + cost)),
+    }
+}
+
+fn day_on_or_after(day: &str, since: Option<NaiveDate>) -> bool {
+    match since {
+        None => true,
+        Some(since) => NaiveDate::parse_from_str(day, "%Y-%m-%d").is_ok_and(|day| day >= since),
+    }
+}
+
+fn merge_counter(counter: &mut UsageCounter, other: &UsageCounter) {
+    counter.requests += other.requests;
+    counter.input_tokens += other.input_tokens;
+    counter.output_tokens += other.output_tokens;
+}
+
 fn add_counter(counter: &mut UsageCounter, event: &UsageEvent) {
     counter.requests += 1;
     counter.input_tokens += event.input_tokens;
     counter.output_tokens += event.output_tokens;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ModelPrice, ResetScope, UsageCounter, UsageEvent, UsageStats, budget_status, load,
+        load_prices, month_usage, record, report_by_day, report_by_model, report_total, reset,
+        top_entries,
+    };
+    use crate::config::BudgetConfig;
+    use chrono::NaiveDate;
+    use std::collections::BTreeMap;
+    use std::env;
+    use std::fs;
+    use std::thread;
+    use uuid::Uuid;
+
+    #[test]
+    fn concurrent_records_do_not_lose_increments() {
+        let dir = env::temp_dir().join(format!("goldagent-usage-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("usage.json");
+
+        let workers = (0..2)
+            .map(|_| {
+                let path = path.clone();
+                thread::spawn(move || {
+                    for _ in 0..25 {
+                        let event = UsageEvent {
+                            model_key: "openai:test".to_string(),
+                            input_tokens: 3,
+                            output_tokens: 2,
+                        };
+                        record(&path, &event).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let stats = load(&path).unwrap();
+        assert_eq!(stats.total.requests, 50);
+        assert_eq!(stats.total.input_tokens, 150);
+        assert_eq!(stats.by_model["openai:test"].output_tokens, 100);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn ranks_models_by_output_tokens() {
+        let counter = |input_tokens, output_tokens| UsageCounter {
+            requests: 1,
+            input_tokens,
+            output_tokens,
+        };
+        let by_model = BTreeMap::from([
+            ("openai:gpt-5.2".to_string(), counter(900, 120)),
+            ("anthropic:claude-sonnet-4-5".to_string(), counter(300, 450)),
+            ("zhipu:glm-5".to_string(), counter(5_000, 80)),
+        ]);
+
+        let top = top_entries(&by_model, 2);
+
+        let keys = top.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+        assert_eq!(keys, ["anthropic:claude-sonnet-4-5", "openai:gpt-5.2"]);
+    }
+
+    #[test]
+    fn reports_filter_by_since_and_estimate_cost() {
+        let counter = |requests, input_tokens, output_tokens| UsageCounter {
+            requests,
+            input_tokens,
+            output_tokens,
+        };
+        let mut stats = UsageStats::default();
+        stats
+            .by_day
+            .insert("2026-10-01".to_string(), counter(1, 1_000, 0));
+        stats
+            .by_day
+            .insert("2026-10-02".to_string(), counter(3, 3_000, 2_000));
+        stats
+            .by_model
+            .insert("openai:gpt-4o".to_string(), counter(2, 3_000, 1_000));
+        stats
+            .by_model
+            .insert("local:custom".to_string(), counter(2, 1_000, 1_000));
+        stats.by_day_model.insert(
+            "2026-10-02".to_string(),
+            BTreeMap::from([
+                ("openai:gpt-4o".to_string(), counter(2, 2_000, 1_000)),
+                ("local:custom".to_string(), counter(1, 1_000, 1_000)),
+            ]),
+        );
+
+        let dir = std::env::temp_dir().join(format!("goldagent-usage-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let prices_file = dir.join("prices.json");
+        fs::write(
+            &prices_file,
+            r#"{"local:": {"input_per_1k": 1.0, "output_per_1k": 2.0}}"#,
+        )
+        .unwrap();
+        let prices = load_prices(&prices_file).unwrap();
+        assert_eq!(
+            prices["openai:gpt-4o"],
+            ModelPrice {
+                input_per_1k: 0.0025,
+                output_per_1k: 0.01
+            }
+        );
+
+        let since = NaiveDate::from_ymd_opt(2026, 10, 2);
+        let days = report_by_day(&stats, since, &prices);
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].counter.requests, 3);
+        let day_cost = days[0].cost.unwrap();
+        assert!((day_cost - (0.005 + 0.01 + 3.0)).abs() < 1e-9);
+
+        let all_days = report_by_day(&stats, None, &prices);
+        assert_eq!(all_days[0].cost, None, "no breakdown for the first day");
+        assert_eq!(report_total(&all_days).cost, None);
+        assert_eq!(report_total(&all_days).counter.input_tokens, 4_000);
+
+        let models = report_by_model(&stats, since, &prices);
+        let gpt = models
+            .iter()
+            .find(|row| row.key == "openai:gpt-4o")
+            .unwrap();
+        assert_eq!(gpt.counter.input_tokens, 2_000);
+        let all_models = report_by_model(&stats, None, &prices);
+        assert_eq!(all_models.len(), 2);
+        assert!((report_total(&all_models).cost.unwrap() - (0.0075 + 0.01 + 3.0)).abs() < 1e-9);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn budget_counts_only_the_current_month() {
+        let counter = |input_tokens, output_tokens| UsageCounter {
+            requests: 1,
+            input_tokens,
+            output_tokens,
+        };
+        let mut stats = UsageStats::default();
+        stats
+            .by_day
+            .insert("2026-09-30".to_string(), counter(9_000, 9_000));
+        stats
+            .by_day
+            .insert("2026-10-01".to_string(), counter(500, 100));
+        stats
+            .by_day
+            .insert("2026-10-18".to_string(), counter(300, 50));
+
+        assert_eq!(month_usage(&stats, "2026-10").input_tokens, 800);
+
+        let budget = BudgetConfig {
+            monthly_input_tokens: Some(1_000),
+            monthly_output_tokens: Some(150),
+        };
+        let status = budget_status(&budget, &stats, "2026-10");
+        assert!(status.exceeded(), "output limit reached");
+        assert!((status.input_ratio.unwrap() - 0.8).abs() < 1e-9);
+
+        let roomy = BudgetConfig {
+            monthly_input_tokens: Some(10_000),
+            monthly_output_tokens: None,
+        };
+        let status = budget_status(&roomy, &stats, "2026-10");
+        assert!(!status.exceeded());
+        assert!(!budget_status(&BudgetConfig::default(), &stats, "2026-10").exceeded());
+    }
+
+    #[test]
+    fn reset_clears_only_the_chosen_scope() {
+        let dir = env::temp_dir().join(format!("goldagent-usage-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("usage.json");
+        let event = UsageEvent {
+            model_key: "openai:test".to_string(),
+            input_tokens: 10,
+            output_tokens: 5,
+        };
+        record(&path, &event).unwrap();
+
+        let stats = reset(&path, ResetScope::ByModel).unwrap();
+        assert!(stats.by_model.is_empty());
+        assert_eq!(stats.total.requests, 1);
+        assert_eq!(stats.by_day.len(), 1);
+
+        let stats = reset(&path, ResetScope::ByDay).unwrap();
+        assert!(stats.by_day.is_empty() && stats.by_day_model.is_empty());
+        assert_eq!(load(&path).unwrap().total.input_tokens, 10);
+
+        let stats = reset(&path, ResetScope::All).unwrap();
+        assert_eq!(stats.total.requests, 0);
+        assert!(load(&path).unwrap().updated_at.is_some());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}